@@ -0,0 +1,68 @@
+/* SPDX-License-Identifier: GPL-3.0-or-later OR Apache-2.0 */
+/*
+ * C interface to the arm_gic crate, built with its `ffi` feature.
+ *
+ * Handles come from a static pool of 64, one per CPU: the boot CPU creates
+ * one with arm_gic_v2_new() or arm_gic_v3_new() and initialises the GIC with
+ * arm_gic_init_primary(), each secondary CPU gets its own from
+ * arm_gic_secondary_start(). A CPU must only use its own handle.
+ */
+
+#ifndef ARM_GIC_H
+#define ARM_GIC_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes. */
+#define ARM_GIC_OK 0
+/* Interrupt ID out of range. */
+#define ARM_GIC_EINVAL (-1)
+/* The GIC isn't of the version of the driver. */
+#define ARM_GIC_EVERSION (-2)
+/* Timeout waiting for the GIC. */
+#define ARM_GIC_ETIMEOUT (-3)
+/* No redistributor for the CPU. */
+#define ARM_GIC_ENOREDIST (-4)
+/* Feature not implemented by the GIC. */
+#define ARM_GIC_EUNSUPPORTED (-5)
+/* Any other error. */
+#define ARM_GIC_EFAULT (-6)
+
+/* Returned by arm_gic_ack() when no interrupt is pending. */
+#define ARM_GIC_SPURIOUS 1023u
+
+struct arm_gic;
+
+/* Return NULL if an address is NULL or the pool is exhausted. */
+struct arm_gic *arm_gic_v2_new(void *gicd, void *gicc);
+struct arm_gic *arm_gic_v3_new(void *gicd, void *gicr);
+
+int arm_gic_init_primary(struct arm_gic *gic);
+/* Returns NULL on failure. */
+struct arm_gic *arm_gic_secondary_start(const struct arm_gic *primary);
+
+int arm_gic_enable(struct arm_gic *gic, uint32_t intid);
+int arm_gic_disable(struct arm_gic *gic, uint32_t intid);
+
+/*
+ * Returns the acknowledge value, to pass unchanged to arm_gic_eoi(), or
+ * ARM_GIC_SPURIOUS. On a GICv2, bits 12 to 10 of the value have the CPU that
+ * sent an SGI; arm_gic_iar_intid() returns the INTID.
+ */
+uint32_t arm_gic_ack(const struct arm_gic *gic);
+/* Returns ARM_GIC_SPURIOUS if `iar` can't have been returned by arm_gic_ack(). */
+uint32_t arm_gic_iar_intid(const struct arm_gic *gic, uint32_t iar);
+int arm_gic_eoi(const struct arm_gic *gic, uint32_t iar);
+
+/* `targets` has a bit for each target CPU, by GIC processor number. */
+int arm_gic_send_sgi(const struct arm_gic *gic, uint32_t sgi, uint64_t targets);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ARM_GIC_H */
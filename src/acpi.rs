@@ -0,0 +1,82 @@
+//! Mapping of ACPI Global System Interrupts onto GIC interrupt IDs.
+//!
+//! On GIC based systems a GSIV is the GIC INTID itself, so SPI `n` appears in
+//! `_PRT`, `_CRS` and the static tables as GSI `n + 32`, and PPIs (as used by
+//! the GTDT timers) as GSI 16 to 31.
+
+use crate::{GicError, IntId, TriggerMode};
+
+impl IntId {
+    /// Converts an ACPI Global System Interrupt number to an interrupt ID.
+    ///
+    /// Returns [`GicError::InvalidIntId`] for SGIs, which devices can't signal,
+    /// and for numbers at or above the special interrupt range.
+    pub fn from_acpi_gsi(gsi: u32) -> Result<IntId, GicError> {
+        let intid = gsi as usize;
+        if (Self::PPI_START..Self::SPECIAL_START).contains(&intid) {
+            Ok(IntId(intid))
+        } else {
+            Err(GicError::InvalidIntId)
+        }
+    }
+}
+
+impl TriggerMode {
+    /// Edge-triggered bit of the flags in Interrupt and Extended Interrupt
+    /// resource descriptors.
+    const ACPI_RESOURCE_EDGE: u8 = 1 << 1;
+    /// Edge-triggered bit of the flags in MADT and GTDT entries.
+    const ACPI_TABLE_EDGE: u32 = 1 << 0;
+
+    /// Returns the trigger mode encoded in the flags byte of an ACPI Interrupt
+    /// or Extended Interrupt resource descriptor, such as those returned by `_CRS`.
+    ///
+    /// The polarity bit is ignored, as the GIC only supports active-high levels
+    /// and rising edges and any inversion happens before the signal reaches it.
+    pub const fn from_acpi_resource_flags(flags: u8) -> Self {
+        if flags & Self::ACPI_RESOURCE_EDGE != 0 {
+            TriggerMode::Edge
+        } else {
+            TriggerMode::Level
+        }
+    }
+
+    /// Returns the trigger mode encoded in the flags of a MADT or GTDT entry,
+    /// for example the GTDT timer or the MADT GICC performance interrupt flags.
+    ///
+    /// The polarity bit is ignored, see [`TriggerMode::from_acpi_resource_flags`].
+    pub const fn from_acpi_table_flags(flags: u32) -> Self {
+        if flags & Self::ACPI_TABLE_EDGE != 0 {
+            TriggerMode::Edge
+        } else {
+            TriggerMode::Level
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_acpi_gsi() {
+        assert_eq!(IntId::from_acpi_gsi(30), Ok(IntId::ppi(14)));
+        assert_eq!(IntId::from_acpi_gsi(33), Ok(IntId::spi(1)));
+        assert_eq!(IntId::from_acpi_gsi(1), Err(GicError::InvalidIntId));
+        assert_eq!(IntId::from_acpi_gsi(1023), Err(GicError::InvalidIntId));
+    }
+
+    #[test]
+    fn test_acpi_flags() {
+        assert_eq!(
+            TriggerMode::from_acpi_resource_flags(0x03),
+            TriggerMode::Edge
+        );
+        assert_eq!(
+            TriggerMode::from_acpi_resource_flags(0x05),
+            TriggerMode::Level
+        );
+        assert_eq!(TriggerMode::from_acpi_table_flags(0x1), TriggerMode::Edge);
+        assert_eq!(TriggerMode::from_acpi_table_flags(0x2), TriggerMode::Level);
+    }
+}
@@ -0,0 +1,188 @@
+//! The IRQ controller interface of the ArceOS platform layer.
+//!
+//! Enabled by the `arceos` feature. ArceOS platforms expose free functions
+//! taking raw interrupt numbers, `init_primary`, `init_secondary`,
+//! `set_enable`, `register_handler` and `dispatch_irq`, which an
+//! [`ArceosIrq`] in a static implements for either driver:
+//!
+//! ```ignore
+//! static IRQ: ArceosIrq<GicV2, MpidrAff0, 8, 1024> = ArceosIrq::new();
+//!
+//! pub fn set_enable(irq_num: usize, enabled: bool) {
+//!     IRQ.set_enable(irq_num, enabled);
+//! }
+//!
+//! pub fn register_handler(irq_num: usize, handler: IrqHandler) -> bool {
+//!     IRQ.register_handler(irq_num, handler)
+//! }
+//!
+//! pub fn dispatch_irq(_unused: usize) {
+//!     IRQ.dispatch_irq();
+//! }
+//! ```
+//!
+//! Raw interrupt numbers are INTIDs, as on ArceOS, so the timer PPI 14 is 30.
+
+use core::cell::UnsafeCell;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU8, Ordering};
+
+use crate::logging::error;
+use crate::{CpuIndex, GenericArmGic, GicError, IntId, PerCpuGic};
+
+/// An interrupt handler of ArceOS, which takes no argument.
+pub type IrqHandler = fn();
+
+/// A GIC driver `G` on up to `C` CPUs, looked up by the [`CpuIndex`] `I`,
+/// with handlers for the first `N` interrupt numbers.
+pub struct ArceosIrq<G, I, const C: usize, const N: usize> {
+    /// The handle the boot CPU initialised, copied by the others.
+    primary: UnsafeCell<Option<G>>,
+    /// Whether `primary` is unset, being initialised or set.
+    primary_state: AtomicU8,
+    gics: PerCpuGic<G, I, C>,
+    /// The handlers, as `fn()` pointers, or null.
+    handlers: [AtomicPtr<()>; N],
+}
+
+// SAFETY: The primary handle is only written by the call that moved the state
+// from `UNSET` to `INITIALISING`, before it publishes it by moving the state to
+// `SET`, and never mutably accessed again.
+unsafe impl<G: Send + Sync, I, const C: usize, const N: usize> Sync for ArceosIrq<G, I, C, N> {}
+
+impl<G: GenericArmGic, I: CpuIndex, const C: usize, const N: usize> ArceosIrq<G, I, C, N> {
+    const UNSET: u8 = 0;
+    const INITIALISING: u8 = 1;
+    const SET: u8 = 2;
+
+    /// Returns an adapter without GIC or handlers.
+    pub const fn new() -> Self {
+        Self {
+            primary: UnsafeCell::new(None),
+            primary_state: AtomicU8::new(Self::UNSET),
+            gics: PerCpuGic::new(),
+            handlers: [const { AtomicPtr::new(ptr::null_mut()) }; N],
+        }
+    }
+
+    /// Initialises the GIC with `gic` on the boot CPU.
+    ///
+    /// Panics if called twice, unless the first call failed.
+    pub fn init_primary(&self, mut gic: G) -> Result<(), GicError> {
+        assert!(
+            self.primary_state
+                .compare_exchange(
+                    Self::UNSET,
+                    Self::INITIALISING,
+                    Ordering::Acquire,
+                    Ordering::Relaxed
+                )
+                .is_ok(),
+            "GIC already initialised"
+        );
+        if let Err(e) = gic.init_primary() {
+            self.primary_state.store(Self::UNSET, Ordering::Release);
+            return Err(e);
+        }
+        // SAFETY: This call claimed the slot, and nothing reads it before the
+        // state is set.
+        unsafe { *self.primary.get() = Some(gic) };
+        self.primary_state.store(Self::SET, Ordering::Release);
+        self.gics.set_current(gic);
+        Ok(())
+    }
+
+    /// Initialises the GIC for a secondary CPU, with a copy of the handle of
+    /// the boot CPU.
+    ///
+    /// Panics if [`init_primary`](Self::init_primary) didn't succeed first.
+    pub fn init_secondary(&self) {
+        assert!(
+            self.primary_state.load(Ordering::Acquire) == Self::SET,
+            "GIC not initialised"
+        );
+        // SAFETY: The slot is never written again once its state is set.
+        let mut gic = unsafe { *self.primary.get() }.unwrap();
+        gic.per_cpu_init();
+        self.gics.set_current(gic);
+    }
+
+    /// Returns the INTID of `irq_num` if it is in range: below `N`, and that
+    /// of an SGI, a PPI, an SPI or an extended PPI or SPI.
+    fn intid(irq_num: usize) -> Option<IntId> {
+        let intid = IntId::from(irq_num);
+        let valid = irq_num < IntId::SPECIAL_START || intid.is_eppi() || intid.is_espi();
+        (irq_num < N && valid).then_some(intid)
+    }
+
+    /// Enables or disables the interrupt `irq_num`, doing nothing before the
+    /// current CPU is initialised or if the number is out of range.
+    pub fn set_enable(&self, irq_num: usize, enabled: bool) {
+        let Some(intid) = Self::intid(irq_num) else {
+            error!("GIC: IRQ {} out of range", irq_num);
+            return;
+        };
+        let Some(gic) = self.gics.current() else {
+            error!("GIC: IRQ {} configured before initialisation", irq_num);
+            return;
+        };
+        if enabled {
+            gic.enable_interrupt(intid);
+        } else {
+            gic.disable_interrupt(intid);
+        }
+    }
+
+    /// Registers `handler` for the interrupt `irq_num` and enables it.
+    ///
+    /// Returns false if the number is out of range or already has a handler.
+    pub fn register_handler(&self, irq_num: usize, handler: IrqHandler) -> bool {
+        let Some(slot) = Self::intid(irq_num).and(self.handlers.get(irq_num)) else {
+            return false;
+        };
+        if slot
+            .compare_exchange(
+                ptr::null_mut(),
+                handler as *mut (),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            return false;
+        }
+        self.set_enable(irq_num, true);
+        true
+    }
+
+    /// Handles every interrupt signalled to the current CPU with the
+    /// registered handlers.
+    ///
+    /// Returns the number of interrupts handled, unhandled ones being logged.
+    pub fn dispatch_irq(&self) -> usize {
+        let Some(gic) = self.gics.current() else {
+            return 0;
+        };
+        gic.drain(|intid| {
+            let handler = self
+                .handlers
+                .get(usize::from(intid))
+                .map_or(ptr::null_mut(), |slot| slot.load(Ordering::Acquire));
+            if handler.is_null() {
+                error!("GIC: unhandled IRQ {}", usize::from(intid));
+            } else {
+                // SAFETY: Only `fn()` pointers are stored in the handlers.
+                let handler = unsafe { core::mem::transmute::<*mut (), IrqHandler>(handler) };
+                handler();
+            }
+        })
+    }
+}
+
+impl<G: GenericArmGic, I: CpuIndex, const C: usize, const N: usize> Default
+    for ArceosIrq<G, I, C, N>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,169 @@
+//! Construction of a [`GicV3`] from the components the platform provides.
+//!
+//! The positional constructors of [`GicV3`] take whatever pointers they are
+//! given. A [`GicV3Builder`] names each component instead, and
+//! [`build`](GicV3Builder::build) checks the set against what the GIC reports
+//! before the driver touches anything:
+//!
+//! ```ignore
+//! let builder = GicV3::builder()
+//!     .distributor(GICD_BASE as *mut u8)
+//!     .redistributors(GICR_BASE as *mut u8)
+//!     .its(GITS_BASE as *mut u8)
+//!     .security_state(SecurityState::Dual)
+//!     .delay(&TIMER);
+//! let mut gic = builder.build()?;
+//! let its = builder.build_its(StaticItsMemory::<8, 4>::new(pages, pages_pa))?;
+//! ```
+
+use core::fmt::{self, Debug, Formatter};
+
+use crate::{Delay, GenericArmGic, GicError, GicV3, Its, ItsMemory, ReservedSet};
+
+/// The security states a GIC implements.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SecurityState {
+    /// A single security state, `GICD_CTLR.DS` being set.
+    Single,
+    /// Secure and non-secure states.
+    Dual,
+}
+
+/// The components of a GICv3 or GICv4, collected for [`build`](Self::build).
+#[derive(Copy, Clone, Default)]
+pub struct GicV3Builder {
+    gicd: Option<*mut u8>,
+    gicr: Option<*mut u8>,
+    its: Option<*mut u8>,
+    security: Option<SecurityState>,
+    delay: Option<&'static dyn Delay>,
+    reserved: ReservedSet,
+}
+
+impl GicV3 {
+    /// Returns a builder without any component.
+    pub const fn builder() -> GicV3Builder {
+        GicV3Builder {
+            gicd: None,
+            gicr: None,
+            its: None,
+            security: None,
+            delay: None,
+            reserved: ReservedSet::new(),
+        }
+    }
+}
+
+impl Debug for GicV3Builder {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("GicV3Builder")
+            .field("gicd", &self.gicd)
+            .field("gicr", &self.gicr)
+            .field("its", &self.its)
+            .field("security", &self.security)
+            .field("delay", &self.delay.is_some())
+            .field("reserved", &self.reserved)
+            .finish()
+    }
+}
+
+impl GicV3Builder {
+    /// Sets the base address of the distributor, the only required component.
+    ///
+    /// The requirements of [`GicV3::new`] apply to every address given to the
+    /// builder.
+    pub const fn distributor(mut self, base: *mut u8) -> Self {
+        self.gicd = Some(base);
+        self
+    }
+
+    /// Sets the start of the redistributor region, which must be mapped in
+    /// full. The distance between redistributors is detected from each of
+    /// them.
+    ///
+    /// Without redistributors, the driver is built as by
+    /// [`GicV3::new_without_redistributor`].
+    pub const fn redistributors(mut self, region: *mut u8) -> Self {
+        self.gicr = Some(region);
+        self
+    }
+
+    /// Sets the base address of an ITS, for [`build_its`](Self::build_its).
+    pub const fn its(mut self, base: *mut u8) -> Self {
+        self.its = Some(base);
+        self
+    }
+
+    /// Sets the security states the GIC is expected to implement.
+    pub const fn security_state(mut self, state: SecurityState) -> Self {
+        self.security = Some(state);
+        self
+    }
+
+    /// Bounds the waits of the driver by `delay`, as [`GicV3::with_delay`].
+    pub const fn delay(mut self, delay: &'static dyn Delay) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Reserves the interrupts of `reserved` to the firmware, as
+    /// [`GicV3::with_reserved`].
+    pub const fn reserved(mut self, reserved: ReservedSet) -> Self {
+        self.reserved = reserved;
+        self
+    }
+
+    /// Builds the driver, without initialising the GIC.
+    ///
+    /// Returns [`GicError::MissingComponent`] if no distributor was given,
+    /// [`GicError::InvalidMapping`] if the distributor or the redistributors
+    /// don't identify a GICv3 or GICv4, or [`GicError::Unsupported`] if the
+    /// GIC has an ITS without supporting LPIs or a security state other than
+    /// the one requested.
+    pub fn build(&self) -> Result<GicV3, GicError> {
+        let gicd = self.gicd.ok_or(GicError::MissingComponent)?;
+        let mut gic = match self.gicr {
+            Some(gicr) => GicV3::new(gicd, gicr),
+            None => GicV3::new_without_redistributor(gicd),
+        }
+        .with_reserved(self.reserved);
+        if let Some(delay) = self.delay {
+            gic = gic.with_delay(delay);
+        }
+        gic.validate_mapping()?;
+        if self.its.is_some() && !gic.capabilities().lpi {
+            return Err(GicError::Unsupported);
+        }
+        let security = if gic.security_disabled() {
+            SecurityState::Single
+        } else {
+            SecurityState::Dual
+        };
+        if self.security.is_some_and(|expected| expected != security) {
+            return Err(GicError::Unsupported);
+        }
+        Ok(gic)
+    }
+
+    /// Builds the driver of the ITS, taking its memory from `mem`.
+    ///
+    /// Returns [`GicError::MissingComponent`] if no ITS was given.
+    pub fn build_its<M: ItsMemory>(&self, mem: M) -> Result<Its<M>, GicError> {
+        let its = Its::new(self.its.ok_or(GicError::MissingComponent)?, mem);
+        Ok(match self.delay {
+            Some(delay) => its.with_delay(delay),
+            None => its,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_distributor() {
+        let builder = GicV3::builder().redistributors(0x1000 as *mut u8);
+        assert_eq!(builder.build().unwrap_err(), GicError::MissingComponent);
+    }
+}
@@ -0,0 +1,28 @@
+//! Data cache maintenance for tables shared with a non-coherent GIC.
+
+use crate::fence::{self, Domain};
+use crate::sysregs::read_sysreg;
+
+/// Cleans the data cache lines covering `len` bytes at `addr` to the point of
+/// coherency, so a GIC that doesn't snoop the caches observes the data.
+pub(crate) fn clean_dcache_range(addr: *const u8, len: usize) {
+    // SAFETY: Reading this system register doesn't access memory in any way.
+    let ctr = unsafe { read_sysreg!(ctr_el0) };
+    // CTR_EL0.DminLine, as CTR.DminLine in AArch32, is the log2 of the smallest
+    // line size in words.
+    let line = 4 << ((ctr >> 16) & 0xf);
+
+    let start = addr as usize & !(line - 1);
+    let end = addr as usize + len;
+    for va in (start..end).step_by(line) {
+        // SAFETY: Cleaning a line doesn't change the contents of memory.
+        unsafe {
+            #[cfg(not(target_arch = "arm"))]
+            core::arch::asm!("dc cvac, {va}", va = in(reg) va, options(nostack));
+            // DCCMVAC.
+            #[cfg(target_arch = "arm")]
+            core::arch::asm!("mcr p15, 0, {va}, c7, c10, 1", va = in(reg) va, options(nostack));
+        }
+    }
+    fence::dsb(Domain::Sy, "cache clean");
+}
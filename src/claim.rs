@@ -0,0 +1,150 @@
+//! Interrupt ownership tracking for debug builds.
+//!
+//! Enabled by the `claim-tracking` feature. Each INTID may be claimed by one
+//! owner (a short driver or subsystem name); enabling or reconfiguring a claimed
+//! interrupt from anywhere else panics, so driver conflicts show up the first
+//! time they happen instead of as lost interrupts.
+//!
+//! The claims are global rather than part of a driver instance, so they hold
+//! across the copies of the driver each CPU may use. They cover the SGIs, PPIs
+//! and SPIs and their extended ranges; claims on LPIs are logged and ignored. Each owner is guarded by
+//! a spinlock held only to read or replace it, so an interrupt handler must
+//! not claim an interrupt while it may have preempted a claim of the same
+//! interrupt on the same core.
+
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::logging::error;
+use crate::IntId;
+
+/// The owner of an interrupt, and the lock guarding it.
+struct Claim {
+    locked: AtomicBool,
+    owner: UnsafeCell<Option<&'static str>>,
+}
+
+// SAFETY: The owner is only accessed with the lock held.
+unsafe impl Sync for Claim {}
+
+/// Number of interrupts that can be claimed: those below the special INTIDs,
+/// then the extended PPIs and the extended SPIs.
+const CLAIM_COUNT: usize = IntId::GIC_MAX_IRQ + IntId::EPPI_COUNT + IntId::ESPI_COUNT;
+
+/// The owner of every interrupt the GIC can route, but LPIs.
+static CLAIMS: [Claim; CLAIM_COUNT] = [const {
+    Claim {
+        locked: AtomicBool::new(false),
+        owner: UnsafeCell::new(None),
+    }
+}; CLAIM_COUNT];
+
+/// Returns the index of `intid` in [`CLAIMS`], or `None` if it can't be
+/// claimed.
+fn index(intid: IntId) -> Option<usize> {
+    if intid.0 < IntId::GIC_MAX_IRQ {
+        Some(intid.0)
+    } else if intid.is_eppi() {
+        Some(IntId::GIC_MAX_IRQ + intid.0 - IntId::EPPI_START)
+    } else if intid.is_espi() {
+        Some(IntId::GIC_MAX_IRQ + IntId::EPPI_COUNT + intid.0 - IntId::ESPI_START)
+    } else {
+        None
+    }
+}
+
+/// Runs `f` on the owner of `intid`, or returns `None` if the interrupt can't
+/// be claimed.
+fn with_owner<R>(intid: IntId, f: impl FnOnce(&mut Option<&'static str>) -> R) -> Option<R> {
+    let claim = &CLAIMS[index(intid)?];
+    while claim
+        .locked
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        spin_loop();
+    }
+    // SAFETY: The lock is held, so nothing else accesses the owner.
+    let result = f(unsafe { &mut *claim.owner.get() });
+    claim.locked.store(false, Ordering::Release);
+    Some(result)
+}
+
+/// Records `owner` as the owner of `intid`.
+///
+/// Claiming an interrupt again with the same owner is allowed, claiming one
+/// that belongs to somebody else panics. Claims on interrupts outside the
+/// table are logged and ignored.
+pub(crate) fn claim(intid: IntId, owner: &'static str) {
+    let conflict = with_owner(intid, |slot| match *slot {
+        Some(current) if current != owner => Some(current),
+        _ => {
+            *slot = Some(owner);
+            None
+        }
+    });
+    match conflict {
+        Some(Some(current)) => panic!(
+            "{:?} claimed by {} is already owned by {}",
+            intid, owner, current
+        ),
+        Some(None) => {}
+        None => error!("GIC: claim of INTID {} by {} not tracked", intid.0, owner),
+    }
+}
+
+/// Drops the claim on `intid`, returning its previous owner.
+pub(crate) fn release(intid: IntId) -> Option<&'static str> {
+    with_owner(intid, Option::take).flatten()
+}
+
+/// Panics if `intid` is claimed, as `action` would bypass its owner.
+pub(crate) fn check_unclaimed(intid: IntId, action: &str) {
+    if let Some(owner) = with_owner(intid, |slot| *slot).flatten() {
+        panic!(
+            "{:?} owned by {} {} without its claim",
+            intid, owner, action
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The claims are global, so each test uses its own INTIDs.
+
+    #[test]
+    fn test_claim_release() {
+        claim(IntId::spi(980), "uart");
+        claim(IntId::spi(980), "uart");
+        check_unclaimed(IntId::spi(981), "enabled");
+        assert_eq!(release(IntId::spi(980)), Some("uart"));
+        check_unclaimed(IntId::spi(980), "enabled");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_conflicting_claim() {
+        claim(IntId::spi(982), "timer");
+        claim(IntId::spi(982), "pmu");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_conflicting_espi_claim() {
+        claim(IntId::espi(7), "nic");
+        claim(IntId::espi(7), "dma");
+    }
+
+    #[test]
+    fn test_extended_ranges() {
+        claim(IntId::eppi(63), "pmu");
+        check_unclaimed(IntId::eppi(62), "enabled");
+        check_unclaimed(IntId::espi(1023), "enabled");
+        assert_eq!(release(IntId::eppi(63)), Some("pmu"));
+        assert_eq!(index(IntId::espi(1023)), Some(CLAIM_COUNT - 1));
+        assert_eq!(index(IntId::from(8192)), None);
+    }
+}
@@ -0,0 +1,146 @@
+//! Interrupt IDs restricted to one class of interrupt.
+//!
+//! Functions that only make sense for one class take [`Ppi`], [`Spi`] or
+//! [`Lpi`], as the SGI functions take an [`SgiId`](crate::SgiId), so passing
+//! the wrong kind of interrupt fails to compile instead of panicking. Each
+//! converts into an [`IntId`] for the functions taking any interrupt, and is
+//! as cheap to pass around.
+
+use crate::{GicError, IntId};
+
+/// A Private Peripheral Interrupt, from PPI 0 to 15.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
+pub struct Ppi(u8);
+
+impl Ppi {
+    /// Returns the PPI with the given number, as
+    /// [`IntId::ppi`](crate::IntId::ppi) takes it.
+    ///
+    /// Panics if `ppi` is 16 or more.
+    pub const fn new(ppi: u8) -> Self {
+        assert!(
+            (ppi as usize) < IntId::SPI_START - IntId::PPI_START,
+            "PPI out of range"
+        );
+        Self(ppi)
+    }
+
+    /// Returns the number of the PPI.
+    pub const fn number(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the interrupt ID of the PPI.
+    pub const fn intid(self) -> IntId {
+        IntId::ppi(self.0 as usize)
+    }
+}
+
+/// A Shared Peripheral Interrupt, from SPI 0 to 987.
+///
+/// Extended SPIs aren't included, as most functions taking a `Spi` only
+/// handle the original range.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
+pub struct Spi(u16);
+
+impl Spi {
+    /// Returns the SPI with the given number, as
+    /// [`IntId::spi`](crate::IntId::spi) takes it.
+    ///
+    /// Panics if `spi` is 988 or more.
+    pub const fn new(spi: u16) -> Self {
+        assert!(
+            (spi as usize) < IntId::SPECIAL_START - IntId::SPI_START,
+            "SPI out of range"
+        );
+        Self(spi)
+    }
+
+    /// Returns the number of the SPI.
+    pub const fn number(self) -> u16 {
+        self.0
+    }
+
+    /// Returns the interrupt ID of the SPI.
+    pub const fn intid(self) -> IntId {
+        IntId::spi(self.0 as usize)
+    }
+}
+
+/// A Locality-specific Peripheral Interrupt.
+///
+/// Unlike the other classes, an LPI is named by its INTID, from 8192, as the
+/// LPI tables and the ITS index them.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[repr(transparent)]
+pub struct Lpi(u32);
+
+impl Lpi {
+    /// Returns the LPI with the INTID `intid`.
+    ///
+    /// Panics if `intid` is below 8192.
+    pub const fn new(intid: u32) -> Self {
+        assert!(intid as usize >= IntId::LPI_START, "not an LPI");
+        Self(intid)
+    }
+
+    /// Returns the INTID of the LPI.
+    pub const fn number(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the interrupt ID of the LPI.
+    pub const fn intid(self) -> IntId {
+        IntId(self.0 as usize)
+    }
+}
+
+macro_rules! intid_conversions {
+    ($class:ident, $is_class:ident, $repr:ty, $offset:expr) => {
+        impl From<$class> for IntId {
+            fn from(class: $class) -> Self {
+                class.intid()
+            }
+        }
+
+        impl TryFrom<IntId> for $class {
+            type Error = GicError;
+
+            /// Returns the interrupt of this class with the ID `intid`, or
+            /// [`GicError::InvalidIntId`] if it is of another class.
+            fn try_from(intid: IntId) -> Result<Self, GicError> {
+                if intid.$is_class() {
+                    Ok(Self((intid.0 - $offset) as $repr))
+                } else {
+                    Err(GicError::InvalidIntId)
+                }
+            }
+        }
+    };
+}
+
+intid_conversions!(Ppi, is_ppi, u8, IntId::PPI_START);
+intid_conversions!(Spi, is_spi, u16, IntId::SPI_START);
+intid_conversions!(Lpi, is_lpi, u32, 0);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(IntId::from(Ppi::new(14)), IntId::ppi(14));
+        assert_eq!(Ppi::try_from(IntId::ppi(14)), Ok(Ppi::new(14)));
+        assert_eq!(Ppi::try_from(IntId::sgi(1)), Err(GicError::InvalidIntId));
+
+        assert_eq!(IntId::from(Spi::new(42)), IntId::spi(42));
+        assert_eq!(Spi::try_from(IntId::spi(42)), Ok(Spi::new(42)));
+        assert_eq!(Spi::try_from(IntId::espi(0)), Err(GicError::InvalidIntId));
+
+        assert_eq!(IntId::from(Lpi::new(8200)), IntId::from(8200));
+        assert_eq!(Lpi::try_from(IntId::from(8192)), Ok(Lpi::new(8192)));
+        assert_eq!(Lpi::try_from(IntId::spi(42)), Err(GicError::InvalidIntId));
+    }
+}
@@ -0,0 +1,200 @@
+//! Software coalescing of high-rate level-triggered interrupts.
+//!
+//! A device such as a NIC can assert its level interrupt faster than the
+//! kernel handles it, starving everything else. A [`Coalescer`] counts the
+//! acknowledgements of the interrupts it watches, and once one is acknowledged
+//! [`threshold`](Coalescer::new) times within a window, masks it and asks the
+//! kernel, through an [`UnmaskScheduler`], to unmask it after a hold-off. The
+//! device then gets its work done in batches:
+//!
+//! ```ignore
+//! gic.drain(|intid| {
+//!     HANDLERS.dispatch(intid);
+//!     COALESCER.on_ack(&gic, intid, timer_us());
+//! });
+//!
+//! // Later, from the timer callback the scheduler armed:
+//! COALESCER.unmask(&gic, intid);
+//! ```
+//!
+//! The interrupt is still ended as usual once handled; masking only stops it
+//! from being signalled again. The table of watched interrupts has a fixed
+//! capacity and doesn't allocate.
+
+use crate::{GenericArmGic, GicError, IntId};
+
+/// Arms a one-shot timer to call [`Coalescer::unmask`] for the interrupt
+/// after the given number of microseconds.
+pub type UnmaskScheduler = fn(IntId, u32);
+
+#[derive(Copy, Clone, Debug)]
+struct Entry {
+    intid: IntId,
+    /// Time of the first acknowledgement of the current window.
+    window_start: u64,
+    /// Acknowledgements in the current window.
+    count: u32,
+    /// Whether the interrupt is masked, waiting for its unmask.
+    masked: bool,
+}
+
+/// Coalescing of up to `N` interrupts.
+#[derive(Copy, Clone, Debug)]
+pub struct Coalescer<const N: usize> {
+    threshold: u32,
+    window_us: u64,
+    holdoff_us: u32,
+    schedule: UnmaskScheduler,
+    entries: [Option<Entry>; N],
+}
+
+impl<const N: usize> Coalescer<N> {
+    /// Returns a coalescer watching no interrupt, which masks an interrupt
+    /// once it is acknowledged `threshold` times within `window_us`
+    /// microseconds, calling `schedule` to unmask it `holdoff_us`
+    /// microseconds later.
+    pub const fn new(
+        threshold: u32,
+        window_us: u64,
+        holdoff_us: u32,
+        schedule: UnmaskScheduler,
+    ) -> Self {
+        Self {
+            threshold,
+            window_us,
+            holdoff_us,
+            schedule,
+            entries: [None; N],
+        }
+    }
+
+    /// Starts coalescing the interrupt `intid`, which should be
+    /// level-triggered: an edge asserted while masked may be lost.
+    ///
+    /// Returns [`GicError::CapacityExceeded`] if `N` interrupts are already
+    /// watched.
+    pub fn watch(&mut self, intid: IntId) -> Result<(), GicError> {
+        if self.entry(intid).is_some() {
+            return Ok(());
+        }
+        let slot = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.is_none())
+            .ok_or(GicError::CapacityExceeded)?;
+        *slot = Some(Entry {
+            intid,
+            window_start: 0,
+            count: 0,
+            masked: false,
+        });
+        Ok(())
+    }
+
+    /// Stops coalescing the interrupt `intid`, unmasking it if it is masked.
+    pub fn unwatch(&mut self, gic: &impl GenericArmGic, intid: IntId) {
+        self.unmask(gic, intid);
+        if let Some(slot) = self
+            .entries
+            .iter_mut()
+            .find(|entry| matches!(entry, Some(entry) if entry.intid == intid))
+        {
+            *slot = None;
+        }
+    }
+
+    fn entry(&mut self, intid: IntId) -> Option<&mut Entry> {
+        self.entries
+            .iter_mut()
+            .flatten()
+            .find(|entry| entry.intid == intid)
+    }
+
+    /// Counts an acknowledgement of `intid` at `now_us`, and returns whether
+    /// it reached the threshold, restarting the count if so.
+    fn count(&mut self, intid: IntId, now_us: u64) -> bool {
+        let (threshold, window_us) = (self.threshold, self.window_us);
+        let Some(entry) = self.entry(intid) else {
+            return false;
+        };
+        if entry.masked {
+            return false;
+        }
+        if entry.count == 0 || now_us.wrapping_sub(entry.window_start) > window_us {
+            entry.window_start = now_us;
+            entry.count = 0;
+        }
+        entry.count += 1;
+        if entry.count < threshold {
+            return false;
+        }
+        entry.count = 0;
+        entry.masked = true;
+        true
+    }
+
+    /// Records an acknowledgement of `intid` at `now_us`, in microseconds of
+    /// any monotonic clock.
+    ///
+    /// If the interrupt is watched and reached the threshold within the
+    /// window, it is masked, the unmask is scheduled and true is returned.
+    pub fn on_ack(&mut self, gic: &impl GenericArmGic, intid: IntId, now_us: u64) -> bool {
+        if !self.count(intid, now_us) {
+            return false;
+        }
+        gic.disable_interrupt(intid);
+        (self.schedule)(intid, self.holdoff_us);
+        true
+    }
+
+    /// Unmasks the interrupt `intid` if this masked it, starting a new
+    /// window.
+    pub fn unmask(&mut self, gic: &impl GenericArmGic, intid: IntId) {
+        if let Some(entry) = self.entry(intid).filter(|entry| entry.masked) {
+            entry.masked = false;
+            gic.enable_interrupt(intid);
+        }
+    }
+
+    /// Returns whether the interrupt `intid` is masked by this.
+    pub fn is_masked(&self, intid: IntId) -> bool {
+        self.entries
+            .iter()
+            .flatten()
+            .any(|entry| entry.intid == intid && entry.masked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(_intid: IntId, _delay_us: u32) {}
+
+    #[test]
+    fn test_threshold_within_window() {
+        let mut coalescer = Coalescer::<2>::new(3, 100, 1000, schedule);
+        let intid = IntId::spi(4);
+        coalescer.watch(intid).unwrap();
+        assert!(!coalescer.count(IntId::spi(5), 0));
+        assert!(!coalescer.count(intid, 0));
+        assert!(!coalescer.count(intid, 50));
+        // The window expired, so the count starts again.
+        assert!(!coalescer.count(intid, 200));
+        assert!(!coalescer.count(intid, 210));
+        assert!(coalescer.count(intid, 220));
+        assert!(coalescer.is_masked(intid));
+        assert!(!coalescer.count(intid, 230));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut coalescer = Coalescer::<1>::new(3, 100, 1000, schedule);
+        coalescer.watch(IntId::spi(1)).unwrap();
+        coalescer.watch(IntId::spi(1)).unwrap();
+        assert_eq!(
+            coalescer.watch(IntId::spi(2)),
+            Err(GicError::CapacityExceeded)
+        );
+    }
+}
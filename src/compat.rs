@@ -0,0 +1,195 @@
+//! Names of the `arm-gic` crate, for kernels migrating from it.
+//!
+//! Enabled by the `arm-gic-compat` feature. The module mirrors the layout of
+//! `arm-gic`, so switching usually only takes replacing `arm_gic::` with
+//! `arm_gic::compat::` in the imports:
+//!
+//! ```ignore
+//! use arm_gic::compat::gicv3::{GicV3, SgiTarget};
+//! use arm_gic::compat::{irq_enable, IntId, Trigger};
+//!
+//! let mut gic = unsafe { GicV3::new(GICD_BASE as *mut u64, GICR_BASE as *mut u64) };
+//! gic.setup();
+//! gic.set_trigger(IntId::spi(3), Trigger::Level);
+//! gic.enable_interrupt(IntId::spi(3), true);
+//! irq_enable();
+//! ```
+//!
+//! The constructors of [`IntId`] take `usize` rather than `u32`. The driver
+//! underneath is [`crate::GicV3`], which [`gicv3::GicV3::inner`] returns for
+//! everything `arm-gic` doesn't have.
+
+#[cfg(not(target_arch = "arm"))]
+use aarch64_cpu::asm::wfi;
+#[cfg(not(target_arch = "arm"))]
+use aarch64_cpu::registers::DAIF;
+#[cfg(not(target_arch = "arm"))]
+use tock_registers::interfaces::ReadWriteable;
+
+pub use crate::IntId;
+pub use crate::TriggerMode as Trigger;
+
+/// The GICv3 driver and its types, as in `arm_gic::gicv3`.
+pub mod gicv3 {
+    use crate::sgi::{AFF1_SHIFT, AFF2_SHIFT, AFF3_SHIFT};
+    use crate::sysregs::write_sysreg;
+    use crate::{GenericArmGic, IntId, TriggerMode};
+
+    /// The CPUs an SGI is sent to.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum SgiTarget {
+        /// Every CPU except the current one.
+        All,
+        /// The CPUs of `target_list` in the cluster with the given affinity.
+        List {
+            /// Affinity level 3 of the cluster.
+            affinity3: u8,
+            /// Affinity level 2 of the cluster.
+            affinity2: u8,
+            /// Affinity level 1 of the cluster.
+            affinity1: u8,
+            /// One bit for each `Aff0` from 0 to 15.
+            target_list: u16,
+        },
+    }
+
+    /// A GICv3 driver with the methods of `arm_gic::gicv3::GicV3`.
+    #[derive(Copy, Clone, Debug)]
+    pub struct GicV3 {
+        inner: crate::GicV3,
+    }
+
+    impl GicV3 {
+        /// `ICC_SGI1R_EL1.IRM`: the SGI goes to every CPU but the sender.
+        const SGI1R_IRM: u64 = 1 << 40;
+        /// Bit position of the INTID in `ICC_SGI1R_EL1`.
+        const SGI1R_INTID_SHIFT: u32 = 24;
+
+        /// Constructs a driver for the GIC with the given distributor and
+        /// redistributor base addresses.
+        ///
+        /// # Safety
+        ///
+        /// The requirements of [`crate::GicV3::new`] apply.
+        pub unsafe fn new(gicd: *mut u64, gicr: *mut u64) -> Self {
+            Self {
+                inner: crate::GicV3::new(gicd.cast(), gicr.cast()),
+            }
+        }
+
+        /// Returns the driver underneath.
+        pub fn inner(&mut self) -> &mut crate::GicV3 {
+            &mut self.inner
+        }
+
+        /// Initialises the GIC and the CPU interface of the current CPU.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the GIC isn't a GICv3 or GICv4, as `arm-gic` has no way to
+        /// report it.
+        pub fn setup(&mut self) {
+            self.inner
+                .init_primary()
+                .expect("GICv3 initialisation failed");
+        }
+
+        /// Enables or disables the interrupt with the given ID.
+        pub fn enable_interrupt(&mut self, intid: IntId, enable: bool) {
+            if enable {
+                self.inner.enable_interrupt(intid);
+            } else {
+                self.inner.disable_interrupt(intid);
+            }
+        }
+
+        /// Sets the priority of the interrupt with the given ID.
+        pub fn set_interrupt_priority(&mut self, intid: IntId, priority: u8) {
+            self.inner.set_priority(intid, priority);
+        }
+
+        /// Configures the trigger type of the interrupt with the given ID,
+        /// ignoring a trigger type fixed to the other mode like `arm-gic`.
+        pub fn set_trigger(&mut self, intid: IntId, trigger: TriggerMode) {
+            let _ = self.inner.set_trigger(intid, trigger);
+        }
+
+        /// Sends the SGI `intid` to `target`.
+        pub fn send_sgi(intid: IntId, target: SgiTarget) {
+            assert!(intid.is_sgi(), "{:?} is not an SGI", intid);
+            let target = match target {
+                SgiTarget::All => Self::SGI1R_IRM,
+                SgiTarget::List {
+                    affinity3,
+                    affinity2,
+                    affinity1,
+                    target_list,
+                } => {
+                    u64::from(affinity3) << AFF3_SHIFT
+                        | u64::from(affinity2) << AFF2_SHIFT
+                        | u64::from(affinity1) << AFF1_SHIFT
+                        | u64::from(target_list)
+                }
+            };
+            // SAFETY: Writing to this system register doesn't access memory in any way.
+            unsafe {
+                write_sysreg!(
+                    icc_sgi1r_el1,
+                    target | (intid.0 as u64) << Self::SGI1R_INTID_SHIFT
+                )
+            }
+        }
+
+        /// Acknowledges the highest priority Group 1 interrupt, returning
+        /// `None` if none is pending.
+        pub fn get_and_acknowledge_interrupt() -> Option<IntId> {
+            let intid = IntId(crate::GicV3::ack_raw() as usize);
+            (!intid.is_special()).then_some(intid)
+        }
+
+        /// Ends the interrupt with the given ID.
+        pub fn end_interrupt(intid: IntId) {
+            crate::GicV3::eoi_raw(intid.0 as u32);
+        }
+
+        /// Sets the priority mask of the current CPU, only interrupts of a
+        /// priority value lower than `min_priority` being signalled.
+        pub fn set_priority_mask(min_priority: u8) {
+            // SAFETY: Writing to this system register doesn't access memory in any way.
+            unsafe { write_sysreg!(icc_pmr_el1, min_priority.into()) }
+        }
+    }
+}
+
+/// Unmasks IRQs on the current CPU.
+pub fn irq_enable() {
+    #[cfg(not(target_arch = "arm"))]
+    DAIF.modify(DAIF::I::Unmasked);
+    // SAFETY: Unmasking IRQs doesn't access memory in any way.
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("cpsie i", options(nomem, nostack));
+    }
+}
+
+/// Masks IRQs on the current CPU.
+pub fn irq_disable() {
+    #[cfg(not(target_arch = "arm"))]
+    DAIF.modify(DAIF::I::Masked);
+    // SAFETY: Masking IRQs doesn't access memory in any way.
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("cpsid i", options(nomem, nostack));
+    }
+}
+
+/// Waits for an interrupt to be signalled to the current CPU.
+pub fn wait_for_interrupt() {
+    #[cfg(not(target_arch = "arm"))]
+    wfi();
+    // SAFETY: Waiting for an interrupt doesn't access memory in any way.
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("wfi", options(nomem, nostack));
+    }
+}
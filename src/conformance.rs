@@ -0,0 +1,506 @@
+//! A model of a GICv3 distributor, to check the register layout and the
+//! register accesses of the driver against it.
+//!
+//! Plain memory standing in for the registers in unit tests can't tell a
+//! set-enable register from a clear-enable one, or a RAZ/WI region from RAM.
+//! [`ModelGicd`] implements the behaviour the GICv3 architecture specifies for
+//! a distributor with a single security state and affinity routing enabled,
+//! where the SGI and PPI registers of the distributor are RAZ/WI.
+//!
+//! The model is written from the specification, not from an emulator or a
+//! hardware trace, so it only checks the driver against our reading of the
+//! architecture. The driver tests replay the register words the driver leaves
+//! through the model. They don't see the order of its accesses.
+
+const CTLR: usize = 0x0000;
+const TYPER: usize = 0x0004;
+const IGROUPR: usize = 0x0080;
+const ISENABLER: usize = 0x0100;
+const ICENABLER: usize = 0x0180;
+const ISPENDR: usize = 0x0200;
+const ICPENDR: usize = 0x0280;
+const ISACTIVER: usize = 0x0300;
+const ICACTIVER: usize = 0x0380;
+const IPRIORITYR: usize = 0x0400;
+const ICFGR: usize = 0x0c00;
+const IROUTER: usize = 0x6000;
+const PIDR2: usize = 0xffe8;
+
+/// Size of the bitmap register arrays, one bit per INTID.
+const BITMAP_SIZE: usize = 0x80;
+
+/// An access of a script the model is checked with.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Mmio {
+    /// A read of `size` bytes at `offset`, which must return `value`.
+    Read {
+        offset: usize,
+        value: u64,
+        size: u32,
+    },
+    /// A write of `value`, `size` bytes wide, to `offset`.
+    Write {
+        offset: usize,
+        value: u64,
+        size: u32,
+    },
+}
+
+/// A GICv3 distributor, as far as the driver uses it.
+#[derive(Clone, Debug)]
+pub(crate) struct ModelGicd {
+    /// Number of INTIDs implemented, SGIs and PPIs included.
+    irqs: usize,
+    ctlr: u32,
+    group: [u32; 32],
+    enabled: [u32; 32],
+    pending: [u32; 32],
+    active: [u32; 32],
+    priority: [u8; 1024],
+    /// The edge-triggered bits, one per INTID.
+    edge: [u32; 32],
+    route: [u64; 1024],
+}
+
+impl ModelGicd {
+    const CTLR_DS: u32 = 1 << 6;
+    /// `EnableGrp0`, `EnableGrp1` and `ARE`, the writable bits with a single
+    /// security state.
+    const CTLR_WRITABLE: u32 = 0b1_0011;
+    /// `IDbits`: 16 bits of INTID.
+    const TYPER_ID_BITS: u32 = 15 << 19;
+    /// The affinity fields and `Interrupt_Routing_Mode` of `GICD_IROUTER`.
+    const IROUTER_MASK: u64 = 0xff_8000_0000 | 0xff_ffff;
+
+    /// Returns a distributor out of reset implementing `irqs` INTIDs, a
+    /// multiple of 32.
+    pub(crate) fn new(irqs: usize) -> Self {
+        Self {
+            irqs,
+            ctlr: Self::CTLR_DS,
+            group: [0; 32],
+            enabled: [0; 32],
+            pending: [0; 32],
+            active: [0; 32],
+            priority: [0; 1024],
+            edge: [0; 32],
+            route: [0; 1024],
+        }
+    }
+
+    /// Returns whether `id` has registers in the distributor: an implemented
+    /// SPI, as affinity routing leaves the others to the redistributors.
+    fn is_shared(&self, id: usize) -> bool {
+        (32..self.irqs).contains(&id)
+    }
+
+    /// Returns the value of the register at `offset`, or the zero a RAZ
+    /// region reads as.
+    pub(crate) fn read(&mut self, offset: usize, size: u32) -> u64 {
+        let value = match offset {
+            CTLR => self.ctlr,
+            TYPER => (self.irqs / 32 - 1) as u32 | Self::TYPER_ID_BITS,
+            PIDR2 => 0x3b,
+            IROUTER..0x7fe0 => {
+                let id = (offset - IROUTER) / 8;
+                let route = if self.is_shared(id) {
+                    self.route[id]
+                } else {
+                    0
+                };
+                return if size == 8 {
+                    route
+                } else {
+                    route >> (offset % 8 * 8) & 0xffff_ffff
+                };
+            }
+            _ => self.access(offset, None),
+        };
+        value.into()
+    }
+
+    /// Applies a write of `value` to the register at `offset`, ignoring the
+    /// write to a WI region.
+    pub(crate) fn write(&mut self, offset: usize, value: u64, size: u32) {
+        match offset {
+            CTLR => self.ctlr = value as u32 & Self::CTLR_WRITABLE | Self::CTLR_DS,
+            IROUTER..0x7fe0 => {
+                let id = (offset - IROUTER) / 8;
+                if !self.is_shared(id) {
+                    return;
+                }
+                let route = &mut self.route[id];
+                *route = if size == 8 {
+                    value
+                } else {
+                    let shift = offset % 8 * 8;
+                    *route & !(0xffff_ffff << shift) | value << shift
+                } & Self::IROUTER_MASK;
+            }
+            _ => {
+                self.access(offset, Some(value as u32));
+            }
+        }
+    }
+
+    /// Reads the 32-bit register at `offset`, after writing `write` to it if
+    /// given.
+    fn access(&mut self, offset: usize, write: Option<u32>) -> u32 {
+        let mut bitmaps = [
+            (IGROUPR, &mut self.group, false),
+            (ISENABLER, &mut self.enabled, true),
+            (ISPENDR, &mut self.pending, true),
+            (ISACTIVER, &mut self.active, true),
+        ];
+        for (base, bitmap, set_clear) in &mut bitmaps {
+            let set = *base..*base + BITMAP_SIZE;
+            // The clear register follows the set register.
+            let clear = *base + BITMAP_SIZE..*base + 2 * BITMAP_SIZE;
+            let (offset, clearing) = if set.contains(&offset) {
+                (offset - *base, false)
+            } else if *set_clear && clear.contains(&offset) {
+                (offset - *base - BITMAP_SIZE, true)
+            } else {
+                continue;
+            };
+            let n = offset / 4;
+            if !(32..self.irqs).contains(&(n * 32)) {
+                return 0;
+            }
+            let word = &mut bitmap[n];
+            match (write, *set_clear, clearing) {
+                (Some(value), false, _) => *word = value,
+                (Some(value), true, false) => *word |= value,
+                (Some(value), true, true) => *word &= !value,
+                (None, ..) => {}
+            }
+            return *word;
+        }
+        match offset {
+            IPRIORITYR..0x0800 => {
+                let first = offset - IPRIORITYR;
+                let mut value = 0;
+                for (i, id) in (first..first + 4).enumerate() {
+                    if !self.is_shared(id) {
+                        continue;
+                    }
+                    if let Some(write) = write {
+                        self.priority[id] = (write >> (i * 8)) as u8;
+                    }
+                    value |= u32::from(self.priority[id]) << (i * 8);
+                }
+                value
+            }
+            ICFGR..0x0d00 => {
+                // Two bits per INTID, of which only the upper one is stored.
+                let first = (offset - ICFGR) / 4 * 16;
+                let mut value = 0;
+                for i in 0..16 {
+                    let id = first + i;
+                    if !self.is_shared(id) {
+                        continue;
+                    }
+                    let bit = 1 << (id % 32);
+                    if let Some(write) = write {
+                        if write & 2 << (i * 2) != 0 {
+                            self.edge[id / 32] |= bit;
+                        } else {
+                            self.edge[id / 32] &= !bit;
+                        }
+                    }
+                    if self.edge[id / 32] & bit != 0 {
+                        value |= 2 << (i * 2);
+                    }
+                }
+                value
+            }
+            _ => 0,
+        }
+    }
+
+    /// Returns whether `id` is enabled.
+    pub(crate) fn enabled(&self, id: usize) -> bool {
+        self.enabled[id / 32] & 1 << (id % 32) != 0
+    }
+
+    /// Returns the priority of `id`.
+    pub(crate) fn priority(&self, id: usize) -> u8 {
+        self.priority[id]
+    }
+
+    /// Returns whether `id` is edge-triggered.
+    pub(crate) fn edge_triggered(&self, id: usize) -> bool {
+        self.edge[id / 32] & 1 << (id % 32) != 0
+    }
+
+    /// Returns whether `id` is in Group 1.
+    pub(crate) fn group1(&self, id: usize) -> bool {
+        self.group[id / 32] & 1 << (id % 32) != 0
+    }
+
+    /// Returns the `GICD_IROUTER` value of `id`.
+    pub(crate) fn route(&self, id: usize) -> u64 {
+        self.route[id]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::offset_of;
+
+    use super::*;
+    use crate::registers::gicv3_regs::GicDistributorRegs;
+
+    #[test]
+    fn test_register_layout() {
+        assert_eq!(offset_of!(GicDistributorRegs, CTLR), CTLR);
+        assert_eq!(offset_of!(GicDistributorRegs, TYPER), TYPER);
+        assert_eq!(offset_of!(GicDistributorRegs, IGROUPR), IGROUPR);
+        assert_eq!(offset_of!(GicDistributorRegs, ISENABLER), ISENABLER);
+        assert_eq!(offset_of!(GicDistributorRegs, ICENABLER), ICENABLER);
+        assert_eq!(offset_of!(GicDistributorRegs, ISPENDR), ISPENDR);
+        assert_eq!(offset_of!(GicDistributorRegs, ICPENDR), ICPENDR);
+        assert_eq!(offset_of!(GicDistributorRegs, ISACTIVER), ISACTIVER);
+        assert_eq!(offset_of!(GicDistributorRegs, ICACTIVER), ICACTIVER);
+        assert_eq!(offset_of!(GicDistributorRegs, IPRIORITYR), IPRIORITYR);
+        assert_eq!(offset_of!(GicDistributorRegs, ICFGR), ICFGR);
+        assert_eq!(offset_of!(GicDistributorRegs, IROUTER), IROUTER);
+        assert_eq!(offset_of!(GicDistributorRegs, PIDR2), PIDR2);
+    }
+
+    /// Accesses to a distributor implementing 288 INTIDs, with the values
+    /// the architecture specifies for the reads.
+    const SCRIPT: &[Mmio] = &[
+        // Identification and type.
+        Mmio::Read {
+            offset: PIDR2,
+            value: 0x3b,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: TYPER,
+            value: 0x78_0008,
+            size: 4,
+        },
+        // DS reads as one, the group enables and ARE are writable.
+        Mmio::Write {
+            offset: CTLR,
+            value: 0x13,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: CTLR,
+            value: 0x53,
+            size: 4,
+        },
+        // Set-enable and clear-enable registers share the enable bits, and
+        // writing zeros has no effect.
+        Mmio::Write {
+            offset: ISENABLER + 4,
+            value: 0x21,
+            size: 4,
+        },
+        Mmio::Write {
+            offset: ISENABLER + 4,
+            value: 0,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: ICENABLER + 4,
+            value: 0x21,
+            size: 4,
+        },
+        Mmio::Write {
+            offset: ICENABLER + 4,
+            value: 1,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: ISENABLER + 4,
+            value: 0x20,
+            size: 4,
+        },
+        // Pending and active bits likewise.
+        Mmio::Write {
+            offset: ISPENDR + 8,
+            value: 0x8000_0000,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: ICPENDR + 8,
+            value: 0x8000_0000,
+            size: 4,
+        },
+        Mmio::Write {
+            offset: ICPENDR + 8,
+            value: 0xffff_ffff,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: ISPENDR + 8,
+            value: 0,
+            size: 4,
+        },
+        Mmio::Write {
+            offset: ISACTIVER + 4,
+            value: 2,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: ICACTIVER + 4,
+            value: 2,
+            size: 4,
+        },
+        // With affinity routing, the SGI and PPI registers are RAZ/WI.
+        Mmio::Write {
+            offset: ISENABLER,
+            value: 0xffff_ffff,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: ISENABLER,
+            value: 0,
+            size: 4,
+        },
+        Mmio::Write {
+            offset: IGROUPR,
+            value: 0xffff_ffff,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: IGROUPR,
+            value: 0,
+            size: 4,
+        },
+        Mmio::Write {
+            offset: IPRIORITYR,
+            value: 0xa0a0_a0a0,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: IPRIORITYR,
+            value: 0,
+            size: 4,
+        },
+        Mmio::Write {
+            offset: ICFGR + 4,
+            value: 0xffff_ffff,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: ICFGR + 4,
+            value: 0,
+            size: 4,
+        },
+        // So are the registers of unimplemented SPIs, from INTID 288.
+        Mmio::Write {
+            offset: ISENABLER + 0x24,
+            value: 0xffff_ffff,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: ISENABLER + 0x24,
+            value: 0,
+            size: 4,
+        },
+        Mmio::Write {
+            offset: IROUTER + 288 * 8,
+            value: 1,
+            size: 8,
+        },
+        Mmio::Read {
+            offset: IROUTER + 288 * 8,
+            value: 0,
+            size: 8,
+        },
+        // Groups and priorities of SPIs are plain read/write.
+        Mmio::Write {
+            offset: IGROUPR + 4,
+            value: 0xffff_ffff,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: IGROUPR + 4,
+            value: 0xffff_ffff,
+            size: 4,
+        },
+        Mmio::Write {
+            offset: IPRIORITYR + 0x20,
+            value: 0xa0a0_a080,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: IPRIORITYR + 0x20,
+            value: 0xa0a0_a080,
+            size: 4,
+        },
+        // Only the upper bit of each ICFGR field is writable.
+        Mmio::Write {
+            offset: ICFGR + 8,
+            value: 0xffff_ffff,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: ICFGR + 8,
+            value: 0xaaaa_aaaa,
+            size: 4,
+        },
+        // IROUTER takes 64-bit and 32-bit accesses, and ignores its reserved
+        // bits.
+        Mmio::Write {
+            offset: IROUTER + 0x100,
+            value: u64::MAX,
+            size: 8,
+        },
+        Mmio::Read {
+            offset: IROUTER + 0x100,
+            value: 0xff_80ff_ffff,
+            size: 8,
+        },
+        Mmio::Write {
+            offset: IROUTER + 0x108,
+            value: 0x8000_0000,
+            size: 4,
+        },
+        Mmio::Write {
+            offset: IROUTER + 0x10c,
+            value: 2,
+            size: 4,
+        },
+        Mmio::Read {
+            offset: IROUTER + 0x108,
+            value: 0x2_8000_0000,
+            size: 8,
+        },
+        Mmio::Read {
+            offset: IROUTER + 0x10c,
+            value: 2,
+            size: 4,
+        },
+    ];
+
+    #[test]
+    fn test_script() {
+        let mut gicd = ModelGicd::new(288);
+        for (step, &mmio) in SCRIPT.iter().enumerate() {
+            match mmio {
+                Mmio::Read {
+                    offset,
+                    value,
+                    size,
+                } => assert_eq!(
+                    gicd.read(offset, size),
+                    value,
+                    "step {}: read of {:#x}",
+                    step,
+                    offset
+                ),
+                Mmio::Write {
+                    offset,
+                    value,
+                    size,
+                } => gicd.write(offset, value, size),
+            }
+        }
+    }
+}
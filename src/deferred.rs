@@ -0,0 +1,130 @@
+//! Deferral of interrupt handlers to a bottom half.
+//!
+//! Handlers that take long shouldn't run with interrupts masked. A
+//! [`DeferralQueue`] lets the top half mask such an interrupt and queue its
+//! handler, and [`run_deferred`](DeferralQueue::run_deferred), called later
+//! from a thread or softirq context, runs the queued handlers in order and
+//! unmasks their interrupts again. The queue has a fixed capacity and doesn't
+//! allocate.
+
+use crate::handlers::Handler;
+use crate::{GenericArmGic, GicError, IntId};
+
+/// A queue of up to `N` deferred handlers, run in the order they were deferred.
+#[derive(Copy, Clone, Debug)]
+pub struct DeferralQueue<const N: usize> {
+    entries: [Option<(IntId, Handler)>; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> DeferralQueue<N> {
+    /// Returns an empty queue.
+    pub const fn new() -> Self {
+        Self {
+            entries: [None; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of queued handlers.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether no handler is queued.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Masks the interrupt `intid` and queues `handler` to run for it.
+    ///
+    /// Returns [`GicError::CapacityExceeded`], leaving the interrupt enabled,
+    /// if the queue is full.
+    pub fn defer(
+        &mut self,
+        gic: &impl GenericArmGic,
+        intid: IntId,
+        handler: Handler,
+    ) -> Result<(), GicError> {
+        if self.len == N {
+            return Err(GicError::CapacityExceeded);
+        }
+        gic.disable_interrupt(intid);
+        self.entries[(self.head + self.len) % N] = Some((intid, handler));
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes the oldest queued handler.
+    fn pop(&mut self) -> Option<(IntId, Handler)> {
+        if self.len == 0 {
+            return None;
+        }
+        let entry = self.entries[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        entry
+    }
+
+    /// Returns whether a handler for `intid` is still queued.
+    fn is_queued(&self, intid: IntId) -> bool {
+        (0..self.len)
+            .any(|i| matches!(self.entries[(self.head + i) % N], Some((id, _)) if id == intid))
+    }
+
+    /// Runs the queued handlers, unmasking each interrupt once no handler is
+    /// queued for it anymore.
+    ///
+    /// Returns the number of handlers run.
+    pub fn run_deferred(&mut self, gic: &impl GenericArmGic) -> usize {
+        let mut count = 0;
+        while let Some((intid, handler)) = self.pop() {
+            handler(intid);
+            if !self.is_queued(intid) {
+                gic.enable_interrupt(intid);
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+impl<const N: usize> Default for DeferralQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::Simulation;
+
+    #[test]
+    fn test_defer_and_run() {
+        static SIM: Simulation<64, 1> = Simulation::new();
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+        fn handler(intid: IntId) {
+            assert_eq!(intid, IntId::spi(5));
+            // Still masked while its handler runs.
+            assert!(!SIM.gic().diagnose(intid).unwrap().enabled);
+            RUNS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let gic = SIM.gic();
+        gic.enable_interrupt(IntId::spi(5));
+        let mut queue = DeferralQueue::<2>::new();
+        queue.defer(&gic, IntId::spi(5), handler).unwrap();
+        assert_eq!(queue.len(), 1);
+        assert!(!gic.diagnose(IntId::spi(5)).unwrap().enabled);
+
+        assert_eq!(queue.run_deferred(&gic), 1);
+        assert_eq!(RUNS.load(Ordering::Relaxed), 1);
+        assert!(queue.is_empty());
+        assert!(gic.diagnose(IntId::spi(5)).unwrap().enabled);
+    }
+}
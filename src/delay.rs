@@ -0,0 +1,102 @@
+//! Time source for the bounded waits of the drivers.
+//!
+//! Register write completion, redistributor wake-up and the ITS command queue
+//! are polled until the GIC reports progress. Without a [`Delay`] the polling
+//! gives up after a fixed number of spins, whose duration depends on the CPU;
+//! with one, it gives up after [`Poller::TIMEOUT_US`] of real time.
+
+use core::fmt;
+use core::hint::spin_loop;
+
+/// A source of busy-wait delays, such as the generic timer of the kernel.
+pub trait Delay: Sync {
+    /// Waits for at least `us` microseconds.
+    fn delay_us(&self, us: u32);
+}
+
+/// Polls for a condition, bounded by a [`Delay`] if one is given or by a
+/// number of spins otherwise.
+#[derive(Copy, Clone, Default)]
+pub(crate) struct Poller {
+    delay: Option<&'static dyn Delay>,
+}
+
+impl Poller {
+    /// How long to poll for with a [`Delay`].
+    pub(crate) const TIMEOUT_US: u32 = 1_000_000;
+    /// Interval between polls with a [`Delay`].
+    const INTERVAL_US: u32 = 10;
+
+    pub(crate) const fn new(delay: Option<&'static dyn Delay>) -> Self {
+        Self { delay }
+    }
+
+    /// Calls `done` until it returns true, at most `spins` times without a
+    /// [`Delay`], and returns whether it did.
+    pub(crate) fn poll(&self, spins: usize, mut done: impl FnMut() -> bool) -> bool {
+        match self.delay {
+            Some(delay) => {
+                for _ in 0..Self::TIMEOUT_US / Self::INTERVAL_US {
+                    if done() {
+                        return true;
+                    }
+                    delay.delay_us(Self::INTERVAL_US);
+                }
+            }
+            None => {
+                for _ in 0..spins {
+                    if done() {
+                        return true;
+                    }
+                    spin_loop();
+                }
+            }
+        }
+        done()
+    }
+}
+
+impl fmt::Debug for Poller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Poller")
+            .field("delay", &self.delay.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    struct CountingDelay(AtomicU32);
+
+    impl Delay for CountingDelay {
+        fn delay_us(&self, us: u32) {
+            self.0.fetch_add(us, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_poll() {
+        let polls = Cell::new(0);
+        assert!(!Poller::default().poll(3, || {
+            polls.set(polls.get() + 1);
+            false
+        }));
+        assert_eq!(polls.get(), 4);
+
+        static DELAY: CountingDelay = CountingDelay(AtomicU32::new(0));
+        let poller = Poller::new(Some(&DELAY));
+        assert!(!poller.poll(3, || false));
+        assert_eq!(DELAY.0.load(Ordering::Relaxed), Poller::TIMEOUT_US);
+
+        polls.set(0);
+        assert!(poller.poll(0, || {
+            polls.set(polls.get() + 1);
+            polls.get() == 2
+        }));
+    }
+}
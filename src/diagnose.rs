@@ -0,0 +1,144 @@
+//! Explains why an interrupt isn't signalled to the current CPU.
+//!
+//! [`GenericArmGic::diagnose`] reads every state an interrupt passes through
+//! on its way to the CPU, and [`Diagnosis::blocker`] names the first one that
+//! stops it, which replaces most of a bring-up session spent dumping registers.
+//!
+//! [`GenericArmGic::diagnose`]: crate::GenericArmGic::diagnose
+
+use core::fmt::{self, Display, Formatter};
+
+use crate::IntId;
+
+/// The delivery path of one interrupt to the current CPU, as read back from
+/// the GIC.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Diagnosis {
+    /// The interrupt the diagnosis is for.
+    pub intid: IntId,
+    /// Whether the distributor forwards the group of the interrupt.
+    pub distributor_enabled: bool,
+    /// Whether the redistributor of the current CPU is awake, `None` on GICv2
+    /// or without redistributor access.
+    pub redistributor_awake: Option<bool>,
+    /// Whether the interrupt is enabled.
+    pub enabled: bool,
+    /// Whether the interrupt is pending.
+    pub pending: bool,
+    /// Whether the interrupt is active.
+    pub active: bool,
+    /// Whether the interrupt is in Group 1.
+    pub group1: bool,
+    /// Whether the CPU interface signals the group of the interrupt.
+    pub group_enabled: bool,
+    /// The priority of the interrupt.
+    pub priority: u8,
+    /// The priority mask of the CPU interface.
+    pub priority_mask: u8,
+    /// The running priority of the CPU interface, `0xff` when idle.
+    pub running_priority: u8,
+    /// Whether the interrupt may be delivered to the current CPU. Private
+    /// interrupts always are.
+    pub routed_here: bool,
+}
+
+impl Diagnosis {
+    /// Returns why the interrupt isn't signalled to the current CPU, checking
+    /// the delivery path in order, or `None` if it should be.
+    ///
+    /// Priorities are compared as whole bytes, so an interrupt that doesn't
+    /// preempt because of the binary point still reads as deliverable.
+    pub fn blocker(&self) -> Option<&'static str> {
+        if !self.distributor_enabled {
+            Some("the distributor doesn't forward its group")
+        } else if self.redistributor_awake == Some(false) {
+            Some("the redistributor is asleep")
+        } else if !self.enabled {
+            Some("the interrupt is disabled")
+        } else if !self.routed_here {
+            Some("the interrupt is routed to another CPU")
+        } else if !self.group_enabled {
+            Some("the CPU interface doesn't signal its group")
+        } else if self.priority >= self.priority_mask {
+            Some("its priority isn't higher than the priority mask")
+        } else if !self.pending {
+            Some("the interrupt isn't pending")
+        } else if self.active {
+            Some("the interrupt is still active")
+        } else if self.priority >= self.running_priority {
+            Some("an interrupt of higher priority is being handled")
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Diagnosis {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?}: enabled={} pending={} active={} group1={} priority={:#04x} \
+             distributor_enabled={} group_enabled={} pmr={:#04x} rpr={:#04x} routed_here={}",
+            self.intid,
+            self.enabled,
+            self.pending,
+            self.active,
+            self.group1,
+            self.priority,
+            self.distributor_enabled,
+            self.group_enabled,
+            self.priority_mask,
+            self.running_priority,
+            self.routed_here
+        )?;
+        if let Some(awake) = self.redistributor_awake {
+            write!(f, " redistributor_awake={}", awake)?;
+        }
+        match self.blocker() {
+            Some(reason) => write!(f, ": not signalled, {}", reason),
+            None => write!(f, ": should be signalled"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocker() {
+        let mut diagnosis = Diagnosis {
+            intid: IntId::spi(3),
+            distributor_enabled: true,
+            redistributor_awake: Some(true),
+            enabled: true,
+            pending: true,
+            active: false,
+            group1: true,
+            group_enabled: true,
+            priority: 0xa0,
+            priority_mask: 0xf0,
+            running_priority: 0xff,
+            routed_here: true,
+        };
+        assert_eq!(diagnosis.blocker(), None);
+
+        diagnosis.running_priority = 0x80;
+        assert_eq!(
+            diagnosis.blocker(),
+            Some("an interrupt of higher priority is being handled")
+        );
+        diagnosis.priority_mask = 0xa0;
+        assert_eq!(
+            diagnosis.blocker(),
+            Some("its priority isn't higher than the priority mask")
+        );
+        diagnosis.enabled = false;
+        assert_eq!(diagnosis.blocker(), Some("the interrupt is disabled"));
+        diagnosis.distributor_enabled = false;
+        assert_eq!(
+            diagnosis.blocker(),
+            Some("the distributor doesn't forward its group")
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Register arithmetic shared by the GICv2 and GICv3 drivers.
+//!
+//! The distributors of both versions, and the SGI frame of a GICv3
+//! redistributor, configure interrupts through arrays of 32-bit registers
+//! holding a 1, 2 or 8-bit field per interrupt. The versions only differ in
+//! which array, classic, extended or banked, holds an interrupt. Each driver
+//! picks the array and the index of the interrupt in it, and leaves locating
+//! and updating the field to these helpers, so new per-interrupt settings
+//! behave the same on every version.
+
+use tock_registers::interfaces::{Readable, Writeable};
+use tock_registers::registers::ReadWrite;
+
+use crate::{rmw, GicError, InterruptGroup, NsSgiAccess, TriggerMode};
+
+/// Priority the drivers give every interrupt at initialisation, unless
+/// configured otherwise.
+pub(crate) const DEFAULT_PRIORITY: u8 = 0xa0;
+
+/// Returns the value of an `IPRIORITYR` register giving its four interrupts
+/// `priority`.
+pub(crate) const fn priority_word(priority: u8) -> u32 {
+    u32::from_ne_bytes([priority; 4])
+}
+
+/// Returns the register holding the `width`-bit field of the `n`th interrupt
+/// of `regs`, and the shift of the field.
+pub(crate) fn field<R>(regs: &[R], n: usize, width: usize) -> (&R, usize) {
+    (&regs[n * width / 32], n * width % 32)
+}
+
+/// Replaces the `width`-bit field at `shift` of `reg` with `value`.
+pub(crate) fn write_field(reg: &ReadWrite<u32>, shift: usize, width: usize, value: u32) {
+    let mask = (u32::MAX >> (32 - width)) << shift;
+    rmw::modify(reg, |reg_val| reg_val & !mask | value << shift & mask);
+}
+
+/// Sets or clears the bit at `shift` of `reg`.
+pub(crate) fn write_bit(reg: &ReadWrite<u32>, shift: usize, value: bool) {
+    write_field(reg, shift, 1, value.into());
+}
+
+/// Returns the bit at `shift` of `reg`.
+pub(crate) fn read_bit(reg: &ReadWrite<u32>, shift: usize) -> bool {
+    reg.get() & 1 << shift != 0
+}
+
+/// Enables or disables an interrupt through the bit at `shift` of its
+/// set-enable or clear-enable register.
+pub(crate) fn set_enable(
+    isenabler: &ReadWrite<u32>,
+    icenabler: &ReadWrite<u32>,
+    shift: usize,
+    enable: bool,
+) {
+    if enable {
+        isenabler.set(1 << shift);
+    } else {
+        icenabler.set(1 << shift);
+    }
+}
+
+/// Configures the trigger mode in the two-bit `ICFGR` field at `shift`.
+///
+/// Returns [`GicError::ReadOnlyConfig`] if the trigger mode is fixed to the
+/// other mode, as it is for SGIs and may be for PPIs.
+pub(crate) fn set_trigger(
+    icfgr: &ReadWrite<u32>,
+    shift: usize,
+    tm: TriggerMode,
+) -> Result<(), GicError> {
+    // The upper bit of the field selects edge-triggered.
+    let bit_shift = shift + 1;
+    write_bit(icfgr, bit_shift, tm == TriggerMode::Edge);
+
+    // The field may be write-ignored, so check the mode actually took.
+    if read_bit(icfgr, bit_shift) != (tm == TriggerMode::Edge) {
+        return Err(GicError::ReadOnlyConfig);
+    }
+    Ok(())
+}
+
+/// Assigns an interrupt to `group` through the bit at `shift` of its
+/// `IGROUPR` register.
+pub(crate) fn set_group(igroupr: &ReadWrite<u32>, shift: usize, group: InterruptGroup) {
+    write_bit(igroupr, shift, group == InterruptGroup::Group1);
+}
+
+/// Grants the non-secure access `access` in the two-bit `NSACR` field at
+/// `shift`, and returns the access read back, which is unchanged if the
+/// write was ignored as from the non-secure state.
+pub(crate) fn set_ns_access(
+    nsacr: &ReadWrite<u32>,
+    shift: usize,
+    access: NsSgiAccess,
+) -> NsSgiAccess {
+    let bits = match access {
+        NsSgiAccess::None => 0b00,
+        NsSgiAccess::Group0 => 0b01,
+        NsSgiAccess::Group0AndSecureGroup1 => 0b10,
+    };
+    write_field(nsacr, shift, 2, bits);
+    ns_access(nsacr, shift)
+}
+
+/// Returns the non-secure access granted by the two-bit `NSACR` field at
+/// `shift`. The reserved value `0b11` behaves as `0b10`.
+pub(crate) fn ns_access(nsacr: &ReadWrite<u32>, shift: usize) -> NsSgiAccess {
+    match nsacr.get() >> shift & 0b11 {
+        0b00 => NsSgiAccess::None,
+        0b01 => NsSgiAccess::Group0,
+        _ => NsSgiAccess::Group0AndSecureGroup1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field() {
+        let regs = [0u32; 64];
+        let index = |(reg, shift): (&u32, usize)| {
+            (reg as *const u32 as usize - regs.as_ptr() as usize) / 4 * 32 + shift
+        };
+
+        assert_eq!(index(field(&regs, 33, 1)), 33);
+        assert_eq!(index(field(&regs, 33, 2)), 66);
+        assert_eq!(index(field(&regs, 33, 8)), 264);
+    }
+}
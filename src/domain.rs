@@ -0,0 +1,121 @@
+//! Dense logical IRQ numbers for the interrupts of the GIC.
+//!
+//! Kernels that hand drivers small, stable IRQ numbers rather than INTIDs,
+//! as Linux does with its virqs, can keep the translation in an
+//! [`IrqDomain`]. Logical numbers are allocated from 0 as interrupts are
+//! mapped and reused once unmapped, so they can index arrays of the kernel:
+//!
+//! ```ignore
+//! let irq = DOMAIN.map(IntId::spi(42))?;
+//! gic.drain(|intid| {
+//!     if let Some(irq) = DOMAIN.irq(intid) {
+//!         handle_irq(irq);
+//!     }
+//! });
+//! ```
+//!
+//! The domain has a fixed capacity and doesn't allocate. Looking up the
+//! logical number of an SGI, PPI or SPI takes constant time, that of other
+//! interrupts is linear in the capacity.
+
+use crate::{GicError, IntId};
+
+/// Number of INTIDs with a direct entry in the reverse map.
+const DIRECT: usize = IntId::SPECIAL_START;
+
+/// Marks an INTID without a logical number in the reverse map.
+const UNMAPPED: u16 = u16::MAX;
+
+/// A map between up to `N` INTIDs and the logical IRQ numbers `0..N`.
+#[derive(Copy, Clone, Debug)]
+pub struct IrqDomain<const N: usize> {
+    /// The INTID of each logical number.
+    intids: [Option<IntId>; N],
+    /// The logical number of each SGI, PPI and SPI.
+    irqs: [u16; DIRECT],
+}
+
+impl<const N: usize> IrqDomain<N> {
+    /// Returns a domain without any mapping.
+    pub const fn new() -> Self {
+        assert!(N < UNMAPPED as usize, "IrqDomain too large");
+        Self {
+            intids: [None; N],
+            irqs: [UNMAPPED; DIRECT],
+        }
+    }
+
+    /// Returns the logical number of `intid`, allocating the lowest free one
+    /// if it has none yet.
+    ///
+    /// Returns [`GicError::InvalidIntId`] for special INTIDs, or
+    /// [`GicError::CapacityExceeded`] if all `N` numbers are in use.
+    pub fn map(&mut self, intid: IntId) -> Result<usize, GicError> {
+        if intid.is_special() {
+            return Err(GicError::InvalidIntId);
+        }
+        if let Some(irq) = self.irq(intid) {
+            return Ok(irq);
+        }
+        let irq = self
+            .intids
+            .iter()
+            .position(Option::is_none)
+            .ok_or(GicError::CapacityExceeded)?;
+        self.intids[irq] = Some(intid);
+        if let Some(entry) = self.irqs.get_mut(intid.0) {
+            *entry = irq as u16;
+        }
+        Ok(irq)
+    }
+
+    /// Removes the mapping of the logical number `irq`, returning its INTID.
+    pub fn unmap(&mut self, irq: usize) -> Option<IntId> {
+        let intid = self.intids.get_mut(irq)?.take()?;
+        if let Some(entry) = self.irqs.get_mut(intid.0) {
+            *entry = UNMAPPED;
+        }
+        Some(intid)
+    }
+
+    /// Returns the INTID of the logical number `irq`, if mapped.
+    pub fn intid(&self, irq: usize) -> Option<IntId> {
+        self.intids.get(irq).copied().flatten()
+    }
+
+    /// Returns the logical number of `intid`, if mapped.
+    pub fn irq(&self, intid: IntId) -> Option<usize> {
+        match self.irqs.get(intid.0) {
+            Some(&UNMAPPED) => None,
+            Some(&irq) => Some(irq.into()),
+            None => self.intids.iter().position(|&id| id == Some(intid)),
+        }
+    }
+}
+
+impl<const N: usize> Default for IrqDomain<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_unmap() {
+        let mut domain = IrqDomain::<2>::new();
+        assert_eq!(domain.map(IntId::spi(42)), Ok(0));
+        assert_eq!(domain.map(IntId::from(8192)), Ok(1));
+        assert_eq!(domain.map(IntId::spi(42)), Ok(0));
+        assert_eq!(domain.map(IntId::ppi(3)), Err(GicError::CapacityExceeded));
+        assert_eq!(domain.irq(IntId::from(8192)), Some(1));
+        assert_eq!(domain.intid(0), Some(IntId::spi(42)));
+
+        assert_eq!(domain.unmap(0), Some(IntId::spi(42)));
+        assert_eq!(domain.irq(IntId::spi(42)), None);
+        assert_eq!(domain.map(IntId::ppi(3)), Ok(0));
+        assert_eq!(domain.map(IntId::from(1023)), Err(GicError::InvalidIntId));
+    }
+}
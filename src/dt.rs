@@ -0,0 +1,208 @@
+//! Parsing of device-tree interrupt specifiers.
+//!
+//! The binding is described in the Linux kernel documentation:
+//! <https://www.kernel.org/doc/Documentation/devicetree/bindings/interrupt-controller/arm%2Cgic.yaml>
+//!
+//! The `msi-map` translation of PCI requester IDs follows
+//! <https://www.kernel.org/doc/Documentation/devicetree/bindings/pci/pci-msi.txt>
+
+use crate::{translate_irq, GicError, IntId, InterruptType, IrqFlags, TriggerMode};
+
+impl IrqFlags {
+    /// Mask of the trigger type and level flags in the third cell.
+    const DT_TYPE_MASK: u32 = 0xf;
+    const DT_EDGE_RISING: u32 = 1;
+    const DT_EDGE_FALLING: u32 = 2;
+    const DT_LEVEL_HIGH: u32 = 4;
+    const DT_LEVEL_LOW: u32 = 8;
+
+    /// Parses the flags cell of a device-tree interrupt specifier, ignoring
+    /// the PPI CPU mask in bits \[15:8\].
+    pub const fn from_dt(flags: u32) -> Result<Self, GicError> {
+        match flags & Self::DT_TYPE_MASK {
+            Self::DT_EDGE_RISING => Ok(Self::EdgeRising),
+            Self::DT_EDGE_FALLING => Ok(Self::EdgeFalling),
+            Self::DT_LEVEL_HIGH => Ok(Self::LevelHigh),
+            Self::DT_LEVEL_LOW => Ok(Self::LevelLow),
+            _ => Err(GicError::InvalidDtSpec),
+        }
+    }
+}
+
+impl IntId {
+    /// The first cell value for an SPI.
+    const DT_SPI: u32 = 0;
+    /// The first cell value for a PPI.
+    const DT_PPI: u32 = 1;
+    /// The first cell value for an extended SPI.
+    const DT_ESPI: u32 = 2;
+    /// The first cell value for an extended PPI.
+    const DT_EPPI: u32 = 3;
+
+    /// Parses a 3-cell device-tree interrupt specifier `<type number flags>`,
+    /// or a 4-cell one whose last cell is the phandle of a PPI partition.
+    ///
+    /// The first cell is 0 for an SPI, 1 for a PPI, 2 for an extended SPI or 3
+    /// for an extended PPI, the second is the interrupt number within that
+    /// type and the third holds the trigger flags. The polarity is dropped,
+    /// see [`from_dt_spec_flags`](Self::from_dt_spec_flags) to keep it.
+    pub fn from_dt_spec(cells: &[u32]) -> Result<(IntId, TriggerMode), GicError> {
+        let (intid, flags) = Self::from_dt_spec_flags(cells)?;
+        Ok((intid, flags.trigger()))
+    }
+
+    /// Parses a device-tree interrupt specifier like
+    /// [`from_dt_spec`](Self::from_dt_spec), returning the full flags of the
+    /// line for [`GenericArmGic::set_trigger_flags`](crate::GenericArmGic::set_trigger_flags).
+    pub fn from_dt_spec_flags(cells: &[u32]) -> Result<(IntId, IrqFlags), GicError> {
+        let (&[kind, number, flags] | &[kind, number, flags, _]) = cells else {
+            return Err(GicError::InvalidDtSpec);
+        };
+        let number = number as usize;
+        let intid = match kind {
+            Self::DT_SPI => translate_irq(number, InterruptType::SPI),
+            Self::DT_PPI => translate_irq(number, InterruptType::PPI),
+            Self::DT_ESPI => (number < Self::ESPI_COUNT).then(|| Self::ESPI_START + number),
+            Self::DT_EPPI => (number < Self::EPPI_COUNT).then(|| Self::EPPI_START + number),
+            _ => return Err(GicError::InvalidDtSpec),
+        }
+        .ok_or(GicError::InvalidIntId)?;
+        Ok((IntId(intid), IrqFlags::from_dt(flags)?))
+    }
+}
+
+/// Translates PCI requester IDs to ITS device IDs as described by the
+/// `msi-map` (or `iommu-map`) and `msi-map-mask` properties of a PCI host
+/// bridge.
+///
+/// The map is a list of `<rid-base msi-controller out-base length>` entries of
+/// one cell each, so the `msi-controller` phandle must use zero specifier
+/// cells, as it does for the ITS.
+#[derive(Copy, Clone, Debug)]
+pub struct DevIdMapper<'a> {
+    map: &'a [u32],
+    mask: u32,
+}
+
+impl<'a> DevIdMapper<'a> {
+    /// Number of cells in an `msi-map` entry.
+    const ENTRY_CELLS: usize = 4;
+
+    /// Creates a mapper from the cells of the `msi-map` property and the value
+    /// of `msi-map-mask`, if present.
+    pub fn new(map: &'a [u32], mask: Option<u32>) -> Result<Self, GicError> {
+        if !map.len().is_multiple_of(Self::ENTRY_CELLS) {
+            return Err(GicError::InvalidDtSpec);
+        }
+        Ok(Self {
+            map,
+            mask: mask.unwrap_or(u32::MAX),
+        })
+    }
+
+    /// Returns the phandle of the MSI controller and the device ID for the
+    /// requester ID `rid`, or `None` if no entry covers it.
+    pub fn map(&self, rid: u16) -> Option<(u32, u32)> {
+        let rid = u32::from(rid) & self.mask;
+        self.map.chunks_exact(Self::ENTRY_CELLS).find_map(|entry| {
+            let (rid_base, phandle, out_base, length) = (entry[0], entry[1], entry[2], entry[3]);
+            let offset = rid.checked_sub(rid_base)?;
+            (offset < length).then(|| (phandle, out_base.wrapping_add(offset)))
+        })
+    }
+
+    /// Returns the ITS device ID for the requester ID `rid`, or `None` if no
+    /// entry covers it.
+    pub fn device_id(&self, rid: u16) -> Option<u32> {
+        self.map(rid).map(|(_, device_id)| device_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dev_id_mapper() {
+        assert_eq!(
+            DevIdMapper::new(&[0, 1, 2], None).unwrap_err(),
+            GicError::InvalidDtSpec
+        );
+
+        let map = [0x0, 0x10, 0x1_0000, 0x100, 0x200, 0x11, 0x40, 0x10];
+        let mapper = DevIdMapper::new(&map, None).unwrap();
+        assert_eq!(mapper.map(0x12), Some((0x10, 0x1_0012)));
+        assert_eq!(mapper.map(0x205), Some((0x11, 0x45)));
+        assert_eq!(mapper.map(0x100), None);
+        assert_eq!(mapper.map(0x210), None);
+
+        let mapper = DevIdMapper::new(&map, Some(0xff)).unwrap();
+        assert_eq!(mapper.device_id(0x312), Some(0x1_0012));
+    }
+
+    #[test]
+    fn test_from_dt_spec() {
+        assert_eq!(
+            IntId::from_dt_spec(&[0, 1, 4]),
+            Ok((IntId::spi(1), TriggerMode::Level))
+        );
+        assert_eq!(
+            IntId::from_dt_spec(&[1, 14, 0xf01]),
+            Ok((IntId::ppi(14), TriggerMode::Edge))
+        );
+        assert_eq!(
+            IntId::from_dt_spec(&[1, 16, 4]),
+            Err(GicError::InvalidIntId)
+        );
+        assert_eq!(
+            IntId::from_dt_spec(&[2, 5, 1]),
+            Ok((IntId::espi(5), TriggerMode::Edge))
+        );
+        assert_eq!(
+            IntId::from_dt_spec(&[3, 2, 4, 0]),
+            Ok((IntId::eppi(2), TriggerMode::Level))
+        );
+        assert_eq!(
+            IntId::from_dt_spec(&[3, 64, 4]),
+            Err(GicError::InvalidIntId)
+        );
+        assert_eq!(
+            IntId::from_dt_spec(&[4, 0, 4]),
+            Err(GicError::InvalidDtSpec)
+        );
+        assert_eq!(
+            IntId::from_dt_spec(&[0, 1, 4, 0, 0]),
+            Err(GicError::InvalidDtSpec)
+        );
+        assert_eq!(
+            IntId::from_dt_spec(&[0, 1, 0]),
+            Err(GicError::InvalidDtSpec)
+        );
+        assert_eq!(IntId::from_dt_spec(&[0, 1]), Err(GicError::InvalidDtSpec));
+    }
+
+    #[test]
+    fn test_irq_flags() {
+        assert_eq!(
+            IntId::from_dt_spec_flags(&[0, 1, 8]),
+            Ok((IntId::spi(1), IrqFlags::LevelLow))
+        );
+        assert_eq!(
+            IntId::from_dt_spec_flags(&[1, 14, 0xf02]),
+            Ok((IntId::ppi(14), IrqFlags::EdgeFalling))
+        );
+        assert_eq!(IrqFlags::from_dt(3), Err(GicError::InvalidDtSpec));
+
+        assert_eq!(IrqFlags::LevelHigh.trigger_mode(), Ok(TriggerMode::Level));
+        assert_eq!(IrqFlags::EdgeRising.trigger_mode(), Ok(TriggerMode::Edge));
+        assert_eq!(IrqFlags::LevelLow.trigger(), TriggerMode::Level);
+        assert_eq!(
+            IrqFlags::LevelLow.trigger_mode(),
+            Err(GicError::Unsupported)
+        );
+        assert_eq!(
+            IrqFlags::EdgeFalling.trigger_mode(),
+            Err(GicError::Unsupported)
+        );
+    }
+}
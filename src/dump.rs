@@ -0,0 +1,65 @@
+//! Helpers shared by the `dump_state` implementations.
+
+use core::fmt::{self, Write};
+
+use tock_registers::interfaces::Readable;
+use tock_registers::registers::ReadWrite;
+
+use crate::IntId;
+
+/// The state of a single interrupt as read back from the GIC.
+pub(crate) struct IrqState {
+    pub(crate) enabled: bool,
+    pub(crate) pending: bool,
+    pub(crate) active: bool,
+    pub(crate) priority: u8,
+    /// Target list (GICv2) or routing affinity (GICv3), `None` for private interrupts.
+    pub(crate) target: Option<u64>,
+}
+
+impl IrqState {
+    /// Reads the enable, pending, active and priority state of `id` from a
+    /// register frame laid out like the distributor.
+    pub(crate) fn read(
+        id: usize,
+        isenabler: &[ReadWrite<u32>],
+        ispendr: &[ReadWrite<u32>],
+        isactiver: &[ReadWrite<u32>],
+        ipriorityr: &[ReadWrite<u32>],
+    ) -> Self {
+        Self {
+            enabled: bit(isenabler, id),
+            pending: bit(ispendr, id),
+            active: bit(isactiver, id),
+            priority: byte(ipriorityr, id),
+            target: None,
+        }
+    }
+
+    /// Writes one line describing the interrupt, skipping interrupts that are
+    /// neither enabled, pending nor active.
+    pub(crate) fn write(&self, out: &mut impl Write, intid: IntId) -> fmt::Result {
+        if !(self.enabled || self.pending || self.active) {
+            return Ok(());
+        }
+        write!(
+            out,
+            "  {:?}: enabled={} pending={} active={} priority={:#04x}",
+            intid, self.enabled, self.pending, self.active, self.priority
+        )?;
+        match self.target {
+            Some(target) => writeln!(out, " target={:#x}", target),
+            None => writeln!(out),
+        }
+    }
+}
+
+/// Returns the bit for interrupt `id` in a one-bit-per-interrupt register array.
+pub(crate) fn bit(regs: &[ReadWrite<u32>], id: usize) -> bool {
+    regs[id / 32].get() & (1 << (id % 32)) != 0
+}
+
+/// Returns the byte for interrupt `id` in a one-byte-per-interrupt register array.
+pub(crate) fn byte(regs: &[ReadWrite<u32>], id: usize) -> u8 {
+    (regs[id / 4].get() >> ((id % 4) * 8)) as u8
+}
@@ -0,0 +1,107 @@
+//! A one-line description of the features of a GICv3 or GICv4, for boot
+//! banners.
+//!
+//! [`GicV3::features_summary`] gathers the architecture revision and the
+//! optional features from `GICD_TYPER`, `GICD_TYPER2`, `GICR_TYPER`,
+//! `ICH_VTR_EL2` and `ICC_CTLR_EL1`, so logs from different boards can be
+//! compared at a glance:
+//!
+//! ```text
+//! GICv3.1 LPIs DirectLPI ESPI NMI, 988 SPIs, 1024 ESPIs, 4 LRs, 5 priority bits
+//! ```
+//!
+//! [`GicV3::features_summary`]: crate::GicV3::features_summary
+
+use core::fmt::{self, Display, Formatter};
+
+use crate::GicCapabilities;
+
+/// The architecture revision and features of a GICv3 or GICv4.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FeatureSummary {
+    /// Major architecture revision, 3 or 4.
+    pub major: u8,
+    /// Lowest minor architecture revision implementing the features found.
+    ///
+    /// Revisions that added nothing the registers report can't be told
+    /// apart, so a GICv3.2 shows as GICv3.1.
+    pub minor: u8,
+    /// The optional features, as [`GenericArmGic::capabilities`] reports them.
+    ///
+    /// [`GenericArmGic::capabilities`]: crate::GenericArmGic::capabilities
+    pub capabilities: GicCapabilities,
+    /// Number of extended SPIs the distributor implements.
+    pub extended_spis: usize,
+    /// Number of extended PPIs the redistributor of the current CPU implements.
+    pub extended_ppis: usize,
+    /// Whether SGIs can target CPUs with an Aff0 of 16 or more, from
+    /// `ICC_CTLR_EL1.RSS` and `GICD_TYPER.RSS`.
+    pub range_selector: bool,
+    /// Number of priority bits the CPU interface implements.
+    pub priority_bits: u8,
+}
+
+impl Display for FeatureSummary {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let caps = &self.capabilities;
+        write!(f, "GICv{}.{}", self.major, self.minor)?;
+        for (present, name) in [
+            (caps.lpi, "LPIs"),
+            (caps.direct_lpi, "DirectLPI"),
+            (caps.mbi, "MBIS"),
+            (caps.espi, "ESPI"),
+            (caps.nmi, "NMI"),
+            (caps.vsgi, "vSGI"),
+            (self.range_selector, "RSS"),
+            (caps.security_extension, "Security"),
+        ] {
+            if present {
+                write!(f, " {}", name)?;
+            }
+        }
+        write!(f, ", {} SPIs", caps.spis)?;
+        if self.extended_spis != 0 {
+            write!(f, ", {} ESPIs", self.extended_spis)?;
+        }
+        if self.extended_ppis != 0 {
+            write!(f, ", {} EPPIs", self.extended_ppis)?;
+        }
+        if caps.list_registers != 0 {
+            write!(f, ", {} LRs", caps.list_registers)?;
+        }
+        write!(f, ", {} priority bits", self.priority_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let summary = FeatureSummary {
+            major: 4,
+            minor: 1,
+            capabilities: GicCapabilities {
+                lpi: true,
+                direct_lpi: true,
+                vsgi: true,
+                spis: 988,
+                list_registers: 4,
+                ..GicCapabilities::default()
+            },
+            extended_spis: 0,
+            extended_ppis: 64,
+            range_selector: true,
+            priority_bits: 5,
+        };
+        assert_eq!(
+            summary.to_string(),
+            "GICv4.1 LPIs DirectLPI vSGI RSS, 988 SPIs, 64 EPPIs, 4 LRs, 5 priority bits"
+        );
+    }
+}
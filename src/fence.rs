@@ -0,0 +1,124 @@
+//! Barriers issued by the driver, with optional accounting.
+//!
+//! Every `DSB` and `ISB` of the driver goes through [`dsb`] and [`isb`], which
+//! name the operation they order. With the `fence-counting` feature, each
+//! barrier is counted and logged at debug level with that name, so the
+//! synchronisation cost of an operation can be measured by reading
+//! [`fence_counts`] before and after it.
+
+#[cfg(not(target_arch = "arm"))]
+use aarch64_cpu::asm::barrier;
+
+#[cfg(feature = "fence-counting")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "fence-counting")]
+use crate::logging::debug;
+
+/// Shareability domain and access types a `DSB` waits for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Domain {
+    /// `DSB SY`: all accesses, full system.
+    Sy,
+    /// `DSB ISHST`: stores, inner shareable domain.
+    IshSt,
+}
+
+/// Issues a `DSB` in `domain`, ordering the operation `op`.
+#[inline]
+pub(crate) fn dsb(domain: Domain, op: &str) {
+    #[cfg(feature = "fence-counting")]
+    record(&DSB_COUNT, "dsb", op);
+    #[cfg(not(feature = "fence-counting"))]
+    let _ = op;
+    #[cfg(not(target_arch = "arm"))]
+    match domain {
+        Domain::Sy => barrier::dsb(barrier::SY),
+        Domain::IshSt => barrier::dsb(barrier::ISHST),
+    }
+    // SAFETY: A barrier doesn't access memory in any way.
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        match domain {
+            Domain::Sy => core::arch::asm!("dsb sy", options(nostack)),
+            Domain::IshSt => core::arch::asm!("dsb ishst", options(nostack)),
+        }
+    }
+}
+
+/// Issues an `ISB`, ordering the operation `op`.
+#[inline]
+pub(crate) fn isb(op: &str) {
+    #[cfg(feature = "fence-counting")]
+    record(&ISB_COUNT, "isb", op);
+    #[cfg(not(feature = "fence-counting"))]
+    let _ = op;
+    #[cfg(not(target_arch = "arm"))]
+    barrier::isb(barrier::SY);
+    // SAFETY: A barrier doesn't access memory in any way.
+    #[cfg(target_arch = "arm")]
+    unsafe {
+        core::arch::asm!("isb sy", options(nostack));
+    }
+}
+
+#[cfg(feature = "fence-counting")]
+static DSB_COUNT: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "fence-counting")]
+static ISB_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "fence-counting")]
+fn record(count: &AtomicUsize, kind: &str, op: &str) {
+    count.fetch_add(1, Ordering::Relaxed);
+    debug!("fence: {} for {}", kind, op);
+}
+
+/// Numbers of barriers the driver issued, as counted with the
+/// `fence-counting` feature.
+#[cfg(feature = "fence-counting")]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FenceCounts {
+    /// Number of `DSB` instructions.
+    pub dsb: usize,
+    /// Number of `ISB` instructions.
+    pub isb: usize,
+}
+
+#[cfg(feature = "fence-counting")]
+impl core::ops::Sub for FenceCounts {
+    type Output = Self;
+
+    fn sub(self, earlier: Self) -> Self {
+        Self {
+            dsb: self.dsb - earlier.dsb,
+            isb: self.isb - earlier.isb,
+        }
+    }
+}
+
+/// Returns the numbers of barriers the driver issued so far, on all CPUs.
+///
+/// Subtracting the counts read before an operation from those read after it
+/// gives the barriers of that operation, as long as no other CPU uses the
+/// driver meanwhile.
+#[cfg(feature = "fence-counting")]
+pub fn fence_counts() -> FenceCounts {
+    FenceCounts {
+        dsb: DSB_COUNT.load(Ordering::Relaxed),
+        isb: ISB_COUNT.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(all(test, feature = "fence-counting"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fence_counts() {
+        let before = fence_counts();
+        record(&DSB_COUNT, "dsb", "test");
+        record(&ISB_COUNT, "isb", "test");
+        record(&ISB_COUNT, "isb", "test");
+        assert_eq!(fence_counts() - before, FenceCounts { dsb: 1, isb: 2 });
+    }
+}
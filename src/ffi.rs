@@ -0,0 +1,329 @@
+//! A C interface to the drivers.
+//!
+//! Enabled by the `ffi` feature, for C kernels and boot loaders linking the
+//! crate as a static library. The functions are declared in
+//! `include/arm_gic.h`:
+//!
+//! ```c
+//! struct arm_gic *gic = arm_gic_v3_new(GICD_BASE, GICR_BASE);
+//! if (arm_gic_init_primary(gic) != ARM_GIC_OK)
+//!         panic("GIC");
+//! arm_gic_enable(gic, 30);
+//!
+//! void irq_handler(void)
+//! {
+//!         uint32_t iar;
+//!         while ((iar = arm_gic_ack(this_cpu_gic)) != ARM_GIC_SPURIOUS) {
+//!                 handle(arm_gic_iar_intid(this_cpu_gic, iar));
+//!                 arm_gic_eoi(this_cpu_gic, iar);
+//!         }
+//! }
+//! ```
+//!
+//! The handles are taken from a static pool, one per CPU, as a GICv3 handle
+//! knows the redistributor of its CPU. A secondary CPU gets its own with
+//! `arm_gic_secondary_start`, and must only use that one.
+
+use core::cell::UnsafeCell;
+use core::ffi::c_int;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::logging::error;
+use crate::{CpuSet, GenericArmGic, GicError, GicV2, GicV3, IntId, SgiId};
+
+/// Number of handles the pool holds, so of CPUs the interface can serve.
+const MAX_HANDLES: usize = 64;
+
+const ARM_GIC_OK: c_int = 0;
+const ARM_GIC_EINVAL: c_int = -1;
+const ARM_GIC_EVERSION: c_int = -2;
+const ARM_GIC_ETIMEOUT: c_int = -3;
+const ARM_GIC_ENOREDIST: c_int = -4;
+const ARM_GIC_EUNSUPPORTED: c_int = -5;
+const ARM_GIC_EFAULT: c_int = -6;
+
+/// A driver handle, the `struct arm_gic` of C.
+#[derive(Copy, Clone, Debug)]
+pub enum ArmGic {
+    /// A GICv2 driver.
+    V2(GicV2),
+    /// A GICv3 or GICv4 driver.
+    V3(GicV3),
+}
+
+/// Runs `$body` with `$gic` bound to the driver of the handle `$handle`.
+macro_rules! with_gic {
+    ($handle:expr, |$gic:ident| $body:expr) => {
+        match $handle {
+            ArmGic::V2($gic) => $body,
+            ArmGic::V3($gic) => $body,
+        }
+    };
+}
+
+struct Pool {
+    handles: [UnsafeCell<MaybeUninit<ArmGic>>; MAX_HANDLES],
+    /// Number of handles handed out.
+    next: AtomicUsize,
+}
+
+// SAFETY: Each handle is written once, by the call taking it from the pool,
+// before it is handed out.
+unsafe impl Sync for Pool {}
+
+static POOL: Pool = Pool {
+    handles: [const { UnsafeCell::new(MaybeUninit::uninit()) }; MAX_HANDLES],
+    next: AtomicUsize::new(0),
+};
+
+impl Pool {
+    /// Takes a handle from the pool and stores `gic` in it, returning null if
+    /// the pool is exhausted.
+    fn alloc(&self, gic: ArmGic) -> *mut ArmGic {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        let Some(handle) = self.handles.get(index) else {
+            error!("GIC: more than {} C handles", MAX_HANDLES);
+            return ptr::null_mut();
+        };
+        // SAFETY: Only this call got the index, so nothing references the handle.
+        unsafe { (*handle.get()).write(gic) }
+    }
+}
+
+/// Returns the status code of `result`.
+fn status(result: Result<(), GicError>) -> c_int {
+    match result {
+        Ok(()) => ARM_GIC_OK,
+        Err(GicError::InvalidIntId) => ARM_GIC_EINVAL,
+        Err(GicError::VersionMismatch(_)) => ARM_GIC_EVERSION,
+        Err(GicError::Timeout) => ARM_GIC_ETIMEOUT,
+        Err(GicError::NoRedistributor) => ARM_GIC_ENOREDIST,
+        Err(GicError::Unsupported) => ARM_GIC_EUNSUPPORTED,
+        Err(_) => ARM_GIC_EFAULT,
+    }
+}
+
+/// Returns `intid` if the driver of `gic` handles interrupts with that ID,
+/// LPIs included only if `lpis` is set.
+fn check_intid(gic: &ArmGic, intid: u32, lpis: bool) -> Option<IntId> {
+    let intid = IntId::from(intid as usize);
+    let valid = match gic {
+        ArmGic::V2(_) => intid.0 < IntId::SPECIAL_START,
+        ArmGic::V3(_) => {
+            intid.0 < IntId::SPECIAL_START
+                || intid.is_eppi()
+                || intid.is_espi()
+                || lpis && intid.is_lpi()
+        }
+    };
+    valid.then_some(intid)
+}
+
+/// Returns the interrupt `iar`, as returned by [`arm_gic_ack`], was
+/// acknowledged for, if the driver of `gic` can have returned it.
+fn check_iar(gic: &ArmGic, iar: u32) -> Option<IntId> {
+    match gic {
+        // Bits 12 to 10 have the source CPU of an SGI, the others are zero.
+        ArmGic::V2(_) if iar >> 13 != 0 => None,
+        ArmGic::V2(_) => check_intid(gic, iar & GicV2::IAR_INTID_MASK, false),
+        ArmGic::V3(_) => check_intid(gic, iar, true),
+    }
+}
+
+/// Returns a GICv2 handle for the distributor and CPU interface at `gicd`
+/// and `gicc`, or null if either is null or the pool is exhausted.
+///
+/// # Safety
+///
+/// The requirements of [`GicV2::new`] apply.
+#[no_mangle]
+pub unsafe extern "C" fn arm_gic_v2_new(gicd: *mut u8, gicc: *mut u8) -> *mut ArmGic {
+    if gicd.is_null() || gicc.is_null() {
+        return ptr::null_mut();
+    }
+    POOL.alloc(ArmGic::V2(GicV2::new(gicd, gicc)))
+}
+
+/// Returns a GICv3 handle for the distributor at `gicd` and the
+/// redistributor region at `gicr`, or null if either is null or the pool is
+/// exhausted.
+///
+/// # Safety
+///
+/// The requirements of [`GicV3::new`] apply.
+#[no_mangle]
+pub unsafe extern "C" fn arm_gic_v3_new(gicd: *mut u8, gicr: *mut u8) -> *mut ArmGic {
+    if gicd.is_null() || gicr.is_null() {
+        return ptr::null_mut();
+    }
+    POOL.alloc(ArmGic::V3(GicV3::new(gicd, gicr)))
+}
+
+/// Initialises the GIC from the boot CPU, as
+/// [`init_primary`](GenericArmGic::init_primary).
+///
+/// # Safety
+///
+/// `gic` must be a handle returned by this interface, used by the current
+/// CPU only.
+#[no_mangle]
+pub unsafe extern "C" fn arm_gic_init_primary(gic: *mut ArmGic) -> c_int {
+    status(with_gic!(&mut *gic, |gic| gic.init_primary()))
+}
+
+/// Initialises the GIC for the current secondary CPU, as
+/// [`secondary_cpu_start`](GenericArmGic::secondary_cpu_start), returning
+/// the handle of the CPU, or null on failure.
+///
+/// # Safety
+///
+/// `primary` must be the handle initialised by
+/// [`arm_gic_init_primary`].
+#[no_mangle]
+pub unsafe extern "C" fn arm_gic_secondary_start(primary: *const ArmGic) -> *mut ArmGic {
+    let started = match *primary {
+        ArmGic::V2(gic) => gic.secondary_cpu_start().map(ArmGic::V2),
+        ArmGic::V3(gic) => gic.secondary_cpu_start().map(ArmGic::V3),
+    };
+    match started {
+        Ok(gic) => POOL.alloc(gic),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Enables the interrupt `intid`.
+///
+/// Returns `ARM_GIC_EINVAL` if the GIC has no interrupt `intid` to enable.
+///
+/// # Safety
+///
+/// `gic` must be the handle of the current CPU.
+#[no_mangle]
+pub unsafe extern "C" fn arm_gic_enable(gic: *mut ArmGic, intid: u32) -> c_int {
+    let Some(intid) = check_intid(&*gic, intid, false) else {
+        return ARM_GIC_EINVAL;
+    };
+    with_gic!(&mut *gic, |gic| gic.enable_interrupt(intid));
+    ARM_GIC_OK
+}
+
+/// Disables the interrupt `intid`.
+///
+/// Returns `ARM_GIC_EINVAL` if the GIC has no interrupt `intid` to disable.
+///
+/// # Safety
+///
+/// `gic` must be the handle of the current CPU.
+#[no_mangle]
+pub unsafe extern "C" fn arm_gic_disable(gic: *mut ArmGic, intid: u32) -> c_int {
+    let Some(intid) = check_intid(&*gic, intid, false) else {
+        return ARM_GIC_EINVAL;
+    };
+    with_gic!(&mut *gic, |gic| gic.disable_interrupt(intid));
+    ARM_GIC_OK
+}
+
+/// Acknowledges the highest priority pending interrupt, returning its
+/// acknowledge value, or `ARM_GIC_SPURIOUS` if there is none.
+///
+/// The value is the INTID, with the source CPU of an SGI in bits 12 to 10 on
+/// a GICv2, and must be passed unchanged to [`arm_gic_eoi`].
+///
+/// # Safety
+///
+/// `gic` must be the handle of the current CPU.
+#[no_mangle]
+pub unsafe extern "C" fn arm_gic_ack(gic: *const ArmGic) -> u32 {
+    match &*gic {
+        ArmGic::V2(gic) => gic
+            .acknowledge_raw(true)
+            .map_or(IntId::SPURIOUS as u32, |(_, iar)| iar),
+        ArmGic::V3(gic) => gic
+            .get_and_acknowledge_interrupt()
+            .map_or(IntId::SPURIOUS as u32, |intid| intid.0 as u32),
+    }
+}
+
+/// Returns the INTID of the acknowledge value `iar`, or `ARM_GIC_SPURIOUS` if
+/// [`arm_gic_ack`] can't have returned it.
+///
+/// # Safety
+///
+/// `gic` must be a handle returned by this interface.
+#[no_mangle]
+pub unsafe extern "C" fn arm_gic_iar_intid(gic: *const ArmGic, iar: u32) -> u32 {
+    check_iar(&*gic, iar).map_or(IntId::SPURIOUS as u32, |intid| intid.0 as u32)
+}
+
+/// Ends the interrupt acknowledged with [`arm_gic_ack`] that returned `iar`.
+///
+/// Returns `ARM_GIC_EINVAL` if `iar` can't have been returned.
+///
+/// # Safety
+///
+/// `gic` must be the handle of the current CPU.
+#[no_mangle]
+pub unsafe extern "C" fn arm_gic_eoi(gic: *const ArmGic, iar: u32) -> c_int {
+    let Some(intid) = check_iar(&*gic, iar) else {
+        return ARM_GIC_EINVAL;
+    };
+    match &*gic {
+        ArmGic::V2(gic) => gic.end_raw(intid, iar),
+        ArmGic::V3(gic) => gic.end_interrupt(intid),
+    }
+    ARM_GIC_OK
+}
+
+/// Sends the SGI `sgi` to the CPUs whose bits are set in `targets`, by
+/// [`CpuSet`] index.
+///
+/// Returns `ARM_GIC_EINVAL` if `sgi` isn't an SGI.
+///
+/// # Safety
+///
+/// `gic` must be the handle of the current CPU.
+#[no_mangle]
+pub unsafe extern "C" fn arm_gic_send_sgi(gic: *const ArmGic, sgi: u32, targets: u64) -> c_int {
+    if sgi as usize >= IntId::PPI_START {
+        return ARM_GIC_EINVAL;
+    }
+    let sgi = SgiId::new(sgi as u8);
+    let targets: CpuSet = (0..64).filter(|cpu| targets >> cpu & 1 != 0).collect();
+    with_gic!(&*gic, |gic| gic.send_sgi(sgi, &targets));
+    ARM_GIC_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::NonNull;
+
+    use super::*;
+
+    #[test]
+    fn test_check_intid() {
+        let base = NonNull::<u8>::dangling().as_ptr();
+        let v2 = ArmGic::V2(GicV2::new(base, base));
+        let v3 = ArmGic::V3(GicV3::new(base, base));
+        assert_eq!(check_intid(&v2, 1019, false), Some(IntId::from(1019)));
+        assert_eq!(check_intid(&v2, 1023, true), None);
+        assert_eq!(check_intid(&v2, 4096, true), None);
+        assert_eq!(check_intid(&v3, 4096, false), Some(IntId::espi(0)));
+        assert_eq!(check_intid(&v3, 1024, false), None);
+        assert_eq!(check_intid(&v3, 8192, false), None);
+        assert_eq!(check_intid(&v3, 8192, true), Some(IntId::from(8192)));
+    }
+
+    #[test]
+    fn test_check_iar() {
+        let base = NonNull::<u8>::dangling().as_ptr();
+        let v2 = ArmGic::V2(GicV2::new(base, base));
+        let v3 = ArmGic::V3(GicV3::new(base, base));
+        // SGI 5 from CPU 2.
+        assert_eq!(check_iar(&v2, 2 << 10 | 5), Some(IntId::sgi(5)));
+        assert_eq!(check_iar(&v2, 1 << 13 | 5), None);
+        assert_eq!(check_iar(&v2, 8192), None);
+        assert_eq!(check_iar(&v3, 8192), Some(IntId::from(8192)));
+    }
+}
@@ -2,12 +2,32 @@
 //!
 //! The official documentation: <https://developer.arm.com/documentation/ihi0048/latest/>
 
+use core::fmt;
 use core::ptr::NonNull;
 
+#[cfg(feature = "claim-tracking")]
+use crate::claim;
+use crate::distributor_common;
+use crate::dump::{self, IrqState};
+use crate::fence::{self, Domain};
+#[cfg(feature = "lifecycle-tracking")]
+use crate::lifecycle;
 use crate::registers::gicv2_regs::*;
+use crate::registers::{read_raw, write_raw};
+use crate::reserved::ReservedSet;
+use crate::seal;
+use crate::spurious;
 
-use crate::{GenericArmGic, IntId, TriggerMode};
+use crate::logging::{debug, error, info};
+use crate::mapping::{check_memory_type, check_stable, DeviceMmio, MemoryType};
+use crate::rmw;
+use crate::{
+    translate_irq, CpuSet, Diagnosis, GenericArmGic, GicCapabilities, GicError, HandoffState,
+    IntId, InterruptGroup, InterruptProp, InterruptType, IrqConfig, NsSgiAccess, PriorityGrouping,
+    SecurityGroup, SgiId, Spi, TriggerMode,
+};
 use tock_registers::interfaces::{Readable, Writeable};
+use tock_registers::registers::ReadWrite;
 
 /// The GIC distributor.
 ///
@@ -33,15 +53,32 @@ struct GicDistributor {
     support_irqs: usize,
     #[allow(dead_code)]
     support_cpu: usize,
+    /// Priority given to every interrupt at initialisation.
+    default_priority: u8,
+    /// Whether the GIC is a GICv1.
+    gicv1: bool,
 }
 
 impl GicDistributor {
     const GICD_DISABLE: u32 = 0;
     const GICD_ENABLE: u32 = 1;
+    const GICD_ENABLE_GRP1: u32 = 1 << 1;
 
     const CPU_NUM_SHIFT: usize = 5;
     const CPU_NUM_MASK: u32 = 0b111;
     const IT_LINES_NUM_MASK: u32 = 0b11111;
+    const SECURITY_EXTN_MASK: u32 = 1 << 10;
+
+    /// `GICD_CTLR.ARE_S` of a GICv3, reserved in GICv2.
+    const GICD_ARE: u32 = 1 << 4;
+    const PIDR2_ARCH_SHIFT: u32 = 4;
+    const PIDR2_ARCH_MASK: u32 = 0xf;
+
+    const SGIR_TARGET_LIST_SHIFT: u32 = 16;
+    /// `GICD_SGIR.TargetListFilter` sending the SGI to the requesting CPU only.
+    const SGIR_FILTER_SELF: u32 = 0b10 << 24;
+    /// The `Aff1`, `Aff2` and `Aff3` fields of `MPIDR_EL1`.
+    const MPIDR_UPPER_AFF_MASK: u64 = 0xff_00ff_ff00;
 
     /// Construct a new GIC distributor instance from the base address.
     pub const fn new(base: *mut u8) -> Self {
@@ -49,6 +86,20 @@ impl GicDistributor {
             base: NonNull::new(base).unwrap().cast(),
             support_irqs: 0,
             support_cpu: 0,
+            default_priority: distributor_common::DEFAULT_PRIORITY,
+            gicv1: false,
+        }
+    }
+
+    /// Construct a new GIC distributor instance from the base address, which
+    /// must not be null.
+    const unsafe fn new_unchecked(base: *mut u8) -> Self {
+        Self {
+            base: NonNull::new_unchecked(base).cast(),
+            support_irqs: 0,
+            support_cpu: 0,
+            default_priority: distributor_common::DEFAULT_PRIORITY,
+            gicv1: false,
         }
     }
 
@@ -56,20 +107,82 @@ impl GicDistributor {
         unsafe { self.base.as_ref() }
     }
 
+    /// Returns the number of interrupt IDs the distributor implements.
+    fn max_irqs(&self) -> usize {
+        // The maximum number of interrupts that the GIC supports
+        // If ITLinesNumber=N, the maximum number of interrupts is 32(N+1)
+        let typer = self.regs().TYPER.get();
+        let irq_num = (((typer & Self::IT_LINES_NUM_MASK) + 1) * 32) as usize;
+        irq_num.min(IntId::GIC_MAX_IRQ)
+    }
+
     /// Configures the trigger type for the interrupt with the given ID.
-    fn set_trigger(&mut self, id: usize, tm: TriggerMode) {
-        // type is encoded with two bits, MSB of the two determine type
-        // 16 irqs encoded per ICFGR register
-        let index = id >> 4;
-        let bit_shift = ((id & 0xf) << 1) + 1;
+    ///
+    /// Returns [`GicError::ReadOnlyConfig`] if the trigger type is fixed to the
+    /// other mode, as it is for SGIs and may be for PPIs.
+    fn set_trigger(&self, id: usize, tm: TriggerMode) -> Result<(), GicError> {
+        let (icfgr, shift) = distributor_common::field(&self.regs().ICFGR, id, 2);
+        distributor_common::set_trigger(icfgr, shift, tm)
+    }
+
+    /// Enables or disables the interrupt with the given ID.
+    fn set_enable(&self, id: usize, enable: bool) {
+        let regs = self.regs();
+        let (isenabler, shift) = distributor_common::field(&regs.ISENABLER, id, 1);
+        let (icenabler, _) = distributor_common::field(&regs.ICENABLER, id, 1);
+        distributor_common::set_enable(isenabler, icenabler, shift, enable);
+    }
+
+    /// Returns whether the interrupt with the given ID is enabled.
+    fn is_enabled(&self, id: usize) -> bool {
+        let (isenabler, shift) = distributor_common::field(&self.regs().ISENABLER, id, 1);
+        distributor_common::read_bit(isenabler, shift)
+    }
+
+    /// Clears the pending state of the interrupt with the given ID.
+    fn clear_pending(&self, id: usize) {
+        let (icpendr, shift) = distributor_common::field(&self.regs().ICPENDR, id, 1);
+        icpendr.set(1 << shift);
+    }
 
-        let mut reg_val = self.regs().ICFGR[index].get();
-        match tm {
-            TriggerMode::Edge => reg_val |= 1 << bit_shift,
-            TriggerMode::Level => reg_val &= !(1 << bit_shift),
+    /// Checks that the `count` interrupts from `first` are all SPIs that
+    /// `GICD_TYPER` reports as implemented.
+    fn check_spi_range(&self, first: IntId, count: usize) -> Result<(), GicError> {
+        let end = first.0.checked_add(count).ok_or(GicError::InvalidIntId)?;
+        if first.is_spi() && end <= self.max_irqs() {
+            Ok(())
+        } else {
+            Err(GicError::InvalidIntId)
         }
+    }
 
-        self.regs().ICFGR[index].set(reg_val);
+    /// Disables the SPI with the given ID, and clears its pending and active
+    /// state.
+    fn reset_spi(&self, id: usize) {
+        let regs = self.regs();
+        self.set_enable(id, false);
+        let (icpendr, shift) = distributor_common::field(&regs.ICPENDR, id, 1);
+        icpendr.set(1 << shift);
+        let (icactiver, _) = distributor_common::field(&regs.ICACTIVER, id, 1);
+        icactiver.set(1 << shift);
+    }
+
+    /// Sets the priority of the interrupt with the given ID.
+    fn set_priority(&self, id: usize, priority: u8) {
+        let (ipriorityr, shift) = distributor_common::field(&self.regs().IPRIORITYR, id, 8);
+        distributor_common::write_field(ipriorityr, shift, 8, priority.into());
+    }
+
+    /// Sets the CPU target list of the interrupt with the given ID.
+    fn set_targets(&self, id: usize, targets: u8) {
+        let (itargetsr, shift) = distributor_common::field(&self.regs().ITARGETSR, id, 8);
+        distributor_common::write_field(itargetsr, shift, 8, targets.into());
+    }
+
+    /// Assigns the interrupt with the given ID to `group`.
+    fn set_group(&self, id: usize, group: InterruptGroup) {
+        let (igroupr, shift) = distributor_common::field(&self.regs().IGROUPRn, id, 1);
+        distributor_common::set_group(igroupr, shift, group);
     }
 
     /// Initializes the GIC distributor.
@@ -77,49 +190,116 @@ impl GicDistributor {
     /// It disables all interrupts, sets the target of all SPIs to CPU 0,
     /// configures all SPIs to be edge-triggered, and finally enables the GICD.
     ///
+    /// With `handoff` set, `GICD_CTLR` and the `reserved` interrupts are left as
+    /// secure firmware configured them.
+    ///
     /// This function should be called only once.
-    pub fn init(&mut self) {
-        let typer = self.regs().TYPER.get();
+    pub fn init(&mut self, handoff: bool, reserved: &ReservedSet) -> Result<(), GicError> {
+        self.probe()?;
 
-        // The maximum number of interrupts that the GIC supports
-        // If ITLinesNumber=N, the maximum number of interrupts is 32(N+1)
-        let irq_num = (((typer & Self::IT_LINES_NUM_MASK) + 1) * 32) as usize;
-        match irq_num {
-            0..=IntId::GIC_MAX_IRQ => self.support_irqs = irq_num,
-            _ => self.support_irqs = IntId::GIC_MAX_IRQ,
+        // disable GICD
+        if handoff {
+            info!(
+                "GICv2: keeping firmware GICD_CTLR {:#x}",
+                self.regs().CTLR.get()
+            );
+        } else {
+            self.regs().CTLR.set(Self::GICD_DISABLE);
         }
 
+        self.init_spis(handoff, reserved);
+
+        // enable GIC0
+        if !handoff {
+            self.regs().CTLR.set(Self::GICD_ENABLE);
+        }
+        Ok(())
+    }
+
+    /// Checks the version of the distributor and enables it, leaving the SPIs
+    /// for [`init_spis`](Self::init_spis).
+    fn early_init(&mut self) -> Result<(), GicError> {
+        self.probe()?;
+        self.regs().CTLR.set(Self::GICD_ENABLE);
+        Ok(())
+    }
+
+    /// Checks the version of the distributor and reads its size.
+    fn probe(&mut self) -> Result<(), GicError> {
+        self.validate_version()?;
+        let typer = self.regs().TYPER.get();
+
+        self.support_irqs = self.max_irqs();
+
         self.support_cpu = (((typer >> Self::CPU_NUM_SHIFT) & Self::CPU_NUM_MASK) + 1) as usize;
+        info!(
+            "GICv2: {} interrupts, {} CPU interfaces",
+            self.support_irqs, self.support_cpu
+        );
+        debug!(
+            "GICv2: GICD_TYPER {:#x}, GICD_IIDR {:#x}",
+            typer,
+            self.regs().IIDR.get()
+        );
+        Ok(())
+    }
 
-        // disable GICD
-        self.regs().CTLR.set(Self::GICD_DISABLE);
+    /// Disables and deactivates all SPIs, then targets them to CPU 0 and
+    /// configures them as edge-triggered with the default priority. With
+    /// `handoff`, the `reserved` ones are left alone.
+    fn init_spis(&mut self, handoff: bool, reserved: &ReservedSet) {
+        let kept = if handoff {
+            *reserved
+        } else {
+            ReservedSet::new()
+        };
 
-        // Set all global interrupts to CPU0.
-        for i in (IntId::SPI_START..self.support_irqs).step_by(4) {
-            // Set external interrupts to target cpu 0
-            // once time set 4 interrupts
-            self.regs().ITARGETSR[i / 4].set(0x01_01_01_01);
+        // Deactivate and disable all SPIs
+        for i in (IntId::SPI_START..self.support_irqs).step_by(32) {
+            let mask = !kept.word(IntId(i));
+            self.regs().ICACTIVER[i / 32].set(mask);
+            self.regs().ICENABLER[i / 32].set(mask);
         }
 
-        // Initialize all the SPIs to edge triggered
+        // Set all global interrupts to CPU0, edge triggered where
+        // configurable, with the default priority.
         for i in IntId::SPI_START..self.support_irqs {
-            self.set_trigger(i, TriggerMode::Edge);
+            if !kept.contains(IntId(i)) {
+                self.set_targets(i, 0x01);
+                let _ = self.set_trigger(i, TriggerMode::Edge);
+                self.set_priority(i, self.default_priority);
+            }
         }
+    }
 
-        // Set priority on all global interrupts
-        for i in (IntId::SPI_START..self.support_irqs).step_by(4) {
-            // once time set 4 interrupts
-            self.regs().IPRIORITYR[i / 4].set(0xa0_a0_a0_a0);
+    /// Checks that the distributor is a GICv1 or GICv2 one, switching to the
+    /// GICv1 compatibility mode for a GICv1.
+    ///
+    /// In that mode, a distributor whose ID registers read as zero is taken
+    /// as a GICv1 too, as on some early implementations.
+    fn validate_version(&mut self) -> Result<(), GicError> {
+        // A GICv3 with affinity routing enabled can't be driven through the
+        // GICv2 registers. Its ID registers are elsewhere, so check this first.
+        if self.regs().CTLR.get() & Self::GICD_ARE != 0 {
+            error!("GICv2: GICD_CTLR.ARE set, the GIC is a GICv3");
+            return Err(GicError::VersionMismatch(3));
         }
-
-        // Deactivate and disable all SPIs
-        for i in (IntId::SPI_START..self.support_irqs).step_by(32) {
-            self.regs().ICACTIVER[i / 32].set(u32::MAX);
-            self.regs().ICENABLER[i / 32].set(u32::MAX);
+        let arch = (self.regs().PIDR2.get() >> Self::PIDR2_ARCH_SHIFT) & Self::PIDR2_ARCH_MASK;
+        match arch {
+            0 if self.gicv1 => {
+                info!("GICv2: no GICD_PIDR2.ArchRev, assuming GICv1");
+                Ok(())
+            }
+            1 | 2 => {
+                info!("GICv2: distributor reports GICv{}", arch);
+                self.gicv1 |= arch == 1;
+                Ok(())
+            }
+            _ => {
+                error!("GICv2: unexpected GICD_PIDR2.ArchRev {:#x}", arch);
+                Err(GicError::VersionMismatch(arch as u8))
+            }
         }
-
-        // enable GIC0
-        self.regs().CTLR.set(Self::GICD_ENABLE);
     }
 }
 
@@ -139,15 +319,111 @@ impl GicDistributor {
 #[derive(Debug, Copy, Clone)]
 struct GicCpuInterface {
     base: NonNull<GicCpuInterfaceRegs>,
+    view: CtlrView,
+    /// Whether to disable the legacy interrupt bypass.
+    disable_bypass: bool,
+    /// Priority mask set at initialisation.
+    priority_mask: u8,
+    /// Whether the GIC is a GICv1, without the bypass controls.
+    gicv1: bool,
+}
+
+/// The layout of `GICC_CTLR` the driver sees.
+///
+/// With the Security Extensions, non-secure accesses see a copy of the
+/// register holding only the non-secure controls, bit 0 enabling Group 1
+/// rather than Group 0.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum CtlrView {
+    /// No Security Extensions, or secure accesses: EnableGrp0, EnableGrp1,
+    /// AckCtl and FIQEn in bits 0 to 3, and the bypass disables of both
+    /// groups in bits 5 to 8.
+    Full,
+    /// The non-secure copy: EnableGrp1 in bit 0, and the bypass disables of
+    /// Group 1 in bits 5 and 6.
+    NonSecure,
+    /// A GICv1 without Security Extensions, and so without interrupt groups:
+    /// Enable in bit 0 only.
+    Ungrouped,
 }
 
 impl GicCpuInterface {
     const GICC_ENABLE: u32 = 1;
+    const GICC_ENABLE_GRP1: u32 = 1 << 1;
+    const GICC_ACK_CTL: u32 = 1 << 2;
+    const GICC_FIQ_EN: u32 = 1 << 3;
+    /// FIQBypDisGrp0 and IRQBypDisGrp0 of the full layout, FIQBypDisGrp1 and
+    /// IRQBypDisGrp1 of the non-secure copy.
+    const GICC_BYP_DIS_LOW: u32 = 0b11 << 5;
+    /// FIQBypDisGrp1 and IRQBypDisGrp1 of the full layout.
+    const GICC_BYP_DIS_GRP1: u32 = 0b11 << 7;
+    /// EOImodeS of the full layout, EOImodeNS of the non-secure copy: writes
+    /// to `GICC_EOIR` only drop the priority.
+    const GICC_EOI_MODE: u32 = 1 << 9;
 
     /// Construct a new GIC CPU interface instance from the base address.
     pub const fn new(base: *mut u8) -> Self {
         Self {
             base: NonNull::new(base).unwrap().cast(),
+            view: CtlrView::Full,
+            disable_bypass: false,
+            priority_mask: 0xff,
+            gicv1: false,
+        }
+    }
+
+    /// Construct a new GIC CPU interface instance from the base address, which
+    /// must not be null.
+    const unsafe fn new_unchecked(base: *mut u8) -> Self {
+        Self {
+            base: NonNull::new_unchecked(base).cast(),
+            view: CtlrView::Full,
+            disable_bypass: false,
+            priority_mask: 0xff,
+            gicv1: false,
+        }
+    }
+
+    /// Detects which `GICC_CTLR` layout the current CPU sees.
+    ///
+    /// Non-secure accesses to `GICC_ABPR` of a GIC with the Security
+    /// Extensions are RAZ, while secure ones read the non-secure binary point,
+    /// which is at least 1, so reading it tells the security state of the
+    /// accesses without writing anything.
+    fn detect_view(&self, gicd: &GicDistributor) -> CtlrView {
+        if gicd.regs().TYPER.get() & GicDistributor::SECURITY_EXTN_MASK == 0 {
+            return if gicd.gicv1 {
+                CtlrView::Ungrouped
+            } else {
+                CtlrView::Full
+            };
+        }
+        if self.regs().ABPR.get() != 0 {
+            CtlrView::Full
+        } else {
+            CtlrView::NonSecure
+        }
+    }
+
+    /// Returns the `GICC_CTLR` bits enabling the interrupt groups the current
+    /// security state handles, acknowledged through `GICC_IAR`.
+    fn enable_bits(&self) -> u32 {
+        match self.view {
+            CtlrView::Full => Self::GICC_ENABLE | Self::GICC_ENABLE_GRP1 | Self::GICC_ACK_CTL,
+            CtlrView::NonSecure | CtlrView::Ungrouped => Self::GICC_ENABLE,
+        }
+    }
+
+    /// Returns the `GICC_CTLR` bits disabling the bypass of the interrupt
+    /// groups the current security state controls, if requested.
+    fn bypass_bits(&self) -> u32 {
+        if !self.disable_bypass || self.gicv1 {
+            return 0;
+        }
+        match self.view {
+            CtlrView::Full => Self::GICC_BYP_DIS_LOW | Self::GICC_BYP_DIS_GRP1,
+            CtlrView::NonSecure => Self::GICC_BYP_DIS_LOW,
+            CtlrView::Ungrouped => 0,
         }
     }
 
@@ -157,10 +433,22 @@ impl GicCpuInterface {
 
     /// Initializes the GIC CPU interface.
     ///
-    /// It unmask interrupts at all priority levels and enables the GICC.
+    /// It sets the priority mask and enables the GICC.
     ///
     /// This function should be called only once.
-    pub fn init(&self, gicd: &GicDistributor) {
+    pub fn init(&mut self, gicd: &GicDistributor) {
+        self.gicv1 = gicd.gicv1;
+        self.view = self.detect_view(gicd);
+        debug!(
+            "GICv2: GICC_IIDR {:#x}, {} GICC_CTLR",
+            self.regs().IIDR.get(),
+            match self.view {
+                CtlrView::Full => "full",
+                CtlrView::NonSecure => "non-secure",
+                CtlrView::Ungrouped => "ungrouped",
+            }
+        );
+
         // Deactivate and disable all private interrupts
         gicd.regs().ICACTIVER[0].set(u32::MAX);
         gicd.regs().ICENABLER[0].set(u32::MAX);
@@ -168,13 +456,55 @@ impl GicCpuInterface {
         // Set priority on private interrupts
         for i in (0..IntId::SPI_START).step_by(4) {
             // once time set 4 interrupts
-            gicd.regs().IPRIORITYR[i / 4].set(0xa0_a0_a0_a0);
+            gicd.regs().IPRIORITYR[i / 4]
+                .set(distributor_common::priority_word(gicd.default_priority));
         }
 
-        // unmask interrupts at all priority levels
-        self.regs().PMR.set(0xff);
-        // enable GIC0
-        self.regs().CTLR.set(Self::GICC_ENABLE);
+        // unmask interrupts of higher priority than the mask
+        self.regs().PMR.set(self.priority_mask.into());
+        // enable the groups of this security state
+        self.regs()
+            .CTLR
+            .set(self.enable_bits() | self.bypass_bits());
+    }
+
+    /// Disables or enables the groups of this security state.
+    fn set_enabled(&self, enabled: bool) {
+        rmw::modify(&self.regs().CTLR, |ctlr| {
+            if enabled {
+                ctlr | self.enable_bits()
+            } else {
+                ctlr & !(Self::GICC_ENABLE | Self::GICC_ENABLE_GRP1)
+            }
+        });
+    }
+
+    /// Returns the binary point register of Group 1: the non-secure copy of
+    /// `GICC_BPR`, or `GICC_ABPR` from the secure state, `GICC_CTLR.CBPR`
+    /// being clear. Without groups, the only one is `GICC_BPR`.
+    fn bpr1(&self) -> &ReadWrite<u32> {
+        match self.view {
+            CtlrView::Full => &self.regs().ABPR,
+            CtlrView::NonSecure | CtlrView::Ungrouped => &self.regs().BPR,
+        }
+    }
+
+    /// Enables both groups, signalling Group 0 as FIQ and Group 1 as IRQ.
+    fn enable_fiq_split(&self) {
+        match self.view {
+            CtlrView::Full => {}
+            CtlrView::NonSecure => {
+                error!("GICv2: Group 0 belongs to the secure state, no FIQ split");
+                return;
+            }
+            CtlrView::Ungrouped => {
+                error!("GICv2: GICv1 without interrupt groups, no FIQ split");
+                return;
+            }
+        }
+        rmw::modify(&self.regs().CTLR, |ctlr| {
+            ctlr | Self::GICC_ENABLE | Self::GICC_ENABLE_GRP1 | Self::GICC_FIQ_EN
+        });
     }
 }
 
@@ -185,10 +515,20 @@ unsafe impl Send for GicCpuInterface {}
 unsafe impl Sync for GicCpuInterface {}
 
 /// Driver for an Arm Generic Interrupt Controller version 2.
+///
+/// GICv1 controllers, such as the one of the Cortex-A9 MPCore, are driven in
+/// a compatibility mode avoiding what GICv2 added: the legacy bypass
+/// controls, the aliased Group 1 registers and `GICD_NSACRn`. Without the
+/// Security Extensions, a GICv1 doesn't implement interrupt grouping either,
+/// and only the enable bit of `GICC_CTLR`.
 #[derive(Debug, Copy, Clone)]
 pub struct GicV2 {
     gicd: GicDistributor,
     gicc: GicCpuInterface,
+    /// Whether interrupts are disabled while acknowledged.
+    disable_on_ack: bool,
+    /// Interrupts the driver refuses to configure.
+    reserved: ReservedSet,
 }
 
 unsafe impl Send for GicV2 {}
@@ -198,22 +538,388 @@ impl GicV2 {
     /// # Safety
     ///
     /// The given base addresses must point to the GIC distributor and redistributor registers
-    /// respectively. These regions must be mapped into the address space of the process as device
-    /// memory, and not have any other aliases, either via another instance of this driver or
-    /// otherwise.
+    /// respectively. These regions must be mapped into the address space of the process as
+    /// Device-nGnRnE or Device-nGnRE memory, and not have any other aliases, either via another
+    /// instance of this driver or otherwise.
     pub const fn new(gicd: *mut u8, gicc: *mut u8) -> Self {
         Self {
             gicd: GicDistributor::new(gicd),
             gicc: GicCpuInterface::new(gicc),
+            disable_on_ack: false,
+            reserved: ReservedSet::new(),
+        }
+    }
+
+    /// Constructs a new instance of the driver like [`new`](Self::new), without
+    /// checking the addresses, so it can initialise a `static`. See
+    /// [`GicV3::new_unchecked`](crate::GicV3::new_unchecked) for the pattern.
+    ///
+    /// # Safety
+    ///
+    /// The requirements of [`new`](Self::new) apply, and neither address may be null.
+    pub const unsafe fn new_unchecked(gicd: *mut u8, gicc: *mut u8) -> Self {
+        Self {
+            gicd: GicDistributor::new_unchecked(gicd),
+            gicc: GicCpuInterface::new_unchecked(gicc),
+            disable_on_ack: false,
+            reserved: ReservedSet::new(),
+        }
+    }
+
+    /// Disables the legacy IRQ and FIQ bypass of the CPU interface, through
+    /// the `FIQBypDis` and `IRQBypDis` bits of `GICC_CTLR`, as
+    /// [`per_cpu_init`](GenericArmGic::per_cpu_init) initialises it.
+    ///
+    /// Some boards wire the legacy interrupt lines to the cores, and without
+    /// this deliver interrupts to the wrong exception when the CPU interface
+    /// doesn't signal any.
+    ///
+    /// GICv1 has no bypass controls, so this does nothing there.
+    pub const fn with_bypass_disabled(mut self) -> Self {
+        self.gicc.disable_bypass = true;
+        self
+    }
+
+    /// Drives the GIC in the GICv1 compatibility mode described for
+    /// [`GicV2`], even if its ID registers don't identify a GICv1.
+    ///
+    /// A GIC reporting GICv1 in `GICD_PIDR2` gets this mode anyway, but some
+    /// early ones don't implement the ID registers.
+    pub const fn with_gicv1_compat(mut self) -> Self {
+        self.gicd.gicv1 = true;
+        self
+    }
+
+    /// Sets the priority [`init_primary`](GenericArmGic::init_primary) gives
+    /// every SPI, and [`per_cpu_init`](GenericArmGic::per_cpu_init) the SGIs
+    /// and PPIs of each CPU, `0xa0` by default.
+    pub const fn with_default_priority(mut self, priority: u8) -> Self {
+        self.gicd.default_priority = priority;
+        self
+    }
+
+    /// Sets the priority mask [`per_cpu_init`](GenericArmGic::per_cpu_init)
+    /// writes to `GICC_PMR`, only interrupts of higher priority, that is of
+    /// a lower value, being signalled. The default, `0xff`, unmasks every
+    /// priority.
+    ///
+    /// Systems keeping the highest priorities for watchdogs or pseudo-NMIs
+    /// can start with those alone unmasked, leaving the other interrupts
+    /// masked until the kernel opens the mask itself.
+    pub const fn with_priority_mask(mut self, mask: u8) -> Self {
+        self.gicc.priority_mask = mask;
+        self
+    }
+
+    /// Reserves the interrupts of `reserved` to the firmware, so the driver
+    /// never configures, enables or disables them.
+    ///
+    /// [`init_primary`](GenericArmGic::init_primary) still resets every
+    /// interrupt, so with firmware that set them up, initialise the GIC with
+    /// [`init_primary_handoff`](GenericArmGic::init_primary_handoff).
+    pub const fn with_reserved(mut self, reserved: ReservedSet) -> Self {
+        self.reserved = reserved;
+        self
+    }
+
+    /// Lets the non-secure state generate the secure SGI `sgi` of the
+    /// current CPU as `access` allows, through the banked `GICD_NSACR0`.
+    ///
+    /// Only secure software can do this. Returns [`GicError::Unsupported`] if
+    /// the GIC has no Security Extensions or for
+    /// [`NsSgiAccess::Group0AndSecureGroup1`], or [`GicError::ReadOnlyConfig`]
+    /// if the write didn't take, as from the non-secure state.
+    pub fn set_sgi_ns_access(&self, sgi: SgiId, access: NsSgiAccess) -> Result<(), GicError> {
+        if access == NsSgiAccess::Group0AndSecureGroup1 {
+            return Err(GicError::Unsupported);
+        }
+        let (nsacr, shift) = self.sgi_nsacr(sgi)?;
+        if distributor_common::set_ns_access(nsacr, shift, access) == access {
+            Ok(())
+        } else {
+            Err(GicError::ReadOnlyConfig)
+        }
+    }
+
+    /// Returns which secure SGI `sgi` the non-secure state may generate, like
+    /// [`set_sgi_ns_access`](Self::set_sgi_ns_access). Non-secure software
+    /// reads [`NsSgiAccess::None`].
+    pub fn sgi_ns_access(&self, sgi: SgiId) -> Result<NsSgiAccess, GicError> {
+        let (nsacr, shift) = self.sgi_nsacr(sgi)?;
+        // The upper values also grant access to the pending state and targets
+        // of the SGI, which includes generating it.
+        Ok(match distributor_common::ns_access(nsacr, shift) {
+            NsSgiAccess::None => NsSgiAccess::None,
+            _ => NsSgiAccess::Group0,
+        })
+    }
+
+    /// Configures and enables the interrupts of a Trusted Firmware-A style
+    /// properties table, in order.
+    ///
+    /// GICv2 only has Group 0 and the non-secure Group 1, so
+    /// [`SecurityGroup::G1S`] fails with [`GicError::Unsupported`]. Private
+    /// interrupts are configured for the current CPU only. It stops at the
+    /// first error, leaving the following interrupts unconfigured.
+    pub fn apply_interrupt_props(&self, props: &[InterruptProp]) -> Result<(), GicError> {
+        for prop in props {
+            if prop.group == SecurityGroup::G1S {
+                return Err(GicError::Unsupported);
+            }
+            self.configure(prop.intid, prop.irq_config())?;
+            if !prop.intid.is_private() {
+                seal::pin_routing(prop.intid)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Initialises the GIC like [`init_primary`](GenericArmGic::init_primary),
+    /// then applies the properties table `props` like
+    /// [`apply_interrupt_props`](Self::apply_interrupt_props).
+    pub fn init_primary_with_props(&mut self, props: &[InterruptProp]) -> Result<(), GicError> {
+        self.init_primary()?;
+        self.apply_interrupt_props(props)
+    }
+
+    /// Sends the SGI `sgi` to the CPUs with the given `MPIDR_EL1` values, with
+    /// a single write to `GICD_SGIR`.
+    ///
+    /// CPUs the GIC doesn't serve, as [`cpu_index`](GenericArmGic::cpu_index)
+    /// reports, are skipped.
+    pub fn send_sgi_multi(&self, sgi: SgiId, mpidrs: &[u64]) {
+        let targets: CpuSet = mpidrs
+            .iter()
+            .filter_map(|&mpidr| self.cpu_index(mpidr))
+            .collect();
+        self.send_sgi(sgi, &targets);
+    }
+
+    /// Returns the `GICD_NSACR0` register holding the field of `sgi`, and the
+    /// shift of the field.
+    fn sgi_nsacr(&self, sgi: SgiId) -> Result<(&ReadWrite<u32>, usize), GicError> {
+        if self.gicd.gicv1 || self.gicd.regs().TYPER.get() & GicDistributor::SECURITY_EXTN_MASK == 0
+        {
+            return Err(GicError::Unsupported);
+        }
+        Ok(distributor_common::field(
+            &self.gicd.regs().NSACR,
+            sgi.number().into(),
+            2,
+        ))
+    }
+
+    /// Offset of `GICC_IAR` from the base of the CPU interface.
+    pub const GICC_IAR_OFFSET: usize = 0x0c;
+    /// Offset of `GICC_EOIR` from the base of the CPU interface.
+    pub const GICC_EOIR_OFFSET: usize = 0x10;
+    /// Mask of the interrupt ID in `GICC_IAR`, above which an SGI has the
+    /// number of the CPU that sent it.
+    pub const IAR_INTID_MASK: u32 = 0x3ff;
+
+    /// Acknowledges the highest priority signalled interrupt, recording it if
+    /// it is spurious and `count_spurious` is set.
+    fn acknowledge(&self, count_spurious: bool) -> Option<IntId> {
+        self.acknowledge_raw(count_spurious).map(|(intid, _)| intid)
+    }
+
+    /// Acknowledges the highest priority signalled interrupt like
+    /// [`acknowledge`](Self::acknowledge), also returning the `GICC_IAR` value
+    /// [`end_raw`](Self::end_raw) needs to end an SGI.
+    pub(crate) fn acknowledge_raw(&self, count_spurious: bool) -> Option<(IntId, u32)> {
+        let iar = self.gicc.regs().IAR.get();
+        let id = (iar & Self::IAR_INTID_MASK) as usize;
+        if id == IntId::SPURIOUS && count_spurious {
+            spurious::record();
+        }
+        if id >= IntId::SPECIAL_START {
+            None
+        } else {
+            #[cfg(feature = "lifecycle-tracking")]
+            lifecycle::acknowledged(IntId(id));
+            if self.disable_on_ack {
+                self.gicd.set_enable(id, false);
+            }
+            Some((IntId(id), iar))
+        }
+    }
+
+    /// Ends `intid`, writing `iar`, which has the source CPU of an SGI, to
+    /// `GICC_EOIR`.
+    pub(crate) fn end_raw(&self, intid: IntId, iar: u32) {
+        self.gicc.regs().EOIR.set(iar);
+        #[cfg(feature = "lifecycle-tracking")]
+        lifecycle::ended(intid);
+        if self.disable_on_ack {
+            self.gicd.set_enable(intid.0, true);
         }
     }
+
+    /// Reads `GICC_IAR`, acknowledging the highest priority interrupt, and
+    /// returns the raw value, which is a special INTID if none is pending.
+    ///
+    /// The value includes the source CPU of an SGI, which must be written back
+    /// with [`eoi_raw`](Self::eoi_raw). Unlike
+    /// [`get_and_acknowledge_interrupt`](GenericArmGic::get_and_acknowledge_interrupt)
+    /// this doesn't disable the interrupt with
+    /// [`set_disable_on_ack`](GenericArmGic::set_disable_on_ack). It counts
+    /// spurious interrupts and keeps the `lifecycle-tracking` record, so
+    /// handlers acknowledging interrupts in assembly, at [`GICC_IAR_OFFSET`](Self::GICC_IAR_OFFSET),
+    /// can mix with ones using the driver by calling [`eoi_raw`](Self::eoi_raw)
+    /// for them.
+    #[inline(always)]
+    pub fn ack_raw(&self) -> u32 {
+        let raw = self.gicc.regs().IAR.get();
+        if (raw & Self::IAR_INTID_MASK) as usize == IntId::SPURIOUS {
+            spurious::record();
+        }
+        #[cfg(feature = "lifecycle-tracking")]
+        if ((raw & Self::IAR_INTID_MASK) as usize) < IntId::SPECIAL_START {
+            lifecycle::acknowledged(IntId((raw & Self::IAR_INTID_MASK) as usize));
+        }
+        raw
+    }
+
+    /// Writes `raw`, as returned by [`ack_raw`](Self::ack_raw) or read from
+    /// `GICC_IAR` by assembly, to `GICC_EOIR`, ending the interrupt.
+    #[inline(always)]
+    pub fn eoi_raw(&self, raw: u32) {
+        self.gicc.regs().EOIR.set(raw);
+        #[cfg(feature = "lifecycle-tracking")]
+        lifecycle::ended(IntId((raw & Self::IAR_INTID_MASK) as usize));
+    }
+
+    /// Constructs a new instance of the driver like [`new`](Self::new), from
+    /// register frames whose memory type was checked by [`DeviceMmio::new`].
+    pub fn from_mmio(gicd: DeviceMmio, gicc: DeviceMmio) -> Self {
+        Self::new(gicd.as_ptr(), gicc.as_ptr())
+    }
+
+    /// Sanity-checks the register mappings, as a debugging aid.
+    ///
+    /// The identification registers of the distributor and the CPU interface
+    /// are read twice, and must agree and identify a GICv1 or GICv2. Returns
+    /// [`GicError::InvalidMapping`] if they don't.
+    pub fn validate_mapping(&self) -> Result<(), GicError> {
+        let gicd = self.gicd.regs();
+        check_stable(|| gicd.IIDR.get())?;
+        let arch = (check_stable(|| gicd.PIDR2.get())? >> GicDistributor::PIDR2_ARCH_SHIFT)
+            & GicDistributor::PIDR2_ARCH_MASK;
+        if !matches!(arch, 1 | 2) {
+            error!(
+                "GICv2: GICD_PIDR2.ArchRev {:#x} doesn't identify a GICv2",
+                arch
+            );
+            return Err(GicError::InvalidMapping);
+        }
+        check_stable(|| self.gicc.regs().IIDR.get())?;
+        Ok(())
+    }
+
+    /// Like [`validate_mapping`](Self::validate_mapping), also asking `query`
+    /// for the memory type each register frame is mapped as.
+    pub fn validate_mapping_with(
+        &self,
+        query: impl Fn(*const u8) -> MemoryType,
+    ) -> Result<(), GicError> {
+        check_memory_type(self.gicd.base.as_ptr().cast(), &query)?;
+        check_memory_type(self.gicc.base.as_ptr().cast(), &query)?;
+        self.validate_mapping()
+    }
+
+    /// Moves the driver to new distributor and CPU interface base addresses,
+    /// keeping everything it learned about the GIC.
+    ///
+    /// This lets a kernel switch from an identity mapping to its final one
+    /// after enabling the MMU, without initialising the GIC again.
+    ///
+    /// # Safety
+    ///
+    /// The new addresses must map the same registers as the old ones, with the
+    /// requirements of [`new`](Self::new).
+    pub unsafe fn rebase(&mut self, gicd: *mut u8, gicc: *mut u8) {
+        self.gicd.base = NonNull::new(gicd).unwrap().cast();
+        self.gicc.base = NonNull::new(gicc).unwrap().cast();
+    }
+
+    /// Reads the raw 32-bit GICD register at `offset` bytes from its base.
+    ///
+    /// This is an escape hatch for implementation defined registers that the
+    /// driver does not model.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be 4-byte aligned and name a register inside the distributor frame.
+    /// The access bypasses the driver, so it must not leave state the driver relies on
+    /// in a configuration it doesn't expect.
+    pub unsafe fn gicd_read(&self, offset: usize) -> u32 {
+        read_raw(self.gicd.base, offset)
+    }
+
+    /// Writes `value` to the raw 32-bit GICD register at `offset` bytes from its base.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be 4-byte aligned and name a register inside the distributor frame.
+    /// The access bypasses the driver, so it must not leave state the driver relies on
+    /// in a configuration it doesn't expect.
+    pub unsafe fn gicd_write(&self, offset: usize, value: u32) {
+        write_raw(self.gicd.base, offset, value)
+    }
+
+    /// Reads the raw 32-bit GICC register at `offset` bytes from its base.
+    ///
+    /// This is an escape hatch for implementation defined registers that the
+    /// driver does not model.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be 4-byte aligned and name a register inside the CPU interface frame.
+    /// The access bypasses the driver, so it must not leave state the driver relies on
+    /// in a configuration it doesn't expect.
+    pub unsafe fn gicc_read(&self, offset: usize) -> u32 {
+        read_raw(self.gicc.base, offset)
+    }
+
+    /// Writes `value` to the raw 32-bit GICC register at `offset` bytes from its base.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be 4-byte aligned and name a register inside the CPU interface frame.
+    /// The access bypasses the driver, so it must not leave state the driver relies on
+    /// in a configuration it doesn't expect.
+    pub unsafe fn gicc_write(&self, offset: usize, value: u32) {
+        write_raw(self.gicc.base, offset, value)
+    }
 }
 
 impl GenericArmGic for GicV2 {
     /// Initialises the GIC.
-    fn init_primary(&mut self) {
-        self.gicd.init();
+    fn init_primary(&mut self) -> Result<(), GicError> {
+        self.gicd.init(false, &self.reserved)?;
         self.gicc.init(&self.gicd);
+        Ok(())
+    }
+
+    fn init_primary_handoff(&mut self) -> Result<(), GicError> {
+        self.gicd.init(true, &self.reserved)?;
+        self.gicc.init(&self.gicd);
+        Ok(())
+    }
+
+    fn early_init(&mut self) -> Result<(), GicError> {
+        self.gicd.early_init()?;
+        self.gicc.init(&self.gicd);
+        Ok(())
+    }
+
+    fn finish_init(&mut self) {
+        self.gicd.init_spis(false, &self.reserved);
+    }
+
+    fn translate(&self, id: usize, int_type: InterruptType) -> Option<IntId> {
+        let intid = translate_irq(id, int_type)?;
+        (intid < self.gicd.max_irqs()).then_some(IntId(intid))
     }
 
     /// Initialises the GIC for the current CPU core.
@@ -222,41 +928,388 @@ impl GenericArmGic for GicV2 {
     }
 
     /// Configures the trigger type for the interrupt with the given ID.
-    fn set_trigger(&mut self, intid: IntId, tm: TriggerMode) {
-        // Only configurable for SPI interrupts
-        if intid.0 < IntId::SPI_START {
+    fn set_trigger(&self, intid: IntId, tm: TriggerMode) -> Result<(), GicError> {
+        self.reserved.check(intid)?;
+        seal::check()?;
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        // SGIs are always edge-triggered and PPIs may be fixed too, which
+        // the read-back catches.
+        self.gicd.set_trigger(intid.0, tm)
+    }
+
+    fn reconfigure_trigger(&self, intid: IntId, tm: TriggerMode) -> Result<(), GicError> {
+        self.reserved.check(intid)?;
+        seal::check()?;
+        let enabled = self.gicd.is_enabled(intid.0);
+        self.gicd.set_enable(intid.0, false);
+        self.gicd.clear_pending(intid.0);
+        let result = self.set_trigger(intid, tm);
+        if enabled {
+            self.gicd.set_enable(intid.0, true);
+        }
+        result
+    }
+
+    fn set_group(&self, intid: IntId, group: InterruptGroup) {
+        if self.reserved.refuses(intid, "grouping") || seal::refuses(intid, "grouping") {
+            return;
+        }
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        self.gicd.set_group(intid.0, group);
+    }
+
+    fn set_priority(&self, intid: IntId, priority: u8) {
+        if self.reserved.refuses(intid, "prioritisation") {
+            return;
+        }
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        self.gicd.set_priority(intid.0, priority);
+    }
+
+    fn add_spis(&self, first: IntId, count: usize, config: IrqConfig) -> Result<(), GicError> {
+        self.gicd.check_spi_range(first, count)?;
+        seal::check()?;
+        for id in first.0..first.0 + count {
+            self.reserved.check(IntId(id))?;
+        }
+        for id in first.0..first.0 + count {
+            self.gicd.reset_spi(id);
+            self.configure(IntId(id), config)?;
+        }
+        Ok(())
+    }
+
+    fn set_affinity(&self, intid: IntId, affinity: u64) {
+        // The targets of private interrupts are read-only.
+        if !intid.is_spi()
+            || self.reserved.refuses(intid, "routing")
+            || seal::refuses_routing(intid)
+        {
             return;
         }
-        self.gicd.set_trigger(intid.0, tm);
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        self.gicd.set_targets(intid.0, affinity as u8);
+    }
+
+    /// From the secure state, Group 0 is disabled too.
+    fn cpu_interface_disable(&self) {
+        self.gicc.set_enabled(false);
+    }
+
+    fn cpu_interface_enable(&self) {
+        self.gicc.set_enabled(true);
+    }
+
+    fn set_priority_grouping(&self, grouping: PriorityGrouping) -> PriorityGrouping {
+        self.gicc.bpr1().set(grouping.bpr1());
+        self.priority_grouping()
+    }
+
+    fn priority_grouping(&self) -> PriorityGrouping {
+        PriorityGrouping::from_bpr1(self.gicc.bpr1().get())
+    }
+
+    /// Only the secure view of a GIC with the Security Extensions, or one
+    /// without them, has both groups: otherwise this logs an error and leaves
+    /// `GICD_CTLR` and `GICC_CTLR` alone.
+    fn enable_fiq_split(&self) {
+        if self.gicc.view == CtlrView::Full {
+            rmw::modify(&self.gicd.regs().CTLR, |ctlr| {
+                ctlr | GicDistributor::GICD_ENABLE | GicDistributor::GICD_ENABLE_GRP1
+            });
+        }
+        self.gicc.enable_fiq_split();
     }
 
     /// Enables the interrupt with the given ID.
-    fn enable_interrupt(&mut self, intid: IntId) {
-        let index = intid.0 / 32;
-        let bit = 1 << (intid.0 % 32);
-        self.gicd.regs().ISENABLER[index].set(bit);
+    fn enable_interrupt(&self, intid: IntId) {
+        if self.reserved.refuses(intid, "enabling") {
+            return;
+        }
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "enabled");
+        #[cfg(feature = "lifecycle-tracking")]
+        lifecycle::check_inactive(intid, "enabled");
+        self.gicd.set_enable(intid.0, true);
     }
 
     /// Disable the interrupt with the given ID.
-    fn disable_interrupt(&mut self, intid: IntId) {
-        let index = intid.0 / 32;
-        let bit = 1 << (intid.0 % 32);
-        self.gicd.regs().ICENABLER[index].set(bit);
+    fn disable_interrupt(&self, intid: IntId) {
+        if self.reserved.refuses(intid, "disabling") {
+            return;
+        }
+        self.gicd.set_enable(intid.0, false);
+    }
+
+    fn is_reserved(&self, intid: IntId) -> bool {
+        self.reserved.contains(intid)
+    }
+
+    #[cfg(feature = "claim-tracking")]
+    fn enable_interrupt_claimed(&self, intid: IntId, owner: &'static str) {
+        if self.reserved.refuses(intid, "enabling") {
+            return;
+        }
+        claim::claim(intid, owner);
+        self.gicd.set_enable(intid.0, true);
+    }
+
+    #[cfg(feature = "claim-tracking")]
+    fn release_interrupt(&self, intid: IntId) -> Option<&'static str> {
+        self.gicd.set_enable(intid.0, false);
+        claim::release(intid)
     }
 
     fn get_and_acknowledge_interrupt(&self) -> Option<IntId> {
-        let iar = self.gicc.regs().IAR.get();
+        self.acknowledge(true)
+    }
+
+    fn drain(&self, mut handler: impl FnMut(IntId)) -> usize {
+        let mut count = 0;
+        while let Some((intid, iar)) = self.acknowledge_raw(count == 0) {
+            handler(intid);
+            self.end_raw(intid, iar);
+            count += 1;
+        }
+        count
+    }
+
+    /// Informs the interrupt controller that the CPU has completed processing the given interrupt.
+    /// This drops the interrupt priority and deactivates the interrupt.
+    fn end_interrupt(&self, intid: IntId) {
+        self.end_raw(intid, intid.0 as u32);
+    }
+
+    fn set_disable_on_ack(&mut self, enable: bool) {
+        self.disable_on_ack = enable;
+    }
+
+    /// GICv1 has no aliased registers, but its secure `GICC_IAR` also
+    /// acknowledges Group 1 interrupts, so that is used for both groups.
+    fn get_and_acknowledge_group_interrupt(&self, group: InterruptGroup) -> Option<IntId> {
+        let iar = match group {
+            InterruptGroup::Group1 if !self.gicd.gicv1 => self.gicc.regs().AIAR.get(),
+            _ => self.gicc.regs().IAR.get(),
+        };
         let id = (iar & 0x3ff) as usize;
+        if id == IntId::SPURIOUS {
+            spurious::record();
+        }
         if id >= IntId::SPECIAL_START {
             None
         } else {
+            #[cfg(feature = "lifecycle-tracking")]
+            lifecycle::acknowledged(IntId(id));
             Some(IntId(id))
         }
     }
 
-    /// Informs the interrupt controller that the CPU has completed processing the given interrupt.
-    /// This drops the interrupt priority and deactivates the interrupt.
-    fn end_interrupt(&self, intid: IntId) {
-        self.gicc.regs().EOIR.set(intid.0 as u32);
+    fn end_group_interrupt(&self, intid: IntId, group: InterruptGroup) {
+        match group {
+            InterruptGroup::Group1 if !self.gicd.gicv1 => {
+                self.gicc.regs().AEOIR.set(intid.0 as u32)
+            }
+            _ => self.gicc.regs().EOIR.set(intid.0 as u32),
+        }
+        #[cfg(feature = "lifecycle-tracking")]
+        lifecycle::ended(intid);
+    }
+
+    /// GICv1 has neither `GICC_DIR` nor writable active bits, so this returns
+    /// [`GicError::Unsupported`] there.
+    fn deactivate_unacked(&self, intid: IntId) -> Result<(), GicError> {
+        if intid.0 >= self.gicd.max_irqs() {
+            return Err(GicError::InvalidIntId);
+        }
+        if self.gicd.gicv1 {
+            return Err(GicError::Unsupported);
+        }
+        if self.gicc.regs().CTLR.get() & GicCpuInterface::GICC_EOI_MODE != 0 {
+            self.gicc.regs().DIR.set(intid.0 as u32);
+        } else {
+            let (icactiver, shift) =
+                distributor_common::field(&self.gicd.regs().ICACTIVER, intid.0, 1);
+            icactiver.set(1 << shift);
+        }
+        Ok(())
+    }
+
+    fn trigger_spi(&self, spi: Spi) {
+        let intid = spi.intid();
+        self.gicd.regs().ISPENDR[intid.0 / 32].set(1 << (intid.0 % 32));
+    }
+
+    fn clear_spi(&self, spi: Spi) {
+        let intid = spi.intid();
+        self.gicd.regs().ICPENDR[intid.0 / 32].set(1 << (intid.0 % 32));
+    }
+
+    /// GICv2 doesn't know the affinity of its CPU interfaces, so this assumes
+    /// the single cluster of up to 8 CPUs GICv2 systems usually have, with
+    /// `Aff0` as the CPU interface number.
+    fn cpu_index(&self, mpidr: u64) -> Option<usize> {
+        let aff0 = (mpidr & 0xff) as usize;
+        (mpidr & GicDistributor::MPIDR_UPPER_AFF_MASK == 0 && aff0 < self.gicd.support_cpu)
+            .then_some(aff0)
+    }
+
+    fn affinity_for<const W: usize>(&self, cpus: &CpuSet<W>) -> Option<u64> {
+        let targets = cpus.target_list();
+        (targets != 0).then_some(targets.into())
+    }
+
+    fn send_sgi<const W: usize>(&self, sgi: SgiId, targets: &CpuSet<W>) {
+        fence::dsb(Domain::IshSt, "send SGI");
+        self.gicd.regs().SGIR.set(
+            u32::from(targets.target_list()) << GicDistributor::SGIR_TARGET_LIST_SHIFT
+                | u32::from(sgi.number()),
+        );
+    }
+
+    fn raise_self_sgi(&self, sgi: SgiId) {
+        fence::dsb(Domain::IshSt, "send SGI");
+        self.gicd
+            .regs()
+            .SGIR
+            .set(GicDistributor::SGIR_FILTER_SELF | u32::from(sgi.number()));
+    }
+
+    fn capabilities(&self) -> GicCapabilities {
+        // The remaining features don't exist in GICv2, and the virtual
+        // interface control registers aren't mapped by the driver.
+        GicCapabilities {
+            security_extension: self.gicd.regs().TYPER.get() & GicDistributor::SECURITY_EXTN_MASK
+                != 0,
+            spis: self.gicd.max_irqs() - IntId::SPI_START,
+            ..GicCapabilities::default()
+        }
+    }
+
+    fn inspect<const N: usize>(&self) -> HandoffState<N> {
+        let gicd = self.gicd.regs();
+        let mut state = HandoffState::new(gicd.CTLR.get());
+        for i in 0..self.gicd.max_irqs().div_ceil(32) {
+            state.enabled[i] = gicd.ISENABLER[i].get();
+            state.group1[i] = gicd.IGROUPRn[i].get();
+        }
+        for id in IntId::SPI_START..self.gicd.max_irqs() {
+            if dump::bit(&gicd.ISENABLER, id) {
+                state.push_route(IntId(id), dump::byte(&gicd.ITARGETSR, id).into());
+            }
+        }
+        state
+    }
+
+    fn diagnose(&self, intid: IntId) -> Result<Diagnosis, GicError> {
+        let id = intid.0;
+        if id >= self.gicd.max_irqs() {
+            return Err(GicError::InvalidIntId);
+        }
+        let gicd = self.gicd.regs();
+        let gicc = self.gicc.regs();
+        let state = IrqState::read(
+            id,
+            &gicd.ISENABLER,
+            &gicd.ISPENDR,
+            &gicd.ISACTIVER,
+            &gicd.IPRIORITYR,
+        );
+        // In the non-secure view only Group 1 is visible, and bit 0 of both
+        // control registers enables it. Without groups, bit 0 enables all.
+        let (group1, enable_bit) = match self.gicc.view {
+            CtlrView::Full => {
+                let group1 = dump::bit(&gicd.IGROUPRn, id);
+                (group1, if group1 { 1 << 1 } else { 1 << 0 })
+            }
+            CtlrView::NonSecure => (true, 1 << 0),
+            CtlrView::Ungrouped => (false, 1 << 0),
+        };
+        // The first targets register reads as the CPU interface reading it.
+        let this_cpu = dump::byte(&gicd.ITARGETSR, 0);
+        Ok(Diagnosis {
+            intid,
+            distributor_enabled: gicd.CTLR.get() & enable_bit != 0,
+            redistributor_awake: None,
+            enabled: state.enabled,
+            pending: state.pending,
+            active: state.active,
+            group1,
+            group_enabled: gicc.CTLR.get() & enable_bit != 0,
+            priority: state.priority,
+            priority_mask: gicc.PMR.get() as u8,
+            running_priority: gicc.RPR.get() as u8,
+            routed_here: intid.is_private() || dump::byte(&gicd.ITARGETSR, id) & this_cpu != 0,
+        })
+    }
+
+    fn dump_state(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        let gicd = self.gicd.regs();
+        writeln!(
+            out,
+            "GICD_CTLR={:#x} GICD_TYPER={:#x} GICD_IIDR={:#x}",
+            gicd.CTLR.get(),
+            gicd.TYPER.get(),
+            gicd.IIDR.get()
+        )?;
+        for id in 0..self.gicd.max_irqs() {
+            let mut state = IrqState::read(
+                id,
+                &gicd.ISENABLER,
+                &gicd.ISPENDR,
+                &gicd.ISACTIVER,
+                &gicd.IPRIORITYR,
+            );
+            if id >= IntId::SPI_START {
+                state.target = Some(dump::byte(&gicd.ITARGETSR, id).into());
+            }
+            state.write(out, IntId(id))?;
+        }
+
+        let gicc = self.gicc.regs();
+        writeln!(
+            out,
+            "GICC_CTLR={:#x} GICC_PMR={:#x} GICC_BPR={:#x} GICC_RPR={:#x} GICC_HPPIR={:#x}",
+            gicc.CTLR.get(),
+            gicc.PMR.get(),
+            gicc.BPR.get(),
+            gicc.RPR.get(),
+            gicc.HPPIR.get()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A CPU interface frame in memory.
+    #[repr(C, align(4096))]
+    struct Frame([u32; size_of::<GicCpuInterfaceRegs>() / 4]);
+
+    #[test]
+    fn test_drain_sgi_source() {
+        let mut gicd = [0u32; size_of::<GicDistributorRegs>() / 4];
+        let mut frame = Frame([0; size_of::<GicCpuInterfaceRegs>() / 4]);
+        // SGI 5 from CPU 2.
+        let iar = 2 << 10 | 5;
+        frame.0[GicV2::GICC_IAR_OFFSET / 4] = iar;
+        let regs = frame.0.as_mut_ptr();
+        let gic = GicV2::new(gicd.as_mut_ptr().cast(), regs.cast());
+
+        let count = gic.drain(|intid| {
+            assert_eq!(intid, IntId::sgi(5));
+            // SAFETY: The frame outlives the driver, which only reads and
+            // writes it through volatile accesses.
+            unsafe {
+                regs.add(GicV2::GICC_IAR_OFFSET / 4)
+                    .write_volatile(IntId::SPURIOUS as u32)
+            };
+        });
+        assert_eq!(count, 1);
+        assert_eq!(frame.0[GicV2::GICC_EOIR_OFFSET / 4], iar);
     }
 }
@@ -2,17 +2,144 @@
 //!
 //! The official documentation: <https://developer.arm.com/documentation/ihi0048/latest/>
 
-use aarch64_cpu::registers::MPIDR_EL1;
-use core::hint::spin_loop;
+use core::fmt;
 use core::ptr::NonNull;
+use core::slice;
 use tock_registers::interfaces::{Readable, Writeable};
-
+use tock_registers::registers::ReadWrite;
+
+use crate::cache::clean_dcache_range;
+#[cfg(feature = "claim-tracking")]
+use crate::claim;
+use crate::delay::{Delay, Poller};
+use crate::distributor_common;
+use crate::dump::{self, IrqState};
+use crate::fence::{self, Domain};
+#[cfg(feature = "lifecycle-tracking")]
+use crate::lifecycle;
+use crate::logging::{debug, error, info};
+use crate::mapping::{check_memory_type, check_stable, DeviceMmio, MemoryType};
+use crate::perf::{self, Counter};
 use crate::registers::gicv3_regs::*;
-use crate::sysregs::{read_sysreg, write_sysreg};
-use crate::{GenericArmGic, IntId, TriggerMode};
+use crate::registers::{read_raw, write_raw};
+use crate::reserved::ReservedSet;
+use crate::rmw;
+use crate::seal;
+use crate::sgi::{self, sgi1r_target, SgiTargets, SGI1R_GROUP_MASK};
+use crate::spurious;
+use crate::sysregs::{self, read_sysreg, write_sysreg, ExceptionLevel};
+use crate::{
+    translate_irq, CpuSet, Diagnosis, FeatureSummary, GenericArmGic, GicCapabilities, GicError,
+    GroupMismatch, HandoffState, IntId, InterruptGroup, InterruptProp, InterruptType, IrqConfig,
+    NsSgiAccess, PriorityGrouping, SecurityGroup, SgiId, Spi, TriggerMode,
+};
 
 const SGI_OFFSET: usize = 0x10000;
 
+/// Returns the affinity of the current CPU, laid out as `Aff3.Aff2.Aff1.Aff0`
+/// like [`GicRedistributor::affinity`].
+fn current_affinity() -> u32 {
+    mpidr_affinity(sysregs::mpidr())
+}
+
+/// Packs the affinity fields of an `MPIDR_EL1` value as `Aff3.Aff2.Aff1.Aff0`.
+fn mpidr_affinity(mpidr: u64) -> u32 {
+    ((mpidr >> 8) & 0xff00_0000 | mpidr & 0xff_ffff) as u32
+}
+
+/// Unpacks an `Aff3.Aff2.Aff1.Aff0` affinity into the layout of `MPIDR_EL1`.
+fn affinity_mpidr(affinity: u32) -> u64 {
+    u64::from(affinity & 0xff_ffff) | u64::from(affinity >> 24) << 32
+}
+
+/// Returns whether the current CPU can use the GIC system register interface.
+fn sysreg_interface_enabled() -> bool {
+    const PFR0_GIC_SHIFT: u64 = 24;
+    const PFR0_GIC_MASK: u64 = 0xf;
+
+    // SAFETY: Reading this system register doesn't access memory in any way.
+    #[cfg(not(target_arch = "arm"))]
+    let pfr0 = unsafe { read_sysreg!(id_aa64pfr0_el1) };
+    // AArch32 reports the interface in ID_PFR1.GIC, bits [31:28].
+    // SAFETY: Reading this system register doesn't access memory in any way.
+    #[cfg(target_arch = "arm")]
+    let pfr0 = unsafe { read_sysreg!(id_pfr1) } >> 4;
+    if (pfr0 >> PFR0_GIC_SHIFT) & PFR0_GIC_MASK == 0 {
+        return false;
+    }
+    // A higher exception level may keep the interface disabled, making SRE
+    // read as zero.
+    // SAFETY: Accessing these system registers doesn't access memory in any
+    // way, and the CPU implements them.
+    unsafe { ExceptionLevel::current().enable_sre() }
+}
+
+/// The register bank holding the configuration of an interrupt, with the
+/// index of the interrupt within the bank.
+///
+/// Extended SPIs have their own `GICD_*RnE` registers, while in the
+/// redistributor the extended PPIs follow the classic private interrupts in
+/// the same registers.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum RegBank {
+    /// SGIs, PPIs and SPIs.
+    Classic(usize),
+    /// Extended PPIs and SPIs.
+    Extended(usize),
+}
+
+impl RegBank {
+    fn of(intid: IntId) -> Self {
+        if intid.is_espi() {
+            Self::Extended(intid.0 - IntId::ESPI_START)
+        } else if intid.is_eppi() {
+            Self::Extended(intid.0 - IntId::EPPI_START)
+        } else {
+            Self::Classic(intid.0)
+        }
+    }
+
+    /// Returns the register holding the `width`-bit field of `intid` and the
+    /// shift of the field, from the `classic` or `extended` registers.
+    fn field<'a, R>(
+        intid: IntId,
+        classic: &'a [R],
+        extended: &'a [R],
+        width: usize,
+    ) -> (&'a R, usize) {
+        match Self::of(intid) {
+            Self::Classic(n) => distributor_common::field(classic, n, width),
+            Self::Extended(n) => distributor_common::field(extended, n, width),
+        }
+    }
+
+    /// Like [`field`](Self::field), for the private interrupt registers of a
+    /// redistributor, where the extended PPIs follow the 32 classic ones.
+    fn private_field<R>(intid: IntId, regs: &[R], width: usize) -> (&R, usize) {
+        Self::field(intid, regs, &regs[width..], width)
+    }
+}
+
+impl SecurityGroup {
+    /// Returns the group and group modifier bits selecting this group.
+    fn bits(self) -> (bool, bool) {
+        match self {
+            Self::G0 => (false, false),
+            Self::G1S => (false, true),
+            Self::G1NS => (true, false),
+        }
+    }
+
+    /// Returns the group selected by the given group and group modifier bits.
+    fn from_bits(group: bool, modifier: bool) -> Self {
+        match (group, modifier) {
+            (true, _) => Self::G1NS,
+            (false, true) => Self::G1S,
+            (false, false) => Self::G0,
+        }
+    }
+}
+
 /// The GIC-V3 distributor.
 ///
 /// The Distributor block performs interrupt prioritization and distribution
@@ -39,13 +166,20 @@ pub struct GicDistributor {
     support_espi: usize,
     #[allow(dead_code)]
     support_cpu: usize,
+    poller: Poller,
+    /// Priority given to every interrupt at initialisation.
+    default_priority: u8,
 }
 
 /// The GIC-V3 redistributor.
+///
+/// Each PE has its own redistributor, holding the configuration of its private
+/// interrupts and its LPI tables. See [`GicV3::for_each_redistributor`].
 #[derive(Debug, Copy, Clone)]
 pub struct GicRedistributor {
     gicr_base: NonNull<GicRedistributorRegs>,
     support_ppi: usize,
+    poller: Poller,
 }
 
 unsafe impl Send for GicDistributor {}
@@ -55,7 +189,6 @@ unsafe impl Send for GicRedistributor {}
 unsafe impl Sync for GicRedistributor {}
 
 struct Gicv3Quirk {
-    #[allow(dead_code)]
     desc: &'static str,
     iidr: u32,
     mask: u32,
@@ -73,6 +206,17 @@ impl GicDistributor {
     const GIC_PIDR2_ARCH_GICV4: u32 = 0x40;
 
     const GICD_RWP_MASK: u32 = 1 << 31;
+    /// `GICD_IROUTER<n>.Interrupt_Routing_Mode`: any participating PE may take the SPI.
+    const IROUTER_IRM: u64 = 1 << 31;
+
+    const NMI_MASK: u32 = 1 << 9;
+    const SECURITY_EXTN_MASK: u32 = 1 << 10;
+    const MBIS_MASK: u32 = 1 << 16;
+    const LPIS_MASK: u32 = 1 << 17;
+    const RSS_MASK: u32 = 1 << 26;
+    const TYPER2_NASSGICAP_MASK: u32 = 1 << 8;
+    const ID_BITS_SHIFT: u32 = 19;
+    const ID_BITS_MASK: u32 = 0x1f;
 
     /// The GIC-V3 erratum.
     ///
@@ -107,6 +251,21 @@ impl GicDistributor {
             support_cpu: 0,
             support_espi: 0,
             support_irqs: 0,
+            poller: Poller::new(None),
+            default_priority: distributor_common::DEFAULT_PRIORITY,
+        }
+    }
+
+    /// Construct a new GIC distributor instance from the base address, which
+    /// must not be null.
+    const unsafe fn new_unchecked(base: *mut u8) -> Self {
+        Self {
+            base: NonNull::new_unchecked(base).cast(),
+            support_cpu: 0,
+            support_espi: 0,
+            support_irqs: 0,
+            poller: Poller::new(None),
+            default_priority: distributor_common::DEFAULT_PRIORITY,
         }
     }
 
@@ -114,11 +273,85 @@ impl GicDistributor {
         unsafe { self.base.as_ref() }
     }
 
-    fn validate_dist_version(&self) {
+    /// Returns the number of interrupt IDs the distributor implements.
+    fn max_irqs(&self) -> usize {
+        // The maximum number of interrupts that the GIC supports
+        // If ITLinesNumber=N, the maximum number of interrupts is 32(N+1)
+        let typer = self.regs().TYPER.get();
+        let irq_num = (((typer & Self::IT_LINES_NUM_MASK) + 1) * 32) as usize;
+        irq_num.min(IntId::GIC_MAX_IRQ)
+    }
+
+    /// Returns the number of extended SPIs the distributor implements.
+    fn max_espis(&self) -> usize {
+        // Extended SPI range uses INTIDs 4096 - 5119.
+        // This range of SPIs is not available when the GIC is operating in legacy mode.
+        // GICD_TYPER.ESPI indicates whether the extended SPI range is supported or not.
+        // Maximum Extended SPI INTID is (32*(ESPI_range + 1) + 4095)
+        let typer = self.regs().TYPER.get();
+        match typer & Self::ESPI_MASK == 0 {
+            false => {
+                let espi_range = (typer >> Self::ESPI_RANGE_SHIF) & Self::IT_LINES_NUM_MASK;
+                ((espi_range + 1) * 32) as usize
+            }
+            true => 0,
+        }
+    }
+
+    /// Checks that the `count` interrupts from `first` are all SPIs or all
+    /// extended SPIs that `GICD_TYPER` reports as implemented.
+    ///
+    /// Returns [`GicError::Unsupported`] for SPIs if the distributor has none,
+    /// as on some LPI-only GICs, or [`GicError::InvalidIntId`].
+    fn check_spi_range(&self, first: IntId, count: usize) -> Result<(), GicError> {
+        let end = first.0.checked_add(count).ok_or(GicError::InvalidIntId)?;
+        if first.is_spi() && self.max_irqs() <= IntId::SPI_START {
+            return Err(GicError::Unsupported);
+        }
+        let implemented = if first.is_spi() {
+            end <= self.max_irqs()
+        } else {
+            first.is_espi() && end <= IntId::ESPI_START + self.max_espis()
+        };
+        if implemented {
+            Ok(())
+        } else {
+            Err(GicError::InvalidIntId)
+        }
+    }
+
+    /// Disables the SPI or extended SPI `intid`, and clears its pending and
+    /// active state.
+    fn reset_spi(&self, intid: IntId) {
+        let gicd = self.regs();
+        let (icenabler, shift) = RegBank::field(intid, &gicd.ICENABLER, &gicd.ICENABLERnE, 1);
+        icenabler.set(1 << shift);
+        self.wait_rwp();
+        let (icpendr, _) = RegBank::field(intid, &gicd.ICPENDR, &gicd.ICPENDRnE, 1);
+        icpendr.set(1 << shift);
+        let (icactiver, _) = RegBank::field(intid, &gicd.ICACTIVER, &gicd.ICACTIVERnE, 1);
+        icactiver.set(1 << shift);
+    }
+
+    /// Checks that the CPU interface and the distributor are GICv3 or GICv4.
+    fn validate_dist_version(&self) -> Result<(), GicError> {
+        // Without the system register interface, the GIC can only be used as
+        // a GICv2, and the distributor may be too small to hold the GICv3 ID
+        // registers, so don't read them.
+        if !sysreg_interface_enabled() {
+            error!("GICv3: no GIC system register interface, the GIC is a GICv2");
+            return Err(GicError::VersionMismatch(2));
+        }
         let pidr2 = self.regs().PIDR2.get() & Self::GIC_PIDR2_ARCH_MASK;
         match pidr2 {
-            Self::GIC_PIDR2_ARCH_GICV3 | Self::GIC_PIDR2_ARCH_GICV4 => (),
-            _ => panic!("unvalid gic pidr2"),
+            Self::GIC_PIDR2_ARCH_GICV3 | Self::GIC_PIDR2_ARCH_GICV4 => {
+                info!("GICv3: distributor reports GICv{}", pidr2 >> 4);
+                Ok(())
+            }
+            _ => {
+                error!("GICv3: unexpected GICD_PIDR2.ArchRev {:#x}", pidr2 >> 4);
+                Err(GicError::VersionMismatch((pidr2 >> 4) as u8))
+            }
         }
     }
 
@@ -126,77 +359,77 @@ impl GicDistributor {
         let iidr = self.regs().IIDR.get();
         for e in Self::GICV3_QUIRKS {
             if iidr & e.mask == e.iidr {
+                error!("{} matches GICD_IIDR {:#x}", e.desc, iidr);
                 panic!("gic need fix erratum")
             }
         }
     }
 
-    fn init_check(&self) {
-        self.validate_dist_version();
+    fn init_check(&self) -> Result<(), GicError> {
+        self.validate_dist_version()?;
         self.check_gic_erratum();
+        Ok(())
     }
 
     fn base_init(&mut self) {
         let typer = self.regs().TYPER.get();
 
-        // The maximum number of interrupts that the GIC supports
-        //. If ITLinesNumber=N, the maximum number of interrupts is 32(N+1)
-        let irq_num = (((typer & Self::IT_LINES_NUM_MASK) + 1) * 32) as usize;
-        match irq_num {
-            0..=IntId::GIC_MAX_IRQ => self.support_irqs = irq_num,
-            _ => self.support_irqs = IntId::GIC_MAX_IRQ,
+        self.support_irqs = self.max_irqs();
+        self.support_espi = self.max_espis();
+
+        info!(
+            "GICv3: {} interrupts, {} extended SPIs",
+            self.support_irqs, self.support_espi
+        );
+        if self.support_irqs <= IntId::SPI_START {
+            info!("GICv3: no SPIs");
         }
+        debug!("GICv3: GICD_TYPER {:#x}", typer);
+    }
 
-        // Extended SPI range uses INTIDs 4096 - 5119.
-        // This range of SPIs is not available when the GIC is operating in legacy mode.
-        // GICD_TYPER.ESPI indicates whether the extended SPI range is supported or not.
-        // Maximum Extended SPI INTID is (32*(ESPI_range + 1) + 4095)
-        self.support_espi = match typer & Self::ESPI_MASK == 0 {
-            false => {
-                let espi_range = (typer >> Self::ESPI_RANGE_SHIF) & Self::IT_LINES_NUM_MASK;
-                ((espi_range + 1) * 32) as usize
-            }
-            true => 0,
+    /// Returns the number of interrupt ID bits if LPIs are supported.
+    fn lpi_id_bits(&self) -> Option<u32> {
+        let typer = self.regs().TYPER.get();
+        if typer & Self::LPIS_MASK == 0 {
+            return None;
         }
+        Some(((typer >> Self::ID_BITS_SHIFT) & Self::ID_BITS_MASK) + 1)
     }
 
     fn wait_rwp(&self) {
-        let mut loop_count = 10000;
-        loop {
-            if loop_count == 0 {
-                panic!("wait timeout");
-            }
-
-            // When RWP is 0b0, no register write in progress
-            match self.regs().CTLR.get() & Self::GICD_RWP_MASK != 0 {
-                true => {
-                    spin_loop();
-                }
-                false => break,
-            }
-            loop_count -= 1;
+        // When RWP is 0b0, no register write in progress
+        if !self.poller.poll(
+            10000,
+            perf::counting(Counter::RwpPoll, || {
+                self.regs().CTLR.get() & Self::GICD_RWP_MASK == 0
+            }),
+        ) {
+            error!("GICv3: timeout waiting for GICD_CTLR.RWP to clear");
+            panic!("wait timeout");
         }
     }
 
-    fn espi_disable(&self) {
+    fn espi_disable(&self, handoff: bool, kept: &ReservedSet) {
         // disable all espi interrupt
         for i in (0..self.support_espi).step_by(32) {
-            self.regs().ICENABLERnE[i / 32].set(u32::MAX);
-            self.regs().ICACTIVERnE[i / 32].set(u32::MAX);
+            let mask = !kept.word(IntId::espi(i));
+            self.regs().ICENABLERnE[i / 32].set(mask);
+            self.regs().ICACTIVERnE[i / 32].set(mask);
         }
 
         // Configure all ESPI as non-secure Group-1
-        for i in (0..self.support_espi).step_by(32) {
-            self.regs().IGROUPRnE[i / 32].set(u32::MAX);
-        }
-        // Configure all ESPI as level-sensitive
-        for i in (0..self.support_espi).step_by(16) {
-            self.regs().ICFGRnE[i / 16].set(0);
+        if !handoff {
+            for i in (0..self.support_espi).step_by(32) {
+                self.regs().IGROUPRnE[i / 32].set(u32::MAX);
+            }
         }
-
-        // Configure all ESPI as default priority
-        for i in (0..self.support_espi).step_by(4) {
-            self.regs().ICFGRnE[i / 4].set(0xa0_a0_a0_a0);
+        // Configure all ESPI as level-sensitive, with the default priority
+        for i in 0..self.support_espi {
+            let intid = IntId::espi(i);
+            if !kept.contains(intid) {
+                let _ = self.set_trigger(intid, TriggerMode::Level);
+                self.set_priority(intid, self.default_priority);
+            }
         }
     }
 
@@ -217,84 +450,265 @@ impl GicDistributor {
             | Self::mpidr_affinity_level(mpidr, 0)
     }
 
-    fn init(&mut self) {
-        self.init_check();
+    /// Initializes the distributor.
+    ///
+    /// With `handoff` set, the grouping, `GICD_CTLR` and the `reserved`
+    /// interrupts are left as secure firmware configured them, and only
+    /// registers the non-secure state can access are written.
+    fn init(&mut self, handoff: bool, reserved: &ReservedSet) -> Result<(), GicError> {
+        self.init_check()?;
         self.base_init();
 
-        // disable GICD
+        if handoff {
+            info!(
+                "GICv3: keeping firmware GICD_CTLR {:#x}",
+                self.regs().CTLR.get()
+            );
+        } else {
+            // disable GICD
+            self.regs().CTLR.set(Self::GICD_DISABLE);
+            self.wait_rwp();
+        }
+
+        self.init_spis(handoff, reserved);
+
+        // Enable affinity routing and group1
+        if !handoff {
+            self.regs().CTLR.set(self.enable_ctlr().bits());
+            self.wait_rwp();
+        }
+
+        self.route_spis(handoff, reserved);
+        Ok(())
+    }
+
+    /// Checks the distributor and enables affinity routing and Group 1,
+    /// leaving the SPIs for [`init_spis`](Self::init_spis) and
+    /// [`route_spis`](Self::route_spis).
+    fn early_init(&mut self) -> Result<(), GicError> {
+        self.init_check()?;
+        self.base_init();
+        // Affinity routing may only change while the groups are disabled.
         self.regs().CTLR.set(Self::GICD_DISABLE);
         self.wait_rwp();
+        self.regs().CTLR.set(self.enable_ctlr().bits());
+        self.wait_rwp();
+        Ok(())
+    }
+
+    /// Disables and deactivates all SPIs and extended SPIs, then configures
+    /// them as edge-triggered with the default priority and, without
+    /// `handoff`, as non-secure Group 1. With `handoff`, the `reserved` ones
+    /// are left alone.
+    fn init_spis(&mut self, handoff: bool, reserved: &ReservedSet) {
+        let kept = if handoff {
+            *reserved
+        } else {
+            ReservedSet::new()
+        };
 
-        self.espi_disable();
+        // Deactivate and disable all SPIs
+        for i in (IntId::SPI_START..self.support_irqs).step_by(32) {
+            let mask = !kept.word(IntId(i));
+            self.regs().ICACTIVER[i / 32].set(mask);
+            self.regs().ICENABLER[i / 32].set(mask);
+        }
+
+        self.espi_disable(handoff, &kept);
 
         // Configure all SPIs as non-secure Group-1. This will only matter
         // if the GIC only has a single security state.
         // This will not do the right thing if the kernel is running in
         // secure mode,
-        for i in (IntId::SPI_START..self.support_irqs).step_by(32) {
-            self.regs().IGROUPR[i / 32].set(u32::MAX);
+        if !handoff {
+            let security = !self.security_disabled();
+            for i in (IntId::SPI_START..self.support_irqs).step_by(32) {
+                self.regs().IGROUPR[i / 32].set(u32::MAX);
+                if security {
+                    self.regs().IGRPMODR[i / 32].set(0);
+                }
+            }
         }
 
-        // Initialize all the SPIs to edge triggered
+        // Initialize all the SPIs to edge triggered, where configurable, with
+        // the default priority
         for i in IntId::SPI_START..self.support_irqs {
-            self.set_trigger(i, TriggerMode::Edge);
-        }
-
-        // Set priority on all global interrupts
-        for i in (IntId::SPI_START..self.support_irqs).step_by(4) {
-            // once time set 4 interrupts
-            self.regs().IPRIORITYR[i / 4].set(0xa0_a0_a0_a0);
+            if !kept.contains(IntId(i)) {
+                let _ = self.set_trigger(IntId(i), TriggerMode::Edge);
+                self.set_priority(IntId(i), self.default_priority);
+            }
         }
+    }
 
-        // Deactivate and disable all SPIs
-        for i in (IntId::SPI_START..self.support_irqs).step_by(32) {
-            self.regs().ICACTIVER[i / 32].set(u32::MAX);
-            self.regs().ICENABLER[i / 32].set(u32::MAX);
+    /// Routes all SPIs and extended SPIs to the current CPU, but the
+    /// `reserved` ones with `handoff`.
+    fn route_spis(&self, handoff: bool, reserved: &ReservedSet) {
+        let kept = |intid| handoff && reserved.contains(intid);
+        if !self.affinity_routing() {
+            // Firmware or the hypervisor keeps affinity routing off, so route
+            // through the legacy target lists. The first ITARGETSR register
+            // reads as the mask of the current CPU.
+            debug!("GICv3: affinity routing disabled, using GICD_ITARGETSR");
+            let target = self.regs().ITARGETSR[0].get() & 0xff;
+            for i in IntId::SPI_START..self.support_irqs {
+                if !kept(IntId(i)) {
+                    self.set_route(IntId(i), target.into());
+                }
+            }
+            return;
         }
 
-        // Enable affinity routing and group1
-        self.regs()
-            .CTLR
-            .set((GicdCtlr::ARE_S | GicdCtlr::EnableGrp1NS).bits());
-        self.wait_rwp();
-
         // Set all global interrupts to current cpu.
-        let mpidr: u64 = MPIDR_EL1.get() & 0xff00ffffff;
+        let mpidr: u64 = sysregs::mpidr() & 0xff00ffffff;
         for i in IntId::SPI_START..self.support_irqs {
             // Set external interrupts to target cpu 0
-            self.regs().IROUTER[i].set(Self::mpidr_to_affinity_level(mpidr));
+            if !kept(IntId(i)) {
+                self.regs().IROUTER[i].set(Self::mpidr_to_affinity_level(mpidr));
+            }
         }
 
         // set app espi to current cpu
         for i in 0..self.support_espi {
             // Set external interrupts to target cpu 0
-            self.regs().IROUTERnE[i].set(Self::mpidr_to_affinity_level(mpidr));
+            if !kept(IntId::espi(i)) {
+                self.regs().IROUTERnE[i].set(Self::mpidr_to_affinity_level(mpidr));
+            }
+        }
+    }
+
+    /// Returns whether affinity routing is enabled for the security state the
+    /// driver runs in.
+    fn affinity_routing(&self) -> bool {
+        // Bit 4 is ARE_S, or ARE_NS in the non-secure view of a GIC with two
+        // security states.
+        GicdCtlr::from_bits_truncate(self.regs().CTLR.get()).contains(GicdCtlr::ARE_S)
+    }
+
+    /// Routes the SPI or extended SPI with the given ID, to an `IROUTER`
+    /// affinity or, with affinity routing disabled, to a legacy CPU target
+    /// list. Extended SPIs always use affinity routing.
+    fn set_route(&self, intid: IntId, affinity: u64) {
+        let id = intid.0;
+        if let RegBank::Extended(n) = RegBank::of(intid) {
+            self.regs().IROUTERnE[n].set(affinity);
+        } else if self.affinity_routing() {
+            self.regs().IROUTER[id].set(affinity);
+        } else {
+            let (itargetsr, shift) = distributor_common::field(&self.regs().ITARGETSR, id, 8);
+            distributor_common::write_field(itargetsr, shift, 8, (affinity & 0xff) as u32);
         }
     }
 
     /// Configures the trigger type for the interrupt with the given ID.
-    fn set_trigger(&mut self, id: usize, tm: TriggerMode) {
-        // type is encoded with two bits, MSB of the two determine type
-        // 16 irqs encoded per ICFGR register
-        let index = id >> 4;
-        let bit_shift = ((id & 0xf) << 1) + 1;
+    ///
+    /// Returns [`GicError::ReadOnlyConfig`] if the trigger type is fixed to the
+    /// other mode, as it is for SGIs and may be for PPIs.
+    fn set_trigger(&self, intid: IntId, tm: TriggerMode) -> Result<(), GicError> {
+        let regs = self.regs();
+        let (icfgr, shift) = RegBank::field(intid, &regs.ICFGR, &regs.ICFGRnE, 2);
+        distributor_common::set_trigger(icfgr, shift, tm)
+    }
+
+    /// Sets the priority of the interrupt with the given ID.
+    fn set_priority(&self, intid: IntId, priority: u8) {
+        let regs = self.regs();
+        let (ipriorityr, shift) = RegBank::field(intid, &regs.IPRIORITYR, &regs.IPRIORITYRnE, 8);
+        distributor_common::write_field(ipriorityr, shift, 8, priority.into());
+    }
 
-        let mut reg_val = self.regs().ICFGR[index].get();
-        match tm {
-            TriggerMode::Edge => reg_val |= 1 << bit_shift,
-            TriggerMode::Level => reg_val &= !(1 << bit_shift),
+    /// Assigns the interrupt with the given ID to `group`.
+    ///
+    /// With two security states, the group modifier is cleared as well, so
+    /// Group 1 means non-secure Group 1.
+    fn set_group(&self, intid: IntId, group: InterruptGroup) {
+        let regs = self.regs();
+        let (igroupr, shift) = RegBank::field(intid, &regs.IGROUPR, &regs.IGROUPRnE, 1);
+        distributor_common::set_group(igroupr, shift, group);
+        // IGRPMODR is RAZ/WI with a single security state.
+        if !self.security_disabled() {
+            let (igrpmodr, _) = RegBank::field(intid, &regs.IGRPMODR, &regs.IGRPMODRnE, 1);
+            distributor_common::write_bit(igrpmodr, shift, false);
         }
+    }
+
+    /// Writes the group and group modifier bits of the interrupt with the
+    /// given ID, then reads back the group it ended up in.
+    fn set_security_group(&self, intid: IntId, group: SecurityGroup) -> SecurityGroup {
+        let regs = self.regs();
+        let (igroupr, shift) = RegBank::field(intid, &regs.IGROUPR, &regs.IGROUPRnE, 1);
+        let (igrpmodr, _) = RegBank::field(intid, &regs.IGRPMODR, &regs.IGRPMODRnE, 1);
+        let (group_bit, modifier) = group.bits();
+        distributor_common::write_bit(igroupr, shift, group_bit);
+        distributor_common::write_bit(igrpmodr, shift, modifier);
+        self.security_group(intid)
+    }
+
+    /// Returns the group of the interrupt with the given ID, from its group
+    /// and group modifier bits.
+    fn security_group(&self, intid: IntId) -> SecurityGroup {
+        let regs = self.regs();
+        let (igroupr, shift) = RegBank::field(intid, &regs.IGROUPR, &regs.IGROUPRnE, 1);
+        let (igrpmodr, _) = RegBank::field(intid, &regs.IGRPMODR, &regs.IGRPMODRnE, 1);
+        SecurityGroup::from_bits(
+            distributor_common::read_bit(igroupr, shift),
+            distributor_common::read_bit(igrpmodr, shift),
+        )
+    }
+
+    /// Returns whether the GIC supports a single security state, either by
+    /// design or because `GICD_CTLR.DS` was set.
+    fn security_disabled(&self) -> bool {
+        GicdCtlr::from_bits_truncate(self.regs().CTLR.get()).contains(GicdCtlr::DS)
+    }
 
-        self.regs().ICFGR[index].set(reg_val);
+    /// Returns the `GICD_CTLR` value enabling affinity routing and Group 1.
+    fn enable_ctlr(&self) -> GicdCtlr {
+        // With a single security state, or in the non-secure view, bit 4 is
+        // the only ARE bit and bit 1 enables (non-secure) Group 1. DS must be
+        // kept, writing zero to it would be ignored or re-enable security.
+        let ds = GicdCtlr::from_bits_truncate(self.regs().CTLR.get()) & GicdCtlr::DS;
+        GicdCtlr::ARE_S | GicdCtlr::EnableGrp1NS | ds
     }
 }
 
 impl GicRedistributor {
+    /// Size of each of the 64KB frames of a redistributor.
+    const FRAME_SIZE: usize = SGI_OFFSET;
+
+    const TYPER_VLPIS: u64 = 1 << 1;
+    const TYPER_DIRECT_LPI: u64 = 1 << 3;
+    const TYPER_LAST: u64 = 1 << 4;
+    const TYPER_RVPEID: u64 = 1 << 7;
+    const TYPER_PROCESSOR_NUMBER_SHIFT: u64 = 8;
+    const TYPER_PROCESSOR_NUMBER_MASK: u64 = 0xffff;
+    const TYPER_AFFINITY_SHIFT: u64 = 32;
+
+    const PROPBASER_PA_MASK: u64 = 0x000f_ffff_ffff_f000;
+    const PENDBASER_PA_MASK: u64 = 0x000f_ffff_ffff_0000;
+    const PENDBASER_PTZ: u64 = 1 << 62;
+    const PENDING_TABLE_ALIGN: u64 = 0x10000;
+
+    /// `GICR_SYNCR.Busy`.
+    const SYNCR_BUSY: u32 = 1 << 0;
+    /// Index of `GICR_PIDR2` in `IDREGS`.
+    const IDREGS_PIDR2: usize = (0xffe8 - 0xffd0) / 4;
+
     /// Construct a new GIC Redistributor instance from the base address.
     pub const fn new(base: *mut u8) -> Self {
         Self {
             gicr_base: NonNull::new(base).unwrap().cast(),
             support_ppi: 0,
+            poller: Poller::new(None),
+        }
+    }
+
+    /// Construct a new GIC Redistributor instance from the base address, which
+    /// must not be null.
+    const unsafe fn new_unchecked(base: *mut u8) -> Self {
+        Self {
+            gicr_base: NonNull::new_unchecked(base).cast(),
+            support_ppi: 0,
+            poller: Poller::new(None),
         }
     }
 
@@ -312,66 +726,300 @@ impl GicRedistributor {
         }
     }
 
-    fn redis_enable(&self) {
+    /// Returns the base address of the `RD_base` frame.
+    pub fn base(&self) -> *mut u8 {
+        self.gicr_base.as_ptr().cast()
+    }
+
+    /// Returns the affinity of the PE this redistributor belongs to, laid out
+    /// as `Aff3.Aff2.Aff1.Aff0`.
+    pub fn affinity(&self) -> u32 {
+        (self.gicr_regs().TYPER.get() >> Self::TYPER_AFFINITY_SHIFT) as u32
+    }
+
+    /// Returns the processor number of the PE this redistributor belongs to.
+    pub fn processor_number(&self) -> usize {
+        ((self.gicr_regs().TYPER.get() >> Self::TYPER_PROCESSOR_NUMBER_SHIFT)
+            & Self::TYPER_PROCESSOR_NUMBER_MASK) as usize
+    }
+
+    /// Returns whether this is the last redistributor of its region.
+    pub fn is_last(&self) -> bool {
+        self.gicr_regs().TYPER.get() & Self::TYPER_LAST != 0
+    }
+
+    /// Returns the distance to the next redistributor of the region.
+    ///
+    /// This is the `RD_base` and `SGI_base` frames, followed by the `VLPI_base`
+    /// and a reserved frame if the redistributor supports virtual LPIs.
+    pub fn stride(&self) -> usize {
+        if self.gicr_regs().TYPER.get() & Self::TYPER_VLPIS != 0 {
+            4 * Self::FRAME_SIZE
+        } else {
+            2 * Self::FRAME_SIZE
+        }
+    }
+
+    /// Programs the LPI configuration and pending tables of this redistributor.
+    ///
+    /// `prop_table` is the physical address of the LPI configuration table,
+    /// covering `id_bits` interrupt ID bits (at least 14, as LPIs start at 8192),
+    /// and `pending_table` the physical address of the pending table of this
+    /// redistributor. This has no effect once LPIs are enabled.
+    pub fn set_lpi_tables(&self, prop_table: u64, id_bits: u32, pending_table: u64) {
+        let attrs = GICR_BASER_INNER_SHAREABLE | GICR_BASER_RAWAWB;
+        self.gicr_regs()
+            .PROPBASER
+            .set((prop_table & Self::PROPBASER_PA_MASK) | attrs | u64::from(id_bits - 1));
+        self.gicr_regs()
+            .PENDBASER
+            .set((pending_table & Self::PENDBASER_PA_MASK) | attrs);
+    }
+
+    /// Zeroes `table` and programs it as the LPI pending table of this redistributor.
+    ///
+    /// `pa` is the physical address of `table`, which must be 64KB aligned and
+    /// hold one bit for each of the `1 << id_bits` interrupt IDs, that is 8192
+    /// bits for the IDs below the LPI range plus one per LPI. If the GIC isn't
+    /// `coherent` with the CPUs, the zeroed table is cleaned to the point of
+    /// coherency and programmed as non-cacheable, otherwise stale cache lines
+    /// would show up as phantom pending LPIs.
+    ///
+    /// Returns [`GicError::InvalidTable`] if the table is misaligned or too
+    /// small. This has no effect once LPIs are enabled.
+    pub fn init_pending_table(
+        &self,
+        table: &mut [u8],
+        pa: u64,
+        id_bits: u32,
+        coherent: bool,
+    ) -> Result<(), GicError> {
+        Self::check_pending_table(pa, table.len(), id_bits)?;
+
+        table.fill(0);
+        let attrs = if coherent {
+            fence::dsb(Domain::IshSt, "pending table");
+            GICR_BASER_INNER_SHAREABLE | GICR_BASER_RAWAWB
+        } else {
+            clean_dcache_range(table.as_ptr(), table.len());
+            GICR_BASER_NON_CACHEABLE
+        };
+        // The table is known to be zero, so the GIC needn't scan it.
+        self.gicr_regs()
+            .PENDBASER
+            .set((pa & Self::PENDBASER_PA_MASK) | attrs | Self::PENDBASER_PTZ);
+        Ok(())
+    }
+
+    /// Checks that a pending table at `pa` of `len` bytes covers `id_bits`
+    /// interrupt ID bits.
+    fn check_pending_table(pa: u64, len: usize, id_bits: u32) -> Result<(), GicError> {
+        // LPIs start at INTID 8192, so anything below 14 bits has none.
+        if !(14..=32).contains(&id_bits) {
+            return Err(GicError::InvalidTable);
+        }
+        let size = (1u64 << id_bits) / 8;
+        if !pa.is_multiple_of(Self::PENDING_TABLE_ALIGN) || (len as u64) < size {
+            return Err(GicError::InvalidTable);
+        }
+        Ok(())
+    }
+
+    /// Enables LPIs on this redistributor.
+    ///
+    /// The tables must have been programmed with [`set_lpi_tables`](Self::set_lpi_tables).
+    /// Many implementations don't allow LPIs to be disabled again.
+    pub fn enable_lpis(&self) {
+        // The tables must be visible to the GIC before it starts reading them.
+        fence::dsb(Domain::Sy, "enable LPIs");
+        let ctlr = self.gicr_regs().CTLR.get();
+        self.gicr_regs()
+            .CTLR
+            .set(ctlr | GicrCtlr::EnableLPIs.bits());
+    }
+
+    /// Returns whether LPIs are enabled on this redistributor.
+    pub fn lpis_enabled(&self) -> bool {
+        GicrCtlr::from_bits_truncate(self.gicr_regs().CTLR.get()).contains(GicrCtlr::EnableLPIs)
+    }
+
+    /// Makes this redistributor reload the configuration of all its LPIs from
+    /// the configuration table, through `GICR_INVALLR`, and waits until it
+    /// has.
+    ///
+    /// This is the way without an ITS to apply many priority changes at once.
+    /// Returns [`GicError::Unsupported`] if the redistributor doesn't support
+    /// direct LPI injection, and so has no `GICR_INVALLR`.
+    pub fn invalidate_all_lpis(&self) -> Result<(), GicError> {
+        if self.gicr_regs().TYPER.get() & Self::TYPER_DIRECT_LPI == 0 {
+            return Err(GicError::Unsupported);
+        }
+        // The configuration table must be updated before the GIC reads it.
+        fence::dsb(Domain::IshSt, "INVALLR");
+        self.gicr_regs().INVALLR.set(0);
+        if self.poller.poll(10000, || {
+            self.gicr_regs().SYNCR.get() & Self::SYNCR_BUSY == 0
+        }) {
+            Ok(())
+        } else {
+            error!("GICv3: timeout waiting for GICR_SYNCR.Busy to clear");
+            Err(GicError::Timeout)
+        }
+    }
+
+    fn wait_rwp(&self) {
+        if !self.poller.poll(
+            10000,
+            perf::counting(Counter::RwpPoll, || {
+                !GicrCtlr::from_bits_truncate(self.gicr_regs().CTLR.get()).contains(GicrCtlr::RWP)
+            }),
+        ) {
+            error!("GICv3: timeout waiting for GICR_CTLR.RWP to clear");
+            panic!("wait timeout");
+        }
+    }
+
+    /// Wakes up the redistributor, failing with [`GicError::Timeout`] if its
+    /// interfaces stay asleep.
+    fn wake(&self) -> Result<(), GicError> {
         let mut waker = self.gicr_regs().WAKER.get();
         // Wake up this CPU redistributor
         waker &= !(WakerFlags::PROCESSOR_SLEEP.bits());
         self.gicr_regs().WAKER.set(waker);
 
-        while WakerFlags::from_bits_truncate(self.gicr_regs().WAKER.get())
-            .contains(WakerFlags::CHILDREN_ASLEEP)
-        {
-            spin_loop();
+        if self.poller.poll(
+            1000000,
+            perf::counting(Counter::WakePoll, || {
+                !WakerFlags::from_bits_truncate(self.gicr_regs().WAKER.get())
+                    .contains(WakerFlags::CHILDREN_ASLEEP)
+            }),
+        ) {
+            Ok(())
+        } else {
+            error!("GICv3: timeout waiting for GICR_WAKER.ChildrenAsleep to clear");
+            Err(GicError::Timeout)
+        }
+    }
+
+    fn redis_enable(&self) {
+        if self.wake().is_err() {
+            panic!("wait timeout");
         }
     }
 
     fn base_init(&mut self) {
-        let typer = self.gicr_regs().TYPER.get() as usize;
-        let mut ppinum = typer >> 27 & 0x1f;
+        let typer = self.gicr_regs().TYPER.get();
+        let mut ppinum = (typer >> 27 & 0x1f) as usize;
         ppinum = match ppinum {
             0 => 16,
             1 | 2 => 16 + 32 * ppinum,
-            _ => panic!("invalid ppinum"),
+            _ => {
+                error!("GICv3: invalid GICR_TYPER.PPInum {}", ppinum);
+                panic!("invalid ppinum")
+            }
         };
+        info!(
+            "GICv3: redistributor at {:#x}, affinity {:#x}, {} PPIs",
+            self.gicr_base.as_ptr() as usize,
+            typer >> 32,
+            ppinum
+        );
 
         self.support_ppi = self.support_ppi.min(ppinum);
     }
 
-    fn init(&mut self) {
+    /// Initializes the redistributor, leaving the grouping of private
+    /// interrupts and the `reserved` ones alone with `handoff` set. `security`
+    /// tells whether the GIC has two security states.
+    fn init(&mut self, handoff: bool, security: bool, priority: u8, reserved: &ReservedSet) {
+        let kept = if handoff {
+            *reserved
+        } else {
+            ReservedSet::new()
+        };
+        // The SGIs and PPIs, then the extended PPIs.
+        let private = |i: usize| {
+            if i < IntId::SPI_START {
+                IntId(i)
+            } else {
+                IntId::eppi(i - IntId::SPI_START)
+            }
+        };
         self.base_init();
         self.redis_enable();
         // Configure SGIs/PPIs as non-secure Group-1
-        for i in (0..self.support_ppi + 16).step_by(32) {
-            self.sgi_regs().IGROUPR0[i / 32].set(u32::MAX);
+        if !handoff {
+            for i in (0..self.support_ppi + 16).step_by(32) {
+                self.sgi_regs().IGROUPR0[i / 32].set(u32::MAX);
+                if security {
+                    self.sgi_regs().IGRPMODR[i / 32].set(0);
+                }
+            }
         }
 
         // Deactivate and disable all private interrupts
         for i in (0..self.support_ppi + 16).step_by(32) {
-            self.sgi_regs().ICACTIVER[i / 32].set(u32::MAX);
-            self.sgi_regs().ICENABLER[i / 32].set(u32::MAX);
+            let mask = !kept.word(private(i));
+            self.sgi_regs().ICACTIVER[i / 32].set(mask);
+            self.sgi_regs().ICENABLER[i / 32].set(mask);
         }
 
         // Set priority on private interrupts
-        for i in (0..self.support_ppi + 16).step_by(4) {
-            // once time set 4 interrupts
-            self.sgi_regs().IPRIORITYR[i / 4].set(0xa0_a0_a0_a0);
+        for i in 0..self.support_ppi + 16 {
+            if !kept.contains(private(i)) {
+                self.set_priority(private(i), priority);
+            }
         }
     }
 
     /// Configures the trigger type for the interrupt with the given ID.
-    fn set_trigger(&mut self, id: usize, tm: TriggerMode) {
-        // type is encoded with two bits, MSB of the two determine type
-        // 16 irqs encoded per ICFGR register
-        let index = id >> 4;
-        let bit_shift = ((id & 0xf) << 1) + 1;
+    ///
+    /// Returns [`GicError::ReadOnlyConfig`] if the trigger type is fixed to the
+    /// other mode, as it is for SGIs and may be for PPIs.
+    fn set_trigger(&self, intid: IntId, tm: TriggerMode) -> Result<(), GicError> {
+        let (icfgr, shift) = RegBank::private_field(intid, &self.sgi_regs().ICFGR, 2);
+        distributor_common::set_trigger(icfgr, shift, tm)
+    }
 
-        let mut reg_val = self.sgi_regs().ICFGR[index].get();
-        match tm {
-            TriggerMode::Edge => reg_val |= 1 << bit_shift,
-            TriggerMode::Level => reg_val &= !(1 << bit_shift),
-        }
+    /// Sets the priority of the private interrupt with the given ID.
+    fn set_priority(&self, intid: IntId, priority: u8) {
+        let (ipriorityr, shift) = RegBank::private_field(intid, &self.sgi_regs().IPRIORITYR, 8);
+        distributor_common::write_field(ipriorityr, shift, 8, priority.into());
+    }
+
+    /// Writes the group and group modifier bits of the private interrupt with
+    /// the given ID, then reads back the group it ended up in.
+    fn set_security_group(&self, intid: IntId, group: SecurityGroup) -> SecurityGroup {
+        let (igroupr, shift) = RegBank::private_field(intid, &self.sgi_regs().IGROUPR0, 1);
+        let (igrpmodr, _) = RegBank::private_field(intid, &self.sgi_regs().IGRPMODR, 1);
+        let (group_bit, modifier) = group.bits();
+        distributor_common::write_bit(igroupr, shift, group_bit);
+        distributor_common::write_bit(igrpmodr, shift, modifier);
+        self.security_group(intid)
+    }
+
+    /// Returns the group of the private interrupt with the given ID, from its
+    /// group and group modifier bits.
+    fn security_group(&self, intid: IntId) -> SecurityGroup {
+        let (igroupr, shift) = RegBank::private_field(intid, &self.sgi_regs().IGROUPR0, 1);
+        let (igrpmodr, _) = RegBank::private_field(intid, &self.sgi_regs().IGRPMODR, 1);
+        SecurityGroup::from_bits(
+            distributor_common::read_bit(igroupr, shift),
+            distributor_common::read_bit(igrpmodr, shift),
+        )
+    }
 
-        self.sgi_regs().ICFGR[index].set(reg_val);
+    /// Assigns the private interrupt with the given ID to `group`.
+    ///
+    /// With two security states (`security` set), the group modifier is
+    /// cleared as well, so Group 1 means non-secure Group 1.
+    fn set_group(&self, intid: IntId, group: InterruptGroup, security: bool) {
+        let (igroupr, shift) = RegBank::private_field(intid, &self.sgi_regs().IGROUPR0, 1);
+        distributor_common::set_group(igroupr, shift, group);
+        if security {
+            let (igrpmodr, _) = RegBank::private_field(intid, &self.sgi_regs().IGRPMODR, 1);
+            distributor_common::write_bit(igrpmodr, shift, false);
+        }
     }
 }
 
@@ -379,108 +1027,1224 @@ impl GicRedistributor {
 #[derive(Debug, Copy, Clone)]
 pub struct GicV3 {
     gicd: GicDistributor,
-    gicr: GicRedistributor,
+    /// Redistributor of the CPU last initialised, `None` if the
+    /// redistributors aren't accessible.
+    gicr: Option<GicRedistributor>,
+    /// First redistributor of the region, which `gicr` is one of.
+    rd_region: Option<GicRedistributor>,
+    /// Whether secure firmware owns the grouping and `GICD_CTLR`.
+    handoff: bool,
+    /// Whether interrupts are disabled while acknowledged.
+    disable_on_ack: bool,
+    /// Whether the legacy interrupt bypass is disabled on each CPU.
+    disable_bypass: bool,
+    /// Priority mask set on each CPU.
+    priority_mask: u8,
+    /// Interrupts the driver refuses to configure.
+    reserved: ReservedSet,
 }
 
 impl GicV3 {
+    const VTR_LIST_REGS_MASK: u64 = 0x1f;
+    const CTLR_PRI_BITS_SHIFT: u64 = 8;
+    const CTLR_PRI_BITS_MASK: u64 = 0b111;
+    const CTLR_RSS: u64 = 1 << 18;
+    const SGI1R_INTID_SHIFT: u64 = 24;
+    /// Priority mask set on each CPU unless configured otherwise, masking
+    /// only the lowest priorities.
+    const DEFAULT_PRIORITY_MASK: u8 = 0xf0;
+
+    /// Base of the `MRS Xt, <reg>` instruction, to be combined with the
+    /// encoding of the register and `t`.
+    pub const MRS: u32 = 0xd520_0000;
+    /// Base of the `MSR <reg>, Xt` instruction, to be combined with the
+    /// encoding of the register and `t`.
+    pub const MSR: u32 = 0xd500_0000;
+    /// Encoding of `ICC_IAR0_EL1` in the `MRS` instruction.
+    pub const ICC_IAR0_EL1: u32 = sysregs::encoding(3, 0, 12, 8, 0);
+    /// Encoding of `ICC_EOIR0_EL1` in the `MSR` instruction.
+    pub const ICC_EOIR0_EL1: u32 = sysregs::encoding(3, 0, 12, 8, 1);
+    /// Encoding of `ICC_IAR1_EL1` in the `MRS` instruction.
+    pub const ICC_IAR1_EL1: u32 = sysregs::encoding(3, 0, 12, 12, 0);
+    /// Encoding of `ICC_EOIR1_EL1` in the `MSR` instruction.
+    pub const ICC_EOIR1_EL1: u32 = sysregs::encoding(3, 0, 12, 12, 1);
+
+    /// Acknowledges the highest priority signalled Group 1 interrupt,
+    /// recording it if it is spurious and `count_spurious` is set.
+    fn acknowledge(&self, count_spurious: bool) -> Option<IntId> {
+        // SAFETY: Reading this system register doesn't access memory in any way.
+        let intid = unsafe { read_sysreg!(icc_iar1_el1) } as usize;
+        if intid == IntId::SPURIOUS && count_spurious {
+            spurious::record();
+        }
+        // 1023 means nothing is pending, the other special IDs are never
+        // valid to handle either.
+        if IntId(intid).is_special() {
+            None
+        } else {
+            #[cfg(feature = "lifecycle-tracking")]
+            lifecycle::acknowledged(IntId(intid));
+            // LPIs are enabled in their configuration table in memory, not
+            // through the GIC registers, so they are left enabled.
+            if self.disable_on_ack && !IntId(intid).is_lpi() {
+                self.set_enable(IntId(intid), false);
+            }
+            Some(IntId(intid))
+        }
+    }
+
+    /// Reads `ICC_IAR1_EL1`, acknowledging the highest priority Group 1
+    /// interrupt, and returns the raw value, which is a special INTID if none
+    /// is pending.
+    ///
+    /// Unlike [`get_and_acknowledge_interrupt`](GenericArmGic::get_and_acknowledge_interrupt)
+    /// this needs no driver instance and doesn't disable the interrupt with
+    /// [`set_disable_on_ack`](GenericArmGic::set_disable_on_ack). It counts
+    /// spurious interrupts and keeps the `lifecycle-tracking` record, so
+    /// handlers acknowledging interrupts in assembly, through [`ICC_IAR1_EL1`](Self::ICC_IAR1_EL1),
+    /// can mix with ones using the driver by calling [`eoi_raw`](Self::eoi_raw)
+    /// for them.
+    #[inline(always)]
+    pub fn ack_raw() -> u32 {
+        // SAFETY: Reading this system register doesn't access memory in any way.
+        let raw = unsafe { read_sysreg!(icc_iar1_el1) } as u32;
+        if raw as usize == IntId::SPURIOUS {
+            spurious::record();
+        }
+        #[cfg(feature = "lifecycle-tracking")]
+        if !IntId(raw as usize).is_special() {
+            lifecycle::acknowledged(IntId(raw as usize));
+        }
+        raw
+    }
+
+    /// Writes `raw`, as returned by [`ack_raw`](Self::ack_raw) or read from
+    /// `ICC_IAR1_EL1` by assembly, to `ICC_EOIR1_EL1`, ending the interrupt.
+    #[inline(always)]
+    pub fn eoi_raw(raw: u32) {
+        // SAFETY: Writing to this system register doesn't access memory in any way.
+        unsafe { write_sysreg!(icc_eoir1_el1, raw.into()) }
+        #[cfg(feature = "lifecycle-tracking")]
+        lifecycle::ended(IntId(raw as usize));
+    }
+
     /// Constructs a new instance of the driver for a GIC with the given distributor and
     /// redistributor base addresses.
     ///
     /// # Safety
     ///
     /// The given base addresses must point to the GIC distributor and redistributor registers
-    /// respectively. These regions must be mapped into the address space of the process as device
-    /// memory, and not have any other aliases, either via another instance of this driver or
-    /// otherwise.
+    /// respectively. These regions must be mapped into the address space of the process as
+    /// Device-nGnRnE or Device-nGnRE memory, and not have any other aliases, either via another
+    /// instance of this driver or otherwise. `gicr` is the start of the redistributor region, which must be mapped in full
+    /// for [`for_each_redistributor`](Self::for_each_redistributor).
     pub const fn new(gicd: *mut u8, gicr: *mut u8) -> Self {
         Self {
             gicd: GicDistributor::new(gicd),
-            gicr: GicRedistributor::new(gicr),
+            gicr: Some(GicRedistributor::new(gicr)),
+            rd_region: Some(GicRedistributor::new(gicr)),
+            handoff: false,
+            disable_on_ack: false,
+            disable_bypass: false,
+            priority_mask: Self::DEFAULT_PRIORITY_MASK,
+            reserved: ReservedSet::new(),
         }
     }
 
-    fn cpu_sys_reg_init(&mut self) {
-        // SAFETY: Writing to this system register doesn't access memory in any way.
-        unsafe {
-            // Enable system register access.
-            write_sysreg!(icc_sre_el1, 0x01);
+    /// Constructs a new instance of the driver for a GIC whose redistributors
+    /// aren't accessible, as some hypervisors only give guests the distributor
+    /// and the system register CPU interface.
+    ///
+    /// Without redistributors LPIs can't be used and
+    /// [`for_each_redistributor`](Self::for_each_redistributor) finds none.
+    /// SGIs and PPIs are configured through the banked distributor registers
+    /// where the GIC provides them, which is with affinity routing disabled;
+    /// otherwise configuring them fails with [`GicError::NoRedistributor`], or
+    /// is logged and ignored by methods that can't fail. SGIs are sent to the
+    /// CPUs of [`CpuSet`] indices taken as `Aff0` of the first cluster.
+    ///
+    /// # Safety
+    ///
+    /// The requirements of [`new`](Self::new) apply to `gicd`.
+    pub const fn new_without_redistributor(gicd: *mut u8) -> Self {
+        Self {
+            gicd: GicDistributor::new(gicd),
+            gicr: None,
+            rd_region: None,
+            handoff: false,
+            disable_on_ack: false,
+            disable_bypass: false,
+            priority_mask: Self::DEFAULT_PRIORITY_MASK,
+            reserved: ReservedSet::new(),
         }
+    }
 
-        unsafe {
-            // Enable system register access.
-            write_sysreg!(icc_pmr_el1, 0xf0);
-        }
+    /// Constructs a new instance of the driver like [`new`](Self::new), without
+    /// checking the addresses, so it can initialise a `static`:
+    ///
+    /// ```ignore
+    /// static GIC: SpinMutex<GicV3> = SpinMutex::new(unsafe {
+    ///     GicV3::new_unchecked(GICD_BASE as *mut u8, GICR_BASE as *mut u8)
+    /// });
+    ///
+    /// fn boot_cpu_init() {
+    ///     GIC.lock().init_primary().unwrap();
+    /// }
+    /// ```
+    ///
+    /// Any cell type of the kernel works, as the driver is `Send`.
+    ///
+    /// # Safety
+    ///
+    /// The requirements of [`new`](Self::new) apply, and neither address may be null.
+    pub const unsafe fn new_unchecked(gicd: *mut u8, gicr: *mut u8) -> Self {
+        Self {
+            gicd: GicDistributor::new_unchecked(gicd),
+            gicr: Some(GicRedistributor::new_unchecked(gicr)),
+            rd_region: Some(GicRedistributor::new_unchecked(gicr)),
+            handoff: false,
+            disable_on_ack: false,
+            disable_bypass: false,
+            priority_mask: Self::DEFAULT_PRIORITY_MASK,
+            reserved: ReservedSet::new(),
+        }
+    }
+
+    /// Constructs a new instance of the driver like [`new`](Self::new), from
+    /// register frames whose memory type was checked by [`DeviceMmio::new`].
+    pub fn from_mmio(gicd: DeviceMmio, gicr: DeviceMmio) -> Self {
+        Self::new(gicd.as_ptr(), gicr.as_ptr())
+    }
+
+    /// Bounds the waits for register writes and redistributor wake-up by
+    /// `delay` instead of a number of spins.
+    pub const fn with_delay(mut self, delay: &'static dyn Delay) -> Self {
+        let poller = Poller::new(Some(delay));
+        self.gicd.poller = poller;
+        if let Some(gicr) = &mut self.gicr {
+            gicr.poller = poller;
+        }
+        if let Some(rd_region) = &mut self.rd_region {
+            rd_region.poller = poller;
+        }
+        self
+    }
+
+    /// Disables the legacy IRQ and FIQ bypass of each CPU interface, through
+    /// `ICC_SRE_ELx.DIB` and `DFB`, as [`per_cpu_init`](GenericArmGic::per_cpu_init)
+    /// initialises it.
+    ///
+    /// Some boards wire the legacy interrupt lines to the cores, and without
+    /// this deliver interrupts to the wrong exception when the CPU interface
+    /// doesn't signal any.
+    pub const fn with_bypass_disabled(mut self) -> Self {
+        self.disable_bypass = true;
+        self
+    }
+
+    /// Sets the priority [`init_primary`](GenericArmGic::init_primary) gives
+    /// every SPI and extended SPI, and [`per_cpu_init`](GenericArmGic::per_cpu_init)
+    /// the SGIs and PPIs of each CPU, `0xa0` by default.
+    pub const fn with_default_priority(mut self, priority: u8) -> Self {
+        self.gicd.default_priority = priority;
+        self
+    }
+
+    /// Sets the priority mask [`per_cpu_init`](GenericArmGic::per_cpu_init)
+    /// writes to `ICC_PMR_EL1`, only interrupts of higher priority, that is
+    /// of a lower value, being signalled. The default, `0xf0`, masks the
+    /// lowest priorities only.
+    ///
+    /// Real-time systems keeping the highest priorities for watchdogs or
+    /// pseudo-NMIs can start with those alone unmasked, leaving the other
+    /// interrupts masked until the kernel opens the mask itself.
+    pub const fn with_priority_mask(mut self, mask: u8) -> Self {
+        self.priority_mask = mask;
+        self
+    }
+
+    /// Reserves the interrupts of `reserved` to the firmware, so the driver
+    /// never configures, enables or disables them, such as the secure PPIs
+    /// or the SPIs the firmware manages.
+    ///
+    /// [`init_primary`](GenericArmGic::init_primary) still resets every
+    /// interrupt, so with firmware that set them up, initialise the GIC with
+    /// [`init_primary_handoff`](GenericArmGic::init_primary_handoff).
+    pub const fn with_reserved(mut self, reserved: ReservedSet) -> Self {
+        self.reserved = reserved;
+        self
+    }
+
+    /// Sanity-checks the register mappings, as a debugging aid.
+    ///
+    /// The identification registers of the distributor and the redistributor
+    /// are read twice, and must agree and identify a GICv3 or GICv4. Returns
+    /// [`GicError::InvalidMapping`] if they don't.
+    pub fn validate_mapping(&self) -> Result<(), GicError> {
+        let gicd = self.gicd.regs();
+        check_stable(|| gicd.IIDR.get())?;
+        Self::check_pidr2("GICD", check_stable(|| gicd.PIDR2.get())?)?;
+        if let Some(gicr) = &self.gicr {
+            let regs = gicr.gicr_regs();
+            check_stable(|| regs.IIDR.get())?;
+            Self::check_pidr2(
+                "GICR",
+                check_stable(|| regs.IDREGS[GicRedistributor::IDREGS_PIDR2].get())?,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the `PIDR2` value of the `frame` registers identifies a
+    /// GICv3 or GICv4.
+    fn check_pidr2(frame: &str, pidr2: u32) -> Result<(), GicError> {
+        let arch = pidr2 & GicDistributor::GIC_PIDR2_ARCH_MASK;
+        if matches!(
+            arch,
+            GicDistributor::GIC_PIDR2_ARCH_GICV3 | GicDistributor::GIC_PIDR2_ARCH_GICV4
+        ) {
+            Ok(())
+        } else {
+            error!(
+                "GICv3: {}_PIDR2 {:#x} doesn't identify a GICv3",
+                frame, arch
+            );
+            Err(GicError::InvalidMapping)
+        }
+    }
+
+    /// Like [`validate_mapping`](Self::validate_mapping), also asking `query`
+    /// for the memory type each register frame is mapped as.
+    pub fn validate_mapping_with(
+        &self,
+        query: impl Fn(*const u8) -> MemoryType,
+    ) -> Result<(), GicError> {
+        check_memory_type(self.gicd.base.as_ptr().cast(), &query)?;
+        if let Some(gicr) = &self.gicr {
+            check_memory_type(gicr.gicr_base.as_ptr().cast(), &query)?;
+        }
+        self.validate_mapping()
+    }
+
+    /// Moves the driver to new distributor and redistributor base addresses,
+    /// keeping everything it learned about the GIC.
+    ///
+    /// This lets a kernel switch from an identity mapping to its final one
+    /// after enabling the MMU, without initialising the GIC again.
+    ///
+    /// # Safety
+    ///
+    /// The new addresses must map the same registers as the old ones, with the
+    /// requirements of [`new`](Self::new). `gicr` is ignored if the driver has
+    /// no redistributors.
+    pub unsafe fn rebase(&mut self, gicd: *mut u8, gicr: *mut u8) {
+        self.gicd.base = NonNull::new(gicd).unwrap().cast();
+        if let (Some(local), Some(rd_region)) = (&mut self.gicr, &mut self.rd_region) {
+            let offset = local.gicr_base.as_ptr() as usize - rd_region.gicr_base.as_ptr() as usize;
+            rd_region.gicr_base = NonNull::new(gicr).unwrap().cast();
+            local.gicr_base = NonNull::new(gicr.add(offset)).unwrap().cast();
+        }
+    }
+
+    /// Reads the raw 32-bit GICD register at `offset` bytes from its base.
+    ///
+    /// This is an escape hatch for implementation defined registers that the
+    /// driver does not model.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be 4-byte aligned and name a register inside the distributor frame.
+    /// The access bypasses the driver, so it must not leave state the driver relies on
+    /// in a configuration it doesn't expect.
+    pub unsafe fn gicd_read(&self, offset: usize) -> u32 {
+        read_raw(self.gicd.base, offset)
+    }
+
+    /// Writes `value` to the raw 32-bit GICD register at `offset` bytes from its base.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be 4-byte aligned and name a register inside the distributor frame.
+    /// The access bypasses the driver, so it must not leave state the driver relies on
+    /// in a configuration it doesn't expect.
+    pub unsafe fn gicd_write(&self, offset: usize, value: u32) {
+        write_raw(self.gicd.base, offset, value)
+    }
+
+    /// Reads the raw 32-bit GICR register at `offset` bytes from its base.
+    ///
+    /// This is an escape hatch for implementation defined registers that the
+    /// driver does not model.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be 4-byte aligned and name a register inside the `RD_base` or
+    /// `SGI_base` frame of the redistributor. The access bypasses the driver, so it
+    /// must not leave state the driver relies on in a configuration it doesn't expect.
+    ///
+    /// Panics if the driver has no redistributors.
+    pub unsafe fn gicr_read(&self, offset: usize) -> u32 {
+        read_raw(self.gicr.expect("no redistributor").gicr_base, offset)
+    }
+
+    /// Writes `value` to the raw 32-bit GICR register at `offset` bytes from its base.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be 4-byte aligned and name a register inside the `RD_base` or
+    /// `SGI_base` frame of the redistributor. The access bypasses the driver, so it
+    /// must not leave state the driver relies on in a configuration it doesn't expect.
+    ///
+    /// Panics if the driver has no redistributors.
+    pub unsafe fn gicr_write(&self, offset: usize, value: u32) {
+        write_raw(
+            self.gicr.expect("no redistributor").gicr_base,
+            offset,
+            value,
+        )
+    }
+
+    /// Returns the architecture revision and features of the GIC, for a boot
+    /// banner.
+    ///
+    /// The CPU interface and redistributor fields are those of the current
+    /// CPU, so call this after initialising it.
+    pub fn features_summary(&self) -> FeatureSummary {
+        let capabilities = self.capabilities();
+        let typer = self.gicd.regs().TYPER.get();
+        let gicr_typer = self.gicr.map_or(0, |gicr| gicr.gicr_regs().TYPER.get());
+        // SAFETY: Reading this system register doesn't access memory in any way.
+        let ctlr = unsafe { read_sysreg!(icc_ctlr_el1) };
+        let major = (self.gicd.regs().PIDR2.get() & GicDistributor::GIC_PIDR2_ARCH_MASK) >> 4;
+        let extended_ppis = match gicr_typer >> 27 & 0x1f {
+            ppinum @ (1 | 2) => 32 * ppinum as usize,
+            _ => 0,
+        };
+        // GICv3.1 added the extended ranges, GICv3.3 and GICv4.2 the NMIs,
+        // GICv4.1 the vPE IDs of GICR_VPROPBASER.
+        let minor = if major >= 4 {
+            match (
+                gicr_typer & GicRedistributor::TYPER_RVPEID != 0,
+                capabilities.nmi,
+            ) {
+                (true, true) => 2,
+                (true, false) => 1,
+                (false, _) => 0,
+            }
+        } else if capabilities.nmi {
+            3
+        } else if capabilities.espi || extended_ppis != 0 {
+            1
+        } else {
+            0
+        };
+        FeatureSummary {
+            major: major as u8,
+            minor,
+            capabilities,
+            extended_spis: self.gicd.max_espis(),
+            extended_ppis,
+            range_selector: typer & GicDistributor::RSS_MASK != 0 && ctlr & Self::CTLR_RSS != 0,
+            priority_bits: ((ctlr >> Self::CTLR_PRI_BITS_SHIFT & Self::CTLR_PRI_BITS_MASK) + 1)
+                as u8,
+        }
+    }
+
+    /// Returns whether the GIC supports a single security state (`GICD_CTLR.DS`
+    /// set).
+    ///
+    /// With two security states, Group 1 interrupts are made non-secure Group 1
+    /// by also clearing their group modifier, which is absent otherwise.
+    pub fn security_disabled(&self) -> bool {
+        self.gicd.security_disabled()
+    }
+
+    /// Disables security by setting `GICD_CTLR.DS`, leaving the GIC with a
+    /// single security state that the non-secure world fully controls.
+    ///
+    /// This only works from the secure state, before the GIC is initialised,
+    /// and can't be undone before reset. Returns [`GicError::Unsupported`] if
+    /// the write didn't take.
+    pub fn disable_security(&mut self) -> Result<(), GicError> {
+        let ctlr = self.gicd.regs().CTLR.get();
+        self.gicd.regs().CTLR.set(ctlr | GicdCtlr::DS.bits());
+        self.gicd.wait_rwp();
+        if self.gicd.security_disabled() {
+            info!("GICv3: security disabled");
+            Ok(())
+        } else {
+            Err(GicError::Unsupported)
+        }
+    }
+
+    /// Checks that affinity routing is enabled, as the system register CPU
+    /// interface this driver uses requires.
+    ///
+    /// Some hypervisors and early implementations present a GICv3 with
+    /// `GICD_CTLR.ARE` clear. The distributor then still works in its legacy
+    /// mode: SPIs are routed through `GICD_ITARGETSR`, and
+    /// [`GenericArmGic::set_affinity`] takes a CPU target list as on GICv2. But
+    /// interrupts can only be acknowledged through the memory-mapped CPU
+    /// interface, which this driver doesn't drive, so this returns
+    /// [`GicError::Unsupported`].
+    pub fn check_affinity_routing(&self) -> Result<(), GicError> {
+        if self.gicd.affinity_routing() {
+            Ok(())
+        } else {
+            Err(GicError::Unsupported)
+        }
+    }
+
+    /// Calls `f` for each redistributor of the region starting at the GICR base
+    /// address, in address order, up to the one marked as the last.
+    ///
+    /// The distance between redistributors is detected from each of them, so
+    /// this works whether or not they support virtual LPIs. With the
+    /// `single-core` feature, only the first redistributor is visited.
+    pub fn for_each_redistributor(&self, mut f: impl FnMut(&GicRedistributor)) {
+        self.find_redistributor(|rd| {
+            f(rd);
+            false
+        });
+    }
+
+    /// Returns the first redistributor of the region for which `found`
+    /// returns true.
+    fn find_redistributor(
+        &self,
+        mut found: impl FnMut(&GicRedistributor) -> bool,
+    ) -> Option<GicRedistributor> {
+        let (local, rd_region) = self.gicr.zip(self.rd_region)?;
+        let mut base = rd_region.gicr_base.as_ptr().cast::<u8>();
+        loop {
+            let rd = GicRedistributor {
+                poller: local.poller,
+                support_ppi: local.support_ppi,
+                ..GicRedistributor::new(base)
+            };
+            if found(&rd) {
+                return Some(rd);
+            }
+            if cfg!(feature = "single-core") || rd.is_last() {
+                return None;
+            }
+            // SAFETY: The region extends at least to the redistributor marked as last.
+            base = unsafe { base.add(rd.stride()) };
+        }
+    }
+
+    /// Returns the redistributor of the PE with the given affinity, laid out
+    /// as `Aff3.Aff2.Aff1.Aff0` like [`GicRedistributor::affinity`], or `None`
+    /// if the region has none for it.
+    pub fn redistributor_for(&self, affinity: u32) -> Option<GicRedistributor> {
+        self.find_redistributor(|rd| rd.affinity() == affinity)
+    }
+
+    /// Returns the redistributor of the current CPU.
+    ///
+    /// The driver keeps the one [`per_cpu_init`](GenericArmGic::per_cpu_init)
+    /// last selected, which is another CPU's if the driver is shared between
+    /// CPUs, so the region is searched if its affinity doesn't match.
+    ///
+    /// Returns `None` if the redistributors aren't accessible.
+    fn local_gicr(&self) -> Option<GicRedistributor> {
+        let gicr = self.gicr?;
+        if cfg!(feature = "single-core") {
+            return Some(gicr);
+        }
+        let affinity = current_affinity();
+        if gicr.affinity() == affinity {
+            return Some(gicr);
+        }
+        Some(self.redistributor_for(affinity).unwrap_or(gicr))
+    }
+
+    /// Checks that the SGIs and PPIs of the current CPU can be configured
+    /// through the banked distributor registers, as the driver has no
+    /// redistributors. The GIC only implements them with affinity routing
+    /// disabled.
+    fn check_banked_private(&self, intid: IntId) -> Result<(), GicError> {
+        if self.gicd.affinity_routing() {
+            error!("GICv3: no redistributor to configure INTID {}", intid.0);
+            Err(GicError::NoRedistributor)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks that the distributor implements `intid` if it is an SPI or an
+    /// extended SPI, as [`GicDistributor::check_spi_range`]. Other interrupts
+    /// pass.
+    fn check_shared(&self, intid: IntId) -> Result<(), GicError> {
+        if intid.is_private() {
+            Ok(())
+        } else {
+            self.gicd.check_spi_range(intid, 1)
+        }
+    }
+
+    /// Like [`check_shared`](Self::check_shared), returning whether `intid`
+    /// fails the check and logging that `action` is ignored if so.
+    fn refuses_shared(&self, intid: IntId, action: &str) -> bool {
+        let refused = self.check_shared(intid).is_err();
+        if refused {
+            error!(
+                "GICv3: {} of unimplemented INTID {} ignored",
+                action, intid.0
+            );
+        }
+        refused
+    }
+
+    /// Returns the redistributor of the CPU with the given affinity, for
+    /// configuring one of its SGIs or PPIs with the given ID.
+    fn private_gicr(&self, affinity: u32, intid: IntId) -> Result<GicRedistributor, GicError> {
+        if !intid.is_private() {
+            return Err(GicError::InvalidIntId);
+        }
+        self.redistributor_for(affinity)
+            .ok_or(GicError::NoRedistributor)
+    }
+
+    /// Configures the trigger type of an SGI or PPI of the CPU with the given
+    /// affinity, laid out as `Aff3.Aff2.Aff1.Aff0`, rather than of the current
+    /// CPU like [`GenericArmGic::set_trigger`].
+    ///
+    /// Returns [`GicError::InvalidIntId`] if `intid` isn't private,
+    /// [`GicError::Reserved`] if it is reserved,
+    /// [`GicError::NoRedistributor`] if the CPU has no redistributor, or
+    /// [`GicError::ReadOnlyConfig`] if the trigger type is fixed.
+    pub fn set_private_trigger(
+        &self,
+        affinity: u32,
+        intid: IntId,
+        tm: TriggerMode,
+    ) -> Result<(), GicError> {
+        self.reserved.check(intid)?;
+        seal::check()?;
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        self.private_gicr(affinity, intid)?.set_trigger(intid, tm)
+    }
+
+    /// Sets the priority of an SGI or PPI of the CPU with the given affinity,
+    /// rather than of the current CPU like [`GenericArmGic::set_priority`].
+    ///
+    /// Returns [`GicError::InvalidIntId`] if `intid` isn't private,
+    /// [`GicError::Reserved`] if it is reserved, or
+    /// [`GicError::NoRedistributor`] if the CPU has no redistributor.
+    pub fn set_private_priority(
+        &self,
+        affinity: u32,
+        intid: IntId,
+        priority: u8,
+    ) -> Result<(), GicError> {
+        self.reserved.check(intid)?;
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        self.private_gicr(affinity, intid)?
+            .set_priority(intid, priority);
+        Ok(())
+    }
+
+    /// Programs the LPI tables of every redistributor and enables LPIs on all
+    /// of them, so the boot CPU can prepare LPIs before secondaries start.
+    ///
+    /// `prop_table` is the physical address of the LPI configuration table shared
+    /// by all redistributors, covering `id_bits` interrupt ID bits.
+    /// `pending_table` is called once for each redistributor and returns the
+    /// physical address of its pending table.
+    ///
+    /// Returns [`GicError::Unsupported`] if the GIC doesn't implement LPIs, or
+    /// [`GicError::InvalidTable`] if `id_bits` doesn't cover any LPI or exceeds
+    /// the ID bits the GIC supports.
+    pub fn enable_lpis(
+        &mut self,
+        prop_table: u64,
+        id_bits: u32,
+        mut pending_table: impl FnMut(&GicRedistributor) -> u64,
+    ) -> Result<(), GicError> {
+        let max_id_bits = self.gicd.lpi_id_bits().ok_or(GicError::Unsupported)?;
+        // LPIs start at INTID 8192, so anything below 14 bits has none.
+        if !(14..=max_id_bits).contains(&id_bits) {
+            return Err(GicError::InvalidTable);
+        }
+
+        self.for_each_redistributor(|rd| {
+            rd.set_lpi_tables(prop_table, id_bits, pending_table(rd));
+            rd.enable_lpis();
+        });
+        Ok(())
+    }
+
+    /// Assigns the interrupt with the given ID to one of the three groups of a
+    /// GIC with two security states, programming its group modifier bit.
+    ///
+    /// Only secure software can do this. Returns [`GicError::Reserved`] if
+    /// the interrupt is reserved, [`GicError::Unsupported`] for
+    /// [`SecurityGroup::G1S`] if the GIC has a single security state, or
+    /// [`GicError::ReadOnlyConfig`] if the write didn't take, as from the
+    /// non-secure state.
+    pub fn set_security_group(&self, intid: IntId, group: SecurityGroup) -> Result<(), GicError> {
+        self.reserved.check(intid)?;
+        seal::check()?;
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        if group == SecurityGroup::G1S && self.gicd.security_disabled() {
+            return Err(GicError::Unsupported);
+        }
+        let found = match self.local_gicr() {
+            Some(gicr) if intid.is_private() => gicr.set_security_group(intid, group),
+            _ => {
+                if intid.is_private() {
+                    self.check_banked_private(intid)?;
+                }
+                self.check_shared(intid)?;
+                self.gicd.set_security_group(intid, group)
+            }
+        };
+        if found == group {
+            Ok(())
+        } else {
+            Err(GicError::ReadOnlyConfig)
+        }
+    }
+
+    /// Reads back the group of each interrupt of `expected` from its group
+    /// and group modifier bits, passing those found in another group to
+    /// `report`, and returns their number.
+    ///
+    /// Secure firmware runs this after the non-secure OS has booted, to check
+    /// that the interrupts it owns haven't been reclassified. Private
+    /// interrupts are read for the current CPU only. Returns
+    /// [`GicError::InvalidIntId`] or [`GicError::Unsupported`], before
+    /// reading anything, if an interrupt isn't implemented.
+    pub fn verify_group_assignments(
+        &self,
+        expected: &[(IntId, SecurityGroup)],
+        mut report: impl FnMut(GroupMismatch),
+    ) -> Result<usize, GicError> {
+        let gicr = self.local_gicr();
+        for &(intid, _) in expected {
+            if intid.is_private() && gicr.is_none() {
+                self.check_banked_private(intid)?;
+            }
+            self.check_shared(intid)?;
+        }
+        let mut mismatches = 0;
+        for &(intid, expected) in expected {
+            let found = match gicr {
+                Some(gicr) if intid.is_private() => gicr.security_group(intid),
+                _ => self.gicd.security_group(intid),
+            };
+            if found != expected {
+                error!("GICv3: INTID {} moved out of its group", intid.0);
+                mismatches += 1;
+                report(GroupMismatch {
+                    intid,
+                    expected,
+                    found,
+                });
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Configures and enables the interrupts of a Trusted Firmware-A style
+    /// properties table, in order, each in its group of the three.
+    ///
+    /// Private interrupts are configured for the current CPU only. Only
+    /// secure software can do this; it stops at the first error of
+    /// [`configure`](GenericArmGic::configure) or
+    /// [`set_security_group`](Self::set_security_group), leaving that
+    /// interrupt disabled and the following ones unconfigured.
+    pub fn apply_interrupt_props(&self, props: &[InterruptProp]) -> Result<(), GicError> {
+        for prop in props {
+            let config = prop.irq_config();
+            self.configure(
+                prop.intid,
+                IrqConfig {
+                    enabled: false,
+                    ..config
+                },
+            )?;
+            self.set_security_group(prop.intid, prop.group)?;
+            if !prop.intid.is_private() {
+                seal::pin_routing(prop.intid)?;
+            }
+            self.enable_interrupt(prop.intid);
+        }
+        Ok(())
+    }
+
+    /// Initialises the GIC like [`init_primary`](GenericArmGic::init_primary),
+    /// then applies the properties table `props` like
+    /// [`apply_interrupt_props`](Self::apply_interrupt_props).
+    pub fn init_primary_with_props(&mut self, props: &[InterruptProp]) -> Result<(), GicError> {
+        self.init_primary()?;
+        self.apply_interrupt_props(props)
+    }
+
+    /// Lets the non-secure state generate the secure SGI `sgi` as `access`
+    /// allows, through `GICR_NSACR` of the current CPU, or the banked
+    /// `GICD_NSACR0` without redistributors.
+    ///
+    /// Only secure software can do this. Returns [`GicError::Unsupported`] if
+    /// the GIC has a single security state, or [`GicError::ReadOnlyConfig`] if
+    /// the write didn't take, as from the non-secure state.
+    pub fn set_sgi_ns_access(&self, sgi: SgiId, access: NsSgiAccess) -> Result<(), GicError> {
+        let (nsacr, shift) = self.sgi_nsacr(sgi)?;
+        if distributor_common::set_ns_access(nsacr, shift, access) == access {
+            Ok(())
+        } else {
+            Err(GicError::ReadOnlyConfig)
+        }
+    }
+
+    /// Returns which secure SGI `sgi` the non-secure state may generate, like
+    /// [`set_sgi_ns_access`](Self::set_sgi_ns_access). Non-secure software
+    /// reads [`NsSgiAccess::None`].
+    pub fn sgi_ns_access(&self, sgi: SgiId) -> Result<NsSgiAccess, GicError> {
+        let (nsacr, shift) = self.sgi_nsacr(sgi)?;
+        Ok(distributor_common::ns_access(nsacr, shift))
+    }
+
+    /// Returns the `NSACR` register of the current CPU holding the field of
+    /// `sgi`, and the shift of the field.
+    fn sgi_nsacr(&self, sgi: SgiId) -> Result<(&ReadWrite<u32>, usize), GicError> {
+        let sgi = sgi.intid();
+        if self.gicd.security_disabled() {
+            return Err(GicError::Unsupported);
+        }
+        Ok(match self.local_gicr() {
+            Some(gicr) => {
+                let nsacr: *const ReadWrite<u32> = &gicr.sgi_regs().NSACR;
+                // SAFETY: The redistributor frames stay mapped as long as the driver.
+                let nsacr = unsafe { &*nsacr };
+                distributor_common::field(slice::from_ref(nsacr), sgi.0, 2)
+            }
+            None => {
+                self.check_banked_private(sgi)?;
+                distributor_common::field(&self.gicd.regs().NSACR, sgi.0, 2)
+            }
+        })
+    }
+
+    /// Enables signalling the interrupts of `group` to the current CPU, in
+    /// `GICD_CTLR` and in the CPU interface.
+    ///
+    /// The CPU interface enables Group 0, or Group 1 of the security state the
+    /// CPU runs in, so secure software enables [`SecurityGroup::G1S`] and
+    /// non-secure software [`SecurityGroup::G1NS`].
+    pub fn enable_security_group(&self, group: SecurityGroup) {
+        let enable = match group {
+            SecurityGroup::G0 => GicdCtlr::EnableGrp0,
+            SecurityGroup::G1S => GicdCtlr::EnableGrp1S,
+            SecurityGroup::G1NS => GicdCtlr::EnableGrp1NS,
+        };
+        rmw::modify(&self.gicd.regs().CTLR, |ctlr| ctlr | enable.bits());
+        self.gicd.wait_rwp();
+
+        // SAFETY: Writing to these system registers doesn't access memory in any way.
+        unsafe {
+            match group {
+                SecurityGroup::G0 => write_sysreg!(icc_igrpen0_el1, 0x00000001),
+                SecurityGroup::G1S => ExceptionLevel::current().enable_grp1(true),
+                SecurityGroup::G1NS => ExceptionLevel::current().enable_grp1(false),
+            }
+        }
+    }
+
+    /// Sends the SGI `sgi` to the CPUs of `targets`, with as few barriers as
+    /// possible.
+    ///
+    /// A single `DSB` makes prior memory writes visible to the targets before
+    /// the SGIs are sent, and a single `ISB` after the writes to
+    /// `ICC_SGI1R_EL1` makes sure they take effect.
+    pub fn send_sgi_fast<const N: usize>(&self, sgi: SgiId, targets: &SgiTargets<N>) {
+        let intid = u64::from(sgi.number()) << Self::SGI1R_INTID_SHIFT;
+        fence::dsb(Domain::IshSt, "send SGI");
+        for &value in targets.values() {
+            // SAFETY: Writing to this system register doesn't access memory in any way.
+            unsafe { write_sysreg!(icc_sgi1r_el1, value | intid) }
+        }
+        fence::isb("send SGI");
+    }
+
+    /// Sends the SGI `sgi` to the CPUs with the given `MPIDR_EL1` values, as
+    /// for a TLB shootdown.
+    ///
+    /// The CPUs are grouped by cluster, wherever they are in `mpidrs`, so
+    /// this takes one write to `ICC_SGI1R_EL1` for each group of 16 CPUs
+    /// sharing their `Aff3.Aff2.Aff1` affinity, between a single `DSB` and
+    /// `ISB`. Grouping takes time quadratic in the number of CPUs; for targets
+    /// sent to repeatedly, encode them once with [`SgiTargets`] and use
+    /// [`send_sgi_fast`](Self::send_sgi_fast).
+    pub fn send_sgi_multi(&self, sgi: SgiId, mpidrs: &[u64]) {
+        let intid = u64::from(sgi.number()) << Self::SGI1R_INTID_SHIFT;
+        fence::dsb(Domain::IshSt, "send SGI");
+        sgi::for_each_sgi1r(mpidrs, |value| {
+            // SAFETY: Writing to this system register doesn't access memory in any way.
+            unsafe { write_sysreg!(icc_sgi1r_el1, value | intid) }
+        });
+        fence::isb("send SGI");
+    }
+
+    /// Enables or disables the interrupt with the given ID.
+    fn set_enable(&self, intid: IntId, enable: bool) {
+        let gicd = self.gicd.regs();
+
+        // Disabling only takes effect once the register write has completed.
+        if intid.is_private() {
+            if let Some(gicr) = self.local_gicr() {
+                let sgi = gicr.sgi_regs();
+                let (isenabler, shift) = RegBank::private_field(intid, &sgi.ISENABLER, 1);
+                let (icenabler, _) = RegBank::private_field(intid, &sgi.ICENABLER, 1);
+                distributor_common::set_enable(isenabler, icenabler, shift, enable);
+                if !enable {
+                    gicr.wait_rwp();
+                }
+                return;
+            }
+            if self.check_banked_private(intid).is_err() {
+                return;
+            }
+        }
+        if self.refuses_shared(intid, if enable { "enabling" } else { "disabling" }) {
+            return;
+        }
+        {
+            let (isenabler, shift) = RegBank::field(intid, &gicd.ISENABLER, &gicd.ISENABLERnE, 1);
+            let (icenabler, _) = RegBank::field(intid, &gicd.ICENABLER, &gicd.ICENABLERnE, 1);
+            distributor_common::set_enable(isenabler, icenabler, shift, enable);
+            if !enable {
+                self.gicd.wait_rwp();
+            }
+        }
+    }
+
+    fn cpu_sys_reg_init(&mut self) {
+        let el = ExceptionLevel::current();
+        // SAFETY: Accessing these system registers doesn't access memory in any way.
+        unsafe {
+            // Enable system register access, for the lower exception levels too.
+            el.enable_sre();
+        }
+
+        // SAFETY: The system register interface was just enabled.
+        if self.disable_bypass && !unsafe { el.disable_bypass() } {
+            error!("GICv3: interrupt bypass controlled by a higher exception level");
+        }
+
+        // SAFETY: Writing to this system register doesn't access memory in any way.
+        unsafe {
+            write_sysreg!(icc_pmr_el1, self.priority_mask.into());
+        }
 
         // SAFETY: Writing to this system register doesn't access memory in any way.
         unsafe {
             // Disable use of `ICC_PMR_EL1` as a hint for interrupt distribution, configure a write
             // to an EOI register to also deactivate the interrupt, and configure preemption groups
             // for group 0 and group 1 interrupts separately.
-            write_sysreg!(icc_ctlr_el1, 0x00);
+            el.write_ctlr(0x00);
         }
 
         unsafe {
             // Enable non-secure group 1.
-            write_sysreg!(icc_igrpen1_el1, 0x00000001);
+            el.enable_grp1(false);
         }
     }
 }
 
 impl GenericArmGic for GicV3 {
     /// Initialises the GIC.
-    fn init_primary(&mut self) {
-        self.gicd.init();
+    fn init_primary(&mut self) -> Result<(), GicError> {
+        self.handoff = false;
+        self.gicd.init(false, &self.reserved)?;
+        self.per_cpu_init();
+        Ok(())
+    }
+
+    fn init_primary_handoff(&mut self) -> Result<(), GicError> {
+        self.handoff = true;
+        self.gicd.init(true, &self.reserved)?;
         self.per_cpu_init();
+        Ok(())
+    }
+
+    fn early_init(&mut self) -> Result<(), GicError> {
+        self.handoff = false;
+        self.gicd.early_init()?;
+        self.per_cpu_init();
+        Ok(())
+    }
+
+    fn finish_init(&mut self) {
+        self.gicd.init_spis(self.handoff, &self.reserved);
+        self.gicd.route_spis(self.handoff, &self.reserved);
+    }
+
+    fn translate(&self, id: usize, int_type: InterruptType) -> Option<IntId> {
+        let intid = translate_irq(id, int_type)?;
+        (intid < self.gicd.max_irqs()).then_some(IntId(intid))
     }
 
     fn per_cpu_init(&mut self) {
-        self.gicr.init();
+        // Switch to the redistributor of this CPU, which the GICR base address
+        // given at construction is only for on the boot CPU, if at all.
+        #[cfg(not(feature = "single-core"))]
+        {
+            let affinity = current_affinity();
+            let found = self.redistributor_for(affinity);
+            if let Some(gicr) = &mut self.gicr {
+                match found {
+                    Some(rd) => gicr.gicr_base = rd.gicr_base,
+                    None => error!("GICv3: no redistributor for affinity {:#x}", affinity),
+                }
+            }
+        }
+        if let Some(gicr) = &mut self.gicr {
+            gicr.init(
+                self.handoff,
+                !self.gicd.security_disabled(),
+                self.gicd.default_priority,
+                &self.reserved,
+            );
+        }
         self.cpu_sys_reg_init();
     }
 
+    fn secondary_cpu_start(&self) -> Result<Self, GicError> {
+        if !sysreg_interface_enabled() {
+            error!("GICv3: system register interface disabled on this CPU");
+            return Err(GicError::Unsupported);
+        }
+        let mut gic = *self;
+        if let Some(gicr) = &mut gic.gicr {
+            #[cfg(not(feature = "single-core"))]
+            {
+                let affinity = current_affinity();
+                let rd = self
+                    .redistributor_for(affinity)
+                    .ok_or(GicError::NoRedistributor)?;
+                gicr.gicr_base = rd.gicr_base;
+            }
+            gicr.wake()?;
+        }
+        gic.per_cpu_init();
+        Ok(gic)
+    }
+
     /// Enables the interrupt with the given ID.
-    fn enable_interrupt(&mut self, intid: IntId) {
-        let index = intid.0 / 32;
-        let bit = 1 << (intid.0 % 32);
+    fn enable_interrupt(&self, intid: IntId) {
+        if self.reserved.refuses(intid, "enabling") {
+            return;
+        }
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "enabled");
+        #[cfg(feature = "lifecycle-tracking")]
+        lifecycle::check_inactive(intid, "enabled");
+        self.set_enable(intid, true);
+    }
 
+    fn disable_interrupt(&self, intid: IntId) {
+        if self.reserved.refuses(intid, "disabling") {
+            return;
+        }
+        self.set_enable(intid, false);
+    }
+
+    fn is_reserved(&self, intid: IntId) -> bool {
+        self.reserved.contains(intid)
+    }
+
+    #[cfg(feature = "claim-tracking")]
+    fn enable_interrupt_claimed(&self, intid: IntId, owner: &'static str) {
+        if self.reserved.refuses(intid, "enabling") {
+            return;
+        }
+        claim::claim(intid, owner);
+        self.set_enable(intid, true);
+    }
+
+    #[cfg(feature = "claim-tracking")]
+    fn release_interrupt(&self, intid: IntId) -> Option<&'static str> {
+        self.set_enable(intid, false);
+        claim::release(intid)
+    }
+
+    /// Configures the trigger type for the interrupt with the given ID.
+    fn set_trigger(&self, intid: IntId, tm: TriggerMode) -> Result<(), GicError> {
+        self.reserved.check(intid)?;
+        seal::check()?;
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
         if intid.is_private() {
-            self.gicr.sgi_regs().ISENABLER[index].set(bit);
-        } else {
-            self.gicd.regs().ISENABLER[index].set(bit);
+            if let Some(gicr) = self.local_gicr() {
+                return gicr.set_trigger(intid, tm);
+            }
+            self.check_banked_private(intid)?;
         }
+        self.check_shared(intid)?;
+        self.gicd.set_trigger(intid, tm)
     }
 
-    fn disable_interrupt(&mut self, intid: IntId) {
-        let index = intid.0 / 32;
-        let bit = 1 << (intid.0 % 32);
+    fn reconfigure_trigger(&self, intid: IntId, tm: TriggerMode) -> Result<(), GicError> {
+        self.reserved.check(intid)?;
+        seal::check()?;
+        let gicr = self.local_gicr().filter(|_| intid.is_private());
+        if intid.is_private() && gicr.is_none() {
+            self.check_banked_private(intid)?;
+        }
+        self.check_shared(intid)?;
+        let gicd = self.gicd.regs();
+        let (isenabler, icpendr, shift) = match &gicr {
+            Some(gicr) => {
+                let sgi = gicr.sgi_regs();
+                let (isenabler, shift) = RegBank::private_field(intid, &sgi.ISENABLER, 1);
+                let (icpendr, _) = RegBank::private_field(intid, &sgi.ICPENDR, 1);
+                (isenabler, icpendr, shift)
+            }
+            None => {
+                let (isenabler, shift) =
+                    RegBank::field(intid, &gicd.ISENABLER, &gicd.ISENABLERnE, 1);
+                let (icpendr, _) = RegBank::field(intid, &gicd.ICPENDR, &gicd.ICPENDRnE, 1);
+                (isenabler, icpendr, shift)
+            }
+        };
+        let enabled = distributor_common::read_bit(isenabler, shift);
+        // This waits for the disable to take effect.
+        self.set_enable(intid, false);
+        icpendr.set(1 << shift);
+        let result = self.set_trigger(intid, tm);
+        if enabled {
+            self.set_enable(intid, true);
+        }
+        result
+    }
 
+    fn set_group(&self, intid: IntId, group: InterruptGroup) {
+        if self.reserved.refuses(intid, "grouping") || seal::refuses(intid, "grouping") {
+            return;
+        }
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
         if intid.is_private() {
-            self.gicr.sgi_regs().ICENABLER[index].set(bit);
-        } else {
-            self.gicd.regs().ICENABLER[index].set(bit);
+            if let Some(gicr) = self.local_gicr() {
+                gicr.set_group(intid, group, !self.gicd.security_disabled());
+                return;
+            }
+            if self.check_banked_private(intid).is_err() {
+                return;
+            }
         }
+        if self.refuses_shared(intid, "grouping") {
+            return;
+        }
+        self.gicd.set_group(intid, group);
     }
 
-    /// Configures the trigger type for the interrupt with the given ID.
-    fn set_trigger(&mut self, intid: IntId, tm: TriggerMode) {
+    fn set_priority(&self, intid: IntId, priority: u8) {
+        if self.reserved.refuses(intid, "prioritisation") {
+            return;
+        }
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
         if intid.is_private() {
-            self.gicr.set_trigger(intid.0, tm);
-        } else {
-            self.gicd.set_trigger(intid.0, tm);
+            if let Some(gicr) = self.local_gicr() {
+                gicr.set_priority(intid, priority);
+                return;
+            }
+            if self.check_banked_private(intid).is_err() {
+                return;
+            }
         }
+        if self.refuses_shared(intid, "prioritisation") {
+            return;
+        }
+        self.gicd.set_priority(intid, priority);
+    }
+
+    fn add_spis(&self, first: IntId, count: usize, config: IrqConfig) -> Result<(), GicError> {
+        self.gicd.check_spi_range(first, count)?;
+        seal::check()?;
+        for id in first.0..first.0 + count {
+            self.reserved.check(IntId(id))?;
+        }
+        for id in first.0..first.0 + count {
+            self.gicd.reset_spi(IntId(id));
+            self.configure(IntId(id), config)?;
+        }
+        Ok(())
+    }
+
+    /// With the `single-core` feature, SPIs stay routed to the only CPU and
+    /// this does nothing.
+    fn set_affinity(&self, intid: IntId, affinity: u64) {
+        if cfg!(feature = "single-core")
+            || !intid.is_spi() && !intid.is_espi()
+            || self.reserved.refuses(intid, "routing")
+            || seal::refuses_routing(intid)
+            || self.refuses_shared(intid, "routing")
+        {
+            return;
+        }
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        self.gicd.set_route(intid, affinity);
+    }
+
+    fn cpu_interface_disable(&self) {
+        // SAFETY: `per_cpu_init` enabled the system register interface.
+        unsafe { ExceptionLevel::current().disable_grp1(false) }
+        fence::isb("CPU interface disable");
+    }
+
+    fn cpu_interface_enable(&self) {
+        // SAFETY: `per_cpu_init` enabled the system register interface.
+        unsafe { ExceptionLevel::current().enable_grp1(false) }
+        fence::isb("CPU interface enable");
+    }
+
+    fn set_priority_grouping(&self, grouping: PriorityGrouping) -> PriorityGrouping {
+        // SAFETY: Writing to this system register doesn't access memory in any way.
+        unsafe { write_sysreg!(icc_bpr1_el1, grouping.bpr1().into()) }
+        fence::isb("priority grouping");
+        self.priority_grouping()
+    }
+
+    fn priority_grouping(&self) -> PriorityGrouping {
+        // SAFETY: Reading this system register doesn't access memory in any way.
+        PriorityGrouping::from_bpr1(unsafe { read_sysreg!(icc_bpr1_el1) } as u32)
+    }
+
+    fn enable_fiq_split(&self) {
+        // Group 0 is always signalled as FIQ and Group 1 as IRQ to the
+        // security state the interrupts belong to, so only the enables matter.
+        rmw::modify(&self.gicd.regs().CTLR, |ctlr| {
+            ctlr | GicdCtlr::EnableGrp0.bits()
+        });
+        self.gicd.wait_rwp();
+
+        // SAFETY: Writing to this system register doesn't access memory in any way.
+        unsafe { write_sysreg!(icc_igrpen0_el1, 0x00000001) }
     }
 
     /// Gets the ID of the highest priority signalled interrupt, and acknowledges it.
     ///
     /// Returns `None` if there is no pending interrupt of sufficient priority.
     fn get_and_acknowledge_interrupt(&self) -> Option<IntId> {
-        // SAFETY: Reading this system register doesn't access memory in any way.
-        let intid = unsafe { read_sysreg!(icc_iar1_el1) } as usize;
-        if intid == IntId::SPECIAL_START {
-            None
-        } else {
-            Some(IntId(intid))
+        self.acknowledge(true)
+    }
+
+    fn drain(&self, mut handler: impl FnMut(IntId)) -> usize {
+        let mut count = 0;
+        while let Some(intid) = self.acknowledge(count == 0) {
+            handler(intid);
+            self.end_interrupt(intid);
+            count += 1;
         }
+        count
     }
 
     /// Informs the interrupt controller that the CPU has completed processing the given interrupt.
@@ -488,5 +2252,584 @@ impl GenericArmGic for GicV3 {
     fn end_interrupt(&self, intid: IntId) {
         // SAFETY: Writing to this system register doesn't access memory in any way.
         unsafe { write_sysreg!(icc_eoir1_el1, intid.0 as u64) }
+        #[cfg(feature = "lifecycle-tracking")]
+        lifecycle::ended(intid);
+        if self.disable_on_ack && !intid.is_lpi() {
+            self.set_enable(intid, true);
+        }
+    }
+
+    fn set_disable_on_ack(&mut self, enable: bool) {
+        self.disable_on_ack = enable;
+    }
+
+    fn get_and_acknowledge_group_interrupt(&self, group: InterruptGroup) -> Option<IntId> {
+        // SAFETY: Reading these system registers doesn't access memory in any way.
+        let intid = unsafe {
+            match group {
+                InterruptGroup::Group0 => read_sysreg!(icc_iar0_el1),
+                InterruptGroup::Group1 => read_sysreg!(icc_iar1_el1),
+            }
+        } as usize;
+        if intid == IntId::SPURIOUS {
+            spurious::record();
+        }
+        if IntId(intid).is_special() {
+            None
+        } else {
+            #[cfg(feature = "lifecycle-tracking")]
+            lifecycle::acknowledged(IntId(intid));
+            Some(IntId(intid))
+        }
+    }
+
+    fn end_group_interrupt(&self, intid: IntId, group: InterruptGroup) {
+        // SAFETY: Writing to these system registers doesn't access memory in any way.
+        unsafe {
+            match group {
+                InterruptGroup::Group0 => write_sysreg!(icc_eoir0_el1, intid.0 as u64),
+                InterruptGroup::Group1 => write_sysreg!(icc_eoir1_el1, intid.0 as u64),
+            }
+        }
+        #[cfg(feature = "lifecycle-tracking")]
+        lifecycle::ended(intid);
+    }
+
+    fn deactivate_unacked(&self, intid: IntId) -> Result<(), GicError> {
+        let gicr = self.local_gicr().filter(|_| intid.is_private());
+        if intid.is_private() && gicr.is_none() {
+            self.check_banked_private(intid)?;
+        }
+        self.check_shared(intid)?;
+        // SAFETY: `per_cpu_init` enabled the system register interface.
+        if unsafe { ExceptionLevel::current().split_eoi() } {
+            // SAFETY: Writing to this system register doesn't access memory in any way.
+            unsafe { write_sysreg!(icc_dir_el1, intid.0 as u64) }
+            return Ok(());
+        }
+        let (icactiver, shift) = match &gicr {
+            Some(gicr) => RegBank::private_field(intid, &gicr.sgi_regs().ICACTIVER, 1),
+            None => {
+                let gicd = self.gicd.regs();
+                RegBank::field(intid, &gicd.ICACTIVER, &gicd.ICACTIVERnE, 1)
+            }
+        };
+        icactiver.set(1 << shift);
+        Ok(())
+    }
+
+    fn trigger_spi(&self, spi: Spi) {
+        let intid = spi.intid();
+        if self.refuses_shared(intid, "triggering") {
+            return;
+        }
+        self.gicd.regs().ISPENDR[intid.0 / 32].set(1 << (intid.0 % 32));
+    }
+
+    fn clear_spi(&self, spi: Spi) {
+        let intid = spi.intid();
+        if self.refuses_shared(intid, "clearing") {
+            return;
+        }
+        self.gicd.regs().ICPENDR[intid.0 / 32].set(1 << (intid.0 % 32));
+    }
+
+    /// With the `single-core` feature, the current CPU has index 0 and no
+    /// other CPU has one.
+    fn cpu_index(&self, mpidr: u64) -> Option<usize> {
+        if cfg!(feature = "single-core") {
+            return (mpidr_affinity(mpidr) == current_affinity()).then_some(0);
+        }
+        if self.gicr.is_none() {
+            // Without redistributors, the indices are Aff0 of the first
+            // cluster, which a target list of `ICC_SGI1R_EL1` covers.
+            let aff0 = (mpidr & 0xff) as usize;
+            return (mpidr_affinity(mpidr) & !0xff == 0 && aff0 < 16).then_some(aff0);
+        }
+        self.redistributor_for(mpidr_affinity(mpidr))
+            .map(|rd| rd.processor_number())
+    }
+
+    fn affinity_for<const W: usize>(&self, cpus: &CpuSet<W>) -> Option<u64> {
+        let mut indices = cpus.iter();
+        let index = indices.next()?;
+        if !self.gicd.affinity_routing() {
+            return Some(cpus.target_list().into());
+        }
+        if indices.next().is_some() {
+            return Some(GicDistributor::IROUTER_IRM);
+        }
+        if self.gicr.is_none() {
+            // The indices are Aff0 of the first cluster, as for `cpu_index`.
+            return (index < 16).then_some(index as u64);
+        }
+        let mut affinity = None;
+        self.for_each_redistributor(|rd| {
+            if rd.processor_number() == index {
+                affinity = Some(affinity_mpidr(rd.affinity()));
+            }
+        });
+        affinity
+    }
+
+    fn send_sgi<const W: usize>(&self, sgi: SgiId, targets: &CpuSet<W>) {
+        let intid = u64::from(sgi.number()) << Self::SGI1R_INTID_SHIFT;
+        fence::dsb(Domain::IshSt, "send SGI");
+        if cfg!(feature = "single-core") {
+            if targets.contains(0) {
+                let value = sgi1r_target(sysregs::mpidr());
+                // SAFETY: Writing to this system register doesn't access memory in any way.
+                unsafe { write_sysreg!(icc_sgi1r_el1, value | intid) }
+            }
+            fence::isb("send SGI");
+            return;
+        }
+        // Targets of the same cluster that are found in a row share a write.
+        let mut pending = None;
+        if self.gicr.is_none() {
+            pending = Some(u64::from(targets.cluster_target_list())).filter(|&list| list != 0);
+        }
+        self.for_each_redistributor(|rd| {
+            if !targets.contains(rd.processor_number()) {
+                return;
+            }
+            let value = sgi1r_target(affinity_mpidr(rd.affinity()));
+            pending = match pending {
+                Some(previous) if (previous ^ value) & SGI1R_GROUP_MASK == 0 => {
+                    Some(previous | value)
+                }
+                Some(previous) => {
+                    // SAFETY: Writing to this system register doesn't access memory in any way.
+                    unsafe { write_sysreg!(icc_sgi1r_el1, previous | intid) }
+                    Some(value)
+                }
+                None => Some(value),
+            };
+        });
+        if let Some(value) = pending {
+            // SAFETY: Writing to this system register doesn't access memory in any way.
+            unsafe { write_sysreg!(icc_sgi1r_el1, value | intid) }
+        }
+        fence::isb("send SGI");
+    }
+
+    fn raise_self_sgi(&self, sgi: SgiId) {
+        // The affinity of the current CPU names it directly, without looking
+        // up its redistributor.
+        let value =
+            sgi1r_target(sysregs::mpidr()) | u64::from(sgi.number()) << Self::SGI1R_INTID_SHIFT;
+        fence::dsb(Domain::IshSt, "send SGI");
+        // SAFETY: Writing to this system register doesn't access memory in any way.
+        unsafe { write_sysreg!(icc_sgi1r_el1, value) }
+        fence::isb("send SGI");
+    }
+
+    fn capabilities(&self) -> GicCapabilities {
+        let typer = self.gicd.regs().TYPER.get();
+        let typer2 = self.gicd.regs().TYPER2.get();
+        // The virtual CPU interface registers are only accessible from EL2.
+        let list_registers = if ExceptionLevel::current() >= ExceptionLevel::El2 {
+            // SAFETY: Reading this system register doesn't access memory in any way.
+            let vtr = unsafe { read_sysreg!(ich_vtr_el2) };
+            (vtr & Self::VTR_LIST_REGS_MASK) as usize + 1
+        } else {
+            0
+        };
+        GicCapabilities {
+            lpi: typer & GicDistributor::LPIS_MASK != 0,
+            espi: typer & GicDistributor::ESPI_MASK != 0,
+            nmi: typer & GicDistributor::NMI_MASK != 0,
+            vsgi: typer2 & GicDistributor::TYPER2_NASSGICAP_MASK != 0,
+            direct_lpi: self.gicr.is_some_and(|gicr| {
+                gicr.gicr_regs().TYPER.get() & GicRedistributor::TYPER_DIRECT_LPI != 0
+            }),
+            gicv2m: false,
+            security_extension: typer & GicDistributor::SECURITY_EXTN_MASK != 0,
+            mbi: typer & GicDistributor::MBIS_MASK != 0,
+            spis: self.gicd.max_irqs() - IntId::SPI_START,
+            list_registers,
+        }
+    }
+
+    fn inspect<const N: usize>(&self) -> HandoffState<N> {
+        let gicd = self.gicd.regs();
+        let mut state = HandoffState::new(gicd.CTLR.get());
+        // Without redistributors, the banked distributor registers hold the
+        // private interrupts, or read as zero with affinity routing enabled.
+        for i in 0..self.gicd.max_irqs().div_ceil(32) {
+            state.enabled[i] = gicd.ISENABLER[i].get();
+            state.group1[i] = gicd.IGROUPR[i].get();
+            state.group_modifier[i] = gicd.IGRPMODR[i].get();
+        }
+        if let Some(gicr) = self.local_gicr() {
+            let sgi = gicr.sgi_regs();
+            state.enabled[0] = sgi.ISENABLER[0].get();
+            state.group1[0] = sgi.IGROUPR0[0].get();
+            state.group_modifier[0] = sgi.IGRPMODR[0].get();
+            state.lpis_enabled = gicr.lpis_enabled();
+        }
+
+        let affinity_routing = self.gicd.affinity_routing();
+        for id in IntId::SPI_START..self.gicd.max_irqs() {
+            if dump::bit(&gicd.ISENABLER, id) {
+                state.push_route(
+                    IntId(id),
+                    if affinity_routing {
+                        gicd.IROUTER[id].get()
+                    } else {
+                        dump::byte(&gicd.ITARGETSR, id).into()
+                    },
+                );
+            }
+        }
+        state
+    }
+
+    fn diagnose(&self, intid: IntId) -> Result<Diagnosis, GicError> {
+        let id = intid.0;
+        if id >= self.gicd.max_irqs() {
+            return Err(GicError::InvalidIntId);
+        }
+        let gicd = self.gicd.regs();
+        let gicr = self.local_gicr();
+        let (state, group) = if let Some(gicr) = gicr.filter(|_| intid.is_private()) {
+            let sgi = gicr.sgi_regs();
+            let state = IrqState::read(
+                id,
+                &sgi.ISENABLER,
+                &sgi.ISPENDR,
+                &sgi.ISACTIVER,
+                &sgi.IPRIORITYR,
+            );
+            let group = SecurityGroup::from_bits(
+                dump::bit(&sgi.IGROUPR0, id),
+                dump::bit(&sgi.IGRPMODR, id),
+            );
+            (state, group)
+        } else {
+            let state = IrqState::read(
+                id,
+                &gicd.ISENABLER,
+                &gicd.ISPENDR,
+                &gicd.ISACTIVER,
+                &gicd.IPRIORITYR,
+            );
+            let group = SecurityGroup::from_bits(
+                dump::bit(&gicd.IGROUPR, id),
+                dump::bit(&gicd.IGRPMODR, id),
+            );
+            (state, group)
+        };
+        let ctlr = GicdCtlr::from_bits_truncate(gicd.CTLR.get());
+        let distributor_enabled = ctlr.contains(match group {
+            SecurityGroup::G0 => GicdCtlr::EnableGrp0,
+            SecurityGroup::G1S => GicdCtlr::EnableGrp1S,
+            SecurityGroup::G1NS => GicdCtlr::EnableGrp1NS,
+        });
+        let routed_here = if intid.is_private() {
+            true
+        } else if self.gicd.affinity_routing() {
+            let route = gicd.IROUTER[id].get();
+            route & GicDistributor::IROUTER_IRM != 0
+                || route == GicDistributor::mpidr_to_affinity_level(sysregs::mpidr())
+        } else {
+            // The first targets register reads as the mask of the current CPU.
+            dump::byte(&gicd.ITARGETSR, id) & dump::byte(&gicd.ITARGETSR, 0) != 0
+        };
+        // SAFETY: Reading these system registers doesn't access memory in any way.
+        let (igrpen, pmr, rpr) = unsafe {
+            (
+                match group {
+                    SecurityGroup::G0 => read_sysreg!(icc_igrpen0_el1),
+                    _ => read_sysreg!(icc_igrpen1_el1),
+                },
+                read_sysreg!(icc_pmr_el1),
+                read_sysreg!(icc_rpr_el1),
+            )
+        };
+        Ok(Diagnosis {
+            intid,
+            distributor_enabled,
+            redistributor_awake: gicr.map(|gicr| {
+                !WakerFlags::from_bits_truncate(gicr.gicr_regs().WAKER.get())
+                    .contains(WakerFlags::CHILDREN_ASLEEP)
+            }),
+            enabled: state.enabled,
+            pending: state.pending,
+            active: state.active,
+            group1: group != SecurityGroup::G0,
+            group_enabled: igrpen & 1 != 0,
+            priority: state.priority,
+            priority_mask: pmr as u8,
+            running_priority: rpr as u8,
+            routed_here,
+        })
+    }
+
+    fn dump_state(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        let gicd = self.gicd.regs();
+        writeln!(
+            out,
+            "GICD_CTLR={:#x} GICD_TYPER={:#x} GICD_IIDR={:#x}",
+            gicd.CTLR.get(),
+            gicd.TYPER.get(),
+            gicd.IIDR.get()
+        )?;
+
+        if let Some(gicr) = self.gicr {
+            let regs = gicr.gicr_regs();
+            writeln!(
+                out,
+                "GICR_CTLR={:#x} GICR_TYPER={:#x} GICR_WAKER={:#x}",
+                regs.CTLR.get(),
+                regs.TYPER.get(),
+                regs.WAKER.get()
+            )?;
+            let sgi = gicr.sgi_regs();
+            for id in 0..IntId::SPI_START {
+                IrqState::read(
+                    id,
+                    &sgi.ISENABLER,
+                    &sgi.ISPENDR,
+                    &sgi.ISACTIVER,
+                    &sgi.IPRIORITYR,
+                )
+                .write(out, IntId(id))?;
+            }
+        } else {
+            writeln!(out, "no redistributor")?;
+            for id in 0..IntId::SPI_START {
+                IrqState::read(
+                    id,
+                    &gicd.ISENABLER,
+                    &gicd.ISPENDR,
+                    &gicd.ISACTIVER,
+                    &gicd.IPRIORITYR,
+                )
+                .write(out, IntId(id))?;
+            }
+        }
+        for id in IntId::SPI_START..self.gicd.max_irqs() {
+            let mut state = IrqState::read(
+                id,
+                &gicd.ISENABLER,
+                &gicd.ISPENDR,
+                &gicd.ISACTIVER,
+                &gicd.IPRIORITYR,
+            );
+            state.target = Some(if self.gicd.affinity_routing() {
+                gicd.IROUTER[id].get()
+            } else {
+                dump::byte(&gicd.ITARGETSR, id).into()
+            });
+            state.write(out, IntId(id))?;
+        }
+
+        // SAFETY: Reading these system registers doesn't access memory in any way.
+        let (ctlr, pmr, bpr1, rpr, hppir1, igrpen1) = unsafe {
+            (
+                read_sysreg!(icc_ctlr_el1),
+                read_sysreg!(icc_pmr_el1),
+                read_sysreg!(icc_bpr1_el1),
+                read_sysreg!(icc_rpr_el1),
+                read_sysreg!(icc_hppir1_el1),
+                read_sysreg!(icc_igrpen1_el1),
+            )
+        };
+        writeln!(
+            out,
+            "ICC_CTLR={:#x} ICC_PMR={:#x} ICC_BPR1={:#x} ICC_RPR={:#x} ICC_HPPIR1={:#x} ICC_IGRPEN1={:#x}",
+            ctlr, pmr, bpr1, rpr, hppir1, igrpen1
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conformance::ModelGicd;
+
+    #[test]
+    fn test_sysreg_encodings() {
+        // mrs x0, icc_iar1_el1
+        assert_eq!(GicV3::MRS | GicV3::ICC_IAR1_EL1, 0xd538_cc00);
+        // msr icc_eoir1_el1, x3
+        assert_eq!(GicV3::MSR | GicV3::ICC_EOIR1_EL1 | 3, 0xd518_cc23);
+        // mrs x1, icc_iar0_el1
+        assert_eq!(GicV3::MRS | GicV3::ICC_IAR0_EL1 | 1, 0xd538_c801);
+    }
+
+    #[test]
+    fn test_security_group_bits() {
+        for group in [SecurityGroup::G0, SecurityGroup::G1S, SecurityGroup::G1NS] {
+            let (group_bit, modifier) = group.bits();
+            assert_eq!(SecurityGroup::from_bits(group_bit, modifier), group);
+        }
+    }
+
+    #[test]
+    fn test_reg_bank() {
+        let classic = [0u32; 16];
+        let extended = [0u32; 16];
+        let index = |(reg, shift): (&u32, usize), regs: &[u32]| {
+            (reg as *const u32 as usize - regs.as_ptr() as usize) / 4 * 32 + shift
+        };
+
+        let field = RegBank::field(IntId::spi(3), &classic, &extended, 2);
+        assert_eq!(index(field, &classic), 70);
+        let field = RegBank::field(IntId::espi(33), &classic, &extended, 8);
+        assert_eq!(index(field, &extended), 264);
+        let field = RegBank::private_field(IntId::ppi(1), &classic, 1);
+        assert_eq!(index(field, &classic), 17);
+        let field = RegBank::private_field(IntId::eppi(5), &classic, 1);
+        assert_eq!(index(field, &classic), 37);
+    }
+
+    #[test]
+    fn test_check_pending_table() {
+        assert_eq!(
+            GicRedistributor::check_pending_table(0x4001_0000, 0x2000, 16),
+            Ok(())
+        );
+        assert_eq!(
+            GicRedistributor::check_pending_table(0x4001_1000, 0x2000, 16),
+            Err(GicError::InvalidTable)
+        );
+        assert_eq!(
+            GicRedistributor::check_pending_table(0x4001_0000, 0x1000, 16),
+            Err(GicError::InvalidTable)
+        );
+        assert_eq!(
+            GicRedistributor::check_pending_table(0x4001_0000, 0x2000, 13),
+            Err(GicError::InvalidTable)
+        );
+    }
+
+    /// A distributor frame in memory.
+    #[repr(C, align(4096))]
+    struct Frame([u32; size_of::<GicDistributorRegs>() / 4]);
+
+    #[test]
+    fn test_rebase() {
+        let mut gic = GicV3::new(0x800_0000 as *mut u8, 0x80a_0000 as *mut u8);
+        let local = gic.gicr.as_mut().unwrap();
+        local.gicr_base = NonNull::new(0x80c_0000 as *mut u8).unwrap().cast();
+        local.support_ppi = 64;
+        // SAFETY: The registers are never accessed.
+        unsafe { gic.rebase(0x1000_0000 as *mut u8, 0x1100_0000 as *mut u8) };
+        let local = gic.gicr.unwrap();
+        assert_eq!(local.base() as usize, 0x1102_0000);
+        assert_eq!(local.support_ppi, 64);
+        assert_eq!(gic.rd_region.unwrap().base() as usize, 0x1100_0000);
+    }
+
+    #[test]
+    fn test_no_spis() {
+        let mut frame = Frame([0; size_of::<GicDistributorRegs>() / 4]);
+        let gicd = GicDistributor::new(frame.0.as_mut_ptr().cast());
+        // GICD_TYPER.ITLinesNumber is 0: INTIDs 0 to 31 only.
+        assert_eq!(
+            gicd.check_spi_range(IntId::spi(0), 1),
+            Err(GicError::Unsupported)
+        );
+        assert_eq!(
+            gicd.check_spi_range(IntId::espi(0), 1),
+            Err(GicError::InvalidIntId)
+        );
+
+        // ITLinesNumber 1: 32 SPIs.
+        frame.0[1] = 1;
+        let gicd = GicDistributor::new(frame.0.as_mut_ptr().cast());
+        assert_eq!(gicd.check_spi_range(IntId::spi(0), 32), Ok(()));
+        assert_eq!(
+            gicd.check_spi_range(IntId::spi(0), 33),
+            Err(GicError::InvalidIntId)
+        );
+    }
+
+    #[test]
+    fn test_security_group_readback() {
+        let mut frame = Frame([0; size_of::<GicDistributorRegs>() / 4]);
+        // IGROUPR1 and IGRPMODR1: SPI 0 in Group 1NS, SPI 1 in Secure Group 1.
+        frame.0[0x84 / 4] = 0b01;
+        frame.0[0xd04 / 4] = 0b10;
+        let gicd = GicDistributor::new(frame.0.as_mut_ptr().cast());
+        assert_eq!(gicd.security_group(IntId::spi(0)), SecurityGroup::G1NS);
+        assert_eq!(gicd.security_group(IntId::spi(1)), SecurityGroup::G1S);
+        assert_eq!(gicd.security_group(IntId::spi(2)), SecurityGroup::G0);
+    }
+
+    #[test]
+    fn test_handoff_keeps_reserved() {
+        let mut frame = Frame([0; size_of::<GicDistributorRegs>() / 4]);
+        // 32 SPIs, affinity routing off and the current CPU's target mask.
+        frame.0[1] = 1;
+        frame.0[0x800 / 4] = 0x01;
+        let mut gicd = GicDistributor::new(frame.0.as_mut_ptr().cast());
+        gicd.base_init();
+        let reserved = ReservedSet::new().with(IntId::spi(1)).unwrap();
+        gicd.init_spis(true, &reserved);
+        gicd.route_spis(true, &reserved);
+
+        // ICACTIVER1 and ICENABLER1 skip SPI 1.
+        assert_eq!(frame.0[0x384 / 4], !0b10);
+        assert_eq!(frame.0[0x184 / 4], !0b10);
+        // IPRIORITYR8, ITARGETSR8 and ICFGR2 leave SPI 1 alone.
+        let priority = u32::from(distributor_common::DEFAULT_PRIORITY);
+        assert_eq!(frame.0[0x420 / 4] & 0xffff, priority);
+        assert_eq!(frame.0[0x820 / 4] & 0xffff, 0x01);
+        assert_eq!(frame.0[0xc08 / 4] & 0xf, 0b10);
+    }
+
+    #[test]
+    fn test_handoff_keeps_reserved_private() {
+        /// The RD and SGI frames of a redistributor.
+        #[repr(C, align(4096))]
+        struct Redistributor([u32; 2 * SGI_OFFSET / 4]);
+
+        let mut frames = Redistributor([0; 2 * SGI_OFFSET / 4]);
+        let mut gicr = GicRedistributor::new(frames.0.as_mut_ptr().cast());
+        gicr.support_ppi = 16;
+        let reserved = ReservedSet::new().with(IntId::ppi(14)).unwrap();
+        gicr.init(true, true, 0xa0, &reserved);
+
+        // ICACTIVER0 and ICENABLER0 skip INTID 30.
+        let sgi = &frames.0[SGI_OFFSET / 4..];
+        assert_eq!(sgi[0x380 / 4], !(1 << 30));
+        assert_eq!(sgi[0x180 / 4], !(1 << 30));
+        // IPRIORITYR7 leaves INTID 30 alone.
+        assert_eq!(sgi[0x41c / 4], 0xa0 << 24 | 0xa0 << 8 | 0xa0);
+    }
+
+    /// Replays the words the driver left in `frame`, zeroed before, through
+    /// the model. Only the final value of each register is seen, so this
+    /// suits registers the driver writes whole rather than set/clear pairs.
+    fn replay(frame: &Frame, model: &mut ModelGicd) {
+        for (i, &word) in frame.0.iter().enumerate() {
+            if word != 0 {
+                model.write(i * 4, word.into(), 4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_model_registers() {
+        let mut frame = Frame([0; size_of::<GicDistributorRegs>() / 4]);
+        // GICD_CTLR.ARE, as the driver sets it at initialisation.
+        frame.0[0] = GicdCtlr::ARE_S.bits();
+        let gicd = GicDistributor::new(frame.0.as_mut_ptr().cast());
+        let intid = IntId::spi(1);
+        gicd.set_priority(intid, 0x80);
+        gicd.set_trigger(intid, TriggerMode::Edge).unwrap();
+        gicd.set_group(intid, InterruptGroup::Group1);
+        gicd.set_route(intid, 0x1_0000_0203);
+
+        let mut model = ModelGicd::new(288);
+        replay(&frame, &mut model);
+        assert_eq!(model.priority(intid.0), 0x80);
+        assert!(model.edge_triggered(intid.0));
+        assert!(model.group1(intid.0));
+        assert_eq!(model.route(intid.0), 0x1_0000_0203);
+        assert!(!model.enabled(intid.0));
     }
 }
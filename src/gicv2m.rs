@@ -0,0 +1,231 @@
+//! Allocation of MSIs from GICv2m frames.
+//!
+//! A GICv2m frame turns writes to its `MSI_SETSPI_NS` register into SPIs from
+//! a fixed window. Platforms with several PCIe root complexes often give each
+//! its own frame, so [`GicV2m`] keeps the frames apart: each is registered for
+//! a requester, such as the segment number of a root complex, and MSIs of that
+//! requester are only allocated from its frames.
+//!
+//! The official documentation: <https://developer.arm.com/documentation/ddi0521/latest/>
+
+use core::ptr::NonNull;
+use tock_registers::interfaces::Readable;
+
+use crate::logging::info;
+use crate::registers::gicv2m_regs::*;
+use crate::{GicError, IntId};
+
+/// An MSI allocated from a GICv2m frame: the device signals it by writing
+/// `data` to the physical address `address`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct V2mMsi {
+    /// The SPI the MSI is delivered as.
+    pub intid: IntId,
+    /// Physical address of the doorbell register of the frame.
+    pub address: u64,
+    /// Value the device writes to the doorbell.
+    pub data: u32,
+}
+
+/// The SPIs of a frame and which of them are allocated.
+#[derive(Copy, Clone, Debug)]
+struct SpiWindow {
+    first: usize,
+    count: usize,
+    used: [u32; SpiWindow::MAX_SPIS / 32],
+}
+
+impl SpiWindow {
+    /// `MSI_TYPER.Number` is 10 bits wide.
+    const MAX_SPIS: usize = 1024;
+
+    fn new(first: usize, count: usize) -> Self {
+        Self {
+            first,
+            count: count.min(Self::MAX_SPIS),
+            used: [0; Self::MAX_SPIS / 32],
+        }
+    }
+
+    fn contains(&self, intid: IntId) -> bool {
+        (self.first..self.first + self.count).contains(&intid.0)
+    }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.first < other.first + other.count && other.first < self.first + self.count
+    }
+
+    fn alloc(&mut self) -> Option<IntId> {
+        let n = (0..self.count).find(|n| self.used[n / 32] & 1 << (n % 32) == 0)?;
+        self.used[n / 32] |= 1 << (n % 32);
+        Some(IntId(self.first + n))
+    }
+
+    fn free(&mut self, intid: IntId) {
+        let n = intid.0 - self.first;
+        self.used[n / 32] &= !(1 << (n % 32));
+    }
+
+    fn available(&self) -> usize {
+        let used: u32 = self.used.iter().map(|word| word.count_ones()).sum();
+        self.count - used as usize
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct V2mFrame {
+    /// Physical address of the frame.
+    pa: u64,
+    requester: u32,
+    spis: SpiWindow,
+}
+
+/// Up to `N` GICv2m frames with the requesters they serve.
+#[derive(Copy, Clone, Debug)]
+pub struct GicV2m<const N: usize> {
+    frames: [Option<V2mFrame>; N],
+}
+
+impl<const N: usize> GicV2m<N> {
+    const TYPER_BASE_SHIFT: u32 = 16;
+    const TYPER_FIELD_MASK: u32 = 0x3ff;
+
+    /// Offset of `MSI_SETSPI_NS` in a frame.
+    const SETSPI_NS_OFFSET: u64 = 0x40;
+
+    /// Returns an empty set of frames.
+    pub const fn new() -> Self {
+        Self { frames: [None; N] }
+    }
+
+    /// Registers the frame mapped at `base`, whose physical address is `pa`,
+    /// to serve `requester`, and returns its index.
+    ///
+    /// Returns [`GicError::CapacityExceeded`] if `N` frames are registered
+    /// already, or [`GicError::InvalidTable`] if the SPIs of the frame are no
+    /// SPIs or overlap those of another frame.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to the registers of the GICv2m frame at `pa`, mapped
+    /// as device memory.
+    pub unsafe fn add_frame(
+        &mut self,
+        base: *mut u8,
+        pa: u64,
+        requester: u32,
+    ) -> Result<usize, GicError> {
+        let slot = self
+            .frames
+            .iter()
+            .position(Option::is_none)
+            .ok_or(GicError::CapacityExceeded)?;
+
+        let regs = NonNull::new(base)
+            .ok_or(GicError::InvalidTable)?
+            .cast::<V2mFrameRegs>();
+        let typer = regs.as_ref().TYPER.get();
+        let first = ((typer >> Self::TYPER_BASE_SHIFT) & Self::TYPER_FIELD_MASK) as usize;
+        let count = (typer & Self::TYPER_FIELD_MASK) as usize;
+        let spis = SpiWindow::new(first, count);
+
+        if count == 0
+            || !IntId(first).is_spi()
+            || !IntId(first + count - 1).is_spi()
+            || self.frames().any(|frame| frame.spis.overlaps(&spis))
+        {
+            return Err(GicError::InvalidTable);
+        }
+        info!(
+            "GICv2m: frame at {:#x}, SPIs {}..{}, requester {:#x}",
+            pa,
+            first,
+            first + count,
+            requester
+        );
+        self.frames[slot] = Some(V2mFrame {
+            pa,
+            requester,
+            spis,
+        });
+        Ok(slot)
+    }
+
+    fn frames(&self) -> impl Iterator<Item = &V2mFrame> {
+        self.frames.iter().flatten()
+    }
+
+    /// Allocates an MSI from the frames of `requester`, in the order they were
+    /// registered.
+    ///
+    /// Returns [`GicError::Unsupported`] if no frame serves `requester`, or
+    /// [`GicError::CapacityExceeded`] if all its frames are exhausted.
+    pub fn alloc(&mut self, requester: u32) -> Result<V2mMsi, GicError> {
+        let mut found = false;
+        for frame in self.frames.iter_mut().flatten() {
+            if frame.requester != requester {
+                continue;
+            }
+            found = true;
+            if let Some(intid) = frame.spis.alloc() {
+                return Ok(V2mMsi {
+                    intid,
+                    address: frame.pa + Self::SETSPI_NS_OFFSET,
+                    data: intid.into(),
+                });
+            }
+        }
+        Err(if found {
+            GicError::CapacityExceeded
+        } else {
+            GicError::Unsupported
+        })
+    }
+
+    /// Frees the MSI delivered as `intid`, returning whether it was allocated
+    /// from one of the frames.
+    pub fn free(&mut self, intid: IntId) -> bool {
+        let Some(frame) = self
+            .frames
+            .iter_mut()
+            .flatten()
+            .find(|frame| frame.spis.contains(intid))
+        else {
+            return false;
+        };
+        frame.spis.free(intid);
+        true
+    }
+
+    /// Returns the number of MSIs left in the frame with the given index, or
+    /// `None` if there is no such frame.
+    pub fn available(&self, frame: usize) -> Option<usize> {
+        Some(self.frames.get(frame)?.as_ref()?.spis.available())
+    }
+}
+
+impl<const N: usize> Default for GicV2m<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spi_window() {
+        let mut spis = SpiWindow::new(64, 2);
+        assert!(spis.overlaps(&SpiWindow::new(65, 4)));
+        assert!(!spis.overlaps(&SpiWindow::new(66, 4)));
+
+        assert_eq!(spis.alloc(), Some(IntId(64)));
+        assert_eq!(spis.alloc(), Some(IntId(65)));
+        assert_eq!(spis.alloc(), None);
+        assert_eq!(spis.available(), 0);
+        spis.free(IntId(64));
+        assert_eq!(spis.available(), 1);
+        assert_eq!(spis.alloc(), Some(IntId(64)));
+    }
+}
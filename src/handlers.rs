@@ -0,0 +1,220 @@
+//! Fixed-capacity interrupt handler table.
+//!
+//! A [`HandlerTable`] maps interrupt IDs to handler functions without any heap
+//! allocation, its capacity being a const generic. Several handlers may share
+//! an interrupt; they are called in order of their priority, lowest value
+//! first, and in registration order for equal priorities, so dispatch is
+//! deterministic. It pairs with [`GenericArmGic::drain`]:
+//!
+//! ```ignore
+//! gic.drain(|intid| {
+//!     HANDLERS.dispatch(intid);
+//! });
+//! ```
+//!
+//! Handlers registered with [`register_deferred`](HandlerTable::register_deferred)
+//! are queued on a [`DeferralQueue`] by [`dispatch_or_defer`](HandlerTable::dispatch_or_defer)
+//! instead of running in the interrupt context.
+//!
+//! [`GenericArmGic::drain`]: crate::GenericArmGic::drain
+
+use crate::deferred::DeferralQueue;
+use crate::{GenericArmGic, GicError, IntId};
+
+/// An interrupt handler, called with the ID of the interrupt it handles.
+pub type Handler = fn(IntId);
+
+#[derive(Copy, Clone, Debug)]
+struct Entry {
+    intid: IntId,
+    priority: u8,
+    handler: Handler,
+    deferred: bool,
+}
+
+/// Handlers for up to `N` interrupts, kept sorted for dispatch.
+#[derive(Copy, Clone, Debug)]
+pub struct HandlerTable<const N: usize> {
+    entries: [Option<Entry>; N],
+    len: usize,
+}
+
+impl<const N: usize> HandlerTable<N> {
+    /// Returns an empty table.
+    pub const fn new() -> Self {
+        Self {
+            entries: [None; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the number of registered handlers.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether no handler is registered.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Registers `handler` for `intid` at `priority`, lower values being
+    /// called first.
+    ///
+    /// Returns [`GicError::CapacityExceeded`] if the table is full.
+    pub fn register(
+        &mut self,
+        intid: IntId,
+        priority: u8,
+        handler: Handler,
+    ) -> Result<(), GicError> {
+        self.insert(intid, priority, handler, false)
+    }
+
+    /// Registers `handler` like [`register`](Self::register), but to be
+    /// deferred by [`dispatch_or_defer`](Self::dispatch_or_defer) rather than
+    /// called in the interrupt context.
+    pub fn register_deferred(
+        &mut self,
+        intid: IntId,
+        priority: u8,
+        handler: Handler,
+    ) -> Result<(), GicError> {
+        self.insert(intid, priority, handler, true)
+    }
+
+    fn insert(
+        &mut self,
+        intid: IntId,
+        priority: u8,
+        handler: Handler,
+        deferred: bool,
+    ) -> Result<(), GicError> {
+        if self.len == N {
+            return Err(GicError::CapacityExceeded);
+        }
+        // Insert after every entry that sorts before or equal to the new one.
+        let key = (intid, priority);
+        let pos = self.entries[..self.len]
+            .iter()
+            .flatten()
+            .position(|e| (e.intid, e.priority) > key)
+            .unwrap_or(self.len);
+        self.entries[pos..=self.len].rotate_right(1);
+        self.entries[pos] = Some(Entry {
+            intid,
+            priority,
+            handler,
+            deferred,
+        });
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes `handler` from the handlers of `intid`.
+    ///
+    /// Returns whether it was registered.
+    pub fn unregister(&mut self, intid: IntId, handler: Handler) -> bool {
+        let Some(pos) = self.entries[..self.len]
+            .iter()
+            .flatten()
+            .position(|e| e.intid == intid && core::ptr::fn_addr_eq(e.handler, handler))
+        else {
+            return false;
+        };
+        self.entries[pos..self.len].rotate_left(1);
+        self.len -= 1;
+        self.entries[self.len] = None;
+        true
+    }
+
+    /// Returns the handlers registered for `intid`, in priority order.
+    fn handlers(&self, intid: IntId) -> impl Iterator<Item = &Entry> {
+        let entries = &self.entries[..self.len];
+        let start = entries.partition_point(|e| e.is_some_and(|e| e.intid < intid));
+        entries[start..]
+            .iter()
+            .flatten()
+            .take_while(move |e| e.intid == intid)
+    }
+
+    /// Calls the handlers registered for `intid` in priority order, including
+    /// deferred ones.
+    ///
+    /// Returns the number of handlers called, zero meaning the interrupt is
+    /// unhandled.
+    pub fn dispatch(&self, intid: IntId) -> usize {
+        let mut count = 0;
+        for entry in self.handlers(intid) {
+            (entry.handler)(intid);
+            count += 1;
+        }
+        count
+    }
+
+    /// Calls the handlers registered for `intid` in priority order, except
+    /// for deferred ones, which mask the interrupt and are queued on `queue`
+    /// until [`DeferralQueue::run_deferred`].
+    ///
+    /// Returns the number of handlers called or queued. A deferred handler
+    /// that doesn't fit in `queue` is called right away.
+    pub fn dispatch_or_defer<const M: usize>(
+        &self,
+        gic: &impl GenericArmGic,
+        queue: &mut DeferralQueue<M>,
+        intid: IntId,
+    ) -> usize {
+        let mut count = 0;
+        for entry in self.handlers(intid) {
+            if !entry.deferred || queue.defer(gic, intid, entry.handler).is_err() {
+                (entry.handler)(intid);
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+impl<const N: usize> Default for HandlerTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn first(_: IntId) {
+        CALLS.store(CALLS.load(Ordering::Relaxed) * 10 + 1, Ordering::Relaxed);
+    }
+
+    fn second(_: IntId) {
+        CALLS.store(CALLS.load(Ordering::Relaxed) * 10 + 2, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_dispatch_order() {
+        let mut table = HandlerTable::<3>::new();
+        table.register(IntId::spi(1), 0x80, second).unwrap();
+        table.register(IntId::spi(1), 0x40, first).unwrap();
+        table.register(IntId::ppi(14), 0, first).unwrap();
+        assert_eq!(
+            table.register(IntId::spi(2), 0, first),
+            Err(GicError::CapacityExceeded)
+        );
+
+        assert_eq!(table.dispatch(IntId::spi(1)), 2);
+        assert_eq!(CALLS.load(Ordering::Relaxed), 12);
+        assert_eq!(table.dispatch(IntId::spi(2)), 0);
+
+        assert!(table.unregister(IntId::spi(1), first));
+        assert!(!table.unregister(IntId::spi(1), first));
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.dispatch(IntId::spi(1)), 1);
+    }
+}
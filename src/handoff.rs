@@ -0,0 +1,121 @@
+//! Snapshot of the configuration firmware leaves behind.
+//!
+//! [`GenericArmGic::inspect`] reads the GIC before the driver changes
+//! anything, so a kernel can log or compare what different boot loaders hand
+//! over, or decide how much of the configuration to keep.
+//!
+//! [`GenericArmGic::inspect`]: crate::GenericArmGic::inspect
+
+use crate::IntId;
+
+/// Number of registers holding one bit for each interrupt ID below 1024.
+const BITMAP_WORDS: usize = 32;
+
+/// The configuration of the GIC as found before initialisation.
+///
+/// Interrupt bitmaps hold one bit per INTID below 1024, SGIs and PPIs being
+/// those of the CPU that took the snapshot. Up to `N` routes of enabled SPIs
+/// are kept.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HandoffState<const N: usize> {
+    /// The value of `GICD_CTLR`.
+    pub gicd_ctlr: u32,
+    /// Which interrupts are enabled.
+    pub enabled: [u32; BITMAP_WORDS],
+    /// Which interrupts are in Group 1, from `GICD_IGROUPRn` and
+    /// `GICR_IGROUPR0`. Non-secure software reads zero on a GIC with two
+    /// security states.
+    pub group1: [u32; BITMAP_WORDS],
+    /// The group modifier bits of a GICv3 with two security states, zero
+    /// otherwise.
+    pub group_modifier: [u32; BITMAP_WORDS],
+    /// Whether LPIs are enabled on the redistributor of the current CPU.
+    pub lpis_enabled: bool,
+    routes: [(IntId, u64); N],
+    route_count: usize,
+    routes_truncated: bool,
+}
+
+impl<const N: usize> HandoffState<N> {
+    pub(crate) const fn new(gicd_ctlr: u32) -> Self {
+        Self {
+            gicd_ctlr,
+            enabled: [0; BITMAP_WORDS],
+            group1: [0; BITMAP_WORDS],
+            group_modifier: [0; BITMAP_WORDS],
+            lpis_enabled: false,
+            routes: [(IntId(0), 0); N],
+            route_count: 0,
+            routes_truncated: false,
+        }
+    }
+
+    /// Records the route of the enabled SPI `intid`: the `GICD_IROUTER<n>`
+    /// value of a GICv3 using affinity routing, or the target list otherwise.
+    pub(crate) fn push_route(&mut self, intid: IntId, route: u64) {
+        match self.routes.get_mut(self.route_count) {
+            Some(slot) => {
+                *slot = (intid, route);
+                self.route_count += 1;
+            }
+            None => self.routes_truncated = true,
+        }
+    }
+
+    /// Returns whether the interrupt with the given ID was enabled.
+    pub fn is_enabled(&self, intid: IntId) -> bool {
+        bit(&self.enabled, intid)
+    }
+
+    /// Returns whether the interrupt with the given ID was in Group 1.
+    pub fn is_group1(&self, intid: IntId) -> bool {
+        bit(&self.group1, intid)
+    }
+
+    /// Returns the enabled interrupts, in ID order.
+    pub fn enabled_interrupts(&self) -> impl Iterator<Item = IntId> + '_ {
+        (0..BITMAP_WORDS * 32)
+            .map(IntId)
+            .filter(|&intid| self.is_enabled(intid))
+    }
+
+    /// Returns the routes of the enabled SPIs, as recorded by the driver.
+    pub fn routes(&self) -> &[(IntId, u64)] {
+        &self.routes[..self.route_count]
+    }
+
+    /// Returns whether more SPIs were enabled than `N` routes could be kept for.
+    pub fn routes_truncated(&self) -> bool {
+        self.routes_truncated
+    }
+}
+
+fn bit(bitmap: &[u32; BITMAP_WORDS], intid: IntId) -> bool {
+    bitmap
+        .get(intid.0 / 32)
+        .is_some_and(|word| word & 1 << (intid.0 % 32) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handoff_state() {
+        let mut state = HandoffState::<1>::new(0x12);
+        state.enabled[0] = 1 << 27;
+        state.enabled[1] = 1 << 1;
+        state.push_route(IntId::spi(1), 0x100);
+        state.push_route(IntId::spi(2), 0x200);
+
+        assert!(state.is_enabled(IntId::ppi(11)));
+        assert!(!state.is_group1(IntId::ppi(11)));
+        assert!(!state.is_enabled(IntId(5000)));
+        let mut enabled = state.enabled_interrupts();
+        assert_eq!(enabled.next(), Some(IntId::ppi(11)));
+        assert_eq!(enabled.next(), Some(IntId::spi(1)));
+        assert_eq!(enabled.next(), None);
+        assert_eq!(state.routes(), &[(IntId::spi(1), 0x100)]);
+        assert!(state.routes_truncated());
+    }
+}
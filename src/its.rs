@@ -0,0 +1,1038 @@
+//! Types and definitions for the GICv3 Interrupt Translation Service (ITS).
+//!
+//! The ITS translates MSI writes from devices into LPIs. It is programmed
+//! through a command queue in memory: [`Its::submit`] queues a command without
+//! waiting for the ITS to process it and returns a [`CommandTicket`], which
+//! [`Its::poll_complete`] later checks, so many devices can be set up at once.
+//! The last commands submitted are kept with their status for debugging, see
+//! [`Its::command_history`].
+//!
+//! The official documentation: <https://developer.arm.com/documentation/ihi0069/latest//>
+
+use core::ptr::NonNull;
+use tock_registers::interfaces::{Readable, Writeable};
+
+use crate::delay::{Delay, Poller};
+use crate::fence::{self, Domain};
+use crate::logging::info;
+use crate::perf::{self, Counter};
+use crate::registers::gits_regs::*;
+use crate::registers::{read_raw, write_raw};
+use crate::{GicError, GicRedistributor, IntId, Lpi};
+
+/// Source of the memory the ITS needs for its tables, command queue and the
+/// interrupt translation tables of mapped devices.
+pub trait ItsMemory {
+    /// Allocates `size` bytes of zeroed, physically contiguous memory aligned
+    /// to `align` bytes.
+    ///
+    /// Returns the address the driver uses to access the memory together with
+    /// its physical address, or `None` if the request can't be satisfied.
+    fn alloc_zeroed(&mut self, size: usize, align: usize) -> Option<(NonNull<u8>, u64)>;
+
+    /// Frees the `size` bytes at `addr`, whose physical address is `phys`,
+    /// returned by an earlier [`alloc_zeroed`](Self::alloc_zeroed).
+    ///
+    /// The driver only frees the interrupt translation tables of unmapped
+    /// devices, once the ITS no longer uses them. Providers that never free
+    /// memory can keep the default, which leaks it.
+    fn free(&mut self, addr: NonNull<u8>, phys: u64, size: usize) {
+        let _ = (addr, phys, size);
+    }
+
+    /// Returns the number of device ID bits the device table covers, or
+    /// `None` for as many as the ITS supports.
+    ///
+    /// Bounding it keeps the table small on systems with a few devices of
+    /// low IDs, at the cost of the others failing to map.
+    fn device_bits(&self) -> Option<u32> {
+        None
+    }
+
+    /// Returns the size of the command queue in bytes, a multiple of 4KB of
+    /// at most 1MB.
+    fn queue_size(&self) -> usize {
+        0x10000
+    }
+}
+
+/// A command understood by the ITS.
+///
+/// `rdbase` fields hold the target redistributor as the ITS expects it: its
+/// physical address if `GITS_TYPER.PTA` is set, otherwise its processor number
+/// shifted left by 16. [`Its::rdbase`] returns the right one.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ItsCommand {
+    /// Maps a device to the interrupt translation table at `itt_addr`, which
+    /// holds `1 << event_bits` events, or unmaps it if `valid` is false.
+    Mapd {
+        /// The device.
+        device_id: u32,
+        /// Physical address of the interrupt translation table.
+        itt_addr: u64,
+        /// Number of bits of the event IDs of the device, from 1 to 32.
+        event_bits: u32,
+        /// Whether the device is mapped rather than unmapped.
+        valid: bool,
+    },
+    /// Maps a collection to a redistributor, or unmaps it if `valid` is false.
+    Mapc {
+        /// The collection.
+        collection: u16,
+        /// The target redistributor.
+        rdbase: u64,
+        /// Whether the collection is mapped rather than unmapped.
+        valid: bool,
+    },
+    /// Maps an event of a device to an LPI delivered to a collection.
+    Mapti {
+        /// The device.
+        device_id: u32,
+        /// The event of the device.
+        event_id: u32,
+        /// The LPI the event is translated to.
+        intid: IntId,
+        /// The collection the LPI is delivered to.
+        collection: u16,
+    },
+    /// Makes the LPI of an event pending, as if the device had signalled it.
+    Int {
+        /// The device.
+        device_id: u32,
+        /// The event of the device.
+        event_id: u32,
+    },
+    /// Reloads the configuration of the LPI of an event from memory.
+    Inv {
+        /// The device.
+        device_id: u32,
+        /// The event of the device.
+        event_id: u32,
+    },
+    /// Reloads the configuration of all LPIs of a collection from memory.
+    Invall {
+        /// The collection.
+        collection: u16,
+    },
+    /// Waits until the effects of earlier commands are visible at a redistributor.
+    Sync {
+        /// The redistributor.
+        rdbase: u64,
+    },
+    /// Removes the mapping of an event and clears the pending state of its LPI.
+    Discard {
+        /// The device.
+        device_id: u32,
+        /// The event of the device.
+        event_id: u32,
+    },
+}
+
+impl ItsCommand {
+    const MAPD: u64 = 0x08;
+    const MAPC: u64 = 0x09;
+    const MAPTI: u64 = 0x0a;
+    const INT: u64 = 0x03;
+    const INV: u64 = 0x0c;
+    const INVALL: u64 = 0x0d;
+    const SYNC: u64 = 0x05;
+    const DISCARD: u64 = 0x0f;
+
+    const VALID: u64 = 1 << 63;
+    const ITT_ADDR_MASK: u64 = 0x000f_ffff_ffff_ff00;
+    const RDBASE_MASK: u64 = 0x000f_ffff_ffff_0000;
+
+    /// Returns [`GicError::InvalidItsId`] if the command can't be encoded, as
+    /// for a [`Mapd`](Self::Mapd) with `event_bits` outside 1 to 32.
+    fn check(&self) -> Result<(), GicError> {
+        match *self {
+            Self::Mapd { event_bits, .. } if !(1..=32).contains(&event_bits) => {
+                Err(GicError::InvalidItsId)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the four doublewords of the command as they're written to the queue.
+    fn encode(&self) -> [u64; 4] {
+        let valid = |v: bool| if v { Self::VALID } else { 0 };
+        let device = |opcode: u64, device_id: u32| opcode | u64::from(device_id) << 32;
+
+        match *self {
+            Self::Mapd {
+                device_id,
+                itt_addr,
+                event_bits,
+                valid: v,
+            } => [
+                device(Self::MAPD, device_id),
+                u64::from(event_bits - 1),
+                (itt_addr & Self::ITT_ADDR_MASK) | valid(v),
+                0,
+            ],
+            Self::Mapc {
+                collection,
+                rdbase,
+                valid: v,
+            } => [
+                Self::MAPC,
+                0,
+                (rdbase & Self::RDBASE_MASK) | u64::from(collection) | valid(v),
+                0,
+            ],
+            Self::Mapti {
+                device_id,
+                event_id,
+                intid,
+                collection,
+            } => [
+                device(Self::MAPTI, device_id),
+                u64::from(event_id) | u64::from(u32::from(intid)) << 32,
+                u64::from(collection),
+                0,
+            ],
+            Self::Int {
+                device_id,
+                event_id,
+            } => [device(Self::INT, device_id), u64::from(event_id), 0, 0],
+            Self::Inv {
+                device_id,
+                event_id,
+            } => [device(Self::INV, device_id), u64::from(event_id), 0, 0],
+            Self::Invall { collection } => [Self::INVALL, 0, u64::from(collection), 0],
+            Self::Sync { rdbase } => [Self::SYNC, 0, rdbase & Self::RDBASE_MASK, 0],
+            Self::Discard {
+                device_id,
+                event_id,
+            } => [device(Self::DISCARD, device_id), u64::from(event_id), 0, 0],
+        }
+    }
+}
+
+/// Identifies a command submitted to the ITS command queue.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct CommandTicket(u64);
+
+/// How far the ITS got with a command, as last observed in `GITS_CREADR`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CommandStatus {
+    /// The ITS hasn't processed the command yet.
+    Pending,
+    /// The ITS processed the command.
+    Completed,
+    /// The ITS stopped at the command, which it found invalid.
+    Stalled,
+}
+
+/// A command submitted to the ITS, as recorded in its history.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CommandRecord {
+    /// The ticket returned for the command.
+    pub ticket: CommandTicket,
+    /// The command, with its arguments.
+    pub command: ItsCommand,
+    /// How far the ITS got with the command.
+    pub status: CommandStatus,
+}
+
+/// The last [`HISTORY_LEN`](Self::HISTORY_LEN) commands submitted, indexed by
+/// their ticket modulo the length.
+#[derive(Copy, Clone, Debug)]
+struct CommandHistory {
+    commands: [Option<(CommandTicket, ItsCommand)>; Self::HISTORY_LEN],
+}
+
+impl CommandHistory {
+    const HISTORY_LEN: usize = 16;
+
+    const fn new() -> Self {
+        Self {
+            commands: [None; Self::HISTORY_LEN],
+        }
+    }
+
+    fn record(&mut self, ticket: CommandTicket, command: ItsCommand) {
+        self.commands[ticket.0 as usize % Self::HISTORY_LEN] = Some((ticket, command));
+    }
+
+    /// Returns the recorded commands, oldest first, given the number of
+    /// commands completed and whether the ITS stalled at the next one.
+    fn records(&self, completed: u64, stalled: bool) -> impl Iterator<Item = CommandRecord> + '_ {
+        let oldest = self
+            .commands
+            .iter()
+            .flatten()
+            .map(|(ticket, _)| ticket.0)
+            .min()
+            .unwrap_or(0);
+        (0..Self::HISTORY_LEN).filter_map(move |i| {
+            let (ticket, command) = self.commands[(oldest as usize + i) % Self::HISTORY_LEN]?;
+            let status = if ticket.0 < completed {
+                CommandStatus::Completed
+            } else if ticket.0 == completed && stalled {
+                CommandStatus::Stalled
+            } else {
+                CommandStatus::Pending
+            };
+            Some(CommandRecord {
+                ticket,
+                command,
+                status,
+            })
+        })
+    }
+}
+
+/// The features and limits of an ITS, as `GITS_TYPER` reports them.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ItsInfo {
+    /// Number of bits of the event IDs of a device.
+    pub event_id_bits: u32,
+    /// Number of bits of the device IDs.
+    pub device_id_bits: u32,
+    /// Size in bytes of an entry of the interrupt translation tables.
+    pub itt_entry_size: usize,
+    /// Whether commands target redistributors by physical address rather than
+    /// by processor number.
+    pub pta: bool,
+    /// Number of collections held in the ITS rather than in memory.
+    pub hcc: u8,
+    /// Whether the collection ID width is limited, as `GITS_TYPER.CIL` reports.
+    pub cil: bool,
+    /// Number of bits of the collection IDs.
+    pub collection_id_bits: u32,
+}
+
+impl ItsInfo {
+    const ITT_ENTRY_SIZE_SHIFT: u64 = 4;
+    const ITT_ENTRY_SIZE_MASK: u64 = 0xf;
+    const ID_BITS_SHIFT: u64 = 8;
+    const DEVBITS_SHIFT: u64 = 13;
+    const FIELD_MASK: u64 = 0x1f;
+    const PTA: u64 = 1 << 19;
+    const HCC_SHIFT: u64 = 24;
+    const CIDBITS_SHIFT: u64 = 32;
+    const CIDBITS_MASK: u64 = 0xf;
+    const CIL: u64 = 1 << 36;
+
+    /// Parses the value of `GITS_TYPER`.
+    pub const fn from_typer(typer: u64) -> Self {
+        let cil = typer & Self::CIL != 0;
+        Self {
+            event_id_bits: ((typer >> Self::ID_BITS_SHIFT) & Self::FIELD_MASK) as u32 + 1,
+            device_id_bits: ((typer >> Self::DEVBITS_SHIFT) & Self::FIELD_MASK) as u32 + 1,
+            itt_entry_size: ((typer >> Self::ITT_ENTRY_SIZE_SHIFT) & Self::ITT_ENTRY_SIZE_MASK)
+                as usize
+                + 1,
+            pta: typer & Self::PTA != 0,
+            hcc: (typer >> Self::HCC_SHIFT) as u8,
+            cil,
+            // Without CIL, collection IDs are 16 bits wide.
+            collection_id_bits: if cil {
+                ((typer >> Self::CIDBITS_SHIFT) & Self::CIDBITS_MASK) as u32 + 1
+            } else {
+                16
+            },
+        }
+    }
+}
+
+/// Number of mapped devices whose interrupt translation table is freed when
+/// they are unmapped.
+const ITT_SLOTS: usize = 32;
+
+/// The interrupt translation table of a mapped device, kept to free it once
+/// the device is unmapped.
+#[derive(Copy, Clone, Debug)]
+struct Itt {
+    device_id: u32,
+    addr: NonNull<u8>,
+    phys: u64,
+    size: usize,
+}
+
+/// Returns whether `id` fits in `bits` bits.
+const fn fits(id: u64, bits: u32) -> bool {
+    bits >= u64::BITS || id >> bits == 0
+}
+
+/// Returns the number of event ID bits and the size of the entries of the
+/// interrupt translation table of a device with `events` events, entries
+/// being `entry_size` bytes, or `None` if they overflow.
+fn itt_layout(entry_size: usize, events: u32) -> Option<(u32, usize)> {
+    let event_bits = events.max(2).checked_next_power_of_two()?.trailing_zeros();
+    let size = 1usize.checked_shl(event_bits)?.checked_mul(entry_size)?;
+    Some((event_bits, size))
+}
+
+/// Driver for a GICv3 Interrupt Translation Service.
+pub struct Its<M> {
+    base: NonNull<GitsRegs>,
+    mem: M,
+    queue: NonNull<[u64; 4]>,
+    /// Number of command slots in the queue, zero until initialised.
+    queue_len: usize,
+    /// Slot the next command is written to.
+    write: usize,
+    /// Slot the ITS reads next, as last observed in `GITS_CREADR`.
+    read: usize,
+    /// Number of commands submitted so far, and the ticket of the next one.
+    submitted: u64,
+    /// Number of commands the ITS is known to have processed.
+    completed: u64,
+    /// Whether the ITS stalled at the command after the completed ones.
+    stalled: bool,
+    history: CommandHistory,
+    /// What the ITS reported at initialisation.
+    info: Option<ItsInfo>,
+    /// Number of bits of the device IDs the device table covers.
+    device_bits: u32,
+    /// Address and physical address of the redistributor region.
+    redistributors: Option<(usize, u64)>,
+    /// The interrupt translation tables of the mapped devices, as far as they
+    /// fit.
+    itts: [Option<Itt>; ITT_SLOTS],
+    poller: Poller,
+}
+
+unsafe impl<M: Send> Send for Its<M> {}
+unsafe impl<M: Sync> Sync for Its<M> {}
+
+impl<M: ItsMemory> Its<M> {
+    const CTLR_ENABLED: u32 = 1 << 0;
+    const CTLR_QUIESCENT: u32 = 1 << 31;
+
+    const BASER_ENTRY_SIZE_MASK: u64 = 0x1f;
+
+    const BASER_VALID: u64 = 1 << 63;
+    const BASER_TYPE_SHIFT: u64 = 56;
+    const BASER_TYPE_MASK: u64 = 0b111;
+    const BASER_TYPE_DEVICES: u64 = 1;
+    const BASER_TYPE_COLLECTIONS: u64 = 4;
+    const BASER_ENTRY_SIZE_SHIFT: u64 = 48;
+    const BASER_PAGE_SIZE_MASK: u64 = 0b11 << 8;
+    const BASER_PA_MASK: u64 = 0x0000_ffff_ffff_f000;
+    /// Tables are built from 4KB pages, of which a descriptor can hold 256.
+    const BASER_PAGE_SIZE: usize = 0x1000;
+    const BASER_MAX_PAGES: usize = 256;
+
+    /// Shareability field of `GITS_CBASER` and `GITS_BASERn`.
+    const SHAREABILITY_MASK: u64 = 0b11 << 10;
+    /// Inner shareable, normal inner write-back read/write-allocate memory.
+    const MEMORY_ATTRS: u64 = 0b01 << 10 | 0b111 << 59;
+
+    const COMMAND_SIZE: usize = 32;
+    const QUEUE_OFFSET_MASK: u64 = 0xf_ffe0;
+    const CREADR_STALLED: u64 = 1 << 0;
+
+    const ITT_ALIGN: usize = 256;
+
+    /// Bounds the polling loops in [`init`](Self::init) and [`wait`](Self::wait)
+    /// without a [`Delay`].
+    const POLL_COUNT: usize = 100000;
+
+    /// Constructs a new instance of the driver for an ITS with the given base
+    /// address, taking its memory from `mem`.
+    ///
+    /// # Safety
+    ///
+    /// The given base address must point to the ITS control and translation
+    /// register frames, mapped into the address space of the process as device
+    /// memory without any other aliases.
+    pub const fn new(base: *mut u8, mem: M) -> Self {
+        Self {
+            base: NonNull::new(base).unwrap().cast(),
+            mem,
+            queue: NonNull::dangling(),
+            queue_len: 0,
+            write: 0,
+            read: 0,
+            submitted: 0,
+            completed: 0,
+            stalled: false,
+            history: CommandHistory::new(),
+            info: None,
+            device_bits: 0,
+            redistributors: None,
+            itts: [None; ITT_SLOTS],
+            poller: Poller::new(None),
+        }
+    }
+
+    /// Bounds the polling loops by `delay` instead of a number of spins.
+    pub fn with_delay(mut self, delay: &'static dyn Delay) -> Self {
+        self.poller = Poller::new(Some(delay));
+        self
+    }
+
+    /// Sets the address and physical address of the redistributor region,
+    /// which an ITS addressing redistributors by physical address needs for
+    /// [`rdbase`](Self::rdbase).
+    pub fn with_redistributor_region(mut self, base: *mut u8, phys: u64) -> Self {
+        self.redistributors = Some((base as usize, phys));
+        self
+    }
+
+    const fn regs(&self) -> &GitsRegs {
+        unsafe { self.base.as_ref() }
+    }
+
+    /// Reads the raw 32-bit GITS register at `offset` bytes from its base.
+    ///
+    /// This is an escape hatch for implementation defined registers that the
+    /// driver does not model.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be 4-byte aligned and name a register inside the ITS frames.
+    /// The access bypasses the driver, so it must not leave state the driver
+    /// relies on in a configuration it doesn't expect.
+    pub unsafe fn gits_read(&self, offset: usize) -> u32 {
+        read_raw(self.base, offset)
+    }
+
+    /// Writes `value` to the raw 32-bit GITS register at `offset` bytes from its base.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be 4-byte aligned and name a register inside the ITS frames.
+    /// The access bypasses the driver, so it must not leave state the driver
+    /// relies on in a configuration it doesn't expect.
+    pub unsafe fn gits_write(&self, offset: usize, value: u32) {
+        write_raw(self.base, offset, value)
+    }
+
+    /// Initialises the ITS: allocates the command queue and the device and
+    /// collection tables, then enables the ITS.
+    ///
+    /// The ITS must be coherent with the CPUs; [`GicError::Unsupported`] is
+    /// returned if it only accepts non-shareable memory or doesn't support
+    /// 4KB table pages. [`GicError::InvalidTable`] is returned if the queue
+    /// size of the [`ItsMemory`] is invalid.
+    pub fn init(&mut self) -> Result<(), GicError> {
+        let ctlr = self.regs().CTLR.get();
+        self.regs().CTLR.set(ctlr & !Self::CTLR_ENABLED);
+        self.poll(|its| its.regs().CTLR.get() & Self::CTLR_QUIESCENT != 0)?;
+
+        let info = self.info();
+        self.info = Some(info);
+
+        let queue_size = self.mem.queue_size();
+        if queue_size == 0
+            || !queue_size.is_multiple_of(Self::BASER_PAGE_SIZE)
+            || queue_size > Self::BASER_PAGE_SIZE * Self::BASER_MAX_PAGES
+        {
+            return Err(GicError::InvalidTable);
+        }
+        let (queue, queue_pa) = self
+            .mem
+            .alloc_zeroed(queue_size, Self::BASER_PAGE_SIZE)
+            .ok_or(GicError::OutOfMemory)?;
+        let pages = (queue_size / Self::BASER_PAGE_SIZE) as u64;
+        self.regs().CBASER.set(
+            Self::BASER_VALID | Self::MEMORY_ATTRS | (queue_pa & Self::BASER_PA_MASK) | (pages - 1),
+        );
+        if self.regs().CBASER.get() & Self::SHAREABILITY_MASK == 0 {
+            return Err(GicError::Unsupported);
+        }
+        self.queue = queue.cast();
+        self.queue_len = queue_size / Self::COMMAND_SIZE;
+        self.write = 0;
+        self.read = 0;
+        self.regs().CWRITER.set(0);
+
+        let mut devbits = info.device_id_bits;
+        if let Some(bits) = self.mem.device_bits() {
+            devbits = devbits.min(bits);
+        }
+        self.device_bits = devbits;
+        for n in 0..self.regs().BASER.len() {
+            self.init_table(n, devbits)?;
+        }
+
+        let ctlr = self.regs().CTLR.get();
+        self.regs().CTLR.set(ctlr | Self::CTLR_ENABLED);
+        Ok(())
+    }
+
+    /// Allocates and programs the table described by `GITS_BASER<n>`, if it is
+    /// a device or collection table, narrowing the device ID bits to the
+    /// entries the device table holds.
+    fn init_table(&mut self, n: usize, devbits: u32) -> Result<(), GicError> {
+        let baser = self.regs().BASER[n].get();
+        let entry_size =
+            ((baser >> Self::BASER_ENTRY_SIZE_SHIFT) & Self::BASER_ENTRY_SIZE_MASK) + 1;
+        let max_size = Self::BASER_PAGE_SIZE * Self::BASER_MAX_PAGES;
+        let size = match (baser >> Self::BASER_TYPE_SHIFT) & Self::BASER_TYPE_MASK {
+            // A flat table for every device ID, as far as it fits.
+            Self::BASER_TYPE_DEVICES => {
+                let size = (entry_size << devbits).min(max_size as u64);
+                self.device_bits = devbits.min((size / entry_size).ilog2());
+                size as usize
+            }
+            // One page holds more collections than there are CPUs to target.
+            Self::BASER_TYPE_COLLECTIONS => Self::BASER_PAGE_SIZE,
+            _ => return Ok(()),
+        };
+        let size = size.next_multiple_of(Self::BASER_PAGE_SIZE);
+
+        let (_, table_pa) = self
+            .mem
+            .alloc_zeroed(size, Self::BASER_PAGE_SIZE)
+            .ok_or(GicError::OutOfMemory)?;
+        let pages = (size / Self::BASER_PAGE_SIZE) as u64;
+        self.regs().BASER[n].set(
+            Self::BASER_VALID | Self::MEMORY_ATTRS | (table_pa & Self::BASER_PA_MASK) | (pages - 1),
+        );
+
+        let baser = self.regs().BASER[n].get();
+        if baser & Self::BASER_PAGE_SIZE_MASK != 0 || baser & Self::SHAREABILITY_MASK == 0 {
+            return Err(GicError::Unsupported);
+        }
+        Ok(())
+    }
+
+    /// Queues `command` for the ITS without waiting for it to be processed.
+    ///
+    /// Returns [`GicError::QueueFull`] if the ITS hasn't caught up with earlier
+    /// commands yet; the caller should poll an outstanding ticket and retry.
+    /// A command is processed once [`poll_complete`](Self::poll_complete)
+    /// reports its ticket, but its effects are only guaranteed to be visible
+    /// at a redistributor after a later [`ItsCommand::Sync`] to it completes.
+    ///
+    /// Returns [`GicError::InvalidItsId`] for a command with arguments out of
+    /// range.
+    pub fn submit(&mut self, command: ItsCommand) -> Result<CommandTicket, GicError> {
+        command.check()?;
+        if self.queue_len == 0 {
+            return Err(GicError::NotInitialized);
+        }
+        if self.is_full() {
+            perf::add(Counter::ItsQueueFull);
+            return Err(GicError::QueueFull);
+        }
+
+        // SAFETY: `write` is always a slot inside the queue allocated by `init`.
+        unsafe {
+            self.queue
+                .as_ptr()
+                .add(self.write)
+                .write_volatile(command.encode());
+        }
+        // The command must be visible to the ITS before it is told about it.
+        fence::dsb(Domain::IshSt, "ITS command");
+        self.write = (self.write + 1) % self.queue_len;
+        self.regs()
+            .CWRITER
+            .set((self.write * Self::COMMAND_SIZE) as u64);
+
+        let ticket = CommandTicket(self.submitted);
+        self.submitted += 1;
+        self.history.record(ticket, command);
+        Ok(ticket)
+    }
+
+    /// Returns the last commands submitted, up to 16 and oldest first, with
+    /// their arguments and status.
+    ///
+    /// The status is as of the last check of the queue, by
+    /// [`poll_complete`](Self::poll_complete) or [`wait`](Self::wait) among
+    /// others. A [`CommandStatus::Stalled`] command explains a
+    /// [`GicError::CommandStalled`], and a [`ItsCommand::Sync`] left
+    /// [`CommandStatus::Pending`] after a [`GicError::Timeout`] points at a
+    /// redistributor that never completed it.
+    pub fn command_history(&self) -> impl Iterator<Item = CommandRecord> + '_ {
+        self.history.records(self.completed, self.stalled)
+    }
+
+    /// Returns whether the ITS has processed the command identified by `ticket`.
+    ///
+    /// Returns [`GicError::CommandStalled`] if the ITS stopped processing
+    /// commands because one of them was invalid.
+    pub fn poll_complete(&mut self, ticket: CommandTicket) -> Result<bool, GicError> {
+        self.update_read()?;
+        Ok(ticket.0 < self.completed)
+    }
+
+    /// Spins until the command identified by `ticket` has been processed.
+    pub fn wait(&mut self, ticket: CommandTicket) -> Result<(), GicError> {
+        let mut result = Ok(());
+        self.poll(|its| match its.poll_complete(ticket) {
+            Ok(done) => done,
+            Err(e) => {
+                result = Err(e);
+                true
+            }
+        })?;
+        result
+    }
+
+    /// Returns whether there is no free slot left in the command queue.
+    fn is_full(&mut self) -> bool {
+        let next = (self.write + 1) % self.queue_len;
+        if next != self.read {
+            return false;
+        }
+        // A stalled queue stays full, poll_complete reports the error.
+        let _ = self.update_read();
+        next == self.read
+    }
+
+    /// Accounts for the commands the ITS has read since the last call.
+    fn update_read(&mut self) -> Result<(), GicError> {
+        if self.queue_len == 0 {
+            return Err(GicError::NotInitialized);
+        }
+        let creadr = self.regs().CREADR.get();
+        let read = (creadr & Self::QUEUE_OFFSET_MASK) as usize / Self::COMMAND_SIZE;
+        let consumed = (read + self.queue_len - self.read) % self.queue_len;
+        self.read = read;
+        self.completed += consumed as u64;
+        self.stalled = creadr & Self::CREADR_STALLED != 0;
+
+        if self.stalled {
+            return Err(GicError::CommandStalled);
+        }
+        Ok(())
+    }
+
+    /// Spins until `done` returns true, or fails with [`GicError::Timeout`].
+    fn poll(&mut self, mut done: impl FnMut(&mut Self) -> bool) -> Result<(), GicError> {
+        let poller = self.poller;
+        if poller.poll(Self::POLL_COUNT, || done(self)) {
+            Ok(())
+        } else {
+            Err(GicError::Timeout)
+        }
+    }
+
+    /// Returns the features and limits the ITS reports.
+    pub fn info(&self) -> ItsInfo {
+        ItsInfo::from_typer(self.regs().TYPER.get())
+    }
+
+    /// Returns the information read at initialisation, checking that the
+    /// device ID fits in the device table.
+    fn check_device(&self, device_id: u32) -> Result<ItsInfo, GicError> {
+        let info = self.info.ok_or(GicError::NotInitialized)?;
+        if !fits(device_id.into(), self.device_bits) {
+            return Err(GicError::InvalidItsId);
+        }
+        Ok(info)
+    }
+
+    /// Maps a device that signals up to `events` different events, allocating
+    /// its interrupt translation table.
+    ///
+    /// Returns [`GicError::InvalidItsId`] if the device ID is beyond the
+    /// device table, or the ITS or the size of its table doesn't support that
+    /// many events.
+    pub fn map_device(&mut self, device_id: u32, events: u32) -> Result<CommandTicket, GicError> {
+        let info = self.check_device(device_id)?;
+        if events > 1 && !fits(u64::from(events) - 1, info.event_id_bits) {
+            return Err(GicError::InvalidItsId);
+        }
+        let (event_bits, size) =
+            itt_layout(info.itt_entry_size, events).ok_or(GicError::InvalidItsId)?;
+        let size = size.max(Self::ITT_ALIGN);
+        // Check before allocating, so a retry after QueueFull doesn't leak memory.
+        if self.is_full() {
+            perf::add(Counter::ItsQueueFull);
+            return Err(GicError::QueueFull);
+        }
+
+        let (addr, itt_addr) = self
+            .mem
+            .alloc_zeroed(size, Self::ITT_ALIGN)
+            .ok_or(GicError::OutOfMemory)?;
+        let ticket = self.submit(ItsCommand::Mapd {
+            device_id,
+            itt_addr,
+            event_bits,
+            valid: true,
+        })?;
+
+        // Remapping a device without unmapping it first leaks its old table.
+        let itt = Itt {
+            device_id,
+            addr,
+            phys: itt_addr,
+            size,
+        };
+        let slot = self
+            .itt_slot(device_id)
+            .or_else(|| self.itts.iter().position(Option::is_none));
+        match slot {
+            Some(slot) => self.itts[slot] = Some(itt),
+            None => info!(
+                "ITS: translation table of device {} won't be freed",
+                device_id
+            ),
+        }
+        Ok(ticket)
+    }
+
+    /// Returns the slot of the interrupt translation table of a mapped device.
+    fn itt_slot(&self, device_id: u32) -> Option<usize> {
+        self.itts
+            .iter()
+            .position(|slot| slot.is_some_and(|itt| itt.device_id == device_id))
+    }
+
+    /// Unmaps a device, waiting until that is visible at `rd`, then frees its
+    /// interrupt translation table.
+    ///
+    /// The events of the device should be unmapped first with
+    /// [`unmap_msi`](Self::unmap_msi), as unmapping the device leaves their
+    /// LPIs pending. The table is only freed for the first 32 devices mapped
+    /// at a time, the others leak it.
+    pub fn unmap_device(&mut self, device_id: u32, rd: &GicRedistributor) -> Result<(), GicError> {
+        self.check_device(device_id)?;
+        let rdbase = self.rdbase(rd)?;
+        self.submit(ItsCommand::Mapd {
+            device_id,
+            itt_addr: 0,
+            event_bits: 1,
+            valid: false,
+        })?;
+        // The ITS may still read the table until the unmapping is synchronised.
+        let ticket = self.submit(ItsCommand::Sync { rdbase })?;
+        self.wait(ticket)?;
+
+        if let Some(itt) = self
+            .itt_slot(device_id)
+            .and_then(|slot| self.itts[slot].take())
+        {
+            self.mem.free(itt.addr, itt.phys, itt.size);
+        }
+        Ok(())
+    }
+
+    /// Unmaps an event of a mapped device and clears the pending state of its
+    /// LPI, waiting until that is visible at `rd`, the redistributor of the
+    /// collection the event was mapped to.
+    ///
+    /// Once this returns, the LPI can no longer fire and may be given to
+    /// another event.
+    pub fn unmap_msi(
+        &mut self,
+        device_id: u32,
+        event_id: u32,
+        rd: &GicRedistributor,
+    ) -> Result<(), GicError> {
+        let info = self.check_device(device_id)?;
+        if !fits(event_id.into(), info.event_id_bits) {
+            return Err(GicError::InvalidItsId);
+        }
+        let rdbase = self.rdbase(rd)?;
+        self.submit(ItsCommand::Discard {
+            device_id,
+            event_id,
+        })?;
+        let ticket = self.submit(ItsCommand::Sync { rdbase })?;
+        self.wait(ticket)
+    }
+
+    /// Returns the `rdbase` of commands targeting `rd`, its physical address
+    /// or its processor number depending on `GITS_TYPER.PTA`.
+    ///
+    /// Returns [`GicError::MissingComponent`] if the ITS needs the physical
+    /// address but the redistributor region wasn't given by
+    /// [`with_redistributor_region`](Self::with_redistributor_region), or
+    /// [`GicError::InvalidMapping`] if `rd` is outside the region.
+    pub fn rdbase(&self, rd: &GicRedistributor) -> Result<u64, GicError> {
+        if !self.info.unwrap_or_else(|| self.info()).pta {
+            return Ok((rd.processor_number() as u64) << 16);
+        }
+        let (base, phys) = self.redistributors.ok_or(GicError::MissingComponent)?;
+        let offset = (rd.base() as usize)
+            .checked_sub(base)
+            .ok_or(GicError::InvalidMapping)?;
+        Ok(phys + offset as u64)
+    }
+
+    /// Maps `collection` to the redistributor `rd`, addressed as the ITS
+    /// expects.
+    pub fn map_collection_to(
+        &mut self,
+        collection: u16,
+        rd: &GicRedistributor,
+    ) -> Result<CommandTicket, GicError> {
+        let rdbase = self.rdbase(rd)?;
+        self.map_collection(collection, rdbase)
+    }
+
+    /// Queues a [`ItsCommand::Sync`] to the redistributor `rd`, addressed as
+    /// the ITS expects.
+    pub fn sync(&mut self, rd: &GicRedistributor) -> Result<CommandTicket, GicError> {
+        let rdbase = self.rdbase(rd)?;
+        self.submit(ItsCommand::Sync { rdbase })
+    }
+
+    /// Makes the redistributor `rd`, which `collection` is mapped to, reload
+    /// the configuration of all the LPIs of the collection from memory, and
+    /// waits until it has.
+    ///
+    /// After rewriting the priorities of many LPIs, this is much faster than
+    /// a [`ItsCommand::Inv`] for each. Returns [`GicError::InvalidItsId`] if
+    /// the collection ID is wider than the ITS supports.
+    pub fn invalidate_all_lpis(
+        &mut self,
+        collection: u16,
+        rd: &GicRedistributor,
+    ) -> Result<(), GicError> {
+        let info = self.info.ok_or(GicError::NotInitialized)?;
+        if !fits(collection.into(), info.collection_id_bits) {
+            return Err(GicError::InvalidItsId);
+        }
+        let rdbase = self.rdbase(rd)?;
+        // Submitting orders the command after the writes to the table.
+        self.submit(ItsCommand::Invall { collection })?;
+        let ticket = self.submit(ItsCommand::Sync { rdbase })?;
+        self.wait(ticket)
+    }
+
+    /// Maps `collection` to the redistributor identified by `rdbase`.
+    pub fn map_collection(
+        &mut self,
+        collection: u16,
+        rdbase: u64,
+    ) -> Result<CommandTicket, GicError> {
+        self.submit(ItsCommand::Mapc {
+            collection,
+            rdbase,
+            valid: true,
+        })
+    }
+
+    /// Maps an event of a mapped device to the LPI `lpi`, delivered to the
+    /// redistributor of `collection`.
+    ///
+    /// Returns [`GicError::InvalidItsId`] if the device, event or collection
+    /// ID is wider than the ITS supports.
+    pub fn map_msi(
+        &mut self,
+        device_id: u32,
+        event_id: u32,
+        lpi: Lpi,
+        collection: u16,
+    ) -> Result<CommandTicket, GicError> {
+        let info = self.check_device(device_id)?;
+        if !fits(event_id.into(), info.event_id_bits)
+            || !fits(collection.into(), info.collection_id_bits)
+        {
+            return Err(GicError::InvalidItsId);
+        }
+        self.submit(ItsCommand::Mapti {
+            device_id,
+            event_id,
+            intid: lpi.intid(),
+            collection,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let mapd = ItsCommand::Mapd {
+            device_id: 0x12,
+            itt_addr: 0x8000_0100,
+            event_bits: 5,
+            valid: true,
+        };
+        assert_eq!(mapd.encode(), [0x12_0000_0008, 4, 1 << 63 | 0x8000_0100, 0]);
+
+        let mapti = ItsCommand::Mapti {
+            device_id: 1,
+            event_id: 2,
+            intid: IntId::from(8192),
+            collection: 3,
+        };
+        assert_eq!(mapti.encode(), [0x1_0000_000a, 0x2000_0000_0002, 3, 0]);
+
+        let sync = ItsCommand::Sync { rdbase: 0x2_0000 };
+        assert_eq!(sync.encode(), [0x05, 0, 0x2_0000, 0]);
+
+        let unmap = ItsCommand::Mapd {
+            device_id: 0x12,
+            itt_addr: 0,
+            event_bits: 1,
+            valid: false,
+        };
+        assert_eq!(unmap.encode(), [0x12_0000_0008, 0, 0, 0]);
+
+        let empty = ItsCommand::Mapd {
+            device_id: 0x12,
+            itt_addr: 0,
+            event_bits: 0,
+            valid: true,
+        };
+        assert_eq!(empty.check(), Err(GicError::InvalidItsId));
+        assert_eq!(unmap.check(), Ok(()));
+
+        let invall = ItsCommand::Invall { collection: 3 };
+        assert_eq!(invall.encode(), [0x0d, 0, 3, 0]);
+
+        let discard = ItsCommand::Discard {
+            device_id: 1,
+            event_id: 2,
+        };
+        assert_eq!(discard.encode(), [0x1_0000_000f, 2, 0, 0]);
+    }
+
+    #[test]
+    fn test_its_info() {
+        // 8-byte ITT entries, 16 event ID bits, 20 device ID bits, PTA and 4
+        // collection ID bits.
+        let info = ItsInfo::from_typer(1 << 36 | 3 << 32 | 1 << 19 | 19 << 13 | 15 << 8 | 7 << 4);
+        assert_eq!(
+            info,
+            ItsInfo {
+                event_id_bits: 16,
+                device_id_bits: 20,
+                itt_entry_size: 8,
+                pta: true,
+                hcc: 0,
+                cil: true,
+                collection_id_bits: 4,
+            }
+        );
+        assert_eq!(ItsInfo::from_typer(0).collection_id_bits, 16);
+        assert!(fits(0xffff, 16));
+        assert!(!fits(0x10000, 16));
+        assert_eq!(itt_layout(8, 3), Some((2, 32)));
+        assert_eq!(itt_layout(8, 1 << 10), Some((10, 8 << 10)));
+        assert_eq!(itt_layout(8, (1 << 31) + 1), None);
+    }
+
+    #[test]
+    fn test_command_history() {
+        let mut history = CommandHistory::new();
+        for i in 0..20 {
+            history.record(
+                CommandTicket(i),
+                ItsCommand::Invall {
+                    collection: i as u16,
+                },
+            );
+        }
+        assert_eq!(
+            history.records(10, true).count(),
+            CommandHistory::HISTORY_LEN
+        );
+        let mut records = [None; CommandHistory::HISTORY_LEN];
+        for (slot, record) in records.iter_mut().zip(history.records(10, true)) {
+            *slot = Some(record);
+        }
+        let records = records.map(Option::unwrap);
+        assert_eq!(records[0].ticket, CommandTicket(4));
+        assert_eq!(records[15].ticket, CommandTicket(19));
+        assert_eq!(records[5].status, CommandStatus::Completed);
+        assert_eq!(records[6].status, CommandStatus::Stalled);
+        assert_eq!(records[7].status, CommandStatus::Pending);
+    }
+}
@@ -0,0 +1,124 @@
+//! Statically provisioned memory for an ITS.
+//!
+//! Embedded systems with a known, small set of MSI devices don't need a
+//! memory provider: a [`StaticItsMemory`] hands out the command queue, the
+//! device and collection tables and the interrupt translation tables from
+//! pages reserved in a `static`, sized by its const parameters:
+//!
+//! ```ignore
+//! static mut ITS_PAGES: ItsPages<8> = ItsPages::new();
+//!
+//! // Device IDs up to 15, the pages being identity mapped.
+//! let mem = unsafe {
+//!     StaticItsMemory::<8, 4>::new(&mut *addr_of_mut!(ITS_PAGES), addr_of!(ITS_PAGES) as u64)
+//! };
+//! let mut its = Its::new(GITS_BASE as *mut u8, mem);
+//! its.init()?;
+//! ```
+//!
+//! The command queue takes one page, holding 128 commands, and each table at
+//! least one; the remaining pages hold the translation tables of the devices
+//! as they are mapped. Nothing is ever freed.
+
+use core::ptr::NonNull;
+
+use crate::ItsMemory;
+
+/// Size of the pages of [`ItsPages`], which the ITS tables are built from.
+const PAGE_SIZE: usize = 0x1000;
+
+/// `N` zeroed 4KB pages for a [`StaticItsMemory`].
+#[derive(Debug)]
+#[repr(C, align(4096))]
+pub struct ItsPages<const N: usize>([[u8; PAGE_SIZE]; N]);
+
+impl<const N: usize> ItsPages<N> {
+    /// Returns zeroed pages, to initialise a `static`.
+    pub const fn new() -> Self {
+        Self([[0; PAGE_SIZE]; N])
+    }
+}
+
+impl<const N: usize> Default for ItsPages<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ITS memory taken from `N` static pages, for devices with IDs of up to
+/// `DEVICE_BITS` bits.
+#[derive(Debug)]
+pub struct StaticItsMemory<const N: usize, const DEVICE_BITS: u32> {
+    pages: &'static mut ItsPages<N>,
+    /// Physical address of the pages.
+    phys: u64,
+    /// Number of bytes handed out so far.
+    used: usize,
+}
+
+impl<const N: usize, const DEVICE_BITS: u32> StaticItsMemory<N, DEVICE_BITS> {
+    /// Returns a provider handing out `pages`, whose physical address is
+    /// `phys`.
+    ///
+    /// # Safety
+    ///
+    /// `phys` must be the physical address of `pages`, which must be mapped
+    /// as normal write-back memory coherent with the ITS.
+    pub unsafe fn new(pages: &'static mut ItsPages<N>, phys: u64) -> Self {
+        Self {
+            pages,
+            phys,
+            used: 0,
+        }
+    }
+
+    /// Returns the number of bytes not handed out yet, ignoring alignment.
+    pub fn remaining(&self) -> usize {
+        N * PAGE_SIZE - self.used
+    }
+}
+
+impl<const N: usize, const DEVICE_BITS: u32> ItsMemory for StaticItsMemory<N, DEVICE_BITS> {
+    fn alloc_zeroed(&mut self, size: usize, align: usize) -> Option<(NonNull<u8>, u64)> {
+        // The pages are aligned to a page, which is as much as the ITS asks for.
+        let offset = self.used.checked_next_multiple_of(align.max(1))?;
+        let end = offset.checked_add(size)?;
+        if end > N * PAGE_SIZE {
+            return None;
+        }
+        self.used = end;
+        let memory = &mut self.pages.0.as_flattened_mut()[offset..end];
+        memory.fill(0);
+        Some((NonNull::from(memory).cast(), self.phys + offset as u64))
+    }
+
+    fn device_bits(&self) -> Option<u32> {
+        Some(DEVICE_BITS)
+    }
+
+    fn queue_size(&self) -> usize {
+        PAGE_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::addr_of_mut;
+
+    use super::*;
+
+    #[test]
+    fn test_alloc() {
+        static mut PAGES: ItsPages<2> = ItsPages::new();
+        // SAFETY: Only this test uses the pages, and nothing accesses them
+        // through their physical address.
+        let mut mem =
+            unsafe { StaticItsMemory::<2, 4>::new(&mut *addr_of_mut!(PAGES), 0x8000_0000) };
+        let (_, pa) = mem.alloc_zeroed(0x100, 0x100).unwrap();
+        assert_eq!(pa, 0x8000_0000);
+        let (_, pa) = mem.alloc_zeroed(0x1000, 0x1000).unwrap();
+        assert_eq!(pa, 0x8000_1000);
+        assert_eq!(mem.remaining(), 0);
+        assert!(mem.alloc_zeroed(1, 1).is_none());
+    }
+}
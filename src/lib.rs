@@ -1,32 +1,121 @@
 //! ARM Generic Interrupt Controller (GIC) register definitions and basic
 //! operations.
-//! Driver for the Arm Generic Interrupt Controller version 2 or 3 or 4, on aarch64.
+//! Driver for the Arm Generic Interrupt Controller version 2 or 3 or 4, on aarch64
+//! or 32-bit Arm.
 //!
 //! This top level module contains functions that are not specific to any particular interrupt
 //! controller, as support for other GIC versions may be added in future.
 //!
-//! Note:
-//!  - Interrupt grouping(secure state) is not supported
-//!  - Interrupt proiority(preempt) is not supported
-//!
-//! Please contact the developer if you need this function
+//! Interrupts are assigned to a group with
+//! [`set_group`](GenericArmGic::set_group) and prioritised with
+//! [`set_priority`](GenericArmGic::set_priority) and
+//! [`set_priority_grouping`](GenericArmGic::set_priority_grouping), or both at
+//! once with [`configure`](GenericArmGic::configure).
+//! [`enable_fiq_split`](GenericArmGic::enable_fiq_split) signals Group 0 as FIQ
+//! and Group 1 as IRQ, from secure software or on a GIC with a single security
+//! state.
 
 #![no_std]
-#![feature(const_ptr_as_ref)]
-#![feature(const_option)]
-#![feature(const_nonnull_new)]
+#![recursion_limit = "256"]
 
 use core::fmt;
-use core::fmt::{Debug, Formatter};
+use core::fmt::{Debug, Display, Formatter};
+use core::str::FromStr;
 
+mod acpi;
+#[cfg(feature = "arceos")]
+mod arceos;
+mod builder;
+mod cache;
+#[cfg(feature = "claim-tracking")]
+mod claim;
+mod class;
+mod coalesce;
+#[cfg(feature = "arm-gic-compat")]
+pub mod compat;
+#[cfg(test)]
+mod conformance;
+mod deferred;
+mod delay;
+mod diagnose;
+mod distributor_common;
+mod domain;
+mod dt;
+mod dump;
+mod features;
+mod fence;
+#[cfg(feature = "ffi")]
+mod ffi;
 mod gic_v2;
 mod gic_v3;
+mod gicv2m;
+mod handlers;
+mod handoff;
+mod its;
+mod its_static;
+#[cfg(feature = "lifecycle-tracking")]
+mod lifecycle;
+mod logging;
+mod mapping;
+mod percpu;
+mod perf;
+pub mod platforms;
+mod priority;
+mod reserved;
+mod rmw;
+mod seal;
+mod sgi;
+#[cfg(feature = "sim")]
+mod sim;
+mod smp_call;
+mod spurious;
 mod sysregs;
+mod vgic;
+mod wake;
 
 pub(crate) mod registers;
 
+#[cfg(feature = "arceos")]
+pub use crate::arceos::{ArceosIrq, IrqHandler};
+pub use crate::builder::{GicV3Builder, SecurityState};
+pub use crate::class::{Lpi, Ppi, Spi};
+pub use crate::coalesce::{Coalescer, UnmaskScheduler};
+pub use crate::deferred::DeferralQueue;
+pub use crate::delay::Delay;
+pub use crate::diagnose::Diagnosis;
+pub use crate::domain::IrqDomain;
+pub use crate::dt::DevIdMapper;
+pub use crate::features::FeatureSummary;
+#[cfg(feature = "fence-counting")]
+pub use crate::fence::{fence_counts, FenceCounts};
+#[cfg(feature = "ffi")]
+pub use crate::ffi::ArmGic;
 pub use crate::gic_v2::GicV2;
-pub use crate::gic_v3::GicV3;
+pub use crate::gic_v3::{GicRedistributor, GicV3};
+pub use crate::gicv2m::{GicV2m, V2mMsi};
+pub use crate::handlers::{Handler, HandlerTable};
+pub use crate::handoff::HandoffState;
+pub use crate::its::{
+    CommandRecord, CommandStatus, CommandTicket, Its, ItsCommand, ItsInfo, ItsMemory,
+};
+pub use crate::its_static::{ItsPages, StaticItsMemory};
+pub use crate::mapping::{DeviceMmio, MemoryType};
+pub use crate::percpu::{CpuIndex, MpidrAff0, PerCpuGic};
+#[cfg(feature = "perf-counters")]
+pub use crate::perf::{perf_counters, PerfCounters};
+pub use crate::priority::PriorityGrouping;
+pub use crate::reserved::ReservedSet;
+pub use crate::seal::{is_sealed, pin_routing, seal};
+pub use crate::sgi::{CpuSet, LocalSoftirq, SgiId, SgiTargets};
+#[cfg(feature = "sim")]
+pub use crate::sim::{SimGic, Simulation};
+pub use crate::smp_call::{AtomicMailbox, CallMailbox, RemoteCall, SmpCall};
+pub use crate::spurious::{set_spurious_callback, spurious_count};
+pub use crate::vgic::{
+    El2Forwarding, ForwardingIssue, KvmVgicAttr, KvmVgicGroup, VGicCpuInterface, VirtualInterrupt,
+    VirtualIrqState,
+};
+pub use crate::wake::{SuspendState, WakeSources};
 
 /// An interrupt ID.
 #[derive(Copy, Clone, Eq, Ord, PartialOrd, PartialEq)]
@@ -48,6 +137,27 @@ impl IntId {
     /// The first special interrupt ID.
     const SPECIAL_START: usize = 1020;
 
+    /// One past the last special interrupt ID.
+    const SPECIAL_END: usize = 1024;
+
+    /// The special interrupt ID returned when no interrupt is pending.
+    const SPURIOUS: usize = 1023;
+
+    /// The ID of the first extended Private Peripheral Interrupt.
+    const EPPI_START: usize = 1056;
+
+    /// The number of extended PPIs a redistributor can implement.
+    const EPPI_COUNT: usize = 64;
+
+    /// The ID of the first extended Shared Peripheral Interrupt.
+    const ESPI_START: usize = 4096;
+
+    /// The number of extended SPIs a distributor can implement.
+    const ESPI_COUNT: usize = 1024;
+
+    /// The ID of the first Locality-specific Peripheral Interrupt.
+    const LPI_START: usize = 8192;
+
     /// Returns the interrupt ID for the given Software Generated Interrupt.
     pub const fn sgi(sgi: usize) -> Self {
         assert!(sgi < Self::PPI_START);
@@ -66,16 +176,61 @@ impl IntId {
         Self(Self::SPI_START + spi)
     }
 
+    /// Returns the interrupt ID for the given extended Private Peripheral
+    /// Interrupt, with GICv3.1.
+    pub const fn eppi(eppi: usize) -> Self {
+        assert!(eppi < Self::EPPI_COUNT);
+        Self(Self::EPPI_START + eppi)
+    }
+
+    /// Returns the interrupt ID for the given extended Shared Peripheral
+    /// Interrupt, with GICv3.1.
+    pub const fn espi(espi: usize) -> Self {
+        assert!(espi < Self::ESPI_COUNT);
+        Self(Self::ESPI_START + espi)
+    }
+
     /// Returns whether this interrupt ID is for a Software Generated Interrupt.
-    #[allow(dead_code)]
-    fn is_sgi(self) -> bool {
+    const fn is_sgi(self) -> bool {
         self.0 < Self::PPI_START
     }
 
-    /// Returns whether this interrupt ID is private to a core, i.e. it is an SGI or PPI.
+    /// Returns whether this interrupt ID is for a Private Peripheral Interrupt.
+    fn is_ppi(self) -> bool {
+        (Self::PPI_START..Self::SPI_START).contains(&self.0)
+    }
+
+    /// Returns whether this interrupt ID is for a Shared Peripheral Interrupt.
+    fn is_spi(self) -> bool {
+        (Self::SPI_START..Self::SPECIAL_START).contains(&self.0)
+    }
+
+    /// Returns whether this interrupt ID is for an extended Private Peripheral Interrupt.
+    fn is_eppi(self) -> bool {
+        (Self::EPPI_START..Self::EPPI_START + Self::EPPI_COUNT).contains(&self.0)
+    }
+
+    /// Returns whether this interrupt ID is for an extended Shared Peripheral Interrupt.
+    fn is_espi(self) -> bool {
+        (Self::ESPI_START..Self::ESPI_START + Self::ESPI_COUNT).contains(&self.0)
+    }
+
+    /// Returns whether this interrupt ID is for a Locality-specific Peripheral Interrupt.
+    fn is_lpi(self) -> bool {
+        self.0 >= Self::LPI_START
+    }
+
+    /// Returns whether this interrupt ID is one of the special IDs the CPU
+    /// interface returns instead of an interrupt.
+    fn is_special(self) -> bool {
+        (Self::SPECIAL_START..Self::SPECIAL_END).contains(&self.0)
+    }
+
+    /// Returns whether this interrupt ID is private to a core, i.e. it is an
+    /// SGI, PPI or extended PPI.
     #[allow(dead_code)]
     fn is_private(self) -> bool {
-        self.0 < Self::SPI_START
+        self.0 < Self::SPI_START || self.is_eppi()
     }
 }
 
@@ -97,6 +252,9 @@ pub enum InterruptType {
 }
 
 /// Translate an interrupt of a given type to a GIC INTID.
+///
+/// This only checks the architectural limits; use [`GenericArmGic::translate`]
+/// to also check against the interrupts a particular GIC implements.
 pub const fn translate_irq(id: usize, int_type: InterruptType) -> Option<usize> {
     match int_type {
         InterruptType::SGI => {
@@ -125,8 +283,24 @@ pub const fn translate_irq(id: usize, int_type: InterruptType) -> Option<usize>
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
+
+    use std::format;
+    use std::string::ToString;
+
     use super::*;
 
+    #[test]
+    fn test_interrupt_prop() {
+        let prop = InterruptProp::new(IntId::spi(8), 0x10, SecurityGroup::G0, TriggerMode::Level);
+        let config = prop.irq_config();
+        assert_eq!(config.group, InterruptGroup::Group0);
+        assert_eq!(config.trigger, TriggerMode::Level);
+        assert!(config.enabled);
+        let prop = InterruptProp::new(IntId::sgi(8), 0x10, SecurityGroup::G1S, TriggerMode::Edge);
+        assert_eq!(prop.irq_config().group, InterruptGroup::Group1);
+    }
+
     #[test]
     fn test_translate_irq() {
         assert_eq!(translate_irq(0, InterruptType::SGI), Some(0));
@@ -139,6 +313,36 @@ mod tests {
         assert_eq!(translate_irq(32, InterruptType::PPI), None);
         assert_eq!(translate_irq(32, InterruptType::SPI), Some(64));
     }
+
+    #[test]
+    fn test_intid_from_str() {
+        assert_eq!("SPI 42".parse(), Ok(IntId::spi(42)));
+        assert_eq!("ppi 14".parse(), Ok(IntId::ppi(14)));
+        assert_eq!(" SGI  3 ".parse(), Ok(IntId::sgi(3)));
+        assert_eq!("ESPI 1".parse(), Ok(IntId::espi(1)));
+        assert_eq!("LPI 8193".parse(), Ok(IntId::from(8193)));
+        assert_eq!("LPI 8191".parse::<IntId>(), Err(GicError::InvalidIntId));
+        assert_eq!("PPI 16".parse::<IntId>(), Err(GicError::InvalidIntId));
+        assert_eq!("SPI".parse::<IntId>(), Err(GicError::InvalidIntId));
+        assert_eq!("IRQ 1".parse::<IntId>(), Err(GicError::InvalidIntId));
+    }
+
+    #[test]
+    fn test_intid_debug() {
+        assert_eq!(format!("{:?}", IntId::spi(42)), "SPI 42");
+        assert_eq!(format!("{:?}", IntId::eppi(3)), "EPPI 3");
+        assert_eq!(format!("{:?}", IntId::espi(1)), "ESPI 1");
+        assert_eq!(format!("{:?}", IntId::from(8193)), "LPI 8193");
+        assert_eq!(format!("{:?}", IntId::from(1021)), "Pending-NS (1021)");
+        assert_eq!(format!("{:?}", IntId::from(1022)), "NMI acknowledge (1022)");
+        assert_eq!(format!("{:?}", IntId::from(1023)), "Spurious (1023)");
+        assert_eq!(format!("{:?}", IntId::from(1030)), "Reserved IntId 1030");
+        assert_eq!(format!("{:?}", IntId::from(5120)), "Reserved IntId 5120");
+        // Display round-trips through FromStr.
+        for intid in [IntId::ppi(2), IntId::espi(7), IntId::from(9000)] {
+            assert_eq!(intid.to_string().parse(), Ok(intid));
+        }
+    }
 }
 
 impl Debug for IntId {
@@ -149,8 +353,70 @@ impl Debug for IntId {
             write!(f, "PPI {}", self.0 - Self::PPI_START)
         } else if self.0 < Self::SPECIAL_START {
             write!(f, "SPI {}", self.0 - Self::SPI_START)
+        } else if self.is_special() {
+            // Acknowledging at EL3 returns 1020 and 1021 for a Secure and a
+            // Non-secure Group 1 interrupt, and a GICv3.3 returns 1022 for an
+            // NMI acknowledged through ICC_IAR1_EL1.
+            let name = match self.0 {
+                1020 => "Pending-S",
+                1021 => "Pending-NS",
+                1022 => "NMI acknowledge",
+                _ => "Spurious",
+            };
+            write!(f, "{} ({})", name, self.0)
+        } else if self.is_eppi() {
+            write!(f, "EPPI {}", self.0 - Self::EPPI_START)
+        } else if self.is_espi() {
+            write!(f, "ESPI {}", self.0 - Self::ESPI_START)
+        } else if self.is_lpi() {
+            write!(f, "LPI {}", self.0)
         } else {
-            write!(f, "Special IntId {}", self.0)
+            write!(f, "Reserved IntId {}", self.0)
+        }
+    }
+}
+
+impl Display for IntId {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Debug::fmt(self, f)
+    }
+}
+
+impl FromStr for IntId {
+    type Err = GicError;
+
+    /// Parses an interrupt ID written as its type followed by its number
+    /// within the type, such as `SPI 42` or `PPI 14`, as printed by `Display`.
+    ///
+    /// The type is one of `SGI`, `PPI`, `SPI`, `EPPI`, `ESPI` or `LPI`, in any
+    /// case. LPIs have no separate numbering, so `LPI` takes the INTID itself.
+    fn from_str(s: &str) -> Result<Self, GicError> {
+        let (kind, number) = s
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or(GicError::InvalidIntId)?;
+        let number: usize = number.trim().parse().map_err(|_| GicError::InvalidIntId)?;
+        // The INTID is the base plus the number, which must be in range.
+        let (base, numbers) = [
+            ("SGI", Self::SGI_START, 0..Self::PPI_START - Self::SGI_START),
+            ("PPI", Self::PPI_START, 0..Self::SPI_START - Self::PPI_START),
+            (
+                "SPI",
+                Self::SPI_START,
+                0..Self::SPECIAL_START - Self::SPI_START,
+            ),
+            ("EPPI", Self::EPPI_START, 0..Self::EPPI_COUNT),
+            ("ESPI", Self::ESPI_START, 0..Self::ESPI_COUNT),
+            ("LPI", 0, Self::LPI_START..usize::MAX),
+        ]
+        .into_iter()
+        .find(|(name, _, _)| name.eq_ignore_ascii_case(kind))
+        .map(|(_, base, numbers)| (base, numbers))
+        .ok_or(GicError::InvalidIntId)?;
+        if numbers.contains(&number) {
+            Ok(Self(base + number))
+        } else {
+            Err(GicError::InvalidIntId)
         }
     }
 }
@@ -173,6 +439,78 @@ impl From<usize> for IntId {
     }
 }
 
+/// Errors returned by the GIC driver.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GicError {
+    /// The interrupt ID is out of range for its type.
+    InvalidIntId,
+    /// A device-tree interrupt specifier is malformed or uses an unsupported
+    /// interrupt type or trigger.
+    InvalidDtSpec,
+    /// The GIC doesn't implement the requested feature.
+    Unsupported,
+    /// A table handed to the GIC has an invalid size, alignment or ID range.
+    InvalidTable,
+    /// Memory for a GIC table couldn't be allocated.
+    OutOfMemory,
+    /// The ITS command queue has no free slot.
+    QueueFull,
+    /// The ITS stopped processing commands after an invalid one.
+    CommandStalled,
+    /// The hardware didn't respond in time.
+    Timeout,
+    /// The component hasn't been initialised yet.
+    NotInitialized,
+    /// The configuration is fixed by the hardware and the write was ignored.
+    ReadOnlyConfig,
+    /// A fixed-capacity table of the driver is full.
+    CapacityExceeded,
+    /// The hardware implements a different GIC architecture version than the
+    /// driver is for. This holds the version found, or 0 if it couldn't be
+    /// identified.
+    VersionMismatch(u8),
+    /// A register frame isn't mapped as the driver requires.
+    InvalidMapping,
+    /// No redistributor belongs to the requested CPU.
+    NoRedistributor,
+    /// A component the driver needs wasn't provided.
+    MissingComponent,
+    /// The interrupt is reserved to the firmware.
+    Reserved,
+    /// A device, event or collection ID is wider than the ITS supports.
+    InvalidItsId,
+    /// The interrupt configuration is sealed, see [`seal`].
+    Sealed,
+}
+
+impl fmt::Display for GicError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidIntId => write!(f, "interrupt ID out of range"),
+            Self::InvalidDtSpec => write!(f, "invalid device-tree interrupt specifier"),
+            Self::Unsupported => write!(f, "feature not implemented by the GIC"),
+            Self::InvalidTable => write!(f, "invalid GIC table"),
+            Self::OutOfMemory => write!(f, "out of memory for GIC tables"),
+            Self::QueueFull => write!(f, "ITS command queue full"),
+            Self::CommandStalled => write!(f, "ITS command queue stalled"),
+            Self::Timeout => write!(f, "timeout waiting for the GIC"),
+            Self::NotInitialized => write!(f, "not initialised"),
+            Self::ReadOnlyConfig => write!(f, "configuration is read-only"),
+            Self::CapacityExceeded => write!(f, "table capacity exceeded"),
+            Self::VersionMismatch(0) => write!(f, "unrecognised GIC architecture version"),
+            Self::VersionMismatch(found) => {
+                write!(f, "GIC architecture version mismatch, found GICv{}", found)
+            }
+            Self::InvalidMapping => write!(f, "GIC registers not mapped as device memory"),
+            Self::NoRedistributor => write!(f, "no redistributor for the CPU"),
+            Self::MissingComponent => write!(f, "GIC component not provided"),
+            Self::Reserved => write!(f, "interrupt reserved to the firmware"),
+            Self::InvalidItsId => write!(f, "ITS ID out of range"),
+            Self::Sealed => write!(f, "interrupt configuration sealed"),
+        }
+    }
+}
+
 /// Interrupt trigger mode.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum TriggerMode {
@@ -190,25 +528,503 @@ pub enum TriggerMode {
     Level = 1,
 }
 
+/// Trigger type and polarity of an interrupt line, as device-tree interrupt
+/// specifiers describe it.
+///
+/// The GIC only programs the trigger mode, and samples active-high levels and
+/// rising edges. An active-low or falling-edge line has to be inverted by the
+/// device or the interconnect before it reaches the GIC, which the driver
+/// can't configure, so [`trigger_mode`](Self::trigger_mode) rejects them.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum IrqFlags {
+    /// Edge-triggered on the rising edge.
+    EdgeRising,
+    /// Edge-triggered on the falling edge.
+    EdgeFalling,
+    /// Level-sensitive, active high.
+    LevelHigh,
+    /// Level-sensitive, active low.
+    LevelLow,
+}
+
+impl IrqFlags {
+    /// Returns the trigger mode, whatever the polarity.
+    pub const fn trigger(self) -> TriggerMode {
+        match self {
+            Self::EdgeRising | Self::EdgeFalling => TriggerMode::Edge,
+            Self::LevelHigh | Self::LevelLow => TriggerMode::Level,
+        }
+    }
+
+    /// Returns whether the line is active low or falling-edge, the polarity
+    /// the GIC can't sample.
+    pub const fn is_inverted(self) -> bool {
+        matches!(self, Self::EdgeFalling | Self::LevelLow)
+    }
+
+    /// Returns the trigger mode the GIC is programmed with for this line.
+    ///
+    /// Returns [`GicError::Unsupported`] if the line is inverted.
+    pub const fn trigger_mode(self) -> Result<TriggerMode, GicError> {
+        if self.is_inverted() {
+            Err(GicError::Unsupported)
+        } else {
+            Ok(self.trigger())
+        }
+    }
+}
+
+/// Interrupt group, which selects the exception an interrupt is signalled as
+/// and the registers used to acknowledge and end it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InterruptGroup {
+    /// Group 0, typically signalled as FIQ and handled by secure firmware.
+    Group0,
+    /// Group 1, typically signalled as IRQ. This is the group the driver
+    /// configures all interrupts as.
+    Group1,
+}
+
+/// Interrupt group of a GICv3 with two security states, selected by the group
+/// and group modifier bits of the interrupt.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SecurityGroup {
+    /// Group 0, signalled as FIQ and handled by EL3 firmware.
+    G0,
+    /// Secure Group 1, handled by the secure world.
+    G1S,
+    /// Non-secure Group 1, handled by the non-secure world.
+    G1NS,
+}
+
+/// An interrupt found in another group than expected, as reported by
+/// [`GicV3::verify_group_assignments`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct GroupMismatch {
+    /// The interrupt.
+    pub intid: IntId,
+    /// The group it should be in.
+    pub expected: SecurityGroup,
+    /// The group it is in.
+    pub found: SecurityGroup,
+}
+
+/// Which secure SGIs the non-secure state may generate, as granted by the
+/// two-bit fields of `GICD_NSACR<n>` and `GICR_NSACR`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NsSgiAccess {
+    /// None.
+    None,
+    /// Group 0 SGIs.
+    Group0,
+    /// Group 0 and Secure Group 1 SGIs, only on GICv3.
+    Group0AndSecureGroup1,
+}
+
+/// The full configuration of an interrupt, applied with [`GenericArmGic::configure`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct IrqConfig {
+    /// Trigger mode, which must be [`TriggerMode::Edge`] for SGIs.
+    pub trigger: TriggerMode,
+    /// Priority, lower values being more urgent.
+    pub priority: u8,
+    /// Group the interrupt is signalled in.
+    pub group: InterruptGroup,
+    /// Routing, as for [`GenericArmGic::set_affinity`], or `None` to leave it unchanged.
+    pub affinity: Option<u64>,
+    /// Whether to enable the interrupt once it's configured.
+    pub enabled: bool,
+}
+
+/// The configuration of an interrupt in a board description, applied with
+/// [`GenericArmGic::apply_board_config`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BoardIrq {
+    /// The interrupt.
+    pub intid: IntId,
+    /// Trigger mode, which must be [`TriggerMode::Edge`] for SGIs.
+    pub trigger: TriggerMode,
+    /// Priority, lower values being more urgent.
+    pub priority: u8,
+    /// The CPUs an SPI is routed to, as for [`GenericArmGic::affinity_for`],
+    /// or an empty set to leave its routing unchanged. Ignored for private
+    /// interrupts.
+    pub cpus: CpuSet,
+}
+
+/// The configuration of an interrupt in the style of the `interrupt_prop_t`
+/// tables of Trusted Firmware-A, so the tables of a platform port can be
+/// reused as they are, for [`GicV3::apply_interrupt_props`] and
+/// [`GicV2::apply_interrupt_props`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct InterruptProp {
+    /// The interrupt, `intr_num`.
+    pub intid: IntId,
+    /// Priority, lower values being more urgent, `intr_pri`.
+    pub priority: u8,
+    /// Group, `intr_grp`.
+    pub group: SecurityGroup,
+    /// Trigger mode, `intr_cfg`.
+    pub config: TriggerMode,
+}
+
+impl InterruptProp {
+    /// Returns the properties of the interrupt `intid`, with the arguments in
+    /// the order of the `INTR_PROP_DESC` macro of Trusted Firmware-A.
+    pub const fn new(
+        intid: IntId,
+        priority: u8,
+        group: SecurityGroup,
+        config: TriggerMode,
+    ) -> Self {
+        Self {
+            intid,
+            priority,
+            group,
+            config,
+        }
+    }
+
+    /// Returns the configuration enabling the interrupt with these
+    /// properties, in the group of the same number, as Trusted Firmware-A
+    /// does.
+    pub(crate) fn irq_config(&self) -> IrqConfig {
+        IrqConfig {
+            trigger: self.config,
+            priority: self.priority,
+            group: match self.group {
+                SecurityGroup::G0 => InterruptGroup::Group0,
+                SecurityGroup::G1S | SecurityGroup::G1NS => InterruptGroup::Group1,
+            },
+            affinity: None,
+            enabled: true,
+        }
+    }
+}
+
+/// Optional features of a GIC, as reported by [`GenericArmGic::capabilities`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct GicCapabilities {
+    /// Locality-specific Peripheral Interrupts, delivered through the ITS or
+    /// written directly to the redistributors.
+    pub lpi: bool,
+    /// The extended SPI range starting at INTID 4096.
+    pub espi: bool,
+    /// Non-maskable interrupts.
+    pub nmi: bool,
+    /// Direct injection of virtual SGIs, with GICv4.1.
+    pub vsgi: bool,
+    /// Setting and clearing LPIs directly through the redistributor registers.
+    pub direct_lpi: bool,
+    /// GICv2m MSI frames. The driver doesn't probe for them, as they are
+    /// described separately by the firmware, so this is never set.
+    pub gicv2m: bool,
+    /// The security extensions, i.e. two security states.
+    pub security_extension: bool,
+    /// Message-based SPIs, signalled by writes to the distributor.
+    pub mbi: bool,
+    /// Number of SPIs the distributor implements, not counting extended SPIs.
+    /// Some LPI-only GICs have none, and fail to configure them with
+    /// [`GicError::Unsupported`].
+    pub spis: usize,
+    /// Number of list registers of the virtual CPU interface, or 0 if it isn't
+    /// accessible at the current exception level.
+    pub list_registers: usize,
+}
+
 /// [`GenericArmGic`].
 /// It is used to implement the interface abstraction that the interrupt chip
 /// driver should provide to the outside world.
 /// I hope that the versatility of the driver interface should support more chip architectures.
+///
+/// # Concurrency
+///
+/// Drivers are `Copy` handles onto hardware shared by all CPUs, so the
+/// receiver of each method says what it touches, not which copy may call it:
+///
+/// - Methods taking `&self` only access GIC registers, and may be called from
+///   any CPU and from interrupt handlers at the same time. Registers of the
+///   CPU interface and private interrupts are those of the calling CPU; set
+///   and clear registers are written atomically; and read-modify-write
+///   updates of registers holding several interrupts are serialised across
+///   all handles.
+/// - Methods taking `&mut self` initialise the GIC or change the state of the
+///   handle itself, such as the redistributor it uses, which other copies
+///   don't see. Initialisation must not run concurrently with
+///   anything else.
 pub trait GenericArmGic: Debug + Clone + Copy + Sync + Send + Sized {
     /// Initialises the GIC.
-    fn init_primary(&mut self);
+    ///
+    /// Returns [`GicError::VersionMismatch`], before writing any register, if
+    /// the hardware isn't of the GIC version this driver is for.
+    fn init_primary(&mut self) -> Result<(), GicError>;
+
+    /// Initialises a GIC that secure firmware such as Arm Trusted Firmware has
+    /// already configured.
+    ///
+    /// Unlike [`init_primary`](Self::init_primary), this leaves the interrupt
+    /// grouping and `GICD_CTLR` as the firmware set them and only writes
+    /// registers that are accessible from the non-secure state, so the secure
+    /// configuration isn't silently overwritten. Later calls to
+    /// [`per_cpu_init`](Self::per_cpu_init) on this instance keep the grouping too.
+    ///
+    /// Returns [`GicError::VersionMismatch`] like [`init_primary`](Self::init_primary).
+    fn init_primary_handoff(&mut self) -> Result<(), GicError>;
+
+    /// Initialises just enough of the GIC for the current CPU to take its
+    /// private interrupts, such as its timer, as early in boot as possible.
+    ///
+    /// This enables the distributor and initialises the current CPU like
+    /// [`init_primary`](Self::init_primary), but leaves the SPIs as firmware
+    /// or reset left them. Call [`finish_init`](Self::finish_init) before
+    /// using any SPI.
+    ///
+    /// Returns [`GicError::VersionMismatch`] like [`init_primary`](Self::init_primary).
+    fn early_init(&mut self) -> Result<(), GicError>;
+
+    /// Completes [`early_init`](Self::early_init) by disabling the SPIs and
+    /// configuring them like [`init_primary`](Self::init_primary) does, while
+    /// the private interrupts of the current CPU keep being delivered.
+    fn finish_init(&mut self);
+
+    /// Translates an interrupt of a given type to a GIC INTID, like [`translate_irq`].
+    ///
+    /// Returns `None` if the interrupt is outside the architectural range for its
+    /// type or beyond the interrupts this GIC implements, as reported by `GICD_TYPER`.
+    fn translate(&self, id: usize, int_type: InterruptType) -> Option<IntId>;
 
     /// Initialises the GIC for the current CPU core.
     fn per_cpu_init(&mut self);
 
+    /// Brings up the GIC on a secondary CPU, from the handle the boot CPU
+    /// initialised, and returns the handle for the current CPU.
+    ///
+    /// This runs the steps of [`per_cpu_init`](Self::per_cpu_init) in the
+    /// order the architecture requires, checking those that can fail on a
+    /// misconfigured system:
+    ///
+    /// 1. On GICv3, enables the system register interface, failing with
+    ///    [`GicError::Unsupported`] if a higher exception level keeps it
+    ///    disabled.
+    /// 2. On GICv3, finds the redistributor of the CPU, failing with
+    ///    [`GicError::NoRedistributor`], and wakes it up, failing with
+    ///    [`GicError::Timeout`].
+    /// 3. Disables the SGIs and PPIs, and gives them the default group and
+    ///    priority.
+    /// 4. Sets the priority mask the driver was built with, see
+    ///    [`GicV3::with_priority_mask`].
+    /// 5. Enables the interrupt groups of the CPU interface.
+    ///
+    /// The CPU still has to unmask IRQs itself.
+    fn secondary_cpu_start(&self) -> Result<Self, GicError> {
+        let mut gic = *self;
+        gic.per_cpu_init();
+        Ok(gic)
+    }
+
     /// Configures the trigger type for the interrupt with the given ID.
-    fn set_trigger(&mut self, intid: IntId, trigger: TriggerMode);
+    ///
+    /// The configuration is read back, and [`GicError::ReadOnlyConfig`] is
+    /// returned if the interrupt's trigger type is fixed to the other mode, as
+    /// it is for SGIs and on some implementations for PPIs.
+    fn set_trigger(&self, intid: IntId, trigger: TriggerMode) -> Result<(), GicError>;
+
+    /// Configures the trigger type of the interrupt with the given ID from the
+    /// flags of its line, like [`set_trigger`](Self::set_trigger).
+    ///
+    /// Returns [`GicError::Unsupported`] without changing anything if the line
+    /// is active low or falling-edge, which the GIC can't sample.
+    fn set_trigger_flags(&self, intid: IntId, flags: IrqFlags) -> Result<(), GicError> {
+        self.set_trigger(intid, flags.trigger_mode()?)
+    }
+
+    /// Changes the trigger type of the interrupt with the given ID while it
+    /// may be in use, as after a device-tree overlay changes its line.
+    ///
+    /// Changing the configuration of an enabled interrupt is UNPREDICTABLE,
+    /// so the interrupt is disabled, waiting for that to take effect where the
+    /// GIC reports it, and its pending state, latched under the old mode, is
+    /// cleared. The trigger type is then set like
+    /// [`set_trigger`](Self::set_trigger), and the interrupt enabled again if
+    /// it was. An edge arriving in between is lost, so the device should be
+    /// quiet.
+    ///
+    /// Returns the errors of `set_trigger`, the interrupt being enabled again
+    /// anyway if it was.
+    fn reconfigure_trigger(&self, intid: IntId, trigger: TriggerMode) -> Result<(), GicError>;
+
+    /// Assigns the interrupt with the given ID to `group`.
+    ///
+    /// Only secure software, or software on a GIC with a single security
+    /// state, can move interrupts to Group 0; other writes are ignored.
+    fn set_group(&self, intid: IntId, group: InterruptGroup);
+
+    /// Sets the priority of the interrupt with the given ID, lower values
+    /// being more urgent.
+    ///
+    /// The GIC may implement fewer than 8 priority bits, in which case the low
+    /// bits are ignored.
+    fn set_priority(&self, intid: IntId, priority: u8);
+
+    /// Routes the Shared Peripheral Interrupt with the given ID.
+    ///
+    /// `affinity` is the CPU target list on GICv2, and the `GICD_IROUTER` value
+    /// (`Aff3.Aff2.Aff1.Aff0`, plus the routing mode in bit 31) on GICv3, or a
+    /// target list again if affinity routing is disabled.
+    /// Private interrupts can't be routed, so this is ignored for them.
+    fn set_affinity(&self, intid: IntId, affinity: u64);
+
+    /// Routes the Shared Peripheral Interrupt `spi`, like
+    /// [`set_affinity`](Self::set_affinity) but only taking SPIs.
+    fn route_spi(&self, spi: Spi, affinity: u64) {
+        self.set_affinity(spi.intid(), affinity);
+    }
+
+    /// Applies the whole configuration of the interrupt with the given ID.
+    ///
+    /// The interrupt is disabled first, so it never fires half configured,
+    /// then its trigger, priority, group and routing are set before it is
+    /// enabled again if `config.enabled` is set.
+    ///
+    /// Returns [`GicError::Reserved`] or [`GicError::Sealed`] without
+    /// changing anything if the interrupt is reserved or the configuration
+    /// sealed, or [`GicError::ReadOnlyConfig`] if the trigger type can't be
+    /// set, leaving the interrupt disabled.
+    fn configure(&self, intid: IntId, config: IrqConfig) -> Result<(), GicError> {
+        if self.is_reserved(intid) {
+            return Err(GicError::Reserved);
+        }
+        seal::check()?;
+        self.disable_interrupt(intid);
+        self.set_trigger(intid, config.trigger)?;
+        self.set_priority(intid, config.priority);
+        self.set_group(intid, config.group);
+        if let Some(affinity) = config.affinity {
+            self.set_affinity(intid, affinity);
+        }
+        if config.enabled {
+            self.enable_interrupt(intid);
+        }
+        Ok(())
+    }
+
+    /// Returns the value of [`set_affinity`](Self::set_affinity) routing an
+    /// SPI to the CPUs in `cpus`, or `None` if it can't reach any of them.
+    ///
+    /// GICv2 targets each CPU of the set. A GICv3 routes to a single CPU, or
+    /// to any participating CPU if the set holds several.
+    fn affinity_for<const W: usize>(&self, cpus: &CpuSet<W>) -> Option<u64>;
+
+    /// Configures the interrupts of a board description, such as a table
+    /// generated at build time, as Group 1 and disabled.
+    ///
+    /// Private interrupts are configured for the current CPU only. Returns
+    /// the first error of [`configure`](Self::configure), leaving the
+    /// remaining interrupts unconfigured.
+    fn apply_board_config(&self, table: &[BoardIrq]) -> Result<(), GicError> {
+        for irq in table {
+            self.configure(
+                irq.intid,
+                IrqConfig {
+                    trigger: irq.trigger,
+                    priority: irq.priority,
+                    group: InterruptGroup::Group1,
+                    affinity: self.affinity_for(&irq.cpus),
+                    enabled: false,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Initialises the GIC like [`init_primary`](Self::init_primary), then
+    /// applies the board description `table` like
+    /// [`apply_board_config`](Self::apply_board_config).
+    fn init_primary_with(&mut self, table: &[BoardIrq]) -> Result<(), GicError> {
+        self.init_primary()?;
+        self.apply_board_config(table)
+    }
+
+    /// Brings up `count` SPIs from `first` at runtime, such as the lines of an
+    /// FPGA region after partial reconfiguration or of a hot-plugged device
+    /// block, without running [`init_primary`](Self::init_primary) again.
+    ///
+    /// The whole range is first checked against the SPIs, or extended SPIs on
+    /// GICv3, that `GICD_TYPER` reports. Each interrupt is then disabled, its
+    /// pending and active state cleared, and it is configured with `config`
+    /// like [`configure`](Self::configure).
+    ///
+    /// Returns [`GicError::InvalidIntId`] or [`GicError::Reserved`] without
+    /// changing anything if the range isn't implemented or holds a reserved
+    /// interrupt, or the first error of `configure`.
+    fn add_spis(&self, first: IntId, count: usize, config: IrqConfig) -> Result<(), GicError>;
+
+    /// Enables both interrupt groups on the current CPU, with Group 0
+    /// signalled as FIQ and Group 1 as IRQ.
+    ///
+    /// This is the split used by secure payloads and debuggers that take their
+    /// own interrupts as FIQ. It must run on each CPU after
+    /// [`per_cpu_init`](Self::per_cpu_init), and only has an effect from secure
+    /// software or on a GIC with a single security state.
+    fn enable_fiq_split(&self);
+
+    /// Stops the CPU interface of the current CPU from signalling interrupts,
+    /// by disabling its Group 1 interrupts, for panic paths and orderly
+    /// shutdown.
+    ///
+    /// Unlike masking through `DAIF`, this holds across exception returns,
+    /// and unlike disabling the interrupts it leaves the distributor alone,
+    /// so the other CPUs keep taking theirs. SPIs routed to the current CPU
+    /// stay pending there. A Group 0 enabled by
+    /// [`enable_fiq_split`](Self::enable_fiq_split) stays enabled.
+    fn cpu_interface_disable(&self);
+
+    /// Lets the CPU interface of the current CPU signal Group 1 interrupts
+    /// again after [`cpu_interface_disable`](Self::cpu_interface_disable), as
+    /// [`per_cpu_init`](Self::per_cpu_init) left it.
+    fn cpu_interface_enable(&self);
+
+    /// Sets how the priorities of Group 1 interrupts split into group
+    /// priority and subpriority on the current CPU, through its Group 1
+    /// binary point register.
+    ///
+    /// The GIC may enforce a minimum binary point, so this returns the split
+    /// in effect, which can have fewer preemption levels than requested.
+    fn set_priority_grouping(&self, grouping: PriorityGrouping) -> PriorityGrouping;
 
-    /// Enables the interrupt with the given ID.pub fn enable_interrupt(&mut self, intid: IntId);
-    fn enable_interrupt(&mut self, intid: IntId);
+    /// Returns how the priorities of Group 1 interrupts split on the current
+    /// CPU.
+    fn priority_grouping(&self) -> PriorityGrouping;
+
+    /// Enables the interrupt with the given ID.pub fn enable_interrupt(&self, intid: IntId);
+    fn enable_interrupt(&self, intid: IntId);
 
     /// Disable the interrupt with the given ID.
-    fn disable_interrupt(&mut self, intid: IntId);
+    fn disable_interrupt(&self, intid: IntId);
+
+    /// Returns whether the interrupt with the given ID is reserved to the
+    /// firmware.
+    ///
+    /// The driver refuses to touch a reserved interrupt: the methods returning
+    /// a `Result` fail with [`GicError::Reserved`], and the others log an
+    /// error and do nothing.
+    fn is_reserved(&self, _intid: IntId) -> bool {
+        false
+    }
+
+    /// Enables the interrupt with the given ID on behalf of `owner`.
+    ///
+    /// Panics if the interrupt is already claimed by a different owner. Once
+    /// claimed, enabling the interrupt or changing its trigger mode without the
+    /// claim also panics, so configure the interrupt before claiming it.
+    #[cfg(feature = "claim-tracking")]
+    fn enable_interrupt_claimed(&self, intid: IntId, owner: &'static str);
+
+    /// Disables the interrupt with the given ID and drops its claim.
+    ///
+    /// Returns the previous owner, if any.
+    #[cfg(feature = "claim-tracking")]
+    fn release_interrupt(&self, intid: IntId) -> Option<&'static str>;
 
     /// Gets the ID of the highest priority signalled interrupt, and acknowledges it.
     ///
@@ -218,4 +1034,156 @@ pub trait GenericArmGic: Debug + Clone + Copy + Sync + Send + Sized {
     /// Informs the interrupt controller that the CPU has completed processing the given interrupt.
     /// This drops the interrupt priority and deactivates the interrupt.
     fn end_interrupt(&self, intid: IntId);
+
+    /// Handles every interrupt signalled to the current CPU.
+    ///
+    /// Acknowledges the highest priority interrupt, passes it to `handler` and
+    /// ends it, until the GIC returns a special interrupt ID because nothing
+    /// else is pending. Each interrupt is only ended after `handler` returns,
+    /// so if the handler unmasks IRQs, only interrupts of higher priority can
+    /// preempt it and a nested `drain` call ends them before returning.
+    ///
+    /// Returns the number of interrupts handled.
+    fn drain(&self, mut handler: impl FnMut(IntId)) -> usize {
+        let mut count = 0;
+        while let Some(intid) = self.get_and_acknowledge_interrupt() {
+            handler(intid);
+            self.end_interrupt(intid);
+            count += 1;
+        }
+        count
+    }
+
+    /// Selects whether [`get_and_acknowledge_interrupt`](Self::get_and_acknowledge_interrupt)
+    /// also disables the interrupt it returns, and [`end_interrupt`](Self::end_interrupt)
+    /// enables it again.
+    ///
+    /// This keeps a level-sensitive interrupt from being signalled again, even
+    /// after a handler unmasks IRQs, until its device has been serviced by a
+    /// handler thread or a userspace driver and the interrupt is ended. It
+    /// doesn't apply to the group-specific acknowledge path, nor to LPIs, which
+    /// are enabled in their configuration table rather than by the driver.
+    fn set_disable_on_ack(&mut self, enable: bool);
+
+    /// Gets the ID of the highest priority signalled interrupt of `group`, and
+    /// acknowledges it.
+    ///
+    /// This lets FIQ handlers take Group 0 interrupts. On GICv2 Group 0 uses
+    /// `GICC_IAR` and Group 1 the aliased `GICC_AIAR`, which matches the view of
+    /// secure software; non-secure software should use
+    /// [`get_and_acknowledge_interrupt`](Self::get_and_acknowledge_interrupt).
+    ///
+    /// Returns `None` if there is no pending interrupt of sufficient priority.
+    fn get_and_acknowledge_group_interrupt(&self, group: InterruptGroup) -> Option<IntId>;
+
+    /// Ends an interrupt acknowledged with
+    /// [`get_and_acknowledge_group_interrupt`](Self::get_and_acknowledge_group_interrupt)
+    /// for the same `group`.
+    fn end_group_interrupt(&self, intid: IntId, group: InterruptGroup);
+
+    /// Deactivates the interrupt `intid`, which the current CPU didn't
+    /// acknowledge through this driver, such as one acknowledged by secure
+    /// firmware before handing over to the normal world.
+    ///
+    /// With the EOI mode of the current exception level split, this writes
+    /// the deactivate register of the CPU interface, as after ending an
+    /// interrupt with only a priority drop. Otherwise that write would be
+    /// unpredictable, so the active state is cleared in the distributor, or
+    /// in the redistributor of the current CPU for private interrupts.
+    ///
+    /// This is only legal once whoever acknowledged the interrupt has dropped
+    /// its priority and finished with it: deactivating an interrupt still
+    /// being handled lets it be taken again meanwhile, and one whose priority
+    /// wasn't dropped keeps masking lower priorities on its CPU. Private
+    /// interrupts must be deactivated on the CPU they are active on. The GIC
+    /// ignores the write for interrupts of a group the current security state
+    /// can't access.
+    ///
+    /// Returns [`GicError::InvalidIntId`] for LPIs, which have no active
+    /// state, and for special or unimplemented interrupts, or
+    /// [`GicError::Unsupported`] if the GIC has no means of deactivating it.
+    fn deactivate_unacked(&self, intid: IntId) -> Result<(), GicError>;
+
+    /// Returns the index of the CPU with the given `MPIDR_EL1` value in a
+    /// [`CpuSet`], or `None` if the GIC doesn't serve that CPU.
+    fn cpu_index(&self, mpidr: u64) -> Option<usize>;
+
+    /// Sends the Software Generated Interrupt `sgi` to the CPUs in `targets`.
+    ///
+    /// Prior memory writes are visible to the targets once they take the SGI.
+    fn send_sgi<const W: usize>(&self, sgi: SgiId, targets: &CpuSet<W>);
+
+    /// Sends the Software Generated Interrupt `sgi` to the current CPU only.
+    ///
+    /// Unlike [`send_sgi`](Self::send_sgi) this doesn't look up the CPU among
+    /// the targets, so it suits contexts such as NMI handlers raising a
+    /// [`LocalSoftirq`] to defer work to IRQ context.
+    fn raise_self_sgi(&self, sgi: SgiId);
+
+    /// Returns the optional features this GIC implements, so callers can
+    /// detect them without matching on the GIC version.
+    fn capabilities(&self) -> GicCapabilities;
+
+    /// Writes a human-readable snapshot of the GIC state to `out`.
+    ///
+    /// This covers the distributor control registers, every interrupt that is
+    /// enabled, pending or active, and the CPU interface of the current core.
+    fn dump_state(&self, out: &mut impl fmt::Write) -> fmt::Result;
+
+    /// Captures the configuration firmware left in the GIC, keeping the routes
+    /// of up to `N` enabled SPIs.
+    ///
+    /// This only reads registers, so call it before
+    /// [`init_primary`](Self::init_primary) to see what the boot loader handed
+    /// over.
+    fn inspect<const N: usize>(&self) -> HandoffState<N>;
+
+    /// Reads the state along the delivery path of the interrupt with the given
+    /// ID to the current CPU, to find out why it isn't signalled.
+    ///
+    /// Returns [`GicError::InvalidIntId`] for LPIs, extended SPIs and PPIs,
+    /// and IDs the GIC doesn't implement.
+    fn diagnose(&self, intid: IntId) -> Result<Diagnosis, GicError>;
+
+    /// Makes the Shared Peripheral Interrupt `spi` pending, as if its device
+    /// had signalled it.
+    fn trigger_spi(&self, spi: Spi);
+
+    /// Clears the pending state of the Shared Peripheral Interrupt `spi`.
+    fn clear_spi(&self, spi: Spi);
+
+    /// Checks that the SPI `spi` is delivered to the current CPU.
+    ///
+    /// The SPI is enabled, made pending with [`trigger_spi`](Self::trigger_spi)
+    /// and polled for through the acknowledge register, so this must run with
+    /// IRQs masked on the current CPU (`PSTATE.I` set). Any other interrupt
+    /// acknowledged while polling is ended and therefore lost. The SPI is left
+    /// disabled and not pending afterwards.
+    ///
+    /// Returns whether the test interrupt was acknowledged.
+    fn self_test(&self, spi: Spi) -> bool {
+        const POLL_COUNT: usize = 10000;
+
+        let intid = spi.intid();
+        self.enable_interrupt(intid);
+        self.trigger_spi(spi);
+
+        let mut received = false;
+        for _ in 0..POLL_COUNT {
+            match self.get_and_acknowledge_interrupt() {
+                Some(id) => {
+                    self.end_interrupt(id);
+                    if id == intid {
+                        received = true;
+                        break;
+                    }
+                }
+                None => core::hint::spin_loop(),
+            }
+        }
+
+        self.disable_interrupt(intid);
+        self.clear_spi(spi);
+        received
+    }
 }
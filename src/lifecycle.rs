@@ -0,0 +1,87 @@
+//! Interrupt lifecycle checking for debug builds.
+//!
+//! Enabled by the `lifecycle-tracking` feature. The driver records every
+//! interrupt it acknowledges and ends, and panics when the sequence breaks the
+//! inactive → pending → active → inactive lifecycle: ending an interrupt that
+//! isn't active, such as a second EOI or one without an acknowledge,
+//! acknowledging an SPI that is still active, or enabling an interrupt that is
+//! active. These mistakes otherwise show up as interrupts that silently stop
+//! arriving.
+//!
+//! The record is global rather than part of a driver instance, so it holds
+//! across the copies of the driver each CPU may use. SGIs and PPIs are banked
+//! per CPU, so the number of CPUs they are active on is counted.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::IntId;
+
+/// Number of CPUs each interrupt is active on.
+static ACTIVE: [AtomicU8; IntId::GIC_MAX_IRQ] = [const { AtomicU8::new(0) }; IntId::GIC_MAX_IRQ];
+
+/// Records that the current CPU acknowledged `intid`, making it active.
+pub(crate) fn acknowledged(intid: IntId) {
+    let Some(active) = ACTIVE.get(intid.0) else {
+        return;
+    };
+    let previous = active.fetch_add(1, Ordering::Relaxed);
+    if previous > 0 && !intid.is_private() {
+        panic!("{:?} acknowledged while active, it was never ended", intid);
+    }
+}
+
+/// Records that the current CPU ended `intid`, making it inactive.
+pub(crate) fn ended(intid: IntId) {
+    let Some(active) = ACTIVE.get(intid.0) else {
+        return;
+    };
+    if active
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+        .is_err()
+    {
+        panic!("{:?} ended while not active, without an acknowledge", intid);
+    }
+}
+
+/// Panics if `intid` is active on any CPU, as `action` would break its lifecycle.
+pub(crate) fn check_inactive(intid: IntId, action: &str) {
+    if ACTIVE
+        .get(intid.0)
+        .is_some_and(|active| active.load(Ordering::Relaxed) > 0)
+    {
+        panic!("{:?} {} while active", intid, action);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle() {
+        acknowledged(IntId::spi(1));
+        ended(IntId::spi(1));
+        check_inactive(IntId::spi(1), "enabled");
+
+        // An SGI may be active on several CPUs at once.
+        acknowledged(IntId::sgi(1));
+        acknowledged(IntId::sgi(1));
+        ended(IntId::sgi(1));
+        ended(IntId::sgi(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_double_eoi() {
+        acknowledged(IntId::spi(2));
+        ended(IntId::spi(2));
+        ended(IntId::spi(2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_enable_active() {
+        acknowledged(IntId::spi(3));
+        check_inactive(IntId::spi(3), "enabled");
+    }
+}
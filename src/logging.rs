@@ -0,0 +1,36 @@
+//! Diagnostic output through the optional `log` or `defmt` integrations.
+//!
+//! Messages are forwarded to whichever backends are enabled and compiled out
+//! otherwise. Format strings must stay within the subset both crates accept:
+//! plain `{}` and hex hints on integers and strings.
+
+macro_rules! emit {
+    ($level:ident, $($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        ::log::$level!($($arg)*);
+        #[cfg(feature = "defmt")]
+        ::defmt::$level!($($arg)*);
+        #[cfg(not(any(feature = "log", feature = "defmt")))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+pub(crate) use emit;
+
+/// Logs a message about a discovered capability or configuration decision.
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::logging::emit!(info, $($arg)*) };
+}
+pub(crate) use info;
+
+/// Logs a detail that only matters while bringing up a new platform.
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::logging::emit!(debug, $($arg)*) };
+}
+pub(crate) use debug;
+
+/// Logs an error: a request the driver refuses or can't carry out, or a fatal
+/// condition right before the driver panics.
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::logging::emit!(error, $($arg)*) };
+}
+pub(crate) use error;
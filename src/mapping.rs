@@ -0,0 +1,107 @@
+//! Memory attributes of the GIC register mappings.
+//!
+//! The GIC registers must be mapped as Device-nGnRnE or Device-nGnRE memory.
+//! With gathering or reordering, register writes can be merged or overtake
+//! each other, and with Normal memory reads can be served from a cache or
+//! speculated, so the driver would misbehave in subtle ways rather than fail.
+//! [`DeviceMmio`] carries the memory type into the constructors, and the
+//! `validate_mapping` methods of the drivers check a mapping after the fact.
+
+use core::ptr::NonNull;
+
+use crate::GicError;
+
+/// Memory type of a mapping, as set up in the page tables of the kernel.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MemoryType {
+    /// Device memory without gathering, reordering or early write acknowledgement.
+    DeviceNGnRnE,
+    /// Device memory without gathering or reordering.
+    DeviceNGnRE,
+    /// Device memory without gathering.
+    DeviceNGRE,
+    /// Device memory with gathering, reordering and early write acknowledgement.
+    DeviceGRE,
+    /// Normal non-cacheable memory.
+    NormalNonCacheable,
+    /// Normal cacheable memory.
+    NormalCacheable,
+}
+
+impl MemoryType {
+    /// Returns whether GIC registers can be mapped with this memory type.
+    pub const fn suits_gic(self) -> bool {
+        matches!(self, Self::DeviceNGnRnE | Self::DeviceNGnRE)
+    }
+}
+
+/// The base address of a GIC register frame, mapped with a memory type
+/// that suits the GIC.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DeviceMmio(NonNull<u8>);
+
+impl DeviceMmio {
+    /// Wraps the base address of a register frame mapped as `memory_type`.
+    ///
+    /// Returns [`GicError::InvalidMapping`] if the memory type doesn't suit
+    /// the GIC or `base` is null.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to the register frame, mapped with `memory_type` into
+    /// the address space of the process and without any other aliases.
+    pub unsafe fn new(base: *mut u8, memory_type: MemoryType) -> Result<Self, GicError> {
+        if !memory_type.suits_gic() {
+            return Err(GicError::InvalidMapping);
+        }
+        NonNull::new(base).map(Self).ok_or(GicError::InvalidMapping)
+    }
+
+    /// Returns the base address.
+    pub const fn as_ptr(self) -> *mut u8 {
+        self.0.as_ptr()
+    }
+}
+
+/// Checks with `query` that the frame at `base` is mapped as a memory type
+/// suiting the GIC.
+pub(crate) fn check_memory_type(
+    base: *const u8,
+    query: &impl Fn(*const u8) -> MemoryType,
+) -> Result<(), GicError> {
+    if query(base).suits_gic() {
+        Ok(())
+    } else {
+        Err(GicError::InvalidMapping)
+    }
+}
+
+/// Checks that two reads of an ID register agree, as they don't for a
+/// mapping that doesn't reach the device.
+pub(crate) fn check_stable(read: impl Fn() -> u32) -> Result<u32, GicError> {
+    let value = read();
+    if read() == value {
+        Ok(value)
+    } else {
+        Err(GicError::InvalidMapping)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_mmio() {
+        let base = 0x800_0000 as *mut u8;
+        assert!(unsafe { DeviceMmio::new(base, MemoryType::DeviceNGnRE) }.is_ok());
+        assert_eq!(
+            unsafe { DeviceMmio::new(base, MemoryType::NormalNonCacheable) },
+            Err(GicError::InvalidMapping)
+        );
+        assert_eq!(
+            unsafe { DeviceMmio::new(core::ptr::null_mut(), MemoryType::DeviceNGnRnE) },
+            Err(GicError::InvalidMapping)
+        );
+    }
+}
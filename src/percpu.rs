@@ -0,0 +1,122 @@
+//! Per-CPU storage of GIC handles.
+//!
+//! Interrupt handlers need the GIC handle of the CPU they run on, but trap
+//! entry code rarely has a convenient way to pass it down. A [`PerCpuGic`]
+//! holds one handle per CPU in a static, indexed by a [`CpuIndex`] the kernel
+//! provides, so [`PerCpuGic::current`] can be called from anywhere.
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::sysregs;
+
+/// Maps the CPU the code runs on to a dense index.
+pub trait CpuIndex {
+    /// Returns the index of the current CPU.
+    fn cpu_index() -> usize;
+}
+
+/// Uses `MPIDR_EL1.Aff0` as the CPU index, which suits systems with a single
+/// cluster such as the QEMU `virt` machine.
+#[derive(Copy, Clone, Debug)]
+pub struct MpidrAff0;
+
+impl CpuIndex for MpidrAff0 {
+    fn cpu_index() -> usize {
+        (sysregs::mpidr() & 0xff) as usize
+    }
+}
+
+/// GIC handles of up to `N` CPUs, looked up by the [`CpuIndex`] `I`.
+///
+/// Each CPU sets its own handle once, typically right after its
+/// `per_cpu_init`, and only ever reads its own handle afterwards.
+pub struct PerCpuGic<G, I, const N: usize> {
+    handles: [UnsafeCell<Option<G>>; N],
+    set: [AtomicBool; N],
+    index: PhantomData<I>,
+}
+
+// SAFETY: Each slot is only written once, before the flag publishing it is
+// set, and never mutably accessed again.
+unsafe impl<G: Send + Sync, I, const N: usize> Sync for PerCpuGic<G, I, N> {}
+
+impl<G, I: CpuIndex, const N: usize> PerCpuGic<G, I, N> {
+    /// Returns a table with no handle set.
+    pub const fn new() -> Self {
+        Self {
+            handles: [const { UnsafeCell::new(None) }; N],
+            set: [const { AtomicBool::new(false) }; N],
+            index: PhantomData,
+        }
+    }
+
+    /// Stores `gic` as the handle of the current CPU.
+    ///
+    /// Panics if the CPU index is out of range or the current CPU already has
+    /// a handle.
+    pub fn set_current(&self, gic: G) {
+        let cpu = I::cpu_index();
+        assert!(cpu < N, "CPU index {} out of range", cpu);
+        assert!(
+            !self.set[cpu].load(Ordering::Acquire),
+            "GIC handle of CPU {} already set",
+            cpu
+        );
+        // SAFETY: The flag isn't set yet, so nothing references the slot.
+        unsafe { *self.handles[cpu].get() = Some(gic) };
+        self.set[cpu].store(true, Ordering::Release);
+    }
+
+    /// Returns the handle of the current CPU, or `None` if it wasn't set.
+    ///
+    /// The handle is shared, so only methods taking `&self` can be called
+    /// through it, which covers handling and configuring interrupts but not
+    /// initialisation.
+    pub fn current(&self) -> Option<&G> {
+        let cpu = I::cpu_index();
+        if cpu >= N || !self.set[cpu].load(Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: The slot is never written again once its flag is set.
+        unsafe { (*self.handles[cpu].get()).as_ref() }
+    }
+}
+
+impl<G, I: CpuIndex, const N: usize> Default for PerCpuGic<G, I, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    static CPU: AtomicUsize = AtomicUsize::new(0);
+
+    struct TestIndex;
+
+    impl CpuIndex for TestIndex {
+        fn cpu_index() -> usize {
+            CPU.load(Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn test_per_cpu() {
+        let gics = PerCpuGic::<u32, TestIndex, 2>::new();
+        gics.set_current(10);
+        CPU.store(1, Ordering::Relaxed);
+        assert_eq!(gics.current(), None);
+        gics.set_current(11);
+        assert_eq!(gics.current(), Some(&11));
+        CPU.store(0, Ordering::Relaxed);
+        assert_eq!(gics.current(), Some(&10));
+        CPU.store(2, Ordering::Relaxed);
+        assert_eq!(gics.current(), None);
+    }
+}
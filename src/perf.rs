@@ -0,0 +1,107 @@
+//! Counters of the waits of the driver on the GIC.
+//!
+//! How long the GIC takes to complete register writes, wake redistributors
+//! and consume ITS commands depends on its clocks and on how firmware set it
+//! up, so a regression there shows up as more polling rather than as a
+//! failure. With the `perf-counters` feature, the driver counts these polls,
+//! and [`perf_counters`] returns the totals to compare across builds.
+
+#[cfg(feature = "perf-counters")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A counter of [`PerfCounters`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Counter {
+    /// A poll of `GICD_CTLR.RWP` or `GICR_CTLR.RWP`.
+    RwpPoll,
+    /// A poll of `GICR_WAKER.ChildrenAsleep`.
+    WakePoll,
+    /// A command refused as the ITS command queue was full.
+    ItsQueueFull,
+}
+
+#[cfg(feature = "perf-counters")]
+static COUNTS: [AtomicUsize; 3] = [const { AtomicUsize::new(0) }; 3];
+
+/// Adds one to `counter`.
+#[inline]
+pub(crate) fn add(counter: Counter) {
+    #[cfg(feature = "perf-counters")]
+    COUNTS[counter as usize].fetch_add(1, Ordering::Relaxed);
+    #[cfg(not(feature = "perf-counters"))]
+    let _ = counter;
+}
+
+/// Returns `done`, adding one to `counter` each time it is called, for
+/// [`Poller::poll`](crate::delay::Poller::poll).
+#[inline]
+pub(crate) fn counting(counter: Counter, mut done: impl FnMut() -> bool) -> impl FnMut() -> bool {
+    move || {
+        add(counter);
+        done()
+    }
+}
+
+/// Numbers of polls of the driver, as counted with the `perf-counters`
+/// feature.
+#[cfg(feature = "perf-counters")]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PerfCounters {
+    /// Number of polls for register writes to complete, on the distributor
+    /// and the redistributors.
+    pub rwp_polls: usize,
+    /// Number of polls for redistributors to wake up.
+    pub wake_polls: usize,
+    /// Number of ITS commands refused with
+    /// [`GicError::QueueFull`](crate::GicError::QueueFull).
+    pub its_queue_full: usize,
+}
+
+#[cfg(feature = "perf-counters")]
+impl core::ops::Sub for PerfCounters {
+    type Output = Self;
+
+    fn sub(self, earlier: Self) -> Self {
+        Self {
+            rwp_polls: self.rwp_polls - earlier.rwp_polls,
+            wake_polls: self.wake_polls - earlier.wake_polls,
+            its_queue_full: self.its_queue_full - earlier.its_queue_full,
+        }
+    }
+}
+
+/// Returns the numbers of polls of the driver so far, on all CPUs.
+#[cfg(feature = "perf-counters")]
+pub fn perf_counters() -> PerfCounters {
+    let count = |counter: Counter| COUNTS[counter as usize].load(Ordering::Relaxed);
+    PerfCounters {
+        rwp_polls: count(Counter::RwpPoll),
+        wake_polls: count(Counter::WakePoll),
+        its_queue_full: count(Counter::ItsQueueFull),
+    }
+}
+
+#[cfg(all(test, feature = "perf-counters"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perf_counters() {
+        let before = perf_counters();
+        let mut polls = 0;
+        let mut done = counting(Counter::RwpPoll, || {
+            polls += 1;
+            polls == 3
+        });
+        while !done() {}
+        add(Counter::ItsQueueFull);
+        assert_eq!(
+            perf_counters() - before,
+            PerfCounters {
+                rwp_polls: 3,
+                wake_polls: 0,
+                its_queue_full: 1,
+            }
+        );
+    }
+}
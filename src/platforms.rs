@@ -0,0 +1,126 @@
+//! The GICs of common boards.
+//!
+//! Each constant gives the physical base addresses of the GIC of a board, as
+//! found in its device tree, so that a kernel gets its first interrupt
+//! without looking them up:
+//!
+//! ```ignore
+//! let mut gic = platforms::QEMU_VIRT_GICV3.builder().build()?;
+//! gic.init_primary()?;
+//! ```
+//!
+//! The drivers use the addresses as given, which suits identity-mapped early
+//! boot. Once the MMU maps the GIC elsewhere, pass the virtual addresses to
+//! the constructors of [`GicV2`] or [`GicV3`] instead.
+
+use crate::{GicV2, GicV3, GicV3Builder};
+
+/// The physical base addresses of a GICv2.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct GicV2Platform {
+    /// Name of the board.
+    pub name: &'static str,
+    /// Base of the distributor.
+    pub gicd: usize,
+    /// Base of the CPU interface.
+    pub gicc: usize,
+    /// Base of the GICv2m MSI frame, if the board has one.
+    pub v2m: Option<usize>,
+}
+
+impl GicV2Platform {
+    /// Returns a driver for the GIC, at its physical addresses.
+    pub const fn driver(&self) -> GicV2 {
+        GicV2::new(self.gicd as *mut u8, self.gicc as *mut u8)
+    }
+}
+
+/// The physical base addresses of a GICv3 or GICv4.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct GicV3Platform {
+    /// Name of the board.
+    pub name: &'static str,
+    /// Base of the distributor.
+    pub gicd: usize,
+    /// Start of the redistributor region.
+    pub gicr: usize,
+    /// Bases of the ITSs, if any.
+    pub its: &'static [usize],
+}
+
+impl GicV3Platform {
+    /// Returns a builder with the distributor, the redistributors and the
+    /// first ITS of the GIC, at their physical addresses.
+    pub const fn builder(&self) -> GicV3Builder {
+        let builder = GicV3::builder()
+            .distributor(self.gicd as *mut u8)
+            .redistributors(self.gicr as *mut u8);
+        match self.its {
+            [its, ..] => builder.its(*its as *mut u8),
+            [] => builder,
+        }
+    }
+}
+
+/// The QEMU `virt` machine with `gic-version=2`.
+pub const QEMU_VIRT_GICV2: GicV2Platform = GicV2Platform {
+    name: "QEMU virt",
+    gicd: 0x0800_0000,
+    gicc: 0x0801_0000,
+    v2m: Some(0x0802_0000),
+};
+
+/// The QEMU `virt` machine with `gic-version=3`, and an ITS unless started
+/// with `its=off`.
+pub const QEMU_VIRT_GICV3: GicV3Platform = GicV3Platform {
+    name: "QEMU virt",
+    gicd: 0x0800_0000,
+    gicr: 0x080a_0000,
+    its: &[0x0808_0000],
+};
+
+/// The GIC-400 of the Raspberry Pi 4, in the low peripheral mode the
+/// firmware starts it in.
+pub const RASPBERRY_PI_4: GicV2Platform = GicV2Platform {
+    name: "Raspberry Pi 4",
+    gicd: 0xff84_1000,
+    gicc: 0xff84_2000,
+    v2m: None,
+};
+
+/// The GIC-500 of the i.MX 8M Mini, Nano, Plus and Quad.
+pub const IMX8M: GicV3Platform = GicV3Platform {
+    name: "i.MX 8M",
+    gicd: 0x3880_0000,
+    gicr: 0x3888_0000,
+    its: &[],
+};
+
+/// The GIC-600 of the Rockchip RK3588 and RK3588S.
+pub const RK3588: GicV3Platform = GicV3Platform {
+    name: "RK3588",
+    gicd: 0xfe60_0000,
+    gicr: 0xfe68_0000,
+    its: &[0xfe64_0000, 0xfe66_0000],
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alignment() {
+        for platform in [QEMU_VIRT_GICV2, RASPBERRY_PI_4] {
+            assert_eq!(platform.gicd % 0x1000, 0, "{}", platform.name);
+            assert_eq!(platform.gicc % 0x1000, 0, "{}", platform.name);
+        }
+        // GICv3 register frames are 64 KiB.
+        for platform in [QEMU_VIRT_GICV3, IMX8M, RK3588] {
+            assert_eq!(platform.gicd % 0x1_0000, 0, "{}", platform.name);
+            assert_eq!(platform.gicr % 0x1_0000, 0, "{}", platform.name);
+            for its in platform.its {
+                assert_eq!(its % 0x1_0000, 0, "{}", platform.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Priority grouping, the split of interrupt priorities set by the binary
+//! point registers.
+//!
+//! The upper bits of a priority form its group priority, which decides
+//! whether an interrupt preempts the one running, and the lower bits its
+//! subpriority, which only orders pending interrupts of equal group priority.
+//! Real-time systems bound their interrupt nesting from the number of
+//! preemption levels, which [`PriorityGrouping::for_preemption_levels`]
+//! turns into a split.
+
+/// The split of Group 1 priorities into group priority and subpriority.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PriorityGrouping {
+    /// Number of upper bits of the priority forming the group priority, from
+    /// 1 to 8.
+    group_bits: u8,
+}
+
+impl PriorityGrouping {
+    /// Returns the split with `group_bits` bits of group priority, clamped to
+    /// 1 to 8.
+    pub const fn from_group_bits(group_bits: u8) -> Self {
+        Self {
+            group_bits: if group_bits == 0 {
+                1
+            } else if group_bits > 8 {
+                8
+            } else {
+                group_bits
+            },
+        }
+    }
+
+    /// Returns the split with the fewest group priority bits giving at least
+    /// `levels` preemption levels, up to 256.
+    pub const fn for_preemption_levels(levels: u32) -> Self {
+        let bits = if levels <= 2 {
+            1
+        } else {
+            (levels - 1).ilog2() + 1
+        };
+        Self::from_group_bits(if bits > 8 { 8 } else { bits as u8 })
+    }
+
+    /// Returns the number of bits of group priority.
+    pub const fn group_bits(self) -> u8 {
+        self.group_bits
+    }
+
+    /// Returns the number of preemption levels, i.e. of group priorities.
+    pub const fn preemption_levels(self) -> u32 {
+        1 << self.group_bits
+    }
+
+    /// Returns the group priority of `priority`, with the subpriority bits
+    /// cleared.
+    pub const fn group_priority(self, priority: u8) -> u8 {
+        priority & !self.subpriority_mask()
+    }
+
+    /// Returns the subpriority of `priority`, with the group priority bits
+    /// cleared.
+    pub const fn subpriority(self, priority: u8) -> u8 {
+        priority & self.subpriority_mask()
+    }
+
+    const fn subpriority_mask(self) -> u8 {
+        (0xff_u16 >> self.group_bits) as u8
+    }
+
+    /// Returns the value of the Group 1 binary point registers, `ICC_BPR1_EL1`
+    /// and the non-secure `GICC_BPR` or `GICC_ABPR`, which count the bits of
+    /// subpriority.
+    pub(crate) const fn bpr1(self) -> u32 {
+        8 - self.group_bits as u32
+    }
+
+    /// Parses the value of a Group 1 binary point register.
+    pub(crate) const fn from_bpr1(bpr: u32) -> Self {
+        Self::from_group_bits(8 - (bpr & 0b111) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preemption_levels() {
+        assert_eq!(PriorityGrouping::for_preemption_levels(0).group_bits(), 1);
+        assert_eq!(PriorityGrouping::for_preemption_levels(4).group_bits(), 2);
+        assert_eq!(PriorityGrouping::for_preemption_levels(5).group_bits(), 3);
+        assert_eq!(
+            PriorityGrouping::for_preemption_levels(1000).group_bits(),
+            8
+        );
+
+        let grouping = PriorityGrouping::from_group_bits(3);
+        assert_eq!(grouping.preemption_levels(), 8);
+        assert_eq!(grouping.group_priority(0xb7), 0xa0);
+        assert_eq!(grouping.subpriority(0xb7), 0x17);
+        assert_eq!(grouping.bpr1(), 5);
+        assert_eq!(PriorityGrouping::from_bpr1(5), grouping);
+        assert_eq!(PriorityGrouping::from_group_bits(8).subpriority(0xff), 0);
+    }
+}
@@ -46,7 +46,11 @@ register_structs! {
         (0x0f10 => pub(crate) CPENDSGIR: [ReadWrite<u32>; 4]),
         /// SGI Set-Pending Registers
         (0x0f20 => pub(crate) SPENDSGIR: [ReadWrite<u32>; 4]),
-        (0x0f30 => @END),
+        (0x0f30 => _reserved_3),
+        /// Peripheral ID2 Register.
+        (0x0fe8 => pub(crate) PIDR2: ReadOnly<u32>),
+        (0x0fec => _reserved_4),
+        (0x1000 => @END),
     }
 }
 
@@ -90,3 +94,43 @@ register_structs! {
         (0x1004 => @END),
     }
 }
+
+assert_layout!(GicDistributorRegs, 0x1000 {
+    CTLR: 0x0000,
+    TYPER: 0x0004,
+    IIDR: 0x0008,
+    IGROUPRn: 0x0080,
+    ISENABLER: 0x0100,
+    ICENABLER: 0x0180,
+    ISPENDR: 0x0200,
+    ICPENDR: 0x0280,
+    ISACTIVER: 0x0300,
+    ICACTIVER: 0x0380,
+    IPRIORITYR: 0x0400,
+    ITARGETSR: 0x0800,
+    ICFGR: 0x0c00,
+    NSACR: 0x0e00,
+    SGIR: 0x0f00,
+    CPENDSGIR: 0x0f10,
+    SPENDSGIR: 0x0f20,
+    PIDR2: 0x0fe8,
+});
+
+// The CPU interface ends with GICC_DIR, in the second 4 KiB page.
+assert_layout!(GicCpuInterfaceRegs {
+    CTLR: 0x0000,
+    PMR: 0x0004,
+    BPR: 0x0008,
+    IAR: 0x000c,
+    EOIR: 0x0010,
+    RPR: 0x0014,
+    HPPIR: 0x0018,
+    ABPR: 0x001c,
+    AIAR: 0x0020,
+    AEOIR: 0x0024,
+    AHPPIR: 0x0028,
+    APRn: 0x00d0,
+    NSAPRn: 0x00e0,
+    IIDR: 0x00fc,
+    DIR: 0x1000,
+});
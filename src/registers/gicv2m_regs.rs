@@ -0,0 +1,30 @@
+//! Types and definitions for GICv2m MSI frames.
+//!
+//! The official documentation: <https://developer.arm.com/documentation/ddi0521/latest/>
+
+use tock_registers::register_structs;
+use tock_registers::registers::{ReadOnly, WriteOnly};
+
+register_structs! {
+    /// GICv2m MSI frame registers.
+    #[allow(non_snake_case)]
+    pub(crate) V2mFrameRegs {
+        (0x0000 => _reserved0),
+        /// MSI type register.
+        (0x0008 => pub(crate) TYPER: ReadOnly<u32>),
+        (0x000c => _reserved1),
+        /// Non-secure SPI set register, written by devices to signal an SPI.
+        (0x0040 => pub(crate) SETSPI_NS: WriteOnly<u32>),
+        (0x0044 => _reserved2),
+        /// Interface identification register.
+        (0x0fcc => pub(crate) IIDR: ReadOnly<u32>),
+        (0x0fd0 => _reserved3),
+        (0x1000 => @END),
+    }
+}
+
+assert_layout!(V2mFrameRegs, 0x1000 {
+    TYPER: 0x0008,
+    SETSPI_NS: 0x0040,
+    IIDR: 0x0fcc,
+});
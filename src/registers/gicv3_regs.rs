@@ -146,6 +146,29 @@ bitflags! {
     }
 }
 
+bitflags! {
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub struct GicrCtlr: u32 {
+        const UWP = 1 << 31;
+        const DPG1S = 1 << 26;
+        const DPG1NS = 1 << 25;
+        const DPG0 = 1 << 24;
+        const RWP = 1 << 3;
+        const IR = 1 << 2;
+        const CES = 1 << 1;
+        const EnableLPIs = 1 << 0;
+    }
+}
+
+/// Inner shareable attribute for `GICR_PROPBASER` and `GICR_PENDBASER`.
+pub(crate) const GICR_BASER_INNER_SHAREABLE: u64 = 0b01 << 10;
+/// Normal inner write-back read/write-allocate attribute for `GICR_PROPBASER`
+/// and `GICR_PENDBASER`.
+pub(crate) const GICR_BASER_RAWAWB: u64 = 0b111 << 7;
+/// Normal inner non-cacheable attribute for `GICR_PROPBASER` and `GICR_PENDBASER`.
+pub(crate) const GICR_BASER_NON_CACHEABLE: u64 = 0b001 << 7;
+
 register_structs! {
     /// GIC Redistributor registers.
     #[allow(non_snake_case)]
@@ -257,3 +280,80 @@ register_structs! {
         (0x10000 => @END),
     }
 }
+
+assert_layout!(GicDistributorRegs, 0x10000 {
+    CTLR: 0x0000,
+    TYPER: 0x0004,
+    IIDR: 0x0008,
+    TYPER2: 0x000c,
+    STATUSR: 0x0010,
+    SETSPI_NSR: 0x0040,
+    CLRSPI_NSR: 0x0048,
+    SETSPI_SR: 0x0050,
+    CLRSPI_SR: 0x0058,
+    IGROUPR: 0x0080,
+    ISENABLER: 0x0100,
+    ICENABLER: 0x0180,
+    ISPENDR: 0x0200,
+    ICPENDR: 0x0280,
+    ISACTIVER: 0x0300,
+    ICACTIVER: 0x0380,
+    IPRIORITYR: 0x0400,
+    ITARGETSR: 0x0800,
+    ICFGR: 0x0c00,
+    IGRPMODR: 0x0d00,
+    NSACR: 0x0e00,
+    SGIR: 0x0f00,
+    CPENDSGIR: 0x0f10,
+    SPENDSGIR: 0x0f20,
+    INMIR: 0x0f80,
+    IGROUPRnE: 0x1000,
+    ISENABLERnE: 0x1200,
+    ICENABLERnE: 0x1400,
+    ISPENDRnE: 0x1600,
+    ICPENDRnE: 0x1800,
+    ISACTIVERnE: 0x1a00,
+    ICACTIVERnE: 0x1c00,
+    IPRIORITYRnE: 0x2000,
+    ICFGRnE: 0x3000,
+    IGRPMODRnE: 0x3400,
+    NSACRnE: 0x3600,
+    INMRnE: 0x3b00,
+    IROUTER: 0x6000,
+    IROUTERnE: 0x8000,
+    PIDR2: 0xffe8,
+});
+
+assert_layout!(GicRedistributorRegs, 0x10000 {
+    CTLR: 0x0000,
+    IIDR: 0x0004,
+    TYPER: 0x0008,
+    STATUSR: 0x0010,
+    WAKER: 0x0014,
+    MPAMIDR: 0x0018,
+    PARTIDR: 0x001c,
+    SETLPIR: 0x0040,
+    CLRLPIR: 0x0048,
+    PROPBASER: 0x0070,
+    PENDBASER: 0x0078,
+    INVLPIR: 0x00a0,
+    INVALLR: 0x00b0,
+    SYNCR: 0x00c0,
+    IDREGS: 0xffd0,
+});
+
+assert_layout!(GicSgiRegs, 0x10000 {
+    IGROUPR0: 0x0080,
+    ISENABLER: 0x0100,
+    ICENABLER: 0x0180,
+    ISPENDR: 0x0200,
+    ICPENDR: 0x0280,
+    ISACTIVER: 0x0300,
+    ICACTIVER: 0x0380,
+    IPRIORITYR: 0x0400,
+    ICFGR: 0x0c00,
+    IGRPMODR: 0x0d00,
+    NSACR: 0x0e00,
+    INMIR0: 0x0f80,
+    INMIRnE: 0x0f84,
+});
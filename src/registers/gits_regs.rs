@@ -0,0 +1,50 @@
+//! Types and definitions for the GICv3 Interrupt Translation Service.
+//!
+//! The official documentation: <https://developer.arm.com/documentation/ihi0069/latest//>
+
+use tock_registers::register_structs;
+use tock_registers::registers::{ReadOnly, ReadWrite, WriteOnly};
+
+register_structs! {
+    /// GIC ITS registers.
+    #[allow(non_snake_case)]
+    pub(crate) GitsRegs {
+        /// ITS control register.
+        (0x0000 => pub(crate) CTLR: ReadWrite<u32>),
+        /// Implementer identification register.
+        (0x0004 => pub(crate) IIDR: ReadOnly<u32>),
+        /// ITS type register.
+        (0x0008 => pub(crate) TYPER: ReadOnly<u64>),
+        (0x0010 => _reserved0),
+        /// ITS command queue descriptor.
+        (0x0080 => pub(crate) CBASER: ReadWrite<u64>),
+        /// ITS write register.
+        (0x0088 => pub(crate) CWRITER: ReadWrite<u64>),
+        /// ITS read register.
+        (0x0090 => pub(crate) CREADR: ReadOnly<u64>),
+        (0x0098 => _reserved1),
+        /// ITS translation table descriptors.
+        (0x0100 => pub(crate) BASER: [ReadWrite<u64>; 8]),
+        (0x0140 => _reserved2),
+        /// Peripheral ID2 register.
+        (0xffe8 => pub(crate) PIDR2: ReadOnly<u32>),
+        (0xffec => _reserved3),
+        /// ITS translation register.
+        (0x10040 => pub(crate) TRANSLATER: WriteOnly<u32>),
+        (0x10044 => _reserved4),
+        (0x20000 => @END),
+    }
+}
+
+// The control frame and the translation frame, 64 KiB each.
+assert_layout!(GitsRegs, 0x20000 {
+    CTLR: 0x0000,
+    IIDR: 0x0004,
+    TYPER: 0x0008,
+    CBASER: 0x0080,
+    CWRITER: 0x0088,
+    CREADR: 0x0090,
+    BASER: 0x0100,
+    PIDR2: 0xffe8,
+    TRANSLATER: 0x10040,
+});
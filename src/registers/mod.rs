@@ -25,5 +25,56 @@ macro_rules! impl_uintlike_and_shift {
     };
 }
 
+/// Fails the build unless the frame `$regs` is `$size` bytes long and each
+/// listed register is at its architectural offset.
+///
+/// `register_structs!` only checks the offsets against those written next to
+/// the fields, so a wrong offset there would otherwise only show up as
+/// accesses to the wrong register.
+macro_rules! assert_layout {
+    ($regs:ident $(, $size:literal)? { $($field:ident: $offset:expr),* $(,)? }) => {
+        const _: () = {
+            $(assert!(
+                ::core::mem::size_of::<$regs>() == $size,
+                concat!("size of ", stringify!($regs))
+            );)?
+            $(assert!(
+                ::core::mem::offset_of!($regs, $field) == $offset,
+                concat!("offset of ", stringify!($regs), "::", stringify!($field))
+            );)*
+        };
+    };
+}
+
+use core::ptr::NonNull;
+
+/// Reads the 32-bit register at `offset` bytes from `base`.
+///
+/// # Safety
+///
+/// `base + offset` must be a 4-byte aligned register inside the mapped frame.
+pub(crate) unsafe fn read_raw<T>(base: NonNull<T>, offset: usize) -> u32 {
+    base.cast::<u8>()
+        .add(offset)
+        .cast::<u32>()
+        .as_ptr()
+        .read_volatile()
+}
+
+/// Writes `value` to the 32-bit register at `offset` bytes from `base`.
+///
+/// # Safety
+///
+/// `base + offset` must be a 4-byte aligned register inside the mapped frame.
+pub(crate) unsafe fn write_raw<T>(base: NonNull<T>, offset: usize, value: u32) {
+    base.cast::<u8>()
+        .add(offset)
+        .cast::<u32>()
+        .as_ptr()
+        .write_volatile(value)
+}
+
 pub(crate) mod gicv2_regs;
+pub(crate) mod gicv2m_regs;
 pub(crate) mod gicv3_regs;
+pub(crate) mod gits_regs;
@@ -0,0 +1,118 @@
+//! Interrupts reserved to the firmware.
+//!
+//! Secure PPIs, SPIs the firmware manages or the interrupts of another
+//! world can be marked in a [`ReservedSet`] handed to the driver at
+//! construction. The driver then refuses to configure them: methods returning
+//! a `Result` fail with [`GicError::Reserved`], and the others log an error
+//! and leave the interrupt alone.
+
+use crate::logging::error;
+use crate::{GicError, IntId};
+
+/// Number of words of the bitmap of INTIDs below 1024, followed by the same
+/// number for the extended SPIs.
+const WORDS: usize = 32;
+
+/// A set of reserved interrupts, among the SGIs, PPIs, SPIs and extended SPIs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ReservedSet {
+    bits: [u32; 2 * WORDS],
+}
+
+impl ReservedSet {
+    /// Returns an empty set.
+    pub const fn new() -> Self {
+        Self {
+            bits: [0; 2 * WORDS],
+        }
+    }
+
+    /// Returns the bit of `intid` in the bitmap, or `None` if it can't be
+    /// reserved.
+    pub(crate) const fn index(intid: IntId) -> Option<usize> {
+        if intid.0 < IntId::SPECIAL_START {
+            Some(intid.0)
+        } else if intid.0 >= IntId::ESPI_START && intid.0 < IntId::ESPI_START + IntId::ESPI_COUNT {
+            Some(WORDS * 32 + intid.0 - IntId::ESPI_START)
+        } else {
+            None
+        }
+    }
+
+    /// Adds `intid` to the set, returning the set for chaining.
+    ///
+    /// Returns [`GicError::InvalidIntId`] for special INTIDs, extended PPIs
+    /// and LPIs, which can't be reserved.
+    pub const fn with(mut self, intid: IntId) -> Result<Self, GicError> {
+        let Some(index) = Self::index(intid) else {
+            return Err(GicError::InvalidIntId);
+        };
+        self.bits[index / 32] |= 1 << (index % 32);
+        Ok(self)
+    }
+
+    /// Returns whether `intid` is in the set.
+    pub const fn contains(&self, intid: IntId) -> bool {
+        match Self::index(intid) {
+            Some(index) => self.bits[index / 32] & 1 << (index % 32) != 0,
+            None => false,
+        }
+    }
+
+    /// Returns the bits of the 32 INTIDs from `first`, a multiple of 32.
+    pub(crate) const fn word(&self, first: IntId) -> u32 {
+        match Self::index(first) {
+            Some(index) => self.bits[index / 32],
+            None => 0,
+        }
+    }
+
+    /// Returns [`GicError::Reserved`] if `intid` is in the set.
+    pub(crate) fn check(&self, intid: IntId) -> Result<(), GicError> {
+        if self.contains(intid) {
+            Err(GicError::Reserved)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns whether `intid` is in the set, logging that `action` is
+    /// ignored if it is.
+    pub(crate) fn refuses(&self, intid: IntId, action: &str) -> bool {
+        let reserved = self.contains(intid);
+        if reserved {
+            error!("GIC: {} of reserved INTID {} ignored", action, intid.0);
+        }
+        reserved
+    }
+}
+
+impl Default for ReservedSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_set() {
+        let set = ReservedSet::new()
+            .with(IntId::ppi(13))
+            .unwrap()
+            .with(IntId::espi(5))
+            .unwrap();
+        assert!(set.contains(IntId::ppi(13)));
+        assert!(set.contains(IntId::espi(5)));
+        assert!(!set.contains(IntId::spi(5)));
+        assert!(!set.contains(IntId::espi(4)));
+        assert_eq!(set.check(IntId::ppi(13)), Err(GicError::Reserved));
+        assert_eq!(set.check(IntId::ppi(12)), Ok(()));
+        assert_eq!(
+            ReservedSet::new().with(IntId(IntId::LPI_START)),
+            Err(GicError::InvalidIntId)
+        );
+    }
+}
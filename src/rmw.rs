@@ -0,0 +1,56 @@
+//! Serialisation of read-modify-write register updates.
+//!
+//! Priority, trigger, group and target registers hold the fields of several
+//! interrupts in one word, so changing the field of one interrupt means
+//! reading, modifying and writing back the whole word. The drivers are `Copy`
+//! and used from several cores at once, so two cores configuring neighbouring
+//! interrupts could otherwise lose one of the updates. Each update holds one
+//! of a fixed set of spinlocks, picked by the address of the register.
+//!
+//! The locks use exclusive accesses, so they need the MMU and data cache to be
+//! enabled on cores that initialise the GIC or configure interrupts. An
+//! interrupt handler must not configure interrupts while it may have preempted
+//! a configuration on the same core.
+//!
+//! With the `single-core` feature, nothing else updates the registers, so the
+//! locks are compiled out.
+
+#[cfg(not(feature = "single-core"))]
+use core::hint::spin_loop;
+#[cfg(not(feature = "single-core"))]
+use core::sync::atomic::{AtomicBool, Ordering};
+use tock_registers::interfaces::{Readable, Writeable};
+use tock_registers::registers::ReadWrite;
+
+/// Number of locks, a power of two so any register maps to one cheaply.
+#[cfg(not(feature = "single-core"))]
+const LOCK_COUNT: usize = 64;
+
+#[cfg(not(feature = "single-core"))]
+static LOCKS: [AtomicBool; LOCK_COUNT] = [const { AtomicBool::new(false) }; LOCK_COUNT];
+
+/// Replaces the value of `reg` with `f` applied to it, and returns the value
+/// written.
+#[cfg(feature = "single-core")]
+pub(crate) fn modify(reg: &ReadWrite<u32>, f: impl FnOnce(u32) -> u32) -> u32 {
+    let value = f(reg.get());
+    reg.set(value);
+    value
+}
+
+/// Replaces the value of `reg` with `f` applied to it, without racing other
+/// updates of the same register, and returns the value written.
+#[cfg(not(feature = "single-core"))]
+pub(crate) fn modify(reg: &ReadWrite<u32>, f: impl FnOnce(u32) -> u32) -> u32 {
+    let lock = &LOCKS[(reg as *const ReadWrite<u32> as usize / 4) % LOCK_COUNT];
+    while lock
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        spin_loop();
+    }
+    let value = f(reg.get());
+    reg.set(value);
+    lock.store(false, Ordering::Release);
+    value
+}
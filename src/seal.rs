@@ -0,0 +1,126 @@
+//! Sealing the interrupt configuration after boot.
+//!
+//! High-integrity systems freeze their interrupt map once booted, so that a
+//! fault later on can't move an interrupt to another group, trigger type or
+//! CPU. After [`seal`], the drivers refuse to change the group or trigger
+//! type of any interrupt, and the routing of the interrupts pinned by
+//! [`pin_routing`] or by applying a firmware properties table: methods
+//! returning a `Result` fail with [`GicError::Sealed`], and the others log an
+//! error and leave the interrupt alone. Interrupts can still be enabled,
+//! disabled and prioritised.
+//!
+//! The seal covers all drivers on all CPUs, and lasts until reset.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::logging::{error, info};
+use crate::reserved::ReservedSet;
+use crate::{GicError, IntId};
+
+/// Number of words of the bitmap of pinned interrupts, indexed like a
+/// [`ReservedSet`].
+const WORDS: usize = 64;
+
+struct Seal {
+    sealed: AtomicBool,
+    /// The interrupts whose routing the seal freezes.
+    pinned: [AtomicU32; WORDS],
+}
+
+static SEAL: Seal = Seal::new();
+
+impl Seal {
+    const fn new() -> Self {
+        Self {
+            sealed: AtomicBool::new(false),
+            pinned: [const { AtomicU32::new(0) }; WORDS],
+        }
+    }
+
+    fn pin_routing(&self, intid: IntId) -> Result<(), GicError> {
+        let index = ReservedSet::index(intid).ok_or(GicError::InvalidIntId)?;
+        self.pinned[index / 32].fetch_or(1 << (index % 32), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn is_sealed(&self) -> bool {
+        self.sealed.load(Ordering::Acquire)
+    }
+
+    fn routing_frozen(&self, intid: IntId) -> bool {
+        self.is_sealed()
+            && ReservedSet::index(intid).is_some_and(|index| {
+                self.pinned[index / 32].load(Ordering::Relaxed) & 1 << (index % 32) != 0
+            })
+    }
+}
+
+/// Seals the interrupt configuration until reset.
+pub fn seal() {
+    SEAL.sealed.store(true, Ordering::Release);
+    info!("GIC: interrupt configuration sealed");
+}
+
+/// Returns whether [`seal`] was called.
+pub fn is_sealed() -> bool {
+    SEAL.is_sealed()
+}
+
+/// Freezes the routing of the SPI or extended SPI `intid` once sealed.
+///
+/// Returns [`GicError::InvalidIntId`] for special INTIDs, extended PPIs and
+/// LPIs.
+pub fn pin_routing(intid: IntId) -> Result<(), GicError> {
+    SEAL.pin_routing(intid)
+}
+
+/// Returns [`GicError::Sealed`] if the configuration is sealed.
+pub(crate) fn check() -> Result<(), GicError> {
+    if SEAL.is_sealed() {
+        Err(GicError::Sealed)
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns whether the configuration is sealed, logging that `action` of
+/// `intid` is ignored if it is.
+pub(crate) fn refuses(intid: IntId, action: &str) -> bool {
+    let sealed = SEAL.is_sealed();
+    if sealed {
+        error!("GIC: {} of INTID {} ignored, sealed", action, intid.0);
+    }
+    sealed
+}
+
+/// Returns whether the routing of `intid` is frozen, logging that the
+/// routing is ignored if it is.
+pub(crate) fn refuses_routing(intid: IntId) -> bool {
+    let frozen = SEAL.routing_frozen(intid);
+    if frozen {
+        error!("GIC: routing of INTID {} ignored, sealed", intid.0);
+    }
+    frozen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routing_frozen() {
+        let seal = Seal::new();
+        seal.pin_routing(IntId::spi(3)).unwrap();
+        seal.pin_routing(IntId::espi(7)).unwrap();
+        assert_eq!(
+            seal.pin_routing(IntId::eppi(0)),
+            Err(GicError::InvalidIntId)
+        );
+        assert!(!seal.routing_frozen(IntId::spi(3)));
+
+        seal.sealed.store(true, Ordering::Release);
+        assert!(seal.routing_frozen(IntId::spi(3)));
+        assert!(seal.routing_frozen(IntId::espi(7)));
+        assert!(!seal.routing_frozen(IntId::spi(4)));
+    }
+}
@@ -0,0 +1,386 @@
+//! SGI numbers and pre-encoded SGI targets for GICv3.
+//!
+//! The functions sending SGIs take an [`SgiId`] rather than an [`IntId`], so
+//! that a device interrupt can't be sent as an IPI by mistake.
+//!
+//! Sending an SGI to a set of CPUs takes one write to `ICC_SGI1R_EL1` for each
+//! cluster, that is each `Aff3.Aff2.Aff1` affinity, with a bit for each target
+//! CPU of the cluster. [`SgiTargets`] computes these values once, so frequent
+//! broadcasts like TLB shootdowns only write them out.
+//!
+//! A [`CpuSet`] names the targets by CPU index instead, which works for both
+//! the 8-bit target lists of GICv2 and the affinity-based targets of GICv3.
+//!
+//! A [`LocalSoftirq`] is an SGI each CPU only sends itself, to defer work
+//! from contexts that can't do it, such as NMI handlers, to IRQ context.
+
+use core::iter::FromIterator;
+
+use crate::handlers::Handler;
+use crate::{GenericArmGic, GicError, IntId};
+
+/// Bit position of Aff1 in `ICC_SGI1R_EL1`.
+pub(crate) const AFF1_SHIFT: u32 = 16;
+/// Bit position of Aff2 in `ICC_SGI1R_EL1`.
+pub(crate) const AFF2_SHIFT: u32 = 32;
+/// Bit position of the range selector in `ICC_SGI1R_EL1`.
+const RS_SHIFT: u32 = 44;
+/// Bit position of Aff3 in `ICC_SGI1R_EL1`.
+pub(crate) const AFF3_SHIFT: u32 = 48;
+/// The affinity and range selector fields of `ICC_SGI1R_EL1`, identifying a
+/// group of 16 CPUs.
+pub(crate) const SGI1R_GROUP_MASK: u64 =
+    0xff << AFF1_SHIFT | 0xff << AFF2_SHIFT | 0xf << RS_SHIFT | 0xff << AFF3_SHIFT;
+
+/// The number of a Software Generated Interrupt, from 0 to 15.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct SgiId(u8);
+
+impl SgiId {
+    /// Returns the SGI with the given number.
+    ///
+    /// Panics if `sgi` is 16 or more.
+    pub const fn new(sgi: u8) -> Self {
+        assert!((sgi as usize) < IntId::PPI_START, "SGI out of range");
+        Self(sgi)
+    }
+
+    /// Returns the number of the SGI, which is also its INTID.
+    pub const fn number(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the interrupt ID of the SGI.
+    pub const fn intid(self) -> IntId {
+        IntId::sgi(self.0 as usize)
+    }
+}
+
+impl From<SgiId> for IntId {
+    fn from(sgi: SgiId) -> Self {
+        sgi.intid()
+    }
+}
+
+impl TryFrom<IntId> for SgiId {
+    type Error = GicError;
+
+    /// Returns the SGI with the ID `intid`, or [`GicError::InvalidIntId`] if
+    /// it isn't an SGI.
+    fn try_from(intid: IntId) -> Result<Self, GicError> {
+        if intid.is_sgi() {
+            Ok(Self(intid.0 as u8))
+        } else {
+            Err(GicError::InvalidIntId)
+        }
+    }
+}
+
+/// Returns the `ICC_SGI1R_EL1` value, without the INTID, targeting the CPU with
+/// the given `MPIDR_EL1` value.
+pub(crate) fn sgi1r_target(mpidr: u64) -> u64 {
+    let aff0 = mpidr & 0xff;
+    (mpidr >> 8 & 0xff) << AFF1_SHIFT
+        | (mpidr >> 16 & 0xff) << AFF2_SHIFT
+        | (aff0 / 16) << RS_SHIFT
+        | (mpidr >> 32 & 0xff) << AFF3_SHIFT
+        | 1 << (aff0 % 16)
+}
+
+/// Calls `write` with each `ICC_SGI1R_EL1` value, without the INTID, needed
+/// to reach the CPUs with the given `MPIDR_EL1` values: one for each group of
+/// 16 CPUs sharing their `Aff3.Aff2.Aff1` affinity, wherever they are in the
+/// slice.
+pub(crate) fn for_each_sgi1r(mpidrs: &[u64], mut write: impl FnMut(u64)) {
+    for (i, &mpidr) in mpidrs.iter().enumerate() {
+        let value = sgi1r_target(mpidr);
+        let group = value & SGI1R_GROUP_MASK;
+        let same_group = |&other: &u64| sgi1r_target(other) & SGI1R_GROUP_MASK == group;
+        // The first CPU of each group writes the whole group.
+        if !mpidrs[..i].iter().any(same_group) {
+            write(
+                mpidrs[i + 1..]
+                    .iter()
+                    .filter(|other| same_group(other))
+                    .fold(value, |value, &other| value | sgi1r_target(other)),
+            );
+        }
+    }
+}
+
+/// The `ICC_SGI1R_EL1` values, without the INTID, reaching a set of CPUs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SgiTargets<const N: usize> {
+    values: [u64; N],
+    len: usize,
+}
+
+impl<const N: usize> SgiTargets<N> {
+    /// Encodes the targets for the CPUs with the given `MPIDR_EL1` values,
+    /// needing one register write for each group of 16 CPUs sharing their
+    /// `Aff3.Aff2.Aff1` affinity.
+    ///
+    /// Returns [`GicError::CapacityExceeded`] if the CPUs span more than `N`
+    /// such groups.
+    pub fn new(mpidrs: impl IntoIterator<Item = u64>) -> Result<Self, GicError> {
+        let mut targets = Self {
+            values: [0; N],
+            len: 0,
+        };
+        for mpidr in mpidrs {
+            targets.add(mpidr)?;
+        }
+        Ok(targets)
+    }
+
+    /// Adds the CPU with the given `MPIDR_EL1` value.
+    fn add(&mut self, mpidr: u64) -> Result<(), GicError> {
+        let value = sgi1r_target(mpidr);
+        let group = value & SGI1R_GROUP_MASK;
+        if let Some(existing) = self.values[..self.len]
+            .iter_mut()
+            .find(|v| **v & SGI1R_GROUP_MASK == group)
+        {
+            *existing |= value;
+            return Ok(());
+        }
+        if self.len == N {
+            return Err(GicError::CapacityExceeded);
+        }
+        self.values[self.len] = value;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Returns the encoded register values.
+    pub(crate) fn values(&self) -> &[u64] {
+        &self.values[..self.len]
+    }
+}
+
+/// A set of CPUs by index, holding up to `64 * W` CPUs.
+///
+/// The index of a CPU is its GIC processor number: the CPU interface number
+/// on GICv2, and `GICR_TYPER.Processor_Number` on GICv3, which is the order of
+/// the redistributors on most systems.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CpuSet<const W: usize = 1> {
+    bits: [u64; W],
+}
+
+impl<const W: usize> CpuSet<W> {
+    /// Returns an empty set.
+    pub const fn new() -> Self {
+        Self { bits: [0; W] }
+    }
+
+    /// Returns the set with the CPU with the given index added, so sets can be
+    /// built in constants.
+    ///
+    /// Panics if the index doesn't fit the set.
+    pub const fn with(mut self, index: usize) -> Self {
+        assert!(index < 64 * W, "CPU index out of range");
+        self.bits[index / 64] |= 1 << (index % 64);
+        self
+    }
+
+    /// Returns the set of the CPUs with indices below `count`.
+    pub fn first(count: usize) -> Self {
+        (0..count.min(64 * W)).collect()
+    }
+
+    /// Returns the set of the CPUs with the given `MPIDR_EL1` values, as
+    /// mapped to indices by [`GenericArmGic::cpu_index`].
+    ///
+    /// Returns [`GicError::NoRedistributor`] if the GIC doesn't know one of
+    /// the CPUs, or [`GicError::CapacityExceeded`] if an index doesn't fit.
+    pub fn from_mpidrs(
+        gic: &impl GenericArmGic,
+        mpidrs: impl IntoIterator<Item = u64>,
+    ) -> Result<Self, GicError> {
+        let mut set = Self::new();
+        for mpidr in mpidrs {
+            let index = gic.cpu_index(mpidr).ok_or(GicError::NoRedistributor)?;
+            if !set.insert(index) {
+                return Err(GicError::CapacityExceeded);
+            }
+        }
+        Ok(set)
+    }
+
+    /// Adds the CPU with the given index, returning whether it fits the set.
+    pub fn insert(&mut self, index: usize) -> bool {
+        match self.bits.get_mut(index / 64) {
+            Some(word) => {
+                *word |= 1 << (index % 64);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the CPU with the given index.
+    pub fn remove(&mut self, index: usize) {
+        if let Some(word) = self.bits.get_mut(index / 64) {
+            *word &= !(1 << (index % 64));
+        }
+    }
+
+    /// Returns whether the CPU with the given index is in the set.
+    pub fn contains(&self, index: usize) -> bool {
+        self.bits
+            .get(index / 64)
+            .is_some_and(|word| word & 1 << (index % 64) != 0)
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    /// Returns the indices of the CPUs in the set, in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..64 * W).filter(|&index| self.contains(index))
+    }
+
+    /// Returns the GICv2 target list: one bit for each of the CPUs 0 to 7.
+    pub(crate) fn target_list(&self) -> u8 {
+        self.bits.first().map_or(0, |&word| word as u8)
+    }
+
+    /// Returns the `ICC_SGI1R_EL1` target list of the CPUs 0 to 15, taken as
+    /// `Aff0` in the cluster whose other affinity fields are zero.
+    pub(crate) fn cluster_target_list(&self) -> u16 {
+        self.bits.first().map_or(0, |&word| word as u16)
+    }
+}
+
+impl<const W: usize> Default for CpuSet<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const W: usize> FromIterator<usize> for CpuSet<W> {
+    /// Collects CPU indices into a set, dropping those that don't fit.
+    fn from_iter<T: IntoIterator<Item = usize>>(indices: T) -> Self {
+        let mut set = Self::new();
+        for index in indices {
+            set.insert(index);
+        }
+        set
+    }
+}
+
+/// An SGI a CPU raises on itself to run `handler` in IRQ context, as a
+/// softirq:
+///
+/// ```ignore
+/// static SOFTIRQ: LocalSoftirq = LocalSoftirq::new(SgiId::new(7), run_softirqs);
+///
+/// // In the NMI handler, after queueing the work:
+/// SOFTIRQ.raise(&gic);
+///
+/// // In the IRQ handler:
+/// gic.drain(|intid| {
+///     if !SOFTIRQ.dispatch(intid) {
+///         HANDLERS.dispatch(intid);
+///     }
+/// });
+/// ```
+///
+/// [`dispatch`](Self::dispatch) recognises the SGI with a single comparison,
+/// before any handler table is searched.
+#[derive(Copy, Clone, Debug)]
+pub struct LocalSoftirq {
+    sgi: SgiId,
+    handler: Handler,
+}
+
+impl LocalSoftirq {
+    /// Returns the softirq raised through `sgi`, whose handler is `handler`.
+    pub const fn new(sgi: SgiId, handler: Handler) -> Self {
+        Self { sgi, handler }
+    }
+
+    /// Returns the SGI raising the softirq.
+    pub const fn sgi(&self) -> SgiId {
+        self.sgi
+    }
+
+    /// Raises the softirq on the current CPU.
+    pub fn raise(&self, gic: &impl GenericArmGic) {
+        gic.raise_self_sgi(self.sgi);
+    }
+
+    /// Runs the handler if `intid` is the SGI of the softirq, returning
+    /// whether it did.
+    pub fn dispatch(&self, intid: IntId) -> bool {
+        if intid != self.sgi.intid() {
+            return false;
+        }
+        (self.handler)(intid);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_sgi_targets() {
+        let targets = SgiTargets::<3>::new([0x0, 0x3, 0x1_0102, 0x8000_0011]).unwrap();
+        assert_eq!(targets.values(), [0x9, 0x1_0001_0004, 0x1000_0000_0002]);
+
+        let targets = SgiTargets::<2>::new([0x0, 0x100, 0x200]);
+        assert_eq!(targets, Err(GicError::CapacityExceeded));
+    }
+
+    #[test]
+    fn test_for_each_sgi1r() {
+        let mut values = [0; 4];
+        let mut len = 0;
+        for_each_sgi1r(&[0x100, 0x0, 0x101, 0x3, 0x100], |value| {
+            values[len] = value;
+            len += 1;
+        });
+        assert_eq!(values[..len], [0x1_0003, 0x9]);
+    }
+
+    #[test]
+    fn test_cpu_set() {
+        let mut set: CpuSet<2> = [1, 3, 70, 200].into_iter().collect();
+        assert!(set.contains(70));
+        assert!(!set.contains(200));
+        assert_eq!(set.target_list(), 0b1010);
+        set.remove(3);
+        assert!(set.iter().eq([1, 70]));
+        assert!(!set.insert(128));
+        assert_eq!(CpuSet::<1>::first(3).target_list(), 0b111);
+        assert!(CpuSet::<1>::new().is_empty());
+        assert_eq!(CpuSet::<1>::new().with(0).with(2).target_list(), 0b101);
+    }
+
+    #[test]
+    fn test_sgi_id() {
+        assert_eq!(IntId::from(SgiId::new(15)), IntId::sgi(15));
+        assert_eq!(SgiId::try_from(IntId::sgi(3)), Ok(SgiId::new(3)));
+        assert_eq!(SgiId::try_from(IntId::spi(42)), Err(GicError::InvalidIntId));
+    }
+
+    #[test]
+    fn test_softirq_dispatch() {
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+        fn run(_: IntId) {
+            RUNS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let softirq = LocalSoftirq::new(SgiId::new(7), run);
+        assert!(!softirq.dispatch(IntId::sgi(6)));
+        assert!(softirq.dispatch(IntId::sgi(7)));
+        assert_eq!(RUNS.load(Ordering::Relaxed), 1);
+    }
+}
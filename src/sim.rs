@@ -0,0 +1,741 @@
+//! Simulated interrupt delivery for deterministic unit tests.
+//!
+//! Enabled by the `sim` feature. A [`Simulation`] is a fake GIC kept in
+//! memory: tests enqueue `(cpu, intid)` events, and [`Simulation::step`]
+//! delivers the next one through the same [`GenericArmGic::drain`] loop the
+//! kernel runs in its interrupt handler:
+//!
+//! ```ignore
+//! static SIM: Simulation<64, 16> = Simulation::new();
+//!
+//! let gic = SIM.gic();
+//! gic.enable_interrupt(IntId::spi(3));
+//! SIM.enqueue(1, IntId::spi(3))?;
+//! SIM.run(|intid| {
+//!     HANDLERS.dispatch(intid);
+//! });
+//! ```
+//!
+//! Delivery follows the priority rules of the GIC: the highest priority
+//! pending interrupt is acknowledged first, lowest INTID first among equals,
+//! and only if it preempts the interrupts active on its CPU. A handler calling
+//! [`step`](Simulation::step) again therefore nests a higher priority
+//! interrupt, or leaves a lower priority one pending until the outer handler
+//! returns, which makes nesting and IPI code reproducible on the host.
+//!
+//! All interrupts behave as edge-triggered, the configuration is shared by all
+//! CPUs, and the simulated CPU interfaces don't mask any priority.
+
+use core::fmt::{self, Debug, Formatter};
+use core::iter;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+#[cfg(feature = "claim-tracking")]
+use crate::claim;
+use crate::seal;
+use crate::{
+    spurious, translate_irq, CpuSet, Diagnosis, GenericArmGic, GicCapabilities, GicError,
+    HandoffState, IntId, InterruptGroup, InterruptType, IrqConfig, PriorityGrouping, SgiId, Spi,
+    TriggerMode,
+};
+
+/// Number of CPUs a simulation can deliver to, one bit each in the pending
+/// and active masks.
+const MAX_CPUS: usize = 64;
+
+/// The running priority of a CPU without active interrupts.
+const IDLE_PRIORITY: u8 = 0xff;
+
+/// A fake GIC with `N` interrupts, and a queue of up to `Q` events to deliver.
+pub struct Simulation<const N: usize, const Q: usize> {
+    enabled: [AtomicBool; N],
+    group1: [AtomicBool; N],
+    priority: [AtomicU8; N],
+    /// The CPU index each SPI is routed to.
+    target: [AtomicUsize; N],
+    /// Mask of the CPUs each interrupt is pending on.
+    pending: [AtomicU64; N],
+    /// Mask of the CPUs each interrupt is active on.
+    active: [AtomicU64; N],
+    disable_on_ack: AtomicBool,
+    /// Mask of the CPUs whose CPU interface is disabled.
+    cpu_disabled: AtomicU64,
+    /// Bits of group priority, shared by all CPUs.
+    group_bits: AtomicU8,
+    /// The CPU the interrupt handler currently runs on.
+    current_cpu: AtomicUsize,
+    /// Events as `cpu << 32 | intid`, in a ring from `head`.
+    queue: [AtomicU64; Q],
+    head: AtomicUsize,
+    len: AtomicUsize,
+}
+
+impl<const N: usize, const Q: usize> Simulation<N, Q> {
+    /// Returns a simulation with every interrupt disabled, in Group 1, at
+    /// priority 0 and routed to CPU 0.
+    pub const fn new() -> Self {
+        Self {
+            enabled: [const { AtomicBool::new(false) }; N],
+            group1: [const { AtomicBool::new(true) }; N],
+            priority: [const { AtomicU8::new(0) }; N],
+            target: [const { AtomicUsize::new(0) }; N],
+            pending: [const { AtomicU64::new(0) }; N],
+            active: [const { AtomicU64::new(0) }; N],
+            disable_on_ack: AtomicBool::new(false),
+            cpu_disabled: AtomicU64::new(0),
+            group_bits: AtomicU8::new(8),
+            current_cpu: AtomicUsize::new(0),
+            queue: [const { AtomicU64::new(0) }; Q],
+            head: AtomicUsize::new(0),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns a driver for the simulated GIC.
+    pub const fn gic(&self) -> SimGic<'_, N, Q> {
+        SimGic { sim: self }
+    }
+
+    /// Queues `intid` to be signalled to CPU `cpu`.
+    ///
+    /// Returns [`GicError::InvalidIntId`] if the simulation has no such
+    /// interrupt or CPU, or [`GicError::CapacityExceeded`] if the queue is full.
+    pub fn enqueue(&self, cpu: usize, intid: IntId) -> Result<(), GicError> {
+        if intid.0 >= N || cpu >= MAX_CPUS {
+            return Err(GicError::InvalidIntId);
+        }
+        let len = self.len.load(Ordering::Relaxed);
+        if len == Q {
+            return Err(GicError::CapacityExceeded);
+        }
+        let slot = (self.head.load(Ordering::Relaxed) + len) % Q;
+        self.queue[slot].store((cpu as u64) << 32 | intid.0 as u64, Ordering::Relaxed);
+        self.len.store(len + 1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the number of queued events.
+    pub fn queued(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Delivers the next queued event: makes its interrupt pending on its CPU
+    /// and runs the drain loop of that CPU with `handler`, which may nest
+    /// further steps.
+    ///
+    /// Returns the number of interrupts handled, or `None` if the queue was
+    /// empty.
+    pub fn step(&self, handler: impl FnMut(IntId)) -> Option<usize> {
+        let len = self.len.load(Ordering::Relaxed);
+        if len == 0 {
+            return None;
+        }
+        let head = self.head.load(Ordering::Relaxed);
+        let event = self.queue[head].load(Ordering::Relaxed);
+        self.head.store((head + 1) % Q, Ordering::Relaxed);
+        self.len.store(len - 1, Ordering::Relaxed);
+
+        let cpu = (event >> 32) as usize;
+        self.pending[event as u32 as usize].fetch_or(1 << cpu, Ordering::Relaxed);
+        let interrupted = self.current_cpu.swap(cpu, Ordering::Relaxed);
+        let handled = self.gic().drain(handler);
+        self.current_cpu.store(interrupted, Ordering::Relaxed);
+        Some(handled)
+    }
+
+    /// Delivers queued events until the queue is empty, including those
+    /// queued by the handlers.
+    ///
+    /// Returns the number of interrupts handled.
+    pub fn run(&self, mut handler: impl FnMut(IntId)) -> usize {
+        let mut handled = 0;
+        while let Some(count) = self.step(&mut handler) {
+            handled += count;
+        }
+        handled
+    }
+
+    /// Returns the CPU the simulated interrupt handler runs on, which is also
+    /// the CPU acknowledging through [`SimGic`].
+    pub fn current_cpu(&self) -> usize {
+        self.current_cpu.load(Ordering::Relaxed)
+    }
+
+    /// Sets the CPU [`SimGic`] acknowledges on outside of [`step`](Self::step).
+    pub fn set_current_cpu(&self, cpu: usize) {
+        assert!(cpu < MAX_CPUS, "CPU {} out of range", cpu);
+        self.current_cpu.store(cpu, Ordering::Relaxed);
+    }
+
+    /// Returns whether `intid` is pending on CPU `cpu`.
+    pub fn is_pending(&self, cpu: usize, intid: IntId) -> bool {
+        self.pending[intid.0].load(Ordering::Relaxed) & 1 << cpu != 0
+    }
+
+    /// Returns whether `intid` is active on CPU `cpu`.
+    pub fn is_active(&self, cpu: usize, intid: IntId) -> bool {
+        self.active[intid.0].load(Ordering::Relaxed) & 1 << cpu != 0
+    }
+
+    fn grouping(&self) -> PriorityGrouping {
+        PriorityGrouping::from_group_bits(self.group_bits.load(Ordering::Relaxed))
+    }
+
+    /// Returns the group priority of the highest priority interrupt active on
+    /// `cpu`.
+    fn running_priority(&self, cpu: usize) -> u8 {
+        (0..N)
+            .filter(|&id| self.active[id].load(Ordering::Relaxed) & 1 << cpu != 0)
+            .map(|id| {
+                self.grouping()
+                    .group_priority(self.priority[id].load(Ordering::Relaxed))
+            })
+            .min()
+            .unwrap_or(IDLE_PRIORITY)
+    }
+
+    /// Acknowledges the highest priority interrupt pending on the current CPU
+    /// that preempts its running priority, in `group` if given.
+    fn acknowledge(&self, group: Option<InterruptGroup>, count_spurious: bool) -> Option<IntId> {
+        let cpu = self.current_cpu();
+        let running = self.running_priority(cpu);
+        let disabled = self.cpu_disabled.load(Ordering::Relaxed) & 1 << cpu != 0;
+        let id = (0..N)
+            .filter(|&id| {
+                !disabled
+                    && self.pending[id].load(Ordering::Relaxed) & 1 << cpu != 0
+                    && self.enabled[id].load(Ordering::Relaxed)
+                    && group.is_none_or(|group| self.group(id) == group)
+            })
+            .min_by_key(|&id| self.priority[id].load(Ordering::Relaxed))
+            .filter(|&id| {
+                self.grouping()
+                    .group_priority(self.priority[id].load(Ordering::Relaxed))
+                    < running
+            });
+        let Some(id) = id else {
+            if count_spurious {
+                spurious::record();
+            }
+            return None;
+        };
+        self.pending[id].fetch_and(!(1 << cpu), Ordering::Relaxed);
+        self.active[id].fetch_or(1 << cpu, Ordering::Relaxed);
+        if self.disable_on_ack.load(Ordering::Relaxed) {
+            self.enabled[id].store(false, Ordering::Relaxed);
+        }
+        Some(IntId(id))
+    }
+
+    fn group(&self, id: usize) -> InterruptGroup {
+        if self.group1[id].load(Ordering::Relaxed) {
+            InterruptGroup::Group1
+        } else {
+            InterruptGroup::Group0
+        }
+    }
+
+    /// Queues an event for each of `cpus`, panicking if the queue overflows as
+    /// the trait methods doing so can't report it.
+    fn signal(&self, cpus: impl Iterator<Item = usize>, intid: IntId) {
+        for cpu in cpus {
+            self.enqueue(cpu, intid)
+                .expect("simulation queue full or interrupt out of range");
+        }
+    }
+}
+
+impl<const N: usize, const Q: usize> Default for Simulation<N, Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const Q: usize> Debug for Simulation<N, Q> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Simulation")
+            .field("current_cpu", &self.current_cpu())
+            .field("queued", &self.queued())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The driver of a [`Simulation`], standing in for [`GicV2`](crate::GicV2) or
+/// [`GicV3`](crate::GicV3) in code generic over [`GenericArmGic`].
+#[derive(Copy, Clone, Debug)]
+pub struct SimGic<'a, const N: usize, const Q: usize> {
+    sim: &'a Simulation<N, Q>,
+}
+
+impl<const N: usize, const Q: usize> SimGic<'_, N, Q> {
+    fn check(&self, intid: IntId) -> usize {
+        assert!(intid.0 < N, "{:?} out of range of the simulation", intid);
+        intid.0
+    }
+}
+
+impl<const N: usize, const Q: usize> GenericArmGic for SimGic<'_, N, Q> {
+    fn init_primary(&mut self) -> Result<(), GicError> {
+        Ok(())
+    }
+
+    fn init_primary_handoff(&mut self) -> Result<(), GicError> {
+        Ok(())
+    }
+
+    fn early_init(&mut self) -> Result<(), GicError> {
+        Ok(())
+    }
+
+    fn finish_init(&mut self) {}
+
+    fn translate(&self, id: usize, int_type: InterruptType) -> Option<IntId> {
+        let intid = translate_irq(id, int_type)?;
+        (intid < N).then_some(IntId(intid))
+    }
+
+    fn per_cpu_init(&mut self) {}
+
+    fn set_trigger(&self, intid: IntId, trigger: TriggerMode) -> Result<(), GicError> {
+        seal::check()?;
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        self.check(intid);
+        if intid.is_sgi() && trigger == TriggerMode::Level {
+            return Err(GicError::ReadOnlyConfig);
+        }
+        Ok(())
+    }
+
+    fn reconfigure_trigger(&self, intid: IntId, trigger: TriggerMode) -> Result<(), GicError> {
+        seal::check()?;
+        let id = self.check(intid);
+        let enabled = self.sim.enabled[id].swap(false, Ordering::Relaxed);
+        if intid.is_private() {
+            self.sim.pending[id].fetch_and(!(1 << self.sim.current_cpu()), Ordering::Relaxed);
+        } else {
+            self.sim.pending[id].store(0, Ordering::Relaxed);
+        }
+        let result = self.set_trigger(intid, trigger);
+        self.sim.enabled[id].store(enabled, Ordering::Relaxed);
+        result
+    }
+
+    fn set_group(&self, intid: IntId, group: InterruptGroup) {
+        if seal::refuses(intid, "grouping") {
+            return;
+        }
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        self.sim.group1[self.check(intid)]
+            .store(group == InterruptGroup::Group1, Ordering::Relaxed);
+    }
+
+    fn set_priority(&self, intid: IntId, priority: u8) {
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        self.sim.priority[self.check(intid)].store(priority, Ordering::Relaxed);
+    }
+
+    fn add_spis(&self, first: IntId, count: usize, config: IrqConfig) -> Result<(), GicError> {
+        let end = first.0.checked_add(count).ok_or(GicError::InvalidIntId)?;
+        if !first.is_spi() || end > N {
+            return Err(GicError::InvalidIntId);
+        }
+        seal::check()?;
+        for id in first.0..end {
+            self.sim.enabled[id].store(false, Ordering::Relaxed);
+            self.sim.pending[id].store(0, Ordering::Relaxed);
+            self.sim.active[id].store(0, Ordering::Relaxed);
+            self.configure(IntId(id), config)?;
+        }
+        Ok(())
+    }
+
+    /// Routes the SPI to the CPU index `Aff0` of `affinity`.
+    fn set_affinity(&self, intid: IntId, affinity: u64) {
+        if seal::refuses_routing(intid) {
+            return;
+        }
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "reconfigured");
+        self.sim.target[self.check(intid)].store((affinity & 0xff) as usize, Ordering::Relaxed);
+    }
+
+    fn enable_fiq_split(&self) {}
+
+    fn cpu_interface_disable(&self) {
+        self.sim
+            .cpu_disabled
+            .fetch_or(1 << self.sim.current_cpu(), Ordering::Relaxed);
+    }
+
+    fn cpu_interface_enable(&self) {
+        self.sim
+            .cpu_disabled
+            .fetch_and(!(1 << self.sim.current_cpu()), Ordering::Relaxed);
+    }
+
+    /// The simulation has no minimum binary point, and a single grouping for
+    /// all CPUs.
+    fn set_priority_grouping(&self, grouping: PriorityGrouping) -> PriorityGrouping {
+        self.sim
+            .group_bits
+            .store(grouping.group_bits(), Ordering::Relaxed);
+        grouping
+    }
+
+    fn priority_grouping(&self) -> PriorityGrouping {
+        self.sim.grouping()
+    }
+
+    fn enable_interrupt(&self, intid: IntId) {
+        #[cfg(feature = "claim-tracking")]
+        claim::check_unclaimed(intid, "enabled");
+        self.sim.enabled[self.check(intid)].store(true, Ordering::Relaxed);
+    }
+
+    fn disable_interrupt(&self, intid: IntId) {
+        self.sim.enabled[self.check(intid)].store(false, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "claim-tracking")]
+    fn enable_interrupt_claimed(&self, intid: IntId, owner: &'static str) {
+        claim::claim(intid, owner);
+        self.sim.enabled[self.check(intid)].store(true, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "claim-tracking")]
+    fn release_interrupt(&self, intid: IntId) -> Option<&'static str> {
+        self.sim.enabled[self.check(intid)].store(false, Ordering::Relaxed);
+        claim::release(intid)
+    }
+
+    fn get_and_acknowledge_interrupt(&self) -> Option<IntId> {
+        self.sim.acknowledge(None, true)
+    }
+
+    fn end_interrupt(&self, intid: IntId) {
+        let cpu = self.sim.current_cpu();
+        let id = self.check(intid);
+        self.sim.active[id].fetch_and(!(1 << cpu), Ordering::Relaxed);
+        if self.sim.disable_on_ack.load(Ordering::Relaxed) {
+            self.sim.enabled[id].store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn drain(&self, mut handler: impl FnMut(IntId)) -> usize {
+        let mut count = 0;
+        while let Some(intid) = self.sim.acknowledge(None, count == 0) {
+            handler(intid);
+            self.end_interrupt(intid);
+            count += 1;
+        }
+        count
+    }
+
+    fn set_disable_on_ack(&mut self, enable: bool) {
+        self.sim.disable_on_ack.store(enable, Ordering::Relaxed);
+    }
+
+    fn get_and_acknowledge_group_interrupt(&self, group: InterruptGroup) -> Option<IntId> {
+        self.sim.acknowledge(Some(group), true)
+    }
+
+    fn end_group_interrupt(&self, intid: IntId, _group: InterruptGroup) {
+        self.end_interrupt(intid);
+    }
+
+    /// Private interrupts are deactivated on the current CPU, and shared ones
+    /// on every CPU.
+    fn deactivate_unacked(&self, intid: IntId) -> Result<(), GicError> {
+        if intid.is_special() || intid.0 >= N {
+            return Err(GicError::InvalidIntId);
+        }
+        let cpus = if intid.is_private() {
+            1 << self.sim.current_cpu()
+        } else {
+            u64::MAX
+        };
+        self.sim.active[intid.0].fetch_and(!cpus, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Uses `Aff0` as the CPU index.
+    fn cpu_index(&self, mpidr: u64) -> Option<usize> {
+        let aff0 = (mpidr & 0xff) as usize;
+        (mpidr & 0xff_00ff_ff00 == 0 && aff0 < MAX_CPUS).then_some(aff0)
+    }
+
+    /// Queues the SGI for each target CPU.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue of the simulation overflows.
+    fn send_sgi<const W: usize>(&self, sgi: SgiId, targets: &CpuSet<W>) {
+        self.sim.signal(targets.iter(), sgi.into());
+    }
+
+    /// Returns the first CPU of the set, with `Aff0` as the CPU index, as SPIs
+    /// are delivered to a single CPU.
+    fn affinity_for<const W: usize>(&self, cpus: &CpuSet<W>) -> Option<u64> {
+        cpus.iter()
+            .next()
+            .filter(|&cpu| cpu < MAX_CPUS)
+            .map(|cpu| cpu as u64)
+    }
+
+    fn raise_self_sgi(&self, sgi: SgiId) {
+        self.sim
+            .signal(iter::once(self.sim.current_cpu()), sgi.into());
+    }
+
+    fn capabilities(&self) -> GicCapabilities {
+        GicCapabilities {
+            spis: N.saturating_sub(IntId::SPI_START),
+            ..GicCapabilities::default()
+        }
+    }
+
+    fn dump_state(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(
+            out,
+            "simulation: cpu={} queued={}",
+            self.sim.current_cpu(),
+            self.sim.queued()
+        )?;
+        for id in 0..N {
+            let pending = self.sim.pending[id].load(Ordering::Relaxed);
+            let active = self.sim.active[id].load(Ordering::Relaxed);
+            if pending != 0 || active != 0 {
+                writeln!(
+                    out,
+                    "{:?}: enabled={} priority={:#04x} pending={:#x} active={:#x}",
+                    IntId(id),
+                    self.sim.enabled[id].load(Ordering::Relaxed),
+                    self.sim.priority[id].load(Ordering::Relaxed),
+                    pending,
+                    active
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn inspect<const M: usize>(&self) -> HandoffState<M> {
+        let mut state = HandoffState::new(0);
+        for id in 0..N.min(IntId::GIC_MAX_IRQ) {
+            let bit = 1 << (id % 32);
+            if self.sim.enabled[id].load(Ordering::Relaxed) {
+                state.enabled[id / 32] |= bit;
+                if IntId(id).is_spi() {
+                    state.push_route(
+                        IntId(id),
+                        self.sim.target[id].load(Ordering::Relaxed) as u64,
+                    );
+                }
+            }
+            if self.sim.group1[id].load(Ordering::Relaxed) {
+                state.group1[id / 32] |= bit;
+            }
+        }
+        state
+    }
+
+    fn diagnose(&self, intid: IntId) -> Result<Diagnosis, GicError> {
+        let id = intid.0;
+        if id >= N {
+            return Err(GicError::InvalidIntId);
+        }
+        let cpu = self.sim.current_cpu();
+        Ok(Diagnosis {
+            intid,
+            distributor_enabled: true,
+            redistributor_awake: None,
+            enabled: self.sim.enabled[id].load(Ordering::Relaxed),
+            pending: self.sim.is_pending(cpu, intid),
+            active: self.sim.is_active(cpu, intid),
+            group1: self.sim.group1[id].load(Ordering::Relaxed),
+            group_enabled: true,
+            priority: self.sim.priority[id].load(Ordering::Relaxed),
+            priority_mask: IDLE_PRIORITY,
+            running_priority: self.sim.running_priority(cpu),
+            routed_here: intid.is_private() || self.sim.target[id].load(Ordering::Relaxed) == cpu,
+        })
+    }
+
+    /// Queues the SPI for the CPU it is routed to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue of the simulation overflows.
+    fn trigger_spi(&self, spi: Spi) {
+        let intid = spi.intid();
+        let cpu = self.sim.target[self.check(intid)].load(Ordering::Relaxed);
+        self.sim.signal(core::iter::once(cpu), intid);
+    }
+
+    fn clear_spi(&self, spi: Spi) {
+        let intid = spi.intid();
+        self.sim.pending[self.check(intid)].store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BoardIrq;
+
+    #[test]
+    fn test_board_config() {
+        static SIM: Simulation<64, 8> = Simulation::new();
+        static BOARD: [BoardIrq; 2] = [
+            BoardIrq {
+                intid: IntId::spi(3),
+                trigger: TriggerMode::Level,
+                priority: 0x40,
+                cpus: CpuSet::new().with(2),
+            },
+            BoardIrq {
+                intid: IntId::ppi(14),
+                trigger: TriggerMode::Level,
+                priority: 0x20,
+                cpus: CpuSet::new(),
+            },
+        ];
+
+        let gic = SIM.gic();
+        gic.apply_board_config(&BOARD).unwrap();
+        let spi = gic.diagnose(IntId::spi(3)).unwrap();
+        assert_eq!(spi.priority, 0x40);
+        assert!(!spi.enabled && spi.group1 && !spi.routed_here);
+        SIM.set_current_cpu(2);
+        assert!(gic.diagnose(IntId::spi(3)).unwrap().routed_here);
+        assert_eq!(gic.diagnose(IntId::ppi(14)).unwrap().priority, 0x20);
+    }
+
+    #[test]
+    fn test_reconfigure_trigger() {
+        static SIM: Simulation<64, 8> = Simulation::new();
+        let gic = SIM.gic();
+        gic.enable_interrupt(IntId::spi(4));
+        SIM.pending[IntId::spi(4).0].store(0b11, Ordering::Relaxed);
+        gic.reconfigure_trigger(IntId::spi(4), TriggerMode::Level)
+            .unwrap();
+        assert!(!SIM.is_pending(1, IntId::spi(4)));
+        assert!(gic.diagnose(IntId::spi(4)).unwrap().enabled);
+        assert_eq!(
+            gic.reconfigure_trigger(IntId::sgi(1), TriggerMode::Level),
+            Err(GicError::ReadOnlyConfig)
+        );
+    }
+
+    #[test]
+    fn test_raise_self_sgi() {
+        static SIM: Simulation<64, 8> = Simulation::new();
+        let gic = SIM.gic();
+        gic.enable_interrupt(IntId::sgi(3));
+        SIM.set_current_cpu(2);
+        gic.raise_self_sgi(SgiId::new(3));
+        assert_eq!(
+            SIM.step(|intid| {
+                assert_eq!(intid, IntId::sgi(3));
+                assert_eq!(SIM.current_cpu(), 2);
+            }),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_cpu_interface_disable() {
+        static SIM: Simulation<64, 8> = Simulation::new();
+        let gic = SIM.gic();
+        gic.enable_interrupt(IntId::spi(0));
+        gic.cpu_interface_disable();
+        SIM.enqueue(0, IntId::spi(0)).unwrap();
+        assert_eq!(SIM.step(|_| {}), Some(0));
+        assert!(SIM.is_pending(0, IntId::spi(0)));
+        gic.cpu_interface_enable();
+        assert_eq!(gic.drain(|_| {}), 1);
+    }
+
+    #[test]
+    fn test_disable_on_ack() {
+        static SIM: Simulation<64, 8> = Simulation::new();
+        let mut gic = SIM.gic();
+        gic.set_disable_on_ack(true);
+        gic.enable_interrupt(IntId::spi(4));
+        SIM.enqueue(0, IntId::spi(4)).unwrap();
+        let masked = |intid: IntId| assert!(!SIM.enabled[intid.0].load(Ordering::Relaxed));
+        assert_eq!(SIM.step(masked), Some(1));
+        assert!(SIM.enabled[IntId::spi(4).0].load(Ordering::Relaxed));
+        // It is delivered again after the end of the first delivery.
+        SIM.enqueue(0, IntId::spi(4)).unwrap();
+        assert_eq!(SIM.step(masked), Some(1));
+    }
+
+    #[test]
+    fn test_deactivate_unacked() {
+        static SIM: Simulation<64, 8> = Simulation::new();
+        let gic = SIM.gic();
+        SIM.active[IntId::spi(5).0].store(1 << 3, Ordering::Relaxed);
+        SIM.active[IntId::ppi(2).0].store(0b11, Ordering::Relaxed);
+        gic.deactivate_unacked(IntId::spi(5)).unwrap();
+        gic.deactivate_unacked(IntId::ppi(2)).unwrap();
+        assert!(!SIM.is_active(3, IntId::spi(5)));
+        assert!(!SIM.is_active(0, IntId::ppi(2)));
+        assert!(SIM.is_active(1, IntId::ppi(2)));
+        assert_eq!(
+            gic.deactivate_unacked(IntId::from(1023)),
+            Err(GicError::InvalidIntId)
+        );
+    }
+
+    #[test]
+    fn test_nested_delivery() {
+        static SIM: Simulation<64, 8> = Simulation::new();
+        static ORDER: [AtomicUsize; 4] = [const { AtomicUsize::new(0) }; 4];
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        fn record(intid: IntId) -> usize {
+            let n = COUNT.fetch_add(1, Ordering::Relaxed);
+            ORDER[n].store(intid.0, Ordering::Relaxed);
+            n
+        }
+
+        let gic = SIM.gic();
+        for (intid, priority) in [
+            (IntId::spi(0), 0x80),
+            (IntId::spi(1), 0x40),
+            (IntId::spi(2), 0xa0),
+        ] {
+            gic.set_priority(intid, priority);
+            gic.enable_interrupt(intid);
+        }
+        SIM.enqueue(1, IntId::spi(0)).unwrap();
+
+        let handled = SIM.run(|intid| {
+            record(intid);
+            if intid == IntId::spi(0) {
+                assert_eq!(SIM.current_cpu(), 1);
+                // A higher priority interrupt nests, a lower priority one
+                // waits for the end of this one.
+                SIM.enqueue(1, IntId::spi(2)).unwrap();
+                assert_eq!(SIM.step(|_| {}), Some(0));
+                SIM.enqueue(1, IntId::spi(1)).unwrap();
+                assert_eq!(
+                    SIM.step(|intid| {
+                        record(intid);
+                    }),
+                    Some(1)
+                );
+            }
+        });
+
+        assert_eq!(handled, 2);
+        assert_eq!(COUNT.load(Ordering::Relaxed), 3);
+        let order = ORDER.each_ref().map(|id| id.load(Ordering::Relaxed));
+        assert_eq!(order[..3], [32, 33, 34]);
+        assert_eq!(SIM.current_cpu(), 0);
+        assert!(!SIM.is_active(1, IntId::spi(0)));
+    }
+}
@@ -0,0 +1,250 @@
+//! Remote function calls between CPUs, signalled by an SGI.
+//!
+//! [`SmpCall::smp_call`] asks other CPUs to run a function, named by a number
+//! the kernel assigns, with a word of payload. The request goes through the
+//! mailbox of each target, which the kernel provides as a [`CallMailbox`] so
+//! it can live in per-CPU data, and the SGI of the [`SmpCall`] tells the
+//! targets to look. The IRQ handler of each CPU passes the SGI to
+//! [`SmpCall::dispatch`], which runs the call and acknowledges it, and the
+//! caller may wait for the acknowledgements:
+//!
+//! ```ignore
+//! static CALLS: SmpCall<AtomicMailbox<8>, MpidrAff0, 8> =
+//!     SmpCall::new(SgiId::new(1), AtomicMailbox::new(), run_call);
+//!
+//! fn run_call(fn_id: u32, payload: usize) {
+//!     match fn_id {
+//!         FLUSH_TLB => flush_tlb(payload),
+//!         _ => {}
+//!     }
+//! }
+//!
+//! CALLS.smp_call(&gic, &CpuSet::first(4), FLUSH_TLB, asid, true)?;
+//!
+//! // In the IRQ handler of every CPU:
+//! gic.drain(|intid| {
+//!     if !CALLS.dispatch(intid) {
+//!         HANDLERS.dispatch(intid);
+//!     }
+//! });
+//! ```
+//!
+//! Each CPU has a single call in flight: a caller first waits for the
+//! previous call to a target to complete. Callers must not have IRQs masked,
+//! or two CPUs calling each other wait for each other forever.
+
+use core::hint::spin_loop;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+use crate::{CpuIndex, CpuSet, GenericArmGic, GicError, IntId, SgiId};
+
+/// A request for a CPU to run a function.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RemoteCall {
+    /// The function to run, as numbered by the kernel.
+    pub fn_id: u32,
+    /// The argument of the function.
+    pub payload: usize,
+}
+
+/// The mailboxes of the CPUs, each holding the call a CPU is to run next.
+///
+/// [`SmpCall`] never has two calls in flight to the same CPU, and the SGI
+/// makes the call stored visible to its target, so an implementation only
+/// needs to store one call per CPU.
+pub trait CallMailbox {
+    /// Stores `call` in the mailbox of the CPU with index `cpu`.
+    fn put(&self, cpu: usize, call: RemoteCall);
+
+    /// Returns the call last stored in the mailbox of the CPU with index
+    /// `cpu`.
+    fn get(&self, cpu: usize) -> RemoteCall;
+}
+
+/// Mailboxes for up to `N` CPUs, in a static.
+#[derive(Debug)]
+pub struct AtomicMailbox<const N: usize> {
+    fn_ids: [AtomicU32; N],
+    payloads: [AtomicUsize; N],
+}
+
+impl<const N: usize> AtomicMailbox<N> {
+    /// Returns empty mailboxes.
+    pub const fn new() -> Self {
+        Self {
+            fn_ids: [const { AtomicU32::new(0) }; N],
+            payloads: [const { AtomicUsize::new(0) }; N],
+        }
+    }
+}
+
+impl<const N: usize> Default for AtomicMailbox<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> CallMailbox for AtomicMailbox<N> {
+    fn put(&self, cpu: usize, call: RemoteCall) {
+        self.fn_ids[cpu].store(call.fn_id, Ordering::Relaxed);
+        self.payloads[cpu].store(call.payload, Ordering::Relaxed);
+    }
+
+    fn get(&self, cpu: usize) -> RemoteCall {
+        RemoteCall {
+            fn_id: self.fn_ids[cpu].load(Ordering::Relaxed),
+            payload: self.payloads[cpu].load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Remote calls to up to `N` CPUs through the mailboxes `M`, each CPU finding
+/// its own by the [`CpuIndex`] `I`.
+///
+/// The indices `I` returns must be those the GIC gives the CPUs in a
+/// [`CpuSet`].
+pub struct SmpCall<M, I, const N: usize> {
+    sgi: SgiId,
+    mailbox: M,
+    handler: fn(u32, usize),
+    /// Whether each CPU has a call in flight.
+    busy: [AtomicBool; N],
+    /// Number of calls each CPU completed.
+    completed: [AtomicUsize; N],
+    index: PhantomData<I>,
+}
+
+impl<M: CallMailbox, I: CpuIndex, const N: usize> SmpCall<M, I, N> {
+    /// Returns remote calls signalled through `sgi`, which run `handler` with
+    /// the function number and payload of each call.
+    pub const fn new(sgi: SgiId, mailbox: M, handler: fn(u32, usize)) -> Self {
+        Self {
+            sgi,
+            mailbox,
+            handler,
+            busy: [const { AtomicBool::new(false) }; N],
+            completed: [const { AtomicUsize::new(0) }; N],
+            index: PhantomData,
+        }
+    }
+
+    /// Returns the SGI signalling the calls.
+    pub const fn sgi(&self) -> SgiId {
+        self.sgi
+    }
+
+    /// Runs the function `fn_id` with `payload` on the CPUs of `targets`,
+    /// waiting for all of them to complete it if `wait` is set.
+    ///
+    /// The current CPU runs the function directly if it is a target, after
+    /// signalling the others.
+    ///
+    /// Returns [`GicError::CapacityExceeded`], without calling any CPU, if a
+    /// target has an index of `N` or more.
+    pub fn smp_call<const W: usize>(
+        &self,
+        gic: &impl GenericArmGic,
+        targets: &CpuSet<W>,
+        fn_id: u32,
+        payload: usize,
+        wait: bool,
+    ) -> Result<(), GicError> {
+        if targets.iter().any(|cpu| cpu >= N) {
+            return Err(GicError::CapacityExceeded);
+        }
+        let call = RemoteCall { fn_id, payload };
+        let current = I::cpu_index();
+        let mut remote = CpuSet::<W>::new();
+        let mut completed = [0; N];
+        // The mailboxes are claimed in index order, so concurrent callers
+        // can't wait for each other.
+        for cpu in targets.iter().filter(|&cpu| cpu != current) {
+            while self.busy[cpu]
+                .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                spin_loop();
+            }
+            completed[cpu] = self.completed[cpu].load(Ordering::Relaxed);
+            self.mailbox.put(cpu, call);
+            // Publishes the call to `dispatch`, which reads `busy` first.
+            self.busy[cpu].store(true, Ordering::Release);
+            remote.insert(cpu);
+        }
+        if !remote.is_empty() {
+            gic.send_sgi(self.sgi, &remote);
+        }
+        if targets.contains(current) {
+            (self.handler)(fn_id, payload);
+        }
+        if wait {
+            for cpu in remote.iter() {
+                while self.completed[cpu].load(Ordering::Acquire) == completed[cpu] {
+                    spin_loop();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the call in the mailbox of the current CPU and acknowledges it if
+    /// `intid` is the SGI of the calls, returning whether it was.
+    pub fn dispatch(&self, intid: IntId) -> bool {
+        if intid != self.sgi.intid() {
+            return false;
+        }
+        let cpu = I::cpu_index();
+        if cpu < N && self.busy[cpu].load(Ordering::Acquire) {
+            let call = self.mailbox.get(cpu);
+            (self.handler)(call.fn_id, call.payload);
+            self.completed[cpu].fetch_add(1, Ordering::Release);
+            self.busy[cpu].store(false, Ordering::Release);
+        }
+        true
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::Simulation;
+
+    static SIM: Simulation<64, 8> = Simulation::new();
+    static SUM: AtomicUsize = AtomicUsize::new(0);
+    static CALLS: SmpCall<AtomicMailbox<4>, SimIndex, 4> =
+        SmpCall::new(SgiId::new(1), AtomicMailbox::new(), add);
+
+    struct SimIndex;
+
+    impl CpuIndex for SimIndex {
+        fn cpu_index() -> usize {
+            SIM.current_cpu()
+        }
+    }
+
+    fn add(fn_id: u32, payload: usize) {
+        SUM.fetch_add(fn_id as usize * payload, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_smp_call() {
+        let gic = SIM.gic();
+        gic.enable_interrupt(IntId::sgi(1));
+        CALLS
+            .smp_call(&gic, &CpuSet::<1>::first(3), 2, 5, false)
+            .unwrap();
+        // The current CPU ran it right away.
+        assert_eq!(SUM.load(Ordering::Relaxed), 10);
+        assert_eq!(SIM.run(|intid| assert!(CALLS.dispatch(intid))), 2);
+        assert_eq!(SUM.load(Ordering::Relaxed), 30);
+        assert_eq!(CALLS.completed[1].load(Ordering::Relaxed), 1);
+        assert!(!CALLS.busy[2].load(Ordering::Relaxed));
+
+        assert_eq!(
+            CALLS.smp_call(&gic, &CpuSet::<1>::new().with(4), 1, 1, false),
+            Err(GicError::CapacityExceeded)
+        );
+        assert!(!CALLS.dispatch(IntId::sgi(2)));
+    }
+}
@@ -0,0 +1,66 @@
+//! Statistics of spurious interrupts.
+//!
+//! An acknowledge returning INTID 1023 found nothing to handle. When an
+//! interrupt exception led to it, the interrupt was usually disabled, masked
+//! or taken by another CPU between being signalled and acknowledged, so a high
+//! rate of them points at a race between masking and acknowledging. The driver
+//! counts them on all CPUs, and calls the callback set with
+//! [`set_spurious_callback`] for each.
+//!
+//! [`drain`](crate::GenericArmGic::drain) only counts its first acknowledge,
+//! as the one ending the loop is expected to find nothing.
+
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The callback, as a `fn()` pointer, or null.
+static CALLBACK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Returns the number of spurious interrupts acknowledged so far, on all CPUs.
+pub fn spurious_count() -> usize {
+    COUNT.load(Ordering::Relaxed)
+}
+
+/// Sets the function called on the CPU acknowledging each spurious interrupt,
+/// or removes it with `None`.
+///
+/// The callback runs in the interrupt handler, so it must not block.
+pub fn set_spurious_callback(callback: Option<fn()>) {
+    let callback = callback.map_or(ptr::null_mut(), |callback| callback as *mut ());
+    CALLBACK.store(callback, Ordering::Release);
+}
+
+/// Records that an acknowledge returned the spurious INTID.
+pub(crate) fn record() {
+    COUNT.fetch_add(1, Ordering::Relaxed);
+    let callback = CALLBACK.load(Ordering::Acquire);
+    if !callback.is_null() {
+        // SAFETY: Only `fn()` pointers are stored in `CALLBACK`.
+        let callback = unsafe { core::mem::transmute::<*mut (), fn()>(callback) };
+        callback();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_call() {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_spurious_callback() {
+        let before = spurious_count();
+        set_spurious_callback(Some(count_call));
+        record();
+        set_spurious_callback(None);
+        record();
+        assert_eq!(spurious_count() - before, 2);
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    }
+}
@@ -1,3 +1,20 @@
+//! Access to aarch64 system registers, and to those of the GIC CPU interface
+//! that are banked by exception level.
+//!
+//! The registers are named after their aarch64 encodings throughout the
+//! driver. When built for 32-bit Arm, the macros access the AArch32 `CP15`
+//! encodings of the same registers instead, listed by [`cp15`], so that the
+//! driver also runs at AArch32 EL1 and above.
+
+#[cfg(not(target_arch = "arm"))]
+use aarch64_cpu::registers::CurrentEL;
+#[cfg(not(target_arch = "arm"))]
+use tock_registers::interfaces::Readable;
+
+use crate::fence;
+
+/// Reads the value of the given aarch64 system register.
+#[cfg(not(target_arch = "arm"))]
 macro_rules! read_sysreg {
     ($name:ident) => {
         {
@@ -11,9 +28,33 @@ macro_rules! read_sysreg {
         }
     }
 }
+
+/// Reads the value of the given aarch64 system register, through its
+/// AArch32 encoding.
+///
+/// A 64-bit register split into two AArch32 registers is read as
+/// `low : high`.
+#[cfg(target_arch = "arm")]
+macro_rules! read_sysreg {
+    ($name:ident) => {
+        {
+            let value: u32;
+            ::core::arch::asm!(
+                concat!("mrc ", $crate::sysregs::cp15!($name)),
+                value = out(reg) value,
+                options(nomem, nostack),
+            );
+            u64::from(value)
+        }
+    };
+    ($low:ident : $high:ident) => {
+        read_sysreg!($high) << 32 | read_sysreg!($low)
+    };
+}
 pub(crate) use read_sysreg;
 
 /// Writes the given value to the given aarch64 system register.
+#[cfg(not(target_arch = "arm"))]
 macro_rules! write_sysreg {
     ($name:ident, $value:expr) => {
         {
@@ -26,4 +67,428 @@ macro_rules! write_sysreg {
         }
     }
 }
+
+/// Writes the given value to the given aarch64 system register, through its
+/// AArch32 encoding.
+///
+/// `ICC_SGI1R` is 64 bits wide in AArch32 too, and written with `MCRR`. A
+/// 64-bit register split into two AArch32 registers is written as
+/// `low : high`.
+#[cfg(target_arch = "arm")]
+macro_rules! write_sysreg {
+    (icc_sgi1r_el1, $value:expr) => {
+        {
+            let v: u64 = $value;
+            ::core::arch::asm!(
+                "mcrr p15, 0, {low}, {high}, c12",
+                low = in(reg) v as u32,
+                high = in(reg) (v >> 32) as u32,
+                options(nomem, nostack),
+            )
+        }
+    };
+    ($name:ident, $value:expr) => {
+        {
+            let v: u64 = $value;
+            ::core::arch::asm!(
+                concat!("mcr ", $crate::sysregs::cp15!($name)),
+                value = in(reg) v as u32,
+                options(nomem, nostack),
+            )
+        }
+    };
+    ($low:ident : $high:ident, $value:expr) => {
+        {
+            let v: u64 = $value;
+            write_sysreg!($low, v & 0xffff_ffff);
+            write_sysreg!($high, v >> 32);
+        }
+    };
+}
 pub(crate) use write_sysreg;
+
+/// Returns the `MRC`/`MCR` operands of the AArch32 register holding the given
+/// aarch64 system register, `{value}` standing for the general purpose
+/// register.
+///
+/// `ID_PFR1` and the halves of the list registers, `ICH_LR<n>` and
+/// `ICH_LRC<n>`, have no aarch64 counterpart and go by their AArch32 names.
+#[cfg(target_arch = "arm")]
+macro_rules! cp15 {
+    (ctr_el0) => {
+        "p15, 0, {value}, c0, c0, 1"
+    };
+    (mpidr_el1) => {
+        "p15, 0, {value}, c0, c0, 5"
+    };
+    (id_pfr1) => {
+        "p15, 0, {value}, c0, c1, 1"
+    };
+    (icc_pmr_el1) => {
+        "p15, 0, {value}, c4, c6, 0"
+    };
+    (icc_iar0_el1) => {
+        "p15, 0, {value}, c12, c8, 0"
+    };
+    (icc_eoir0_el1) => {
+        "p15, 0, {value}, c12, c8, 1"
+    };
+    (icc_dir_el1) => {
+        "p15, 0, {value}, c12, c11, 1"
+    };
+    (icc_rpr_el1) => {
+        "p15, 0, {value}, c12, c11, 3"
+    };
+    (icc_iar1_el1) => {
+        "p15, 0, {value}, c12, c12, 0"
+    };
+    (icc_eoir1_el1) => {
+        "p15, 0, {value}, c12, c12, 1"
+    };
+    (icc_hppir1_el1) => {
+        "p15, 0, {value}, c12, c12, 2"
+    };
+    (icc_bpr1_el1) => {
+        "p15, 0, {value}, c12, c12, 3"
+    };
+    (icc_ctlr_el1) => {
+        "p15, 0, {value}, c12, c12, 4"
+    };
+    (icc_sre_el1) => {
+        "p15, 0, {value}, c12, c12, 5"
+    };
+    (icc_igrpen0_el1) => {
+        "p15, 0, {value}, c12, c12, 6"
+    };
+    (icc_igrpen1_el1) => {
+        "p15, 0, {value}, c12, c12, 7"
+    };
+    // ICC_HSRE.
+    (icc_sre_el2) => {
+        "p15, 4, {value}, c12, c9, 5"
+    };
+    // ICC_MCTLR, ICC_MSRE and ICC_MGRPEN1.
+    (icc_ctlr_el3) => {
+        "p15, 6, {value}, c12, c12, 4"
+    };
+    (icc_sre_el3) => {
+        "p15, 6, {value}, c12, c12, 5"
+    };
+    (icc_igrpen1_el3) => {
+        "p15, 6, {value}, c12, c12, 7"
+    };
+    // HCR.
+    (hcr_el2) => {
+        "p15, 4, {value}, c1, c1, 0"
+    };
+    (ich_hcr_el2) => {
+        "p15, 4, {value}, c12, c11, 0"
+    };
+    (ich_vtr_el2) => {
+        "p15, 4, {value}, c12, c11, 1"
+    };
+    (ich_elrsr_el2) => {
+        "p15, 4, {value}, c12, c11, 5"
+    };
+    (ich_vmcr_el2) => {
+        "p15, 4, {value}, c12, c11, 7"
+    };
+    (ich_lr0) => {
+        "p15, 4, {value}, c12, c12, 0"
+    };
+    (ich_lr1) => {
+        "p15, 4, {value}, c12, c12, 1"
+    };
+    (ich_lr2) => {
+        "p15, 4, {value}, c12, c12, 2"
+    };
+    (ich_lr3) => {
+        "p15, 4, {value}, c12, c12, 3"
+    };
+    (ich_lr4) => {
+        "p15, 4, {value}, c12, c12, 4"
+    };
+    (ich_lr5) => {
+        "p15, 4, {value}, c12, c12, 5"
+    };
+    (ich_lr6) => {
+        "p15, 4, {value}, c12, c12, 6"
+    };
+    (ich_lr7) => {
+        "p15, 4, {value}, c12, c12, 7"
+    };
+    (ich_lr8) => {
+        "p15, 4, {value}, c12, c13, 0"
+    };
+    (ich_lr9) => {
+        "p15, 4, {value}, c12, c13, 1"
+    };
+    (ich_lr10) => {
+        "p15, 4, {value}, c12, c13, 2"
+    };
+    (ich_lr11) => {
+        "p15, 4, {value}, c12, c13, 3"
+    };
+    (ich_lr12) => {
+        "p15, 4, {value}, c12, c13, 4"
+    };
+    (ich_lr13) => {
+        "p15, 4, {value}, c12, c13, 5"
+    };
+    (ich_lr14) => {
+        "p15, 4, {value}, c12, c13, 6"
+    };
+    (ich_lr15) => {
+        "p15, 4, {value}, c12, c13, 7"
+    };
+    (ich_lrc0) => {
+        "p15, 4, {value}, c12, c14, 0"
+    };
+    (ich_lrc1) => {
+        "p15, 4, {value}, c12, c14, 1"
+    };
+    (ich_lrc2) => {
+        "p15, 4, {value}, c12, c14, 2"
+    };
+    (ich_lrc3) => {
+        "p15, 4, {value}, c12, c14, 3"
+    };
+    (ich_lrc4) => {
+        "p15, 4, {value}, c12, c14, 4"
+    };
+    (ich_lrc5) => {
+        "p15, 4, {value}, c12, c14, 5"
+    };
+    (ich_lrc6) => {
+        "p15, 4, {value}, c12, c14, 6"
+    };
+    (ich_lrc7) => {
+        "p15, 4, {value}, c12, c14, 7"
+    };
+    (ich_lrc8) => {
+        "p15, 4, {value}, c12, c15, 0"
+    };
+    (ich_lrc9) => {
+        "p15, 4, {value}, c12, c15, 1"
+    };
+    (ich_lrc10) => {
+        "p15, 4, {value}, c12, c15, 2"
+    };
+    (ich_lrc11) => {
+        "p15, 4, {value}, c12, c15, 3"
+    };
+    (ich_lrc12) => {
+        "p15, 4, {value}, c12, c15, 4"
+    };
+    (ich_lrc13) => {
+        "p15, 4, {value}, c12, c15, 5"
+    };
+    (ich_lrc14) => {
+        "p15, 4, {value}, c12, c15, 6"
+    };
+    (ich_lrc15) => {
+        "p15, 4, {value}, c12, c15, 7"
+    };
+}
+#[cfg(target_arch = "arm")]
+pub(crate) use cp15;
+
+/// Returns the `MPIDR_EL1` value of the current CPU, or in AArch32 the
+/// `MPIDR`, which has no `Aff3` field.
+pub(crate) fn mpidr() -> u64 {
+    // SAFETY: Reading this system register doesn't access memory in any way.
+    unsafe { read_sysreg!(mpidr_el1) }
+}
+
+/// Returns the encoding of the system register `S<op0>_<op1>_C<crn>_C<crm>_<op2>`
+/// in bits `[20:5]` of the `MRS` and `MSR` instructions.
+pub(crate) const fn encoding(op0: u32, op1: u32, crn: u32, crm: u32, op2: u32) -> u32 {
+    op0 << 19 | op1 << 16 | crn << 12 | crm << 8 | op2 << 5
+}
+
+/// Exception level the driver runs at.
+///
+/// Most `ICC_*_EL1` registers serve every exception level, but `ICC_SRE`,
+/// `ICC_CTLR` and `ICC_IGRPEN1` have their own copies at EL2 or EL3, which
+/// control whether the lower levels may use the interface at all.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum ExceptionLevel {
+    El1,
+    El2,
+    El3,
+}
+
+impl ExceptionLevel {
+    /// `ICC_SRE_ELx.SRE`: the system register interface is in use.
+    const SRE: u64 = 1 << 0;
+    /// `ICC_SRE_EL2.Enable` and `ICC_SRE_EL3.Enable`: lower levels may access
+    /// their own `ICC_SRE_ELx`.
+    const SRE_ENABLE: u64 = 1 << 3;
+    /// `ICC_SRE_ELx.DIB`: IRQ bypass is disabled.
+    const SRE_DIB: u64 = 1 << 1;
+    /// `ICC_SRE_ELx.DFB`: FIQ bypass is disabled.
+    const SRE_DFB: u64 = 1 << 2;
+
+    /// `ICC_IGRPEN1_EL3.EnableGrp1NS`.
+    const IGRPEN1_EL3_NS: u64 = 1 << 0;
+    /// `ICC_IGRPEN1_EL3.EnableGrp1S`.
+    const IGRPEN1_EL3_S: u64 = 1 << 1;
+    /// `ICC_CTLR_EL1.EOImode`.
+    const CTLR_EL1_EOI_MODE: u64 = 1 << 1;
+    /// `ICC_CTLR_EL3.EOImode_EL3`.
+    const CTLR_EL3_EOI_MODE: u64 = 1 << 2;
+
+    /// Returns the exception level the CPU currently runs at.
+    #[cfg(not(target_arch = "arm"))]
+    pub(crate) fn current() -> Self {
+        match CurrentEL.read(CurrentEL::EL) {
+            3 => Self::El3,
+            2 => Self::El2,
+            _ => Self::El1,
+        }
+    }
+
+    /// Returns the exception level the CPU currently runs at, from the mode
+    /// in `CPSR`: Hyp mode is EL2 and Monitor mode EL3.
+    #[cfg(target_arch = "arm")]
+    pub(crate) fn current() -> Self {
+        const CPSR_MODE_MASK: u32 = 0x1f;
+        const CPSR_MODE_MON: u32 = 0x16;
+        const CPSR_MODE_HYP: u32 = 0x1a;
+
+        let cpsr: u32;
+        // SAFETY: Reading CPSR doesn't access memory in any way.
+        unsafe {
+            core::arch::asm!("mrs {cpsr}, cpsr", cpsr = out(reg) cpsr, options(nomem, nostack));
+        }
+        match cpsr & CPSR_MODE_MASK {
+            CPSR_MODE_MON => Self::El3,
+            CPSR_MODE_HYP => Self::El2,
+            _ => Self::El1,
+        }
+    }
+
+    /// Turns on the system register interface at this level and, from EL2 or
+    /// EL3, lets the levels below turn it on too. Returns whether it is on.
+    ///
+    /// # Safety
+    ///
+    /// The CPU must implement the GIC system register interface.
+    pub(crate) unsafe fn enable_sre(self) -> bool {
+        if self == Self::El3 {
+            let sre = read_sysreg!(icc_sre_el3);
+            write_sysreg!(icc_sre_el3, sre | Self::SRE | Self::SRE_ENABLE);
+            fence::isb("ICC_SRE");
+        }
+        if self >= Self::El2 {
+            let sre = read_sysreg!(icc_sre_el2);
+            write_sysreg!(icc_sre_el2, sre | Self::SRE | Self::SRE_ENABLE);
+            fence::isb("ICC_SRE");
+        }
+        let sre = read_sysreg!(icc_sre_el1);
+        write_sysreg!(icc_sre_el1, sre | Self::SRE);
+        fence::isb("ICC_SRE");
+        read_sysreg!(icc_sre_el1) & Self::SRE != 0
+    }
+
+    /// Disables the legacy IRQ and FIQ bypass signals through `ICC_SRE_ELx` of
+    /// this level. Returns whether they are disabled, as the bits are
+    /// read-only when a higher level controls them.
+    ///
+    /// # Safety
+    ///
+    /// The CPU must implement the GIC system register interface.
+    pub(crate) unsafe fn disable_bypass(self) -> bool {
+        let bits = Self::SRE_DIB | Self::SRE_DFB;
+        let sre = match self {
+            Self::El3 => {
+                write_sysreg!(icc_sre_el3, read_sysreg!(icc_sre_el3) | bits);
+                fence::isb("ICC_SRE");
+                read_sysreg!(icc_sre_el3)
+            }
+            Self::El2 => {
+                write_sysreg!(icc_sre_el2, read_sysreg!(icc_sre_el2) | bits);
+                fence::isb("ICC_SRE");
+                read_sysreg!(icc_sre_el2)
+            }
+            Self::El1 => {
+                write_sysreg!(icc_sre_el1, read_sysreg!(icc_sre_el1) | bits);
+                fence::isb("ICC_SRE");
+                read_sysreg!(icc_sre_el1)
+            }
+        };
+        sre & bits == bits
+    }
+
+    /// Writes `value` to `ICC_CTLR_EL3` at EL3, which also holds the EOI mode
+    /// of EL3, or to `ICC_CTLR_EL1` otherwise.
+    ///
+    /// # Safety
+    ///
+    /// The system register interface must be enabled.
+    pub(crate) unsafe fn write_ctlr(self, value: u64) {
+        if self == Self::El3 {
+            write_sysreg!(icc_ctlr_el3, value);
+        } else {
+            write_sysreg!(icc_ctlr_el1, value);
+        }
+    }
+
+    /// Returns whether writes to the EOI registers at this exception level
+    /// only drop the priority, leaving the deactivation to `ICC_DIR_EL1`.
+    ///
+    /// # Safety
+    ///
+    /// The system register interface must be enabled.
+    pub(crate) unsafe fn split_eoi(self) -> bool {
+        if self == Self::El3 {
+            read_sysreg!(icc_ctlr_el3) & Self::CTLR_EL3_EOI_MODE != 0
+        } else {
+            read_sysreg!(icc_ctlr_el1) & Self::CTLR_EL1_EOI_MODE != 0
+        }
+    }
+
+    /// Enables Group 1 interrupts, secure ones if `secure` is set or
+    /// non-secure ones otherwise.
+    ///
+    /// At EL3 both groups are controlled through `ICC_IGRPEN1_EL3`, while the
+    /// lower levels enable the group of their own security state through
+    /// `ICC_IGRPEN1_EL1`.
+    ///
+    /// # Safety
+    ///
+    /// The system register interface must be enabled.
+    pub(crate) unsafe fn enable_grp1(self, secure: bool) {
+        if self == Self::El3 {
+            let bit = if secure {
+                Self::IGRPEN1_EL3_S
+            } else {
+                Self::IGRPEN1_EL3_NS
+            };
+            let igrpen1 = read_sysreg!(icc_igrpen1_el3);
+            write_sysreg!(icc_igrpen1_el3, igrpen1 | bit);
+        } else {
+            write_sysreg!(icc_igrpen1_el1, 0x00000001);
+        }
+    }
+
+    /// Disables Group 1 interrupts, undoing [`enable_grp1`](Self::enable_grp1)
+    /// for the same `secure`.
+    ///
+    /// # Safety
+    ///
+    /// The system register interface must be enabled.
+    pub(crate) unsafe fn disable_grp1(self, secure: bool) {
+        if self == Self::El3 {
+            let bit = if secure {
+                Self::IGRPEN1_EL3_S
+            } else {
+                Self::IGRPEN1_EL3_NS
+            };
+            let igrpen1 = read_sysreg!(icc_igrpen1_el3);
+            write_sysreg!(icc_igrpen1_el3, igrpen1 & !bit);
+        } else {
+            write_sysreg!(icc_igrpen1_el1, 0);
+        }
+    }
+}
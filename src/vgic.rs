@@ -0,0 +1,614 @@
+//! Software model of a GICv3 virtual CPU interface, for hypervisors.
+//!
+//! A [`VGicCpuInterface`] holds the virtual interrupts of one vCPU. The
+//! hypervisor injects interrupts into it as they arrive, [`flushes`] it into
+//! the `ICH_LR<n>_EL2` list registers before entering the vCPU and
+//! [`syncs`] it back after leaving, so the model always knows which
+//! interrupts the guest has taken and completed. Interrupts that don't fit in
+//! the list registers stay queued in software and the underflow maintenance
+//! interrupt is requested, so they can be flushed once the guest made room.
+//!
+//! [`flushes`]: VGicCpuInterface::flush
+//! [`syncs`]: VGicCpuInterface::sync
+//!
+//! The state of an interface can be exchanged with KVM and QEMU through
+//! [`export_kvm`](VGicCpuInterface::export_kvm) and
+//! [`import_kvm`](VGicCpuInterface::import_kvm).
+//!
+//! The virtual interface only reaches the guest with the forwarding set up
+//! by [`El2Forwarding::VIRTUAL`].
+
+mod forwarding;
+mod kvm;
+
+pub use forwarding::{El2Forwarding, ForwardingIssue};
+pub use kvm::{KvmVgicAttr, KvmVgicGroup};
+
+use crate::sysregs::{read_sysreg, write_sysreg};
+use crate::{GicError, IntId, InterruptGroup};
+
+/// The architectural maximum number of list registers.
+const MAX_LRS: usize = 16;
+
+/// State of a virtual interrupt, as held in a list register.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VirtualIrqState {
+    /// Waiting to be acknowledged by the guest.
+    Pending,
+    /// Acknowledged by the guest and not yet completed.
+    Active,
+    /// Acknowledged and signalled again before the guest completed it.
+    PendingActive,
+}
+
+/// A virtual interrupt queued for a vCPU.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct VirtualInterrupt {
+    /// The interrupt ID the guest sees.
+    pub intid: IntId,
+    /// Priority, lower values being more urgent.
+    pub priority: u8,
+    /// Group the interrupt is signalled in to the guest.
+    pub group: InterruptGroup,
+    /// The physical interrupt deactivated when the guest completes this one,
+    /// for interrupts forwarded from hardware.
+    pub hw: Option<IntId>,
+    /// Current state of the interrupt.
+    pub state: VirtualIrqState,
+}
+
+impl VirtualInterrupt {
+    const LR_STATE_SHIFT: u64 = 62;
+    const LR_STATE_PENDING: u64 = 0b01;
+    const LR_STATE_ACTIVE: u64 = 0b10;
+    const LR_STATE_PENDING_ACTIVE: u64 = 0b11;
+    const LR_HW: u64 = 1 << 61;
+    const LR_GROUP1: u64 = 1 << 60;
+    const LR_PRIORITY_SHIFT: u64 = 48;
+    const LR_PINTID_SHIFT: u64 = 32;
+    const LR_PINTID_MASK: u64 = 0x1fff;
+
+    const GICH_LR_HW: u32 = 1 << 31;
+    const GICH_LR_GROUP1: u32 = 1 << 30;
+    const GICH_LR_STATE_SHIFT: u32 = 28;
+    /// `GICH_LR<n>` only holds the upper 5 bits of the priority.
+    const GICH_LR_PRIORITY_SHIFT: u32 = 23 - 3;
+    const GICH_LR_PRIORITY_MASK: u32 = 0x1f << 23;
+    const GICH_LR_PINTID_SHIFT: u32 = 10;
+    const GICH_LR_ID_MASK: u32 = 0x3ff;
+
+    /// The virtual timer PPI, INTID 27.
+    pub const VIRTUAL_TIMER: IntId = IntId::ppi(11);
+
+    /// Returns a pending virtual interrupt, not backed by a physical one.
+    pub const fn new(intid: IntId, priority: u8, group: InterruptGroup) -> Self {
+        Self {
+            intid,
+            priority,
+            group,
+            hw: None,
+            state: VirtualIrqState::Pending,
+        }
+    }
+
+    /// Returns the pending virtual timer interrupt of a guest, linked to the
+    /// physical `timer` interrupt backing it.
+    ///
+    /// The physical interrupt is deactivated when the guest completes the
+    /// virtual one, so the host doesn't need to take a maintenance interrupt
+    /// to unmask the timer again.
+    pub const fn virtual_timer(timer: IntId, priority: u8) -> Self {
+        Self {
+            intid: Self::VIRTUAL_TIMER,
+            priority,
+            group: InterruptGroup::Group1,
+            hw: Some(timer),
+            state: VirtualIrqState::Pending,
+        }
+    }
+
+    /// Returns the two-bit list register encoding of the state.
+    fn lr_state(&self) -> u64 {
+        match self.state {
+            VirtualIrqState::Pending => Self::LR_STATE_PENDING,
+            VirtualIrqState::Active => Self::LR_STATE_ACTIVE,
+            VirtualIrqState::PendingActive => Self::LR_STATE_PENDING_ACTIVE,
+        }
+    }
+
+    /// Returns the `ICH_LR<n>_EL2` value describing this interrupt.
+    pub fn ich_lr(&self) -> u64 {
+        let mut lr = self.lr_state() << Self::LR_STATE_SHIFT
+            | u64::from(self.priority) << Self::LR_PRIORITY_SHIFT
+            | u64::from(u32::from(self.intid));
+        if self.group == InterruptGroup::Group1 {
+            lr |= Self::LR_GROUP1;
+        }
+        if let Some(hw) = self.hw {
+            lr |= Self::LR_HW
+                | (u64::from(u32::from(hw)) & Self::LR_PINTID_MASK) << Self::LR_PINTID_SHIFT;
+        }
+        lr
+    }
+
+    /// Returns the `GICH_LR<n>` value describing this interrupt, for the
+    /// virtual interface control registers of a GICv2.
+    ///
+    /// Unlike `ICH_LR<n>_EL2`, these hold 10-bit virtual and physical IDs and
+    /// only the upper 5 bits of the priority. The source CPU of a virtual SGI
+    /// isn't encoded.
+    pub fn gich_lr(&self) -> u32 {
+        let mut lr = (self.lr_state() as u32) << Self::GICH_LR_STATE_SHIFT
+            | u32::from(self.priority) << Self::GICH_LR_PRIORITY_SHIFT
+                & Self::GICH_LR_PRIORITY_MASK
+            | u32::from(self.intid) & Self::GICH_LR_ID_MASK;
+        if self.group == InterruptGroup::Group1 {
+            lr |= Self::GICH_LR_GROUP1;
+        }
+        if let Some(hw) = self.hw {
+            lr |= Self::GICH_LR_HW
+                | (u32::from(hw) & Self::GICH_LR_ID_MASK) << Self::GICH_LR_PINTID_SHIFT;
+        }
+        lr
+    }
+
+    /// Returns the state held in a list register, or `None` if it is invalid
+    /// because the guest completed the interrupt.
+    fn decode_lr_state(lr: u64) -> Option<VirtualIrqState> {
+        match lr >> Self::LR_STATE_SHIFT {
+            Self::LR_STATE_PENDING => Some(VirtualIrqState::Pending),
+            Self::LR_STATE_ACTIVE => Some(VirtualIrqState::Active),
+            Self::LR_STATE_PENDING_ACTIVE => Some(VirtualIrqState::PendingActive),
+            _ => None,
+        }
+    }
+}
+
+/// Software model of the virtual CPU interface of one vCPU, holding up to `N`
+/// virtual interrupts.
+#[derive(Copy, Clone, Debug)]
+pub struct VGicCpuInterface<const N: usize> {
+    irqs: [Option<VirtualInterrupt>; N],
+    /// Entry of `irqs` held by each list register since the last flush.
+    lrs: [Option<usize>; MAX_LRS],
+    /// Virtual priority mask, as seen by the guest in `ICC_PMR_EL1`.
+    pub vpmr: u8,
+    /// Virtual Group 0 binary point, as seen by the guest in `ICC_BPR0_EL1`.
+    pub vbpr0: u8,
+    /// Virtual Group 1 binary point, as seen by the guest in `ICC_BPR1_EL1`.
+    pub vbpr1: u8,
+    /// The group enables and control bits of `ICH_VMCR_EL2`: `VENG0`,
+    /// `VENG1`, `VAckCtl`, `VFIQEn`, `VCBPR` and `VEOIM`, as set by the guest
+    /// through `ICC_IGRPEN<n>_EL1` and `ICC_CTLR_EL1`.
+    pub vctl: u32,
+}
+
+impl<const N: usize> VGicCpuInterface<N> {
+    const HCR_UIE: u64 = 1 << 1;
+    const HCR_LRENPIE: u64 = 1 << 2;
+    const HCR_EOICOUNT_SHIFT: u64 = 27;
+    const HCR_EOICOUNT_MASK: u64 = 0x1f << 27;
+    const VTR_LIST_REGS_MASK: u64 = 0x1f;
+
+    const VMCR_VPMR_SHIFT: u64 = 24;
+    const VMCR_VBPR0_SHIFT: u64 = 21;
+    const VMCR_VBPR1_SHIFT: u64 = 18;
+    const VMCR_VBPR_MASK: u64 = 0b111;
+    const VMCR_VENG0: u32 = 1 << 0;
+    const VMCR_VENG1: u32 = 1 << 1;
+    const VMCR_VCBPR: u32 = 1 << 4;
+    const VMCR_VEOIM: u32 = 1 << 9;
+    /// `VEOIM`, `VCBPR`, `VFIQEn`, `VAckCtl`, `VENG1` and `VENG0`.
+    const VMCR_CTL_MASK: u32 = 1 << 9 | 0b1_1111;
+
+    /// Returns an empty virtual CPU interface, with all priorities unmasked.
+    pub const fn new() -> Self {
+        Self {
+            irqs: [None; N],
+            lrs: [None; MAX_LRS],
+            vpmr: 0xff,
+            vbpr0: 2,
+            vbpr1: 3,
+            vctl: Self::VMCR_VENG1,
+        }
+    }
+
+    /// Returns an iterator over the pending and active virtual interrupts.
+    pub fn interrupts(&self) -> impl Iterator<Item = &VirtualInterrupt> {
+        self.irqs.iter().flatten()
+    }
+
+    /// Makes `irq` pending for the guest.
+    ///
+    /// An interrupt that is already pending stays so, and one that is active
+    /// becomes pending and active. Returns [`GicError::CapacityExceeded`] if
+    /// `N` interrupts are already queued.
+    pub fn inject(&mut self, irq: VirtualInterrupt) -> Result<(), GicError> {
+        if let Some(queued) = self
+            .irqs
+            .iter_mut()
+            .flatten()
+            .find(|q| q.intid == irq.intid)
+        {
+            if queued.state == VirtualIrqState::Active {
+                queued.state = VirtualIrqState::PendingActive;
+            }
+            return Ok(());
+        }
+        let slot = self
+            .irqs
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(GicError::CapacityExceeded)?;
+        *slot = Some(VirtualInterrupt {
+            state: VirtualIrqState::Pending,
+            ..irq
+        });
+        Ok(())
+    }
+
+    /// Fills `lrs` with the most urgent virtual interrupts and returns how
+    /// many interrupts didn't fit.
+    ///
+    /// Active interrupts take precedence, as the guest can only complete them
+    /// while they are in a list register. Unused list registers are cleared.
+    fn flush_into(&mut self, lrs: &mut [u64]) -> usize {
+        let mut chosen = [false; N];
+        self.lrs = [None; MAX_LRS];
+        for (n, lr) in lrs.iter_mut().enumerate() {
+            let next = self
+                .irqs
+                .iter()
+                .enumerate()
+                .filter(|(i, irq)| irq.is_some() && !chosen[*i])
+                .min_by_key(|(_, irq)| {
+                    let irq = irq.as_ref().unwrap();
+                    (irq.state == VirtualIrqState::Pending, irq.priority)
+                })
+                .map(|(i, _)| i);
+            *lr = match next {
+                Some(i) => {
+                    chosen[i] = true;
+                    self.lrs[n] = Some(i);
+                    self.irqs[i].unwrap().ich_lr()
+                }
+                None => 0,
+            };
+        }
+        self.interrupts().count() - chosen.iter().filter(|&&c| c).count()
+    }
+
+    /// Updates the interrupts from the list registers read back after the
+    /// vCPU ran, dropping those the guest completed.
+    fn sync_from(&mut self, lrs: &[u64]) {
+        for (n, &lr) in lrs.iter().enumerate() {
+            let Some(i) = self.lrs[n].take() else {
+                continue;
+            };
+            match VirtualInterrupt::decode_lr_state(lr) {
+                Some(state) => self.irqs[i].as_mut().unwrap().state = state,
+                None => self.irqs[i] = None,
+            }
+        }
+    }
+
+    /// Drops the interrupts held by the list registers set in `elrsr`, which
+    /// the guest completed, and returns how many there were.
+    fn complete_empty_lrs(&mut self, elrsr: u64) -> usize {
+        let mut completed = 0;
+        for (n, slot) in self.lrs.iter_mut().enumerate() {
+            if elrsr & (1 << n) == 0 {
+                continue;
+            }
+            if let Some(i) = slot.take() {
+                self.irqs[i] = None;
+                completed += 1;
+            }
+        }
+        completed
+    }
+
+    /// Drops `count` active interrupts that aren't in a list register, most
+    /// urgent first, as the guest completes them in that order.
+    fn complete_outside_lrs(&mut self, count: usize) -> usize {
+        let mut completed = 0;
+        while completed < count {
+            let next = self
+                .irqs
+                .iter()
+                .enumerate()
+                .filter(|(i, irq)| {
+                    irq.is_some_and(|irq| irq.state != VirtualIrqState::Pending)
+                        && !self.lrs.contains(&Some(*i))
+                })
+                .min_by_key(|(_, irq)| irq.unwrap().priority)
+                .map(|(i, _)| i);
+            let Some(i) = next else {
+                break;
+            };
+            let irq = self.irqs[i].as_mut().unwrap();
+            if irq.state == VirtualIrqState::PendingActive {
+                irq.state = VirtualIrqState::Pending;
+            } else {
+                self.irqs[i] = None;
+            }
+            completed += 1;
+        }
+        completed
+    }
+
+    /// Returns the number of list registers the CPU implements.
+    fn num_lrs() -> usize {
+        // SAFETY: Reading this system register doesn't access memory in any way.
+        let vtr = unsafe { read_sysreg!(ich_vtr_el2) };
+        ((vtr & Self::VTR_LIST_REGS_MASK) as usize + 1).min(MAX_LRS)
+    }
+
+    /// Loads the virtual interrupts and the virtual PMR, BPRs and control bits
+    /// into the hardware, right before entering the vCPU.
+    ///
+    /// If more interrupts are queued than there are list registers, the
+    /// underflow maintenance interrupt is enabled so the hypervisor can
+    /// [`sync`](Self::sync) and flush again once the guest handled some of them.
+    /// If an active interrupt is left out, the maintenance interrupt for
+    /// `ICH_HCR_EL2.EOIcount` is enabled too, see
+    /// [`handle_maintenance`](Self::handle_maintenance).
+    /// Returns the number of interrupts left queued in software.
+    pub fn flush(&mut self) -> usize {
+        let mut lrs = [0; MAX_LRS];
+        let lrs = &mut lrs[..Self::num_lrs()];
+        let overflow = self.flush_into(lrs);
+        for (n, &lr) in lrs.iter().enumerate() {
+            write_lr(n, lr);
+        }
+
+        // SAFETY: Accessing these system registers doesn't access memory in any way.
+        unsafe {
+            write_sysreg!(ich_vmcr_el2, self.vmcr());
+            let hcr = read_sysreg!(ich_hcr_el2);
+            let mut hcr = hcr & !(Self::HCR_UIE | Self::HCR_LRENPIE);
+            if overflow > 0 {
+                hcr |= Self::HCR_UIE;
+            }
+            // Completions of active interrupts left out are only counted.
+            if self.active_outside_lrs() {
+                hcr |= Self::HCR_LRENPIE;
+            }
+            write_sysreg!(ich_hcr_el2, hcr);
+        }
+        overflow
+    }
+
+    /// Handles a maintenance interrupt without reading back every list register.
+    ///
+    /// List registers that `ICH_ELRSR_EL2` reports as empty held interrupts the
+    /// guest completed, which are dropped. Completions of active interrupts
+    /// that weren't in a list register are only counted in
+    /// `ICH_HCR_EL2.EOIcount`, which is read and cleared, and as many active
+    /// interrupts outside the list registers are dropped.
+    ///
+    /// Returns the number of interrupts the guest completed.
+    pub fn handle_maintenance(&mut self) -> usize {
+        // SAFETY: Accessing these system registers doesn't access memory in any way.
+        let (elrsr, eoi_count) = unsafe {
+            let hcr = read_sysreg!(ich_hcr_el2);
+            write_sysreg!(ich_hcr_el2, hcr & !Self::HCR_EOICOUNT_MASK);
+            (
+                read_sysreg!(ich_elrsr_el2),
+                ((hcr & Self::HCR_EOICOUNT_MASK) >> Self::HCR_EOICOUNT_SHIFT) as usize,
+            )
+        };
+        self.complete_empty_lrs(elrsr) + self.complete_outside_lrs(eoi_count)
+    }
+
+    /// Returns whether an active interrupt isn't held by any list register.
+    fn active_outside_lrs(&self) -> bool {
+        self.irqs.iter().enumerate().any(|(i, irq)| {
+            irq.is_some_and(|irq| irq.state != VirtualIrqState::Pending)
+                && !self.lrs.contains(&Some(i))
+        })
+    }
+
+    /// Reads back the list registers and the virtual PMR, BPRs and control
+    /// bits after leaving the vCPU.
+    pub fn sync(&mut self) {
+        let mut lrs = [0; MAX_LRS];
+        let lrs = &mut lrs[..Self::num_lrs()];
+        for (n, lr) in lrs.iter_mut().enumerate() {
+            *lr = read_lr(n);
+        }
+        self.sync_from(lrs);
+
+        // SAFETY: Reading this system register doesn't access memory in any way.
+        self.set_vmcr(unsafe { read_sysreg!(ich_vmcr_el2) });
+    }
+
+    /// Returns the `ICH_VMCR_EL2` value holding the virtual PMR, BPRs and
+    /// control bits.
+    fn vmcr(&self) -> u64 {
+        u64::from(self.vpmr) << Self::VMCR_VPMR_SHIFT
+            | (u64::from(self.vbpr0) & Self::VMCR_VBPR_MASK) << Self::VMCR_VBPR0_SHIFT
+            | (u64::from(self.vbpr1) & Self::VMCR_VBPR_MASK) << Self::VMCR_VBPR1_SHIFT
+            | u64::from(self.vctl & Self::VMCR_CTL_MASK)
+    }
+
+    /// Takes the virtual PMR, BPRs and control bits from an `ICH_VMCR_EL2`
+    /// value.
+    fn set_vmcr(&mut self, vmcr: u64) {
+        self.vpmr = (vmcr >> Self::VMCR_VPMR_SHIFT) as u8;
+        self.vbpr0 = ((vmcr >> Self::VMCR_VBPR0_SHIFT) & Self::VMCR_VBPR_MASK) as u8;
+        self.vbpr1 = ((vmcr >> Self::VMCR_VBPR1_SHIFT) & Self::VMCR_VBPR_MASK) as u8;
+        self.vctl = vmcr as u32 & Self::VMCR_CTL_MASK;
+    }
+}
+
+impl<const N: usize> Default for VGicCpuInterface<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Defines `read_lr` and `write_lr`, which access `ICH_LR<n>_EL2` by index.
+///
+/// In AArch32 each list register is split into `ICH_LR<n>` and `ICH_LRC<n>`,
+/// given as `low : high`.
+macro_rules! lr_accessors {
+    ($($n:literal => $($name:ident):+),* $(,)?) => {
+        /// Reads `ICH_LR<n>_EL2`.
+        fn read_lr(n: usize) -> u64 {
+            // SAFETY: Reading these system registers doesn't access memory in any way.
+            unsafe {
+                match n {
+                    $($n => read_sysreg!($($name):+),)*
+                    _ => panic!("invalid list register {}", n),
+                }
+            }
+        }
+
+        /// Writes `value` to `ICH_LR<n>_EL2`.
+        fn write_lr(n: usize, value: u64) {
+            // SAFETY: Writing to these system registers doesn't access memory in any way.
+            unsafe {
+                match n {
+                    $($n => write_sysreg!($($name):+, value),)*
+                    _ => panic!("invalid list register {}", n),
+                }
+            }
+        }
+    };
+}
+
+#[cfg(not(target_arch = "arm"))]
+lr_accessors! {
+    0 => ich_lr0_el2,
+    1 => ich_lr1_el2,
+    2 => ich_lr2_el2,
+    3 => ich_lr3_el2,
+    4 => ich_lr4_el2,
+    5 => ich_lr5_el2,
+    6 => ich_lr6_el2,
+    7 => ich_lr7_el2,
+    8 => ich_lr8_el2,
+    9 => ich_lr9_el2,
+    10 => ich_lr10_el2,
+    11 => ich_lr11_el2,
+    12 => ich_lr12_el2,
+    13 => ich_lr13_el2,
+    14 => ich_lr14_el2,
+    15 => ich_lr15_el2,
+}
+
+#[cfg(target_arch = "arm")]
+lr_accessors! {
+    0 => ich_lr0 : ich_lrc0,
+    1 => ich_lr1 : ich_lrc1,
+    2 => ich_lr2 : ich_lrc2,
+    3 => ich_lr3 : ich_lrc3,
+    4 => ich_lr4 : ich_lrc4,
+    5 => ich_lr5 : ich_lrc5,
+    6 => ich_lr6 : ich_lrc6,
+    7 => ich_lr7 : ich_lrc7,
+    8 => ich_lr8 : ich_lrc8,
+    9 => ich_lr9 : ich_lrc9,
+    10 => ich_lr10 : ich_lrc10,
+    11 => ich_lr11 : ich_lrc11,
+    12 => ich_lr12 : ich_lrc12,
+    13 => ich_lr13 : ich_lrc13,
+    14 => ich_lr14 : ich_lrc14,
+    15 => ich_lr15 : ich_lrc15,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush_overflow_and_sync() {
+        let mut vgic = VGicCpuInterface::<4>::new();
+        for (i, priority) in [0xa0, 0x20, 0x80].into_iter().enumerate() {
+            let irq = VirtualInterrupt::new(IntId::spi(i), priority, InterruptGroup::Group1);
+            vgic.inject(irq).unwrap();
+        }
+
+        let mut lrs = [0; 2];
+        assert_eq!(vgic.flush_into(&mut lrs), 1);
+        assert_eq!(lrs[0], 1 << 62 | 1 << 60 | 0x20 << 48 | 33);
+        assert_eq!(lrs[1], 1 << 62 | 1 << 60 | 0x80 << 48 | 34);
+
+        // The guest completed SPI 1 and acknowledged SPI 2.
+        vgic.sync_from(&[0, lrs[1] & !(0b11 << 62) | 0b10 << 62]);
+        assert_eq!(vgic.interrupts().count(), 2);
+
+        // The active interrupt goes first, even though it is less urgent.
+        let mut lrs = [0; 1];
+        assert_eq!(vgic.flush_into(&mut lrs), 1);
+        assert_eq!(lrs[0] >> 62, 0b10);
+        assert_eq!(lrs[0] as u32, 34);
+    }
+
+    #[test]
+    fn test_vmcr() {
+        let mut vgic = VGicCpuInterface::<4>::new();
+        // The guest disabled Group 1, enabled Group 0 and split EOI.
+        let vmcr = 0xf0 << 24 | 1 << 21 | 4 << 18 | 1 << 9 | 1 << 0;
+        vgic.set_vmcr(vmcr);
+        assert_eq!(vgic.vpmr, 0xf0);
+        assert_eq!(vgic.vctl, 1 << 9 | 1);
+        assert_eq!(vgic.vmcr(), vmcr);
+    }
+
+    #[test]
+    fn test_virtual_timer_lr() {
+        let timer = VirtualInterrupt::virtual_timer(IntId::ppi(14), 0xa0);
+        assert_eq!(
+            timer.ich_lr(),
+            0b01 << 62 | 1 << 61 | 1 << 60 | 0xa0 << 48 | 30 << 32 | 27
+        );
+        assert_eq!(
+            timer.gich_lr(),
+            1 << 31 | 1 << 30 | 0b01 << 28 | 0x14 << 23 | 30 << 10 | 27
+        );
+    }
+
+    #[test]
+    fn test_maintenance() {
+        let mut vgic = VGicCpuInterface::<4>::new();
+        for (i, priority) in [0x40, 0x20, 0x80].into_iter().enumerate() {
+            let mut irq = VirtualInterrupt::new(IntId::spi(i), priority, InterruptGroup::Group1);
+            irq.state = VirtualIrqState::Active;
+            vgic.irqs[i] = Some(irq);
+        }
+        let mut lrs = [0; 1];
+        vgic.flush_into(&mut lrs);
+        assert!(vgic.active_outside_lrs());
+
+        // The guest completed the interrupt in the list register and one more.
+        assert_eq!(vgic.complete_empty_lrs(1), 1);
+        assert_eq!(vgic.complete_outside_lrs(1), 1);
+        assert_eq!(vgic.interrupts().next().unwrap().intid, IntId::spi(2));
+        assert_eq!(vgic.complete_outside_lrs(2), 1);
+        assert_eq!(vgic.interrupts().count(), 0);
+    }
+
+    #[test]
+    fn test_inject_active() {
+        let mut vgic = VGicCpuInterface::<1>::new();
+        let irq = VirtualInterrupt::new(IntId::ppi(11), 0xa0, InterruptGroup::Group1);
+        vgic.inject(irq).unwrap();
+        let mut lrs = [0; 1];
+        vgic.flush_into(&mut lrs);
+        vgic.sync_from(&[lrs[0] & !(0b11 << 62) | 0b10 << 62]);
+
+        vgic.inject(irq).unwrap();
+        let state = vgic.interrupts().next().unwrap().state;
+        assert_eq!(state, VirtualIrqState::PendingActive);
+        assert_eq!(
+            vgic.inject(VirtualInterrupt::new(
+                IntId::ppi(12),
+                0,
+                InterruptGroup::Group1
+            )),
+            Err(GicError::CapacityExceeded)
+        );
+    }
+}
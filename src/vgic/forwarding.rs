@@ -0,0 +1,170 @@
+//! Where physical interrupts go while a guest runs, for hosted hypervisors.
+//!
+//! `HCR_EL2.IMO` and `HCR_EL2.FMO` take physical IRQs and FIQs to EL2 while
+//! EL1 runs, and also redirect the Group 1 and Group 0 `ICC_*_EL1` accesses
+//! of the guest to the virtual CPU interface, which only signals the list
+//! registers with `ICH_HCR_EL2.En` set. The three have to agree: a guest
+//! whose accesses are redirected to a disabled virtual interface gets no
+//! interrupts at all, and list registers filled for a guest that accesses the
+//! physical interface are never seen.
+
+use crate::sysregs::{read_sysreg, write_sysreg, ExceptionLevel};
+use crate::{fence, GicError};
+
+/// The forwarding configuration of the current CPU.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct El2Forwarding {
+    /// `HCR_EL2.IMO`: physical IRQs are taken to EL2, and the guest accesses
+    /// the virtual Group 1 interface.
+    pub imo: bool,
+    /// `HCR_EL2.FMO`: physical FIQs are taken to EL2, and the guest accesses
+    /// the virtual Group 0 interface.
+    pub fmo: bool,
+    /// `ICH_HCR_EL2.En`: the virtual CPU interface signals the list registers
+    /// to the guest.
+    pub virtual_interface: bool,
+}
+
+/// What is wrong with an [`El2Forwarding`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ForwardingIssue {
+    /// The guest accesses the virtual CPU interface, which is disabled, so it
+    /// gets no interrupts.
+    VirtualInterfaceDisabled,
+    /// The virtual CPU interface is enabled but the guest accesses the
+    /// physical one, so it never sees the list registers.
+    VirtualInterfaceUnused,
+    /// IRQs and FIQs are forwarded differently, so the guest accesses one
+    /// group on the physical CPU interface and the other on the virtual one.
+    SplitGroups,
+}
+
+impl El2Forwarding {
+    /// `HCR_EL2.FMO`.
+    const HCR_FMO: u64 = 1 << 3;
+    /// `HCR_EL2.IMO`.
+    const HCR_IMO: u64 = 1 << 4;
+    /// `ICH_HCR_EL2.En`.
+    const ICH_HCR_EN: u64 = 1 << 0;
+
+    /// Physical interrupts are taken at EL2 and the guest gets virtual ones
+    /// from the list registers, as with a [`VGicCpuInterface`].
+    ///
+    /// [`VGicCpuInterface`]: crate::VGicCpuInterface
+    pub const VIRTUAL: Self = Self {
+        imo: true,
+        fmo: true,
+        virtual_interface: true,
+    };
+
+    /// The guest owns the physical CPU interface and takes physical
+    /// interrupts itself, as with a partitioning hypervisor.
+    pub const PASSTHROUGH: Self = Self {
+        imo: false,
+        fmo: false,
+        virtual_interface: false,
+    };
+
+    /// Reads the configuration of the current CPU.
+    ///
+    /// Returns [`GicError::Unsupported`] below EL2, where the registers
+    /// aren't accessible.
+    pub fn read() -> Result<Self, GicError> {
+        Self::check_el()?;
+        // SAFETY: Reading these system registers doesn't access memory in any way.
+        let (hcr, ich_hcr) = unsafe { (read_sysreg!(hcr_el2), read_sysreg!(ich_hcr_el2)) };
+        Ok(Self {
+            imo: hcr & Self::HCR_IMO != 0,
+            fmo: hcr & Self::HCR_FMO != 0,
+            virtual_interface: ich_hcr & Self::ICH_HCR_EN != 0,
+        })
+    }
+
+    /// Applies the configuration to the current CPU, leaving the other bits
+    /// of the registers alone.
+    ///
+    /// Returns [`GicError::Unsupported`] below EL2.
+    pub fn apply(self) -> Result<(), GicError> {
+        Self::check_el()?;
+        let set = |value: u64, bit: u64, on: bool| if on { value | bit } else { value & !bit };
+        // SAFETY: Accessing these system registers doesn't access memory in
+        // any way. They only take effect at EL1, so not for the caller.
+        unsafe {
+            let ich_hcr = read_sysreg!(ich_hcr_el2);
+            write_sysreg!(
+                ich_hcr_el2,
+                set(ich_hcr, Self::ICH_HCR_EN, self.virtual_interface)
+            );
+            let hcr = read_sysreg!(hcr_el2);
+            let hcr = set(hcr, Self::HCR_IMO, self.imo);
+            write_sysreg!(hcr_el2, set(hcr, Self::HCR_FMO, self.fmo));
+        }
+        fence::isb("EL2 forwarding");
+        Ok(())
+    }
+
+    /// Returns whether physical IRQs that arrive while a guest runs are taken
+    /// at EL2.
+    pub const fn irqs_at_el2(self) -> bool {
+        self.imo
+    }
+
+    /// Returns whether physical FIQs that arrive while a guest runs are taken
+    /// at EL2.
+    pub const fn fiqs_at_el2(self) -> bool {
+        self.fmo
+    }
+
+    /// Checks that the guest gets interrupts from the interface it accesses.
+    pub const fn check(self) -> Result<(), ForwardingIssue> {
+        match (self.imo || self.fmo, self.virtual_interface) {
+            (true, false) => Err(ForwardingIssue::VirtualInterfaceDisabled),
+            (false, true) => Err(ForwardingIssue::VirtualInterfaceUnused),
+            _ if self.imo != self.fmo => Err(ForwardingIssue::SplitGroups),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_el() -> Result<(), GicError> {
+        if ExceptionLevel::current() >= ExceptionLevel::El2 {
+            Ok(())
+        } else {
+            Err(GicError::Unsupported)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        assert_eq!(El2Forwarding::VIRTUAL.check(), Ok(()));
+        assert_eq!(El2Forwarding::PASSTHROUGH.check(), Ok(()));
+        assert_eq!(
+            El2Forwarding {
+                virtual_interface: false,
+                ..El2Forwarding::VIRTUAL
+            }
+            .check(),
+            Err(ForwardingIssue::VirtualInterfaceDisabled)
+        );
+        assert_eq!(
+            El2Forwarding {
+                virtual_interface: true,
+                ..El2Forwarding::PASSTHROUGH
+            }
+            .check(),
+            Err(ForwardingIssue::VirtualInterfaceUnused)
+        );
+        assert_eq!(
+            El2Forwarding {
+                fmo: false,
+                ..El2Forwarding::VIRTUAL
+            }
+            .check(),
+            Err(ForwardingIssue::SplitGroups)
+        );
+    }
+}
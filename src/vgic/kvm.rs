@@ -0,0 +1,392 @@
+//! Import and export of virtual interrupt state in the layout of the KVM
+//! `KVM_DEV_TYPE_ARM_VGIC_V3` device attributes, as saved by QEMU.
+//!
+//! The layout is described in the Linux kernel documentation:
+//! <https://docs.kernel.org/virt/kvm/devices/arm-vgic-v3.html>
+
+use super::{VGicCpuInterface, VirtualInterrupt, VirtualIrqState};
+use crate::{GicError, IntId, InterruptGroup};
+
+/// Attribute group of the KVM vGICv3 device.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KvmVgicGroup {
+    /// `KVM_DEV_ARM_VGIC_GRP_DIST_REGS`: distributor registers by offset.
+    DistRegs,
+    /// `KVM_DEV_ARM_VGIC_GRP_REDIST_REGS`: redistributor registers of a vCPU
+    /// by offset, the SGI frame starting at 64KB.
+    RedistRegs,
+    /// `KVM_DEV_ARM_VGIC_GRP_CPU_SYSREGS`: CPU interface registers of a vCPU
+    /// by their system register encoding.
+    CpuSysregs,
+}
+
+impl KvmVgicGroup {
+    /// Returns the value of the `group` field of `struct kvm_device_attr`.
+    pub const fn raw(self) -> u32 {
+        match self {
+            Self::DistRegs => 1,
+            Self::RedistRegs => 5,
+            Self::CpuSysregs => 6,
+        }
+    }
+
+    /// Returns the group with the given raw value, if it is one of those
+    /// holding interrupt state.
+    pub const fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::DistRegs),
+            5 => Some(Self::RedistRegs),
+            6 => Some(Self::CpuSysregs),
+            _ => None,
+        }
+    }
+}
+
+/// A register of the KVM vGICv3 device, as addressed by `struct kvm_device_attr`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct KvmVgicAttr {
+    /// The attribute group.
+    pub group: KvmVgicGroup,
+    /// Affinity of the vCPU as `Aff3.Aff2.Aff1.Aff0`, ignored for the
+    /// distributor.
+    pub mpidr: u32,
+    /// Offset of the register, or its system register encoding.
+    pub offset: u32,
+}
+
+impl KvmVgicAttr {
+    const fn new(group: KvmVgicGroup, mpidr: u32, offset: u32) -> Self {
+        Self {
+            group,
+            mpidr,
+            offset,
+        }
+    }
+
+    /// Returns the value of the `attr` field of `struct kvm_device_attr`.
+    pub const fn attr(&self) -> u64 {
+        (self.mpidr as u64) << 32 | self.offset as u64
+    }
+
+    /// Decodes the `group` and `attr` fields of `struct kvm_device_attr`.
+    ///
+    /// Returns [`GicError::Unsupported`] for groups other than those of
+    /// [`KvmVgicGroup`].
+    pub fn from_raw(group: u32, attr: u64) -> Result<Self, GicError> {
+        let group = KvmVgicGroup::from_raw(group).ok_or(GicError::Unsupported)?;
+        Ok(Self::new(group, (attr >> 32) as u32, attr as u32))
+    }
+}
+
+/// A per-interrupt field held in a bank of 32-bit registers.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Field {
+    Group,
+    Pending,
+    Active,
+    Priority,
+}
+
+impl Field {
+    /// Each field with the offset of its `GICD_*` registers and width in bits.
+    /// The `GICR_*` registers of the SGI frame have the same offsets.
+    const ALL: [(Self, u32, usize); 4] = [
+        (Self::Group, 0x80, 1),
+        (Self::Pending, 0x200, 1),
+        (Self::Active, 0x300, 1),
+        (Self::Priority, 0x400, 8),
+    ];
+
+    /// Registers of the bank: 32 for a single bit per interrupt.
+    const fn bank_len(width: usize) -> u32 {
+        (width * 32) as u32
+    }
+
+    /// Returns the field and the first interrupt of the register at `offset`.
+    fn decode(offset: u32) -> Option<(Self, usize, usize)> {
+        Self::ALL
+            .iter()
+            .find(|(_, base, width)| (*base..*base + Self::bank_len(*width) * 4).contains(&offset))
+            .filter(|_| offset.is_multiple_of(4))
+            .map(|&(field, base, width)| (field, (offset - base) as usize * 8 / width, width))
+    }
+
+    /// Returns the value of the field for `irq`.
+    fn get(self, irq: &VirtualInterrupt) -> u32 {
+        let pending = matches!(
+            irq.state,
+            VirtualIrqState::Pending | VirtualIrqState::PendingActive
+        );
+        let active = matches!(
+            irq.state,
+            VirtualIrqState::Active | VirtualIrqState::PendingActive
+        );
+        match self {
+            Self::Group => (irq.group == InterruptGroup::Group1).into(),
+            Self::Pending => pending.into(),
+            Self::Active => active.into(),
+            Self::Priority => irq.priority.into(),
+        }
+    }
+}
+
+/// Encoding of a system register in the `KVM_DEV_ARM_VGIC_GRP_CPU_SYSREGS`
+/// group: `op0`, `op1`, `CRn`, `CRm` and `op2`.
+const fn sysreg(op0: u32, op1: u32, crn: u32, crm: u32, op2: u32) -> u32 {
+    op0 << 14 | op1 << 11 | crn << 7 | crm << 3 | op2
+}
+
+const ICC_PMR_EL1: u32 = sysreg(3, 0, 4, 6, 0);
+const ICC_BPR0_EL1: u32 = sysreg(3, 0, 12, 8, 3);
+const ICC_BPR1_EL1: u32 = sysreg(3, 0, 12, 12, 3);
+const ICC_CTLR_EL1: u32 = sysreg(3, 0, 12, 12, 4);
+const ICC_IGRPEN0_EL1: u32 = sysreg(3, 0, 12, 12, 6);
+const ICC_IGRPEN1_EL1: u32 = sysreg(3, 0, 12, 12, 7);
+
+/// `ICC_CTLR_EL1.CBPR` and `ICC_CTLR_EL1.EOImode`.
+const ICC_CTLR_CBPR: u32 = 1 << 0;
+const ICC_CTLR_EOIMODE: u32 = 1 << 1;
+
+/// Offset of the SGI frame in the redistributor group.
+const SGI_FRAME: u32 = 0x10000;
+
+impl<const N: usize> VGicCpuInterface<N> {
+    /// Calls `emit` with the registers describing the interrupts of the vCPU
+    /// with affinity `mpidr`, in the KVM device attribute layout.
+    ///
+    /// The pending and active registers of the SGIs and PPIs are all emitted,
+    /// as are those of the SPIs that any interrupt is held for. Group and
+    /// priority registers are emitted where they cover a held interrupt,
+    /// fields of the other interrupts reading as zero. The virtual PMR, BPRs,
+    /// `ICC_CTLR_EL1` and group enables are emitted last; `ICC_CTLR_EL1` only
+    /// has `CBPR` and `EOImode`, its read-only fields reading as zero.
+    /// Extended ranges and LPIs have no place in this layout and are left out.
+    pub fn export_kvm(&self, mpidr: u32, mut emit: impl FnMut(KvmVgicAttr, u32)) {
+        for (field, base, width) in Field::ALL {
+            for reg in 0..Field::bank_len(width) {
+                let first = reg as usize * 32 / width;
+                let intids = first..first + 32 / width;
+                let mut held = false;
+                let mut value = 0;
+                for irq in self
+                    .interrupts()
+                    .filter(|irq| intids.contains(&irq.intid.0))
+                {
+                    held = true;
+                    value |= field.get(irq) << ((irq.intid.0 - first) * width);
+                }
+                let offset = base + reg * 4;
+                if first < IntId::SPI_START {
+                    if held || matches!(field, Field::Pending | Field::Active) {
+                        emit(
+                            KvmVgicAttr::new(KvmVgicGroup::RedistRegs, mpidr, SGI_FRAME + offset),
+                            value,
+                        );
+                    }
+                } else if held {
+                    emit(KvmVgicAttr::new(KvmVgicGroup::DistRegs, 0, offset), value);
+                }
+            }
+        }
+
+        let sysreg = |offset| KvmVgicAttr::new(KvmVgicGroup::CpuSysregs, mpidr, offset);
+        emit(sysreg(ICC_PMR_EL1), self.vpmr.into());
+        emit(sysreg(ICC_BPR0_EL1), self.vbpr0.into());
+        emit(sysreg(ICC_BPR1_EL1), self.vbpr1.into());
+        let mut ctlr = 0;
+        if self.vctl & Self::VMCR_VCBPR != 0 {
+            ctlr |= ICC_CTLR_CBPR;
+        }
+        if self.vctl & Self::VMCR_VEOIM != 0 {
+            ctlr |= ICC_CTLR_EOIMODE;
+        }
+        emit(sysreg(ICC_CTLR_EL1), ctlr);
+        emit(
+            sysreg(ICC_IGRPEN0_EL1),
+            (self.vctl & Self::VMCR_VENG0 != 0).into(),
+        );
+        emit(
+            sysreg(ICC_IGRPEN1_EL1),
+            (self.vctl & Self::VMCR_VENG1 != 0).into(),
+        );
+    }
+
+    /// Sets the `bits` of [`vctl`](Self::vctl) if `set` is, clears them
+    /// otherwise.
+    fn set_vctl(&mut self, bits: u32, set: bool) {
+        if set {
+            self.vctl |= bits;
+        } else {
+            self.vctl &= !bits;
+        }
+    }
+
+    /// Replaces the state of the interface with that of the vCPU with affinity
+    /// `mpidr` in `regs`, registers in the KVM device attribute layout such as
+    /// those of a QEMU snapshot.
+    ///
+    /// The registers may come in any order. Those of other vCPUs, and those
+    /// that don't describe interrupt state, are ignored. Interrupts are taken
+    /// from the pending and active registers, defaulting to Group 1 and
+    /// priority 0 where the group and priority registers don't say otherwise.
+    /// The group enables and the `CBPR` and `EOImode` bits of `ICC_CTLR_EL1`
+    /// go to [`vctl`](Self::vctl). Returns [`GicError::CapacityExceeded`] if more than `N` interrupts are
+    /// pending or active.
+    pub fn import_kvm(&mut self, mpidr: u32, regs: &[(KvmVgicAttr, u32)]) -> Result<(), GicError> {
+        *self = Self::new();
+        let fields = regs.iter().filter_map(|&(attr, value)| {
+            let (field, first, width) = match attr.group {
+                KvmVgicGroup::RedistRegs if attr.mpidr == mpidr => {
+                    let (field, first, width) = Field::decode(attr.offset.checked_sub(SGI_FRAME)?)?;
+                    (first < IntId::SPI_START).then_some((field, first, width))?
+                }
+                KvmVgicGroup::DistRegs => {
+                    Field::decode(attr.offset).filter(|&(_, first, _)| first >= IntId::SPI_START)?
+                }
+                _ => return None,
+            };
+            Some((field, first, width, value))
+        });
+
+        // Create the interrupts first, as the other fields only apply to them.
+        for (field, first, _, value) in fields.clone() {
+            if !matches!(field, Field::Pending | Field::Active) {
+                continue;
+            }
+            for bit in (0..32).filter(|bit| value & 1 << bit != 0) {
+                let intid = IntId(first + bit);
+                let state = match self.irqs.iter_mut().flatten().find(|q| q.intid == intid) {
+                    Some(irq) => {
+                        irq.state = VirtualIrqState::PendingActive;
+                        continue;
+                    }
+                    None if field == Field::Pending => VirtualIrqState::Pending,
+                    None => VirtualIrqState::Active,
+                };
+                let slot = self
+                    .irqs
+                    .iter_mut()
+                    .find(|slot| slot.is_none())
+                    .ok_or(GicError::CapacityExceeded)?;
+                *slot = Some(VirtualInterrupt {
+                    state,
+                    ..VirtualInterrupt::new(intid, 0, InterruptGroup::Group1)
+                });
+            }
+        }
+
+        for (field, first, width, value) in fields {
+            let mask = (1u64 << width) as u32 - 1;
+            for irq in self
+                .irqs
+                .iter_mut()
+                .flatten()
+                .filter(|irq| (first..first + 32 / width).contains(&irq.intid.0))
+            {
+                let bits = value >> ((irq.intid.0 - first) * width) & mask;
+                match field {
+                    Field::Group if bits == 0 => irq.group = InterruptGroup::Group0,
+                    Field::Group => irq.group = InterruptGroup::Group1,
+                    Field::Priority => irq.priority = bits as u8,
+                    Field::Pending | Field::Active => {}
+                }
+            }
+        }
+
+        for &(attr, value) in regs {
+            if attr.group != KvmVgicGroup::CpuSysregs || attr.mpidr != mpidr {
+                continue;
+            }
+            match attr.offset {
+                ICC_PMR_EL1 => self.vpmr = value as u8,
+                ICC_BPR0_EL1 => self.vbpr0 = value as u8,
+                ICC_BPR1_EL1 => self.vbpr1 = value as u8,
+                ICC_CTLR_EL1 => {
+                    self.set_vctl(Self::VMCR_VCBPR, value & ICC_CTLR_CBPR != 0);
+                    self.set_vctl(Self::VMCR_VEOIM, value & ICC_CTLR_EOIMODE != 0);
+                }
+                ICC_IGRPEN0_EL1 => self.set_vctl(Self::VMCR_VENG0, value & 1 != 0),
+                ICC_IGRPEN1_EL1 => self.set_vctl(Self::VMCR_VENG1, value & 1 != 0),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attr() {
+        let attr = KvmVgicAttr::from_raw(5, 0x0100_0203_0001_0200).unwrap();
+        assert_eq!(attr.group, KvmVgicGroup::RedistRegs);
+        assert_eq!(attr.mpidr, 0x0100_0203);
+        assert_eq!(attr.offset, 0x1_0200);
+        assert_eq!(attr.attr(), 0x0100_0203_0001_0200);
+        assert_eq!(KvmVgicAttr::from_raw(2, 0), Err(GicError::Unsupported));
+        assert_eq!(ICC_PMR_EL1, 0xc230);
+    }
+
+    #[test]
+    fn test_export_import() {
+        let mut vgic = VGicCpuInterface::<4>::new();
+        vgic.inject(VirtualInterrupt::new(
+            IntId::ppi(11),
+            0x20,
+            InterruptGroup::Group1,
+        ))
+        .unwrap();
+        let mut spi = VirtualInterrupt::new(IntId::spi(5), 0x80, InterruptGroup::Group0);
+        spi.state = VirtualIrqState::PendingActive;
+        vgic.irqs[1] = Some(spi);
+        vgic.vpmr = 0xf0;
+        // Group 0 only, with EOImode set.
+        vgic.vctl = VGicCpuInterface::<4>::VMCR_VENG0 | VGicCpuInterface::<4>::VMCR_VEOIM;
+
+        let mut regs = [(KvmVgicAttr::new(KvmVgicGroup::DistRegs, 0, 0), 0); 16];
+        let mut len = 0;
+        vgic.export_kvm(7, |attr, value| {
+            regs[len] = (attr, value);
+            len += 1;
+        });
+        let regs = &regs[..len];
+        assert!(regs.contains(&(
+            KvmVgicAttr::new(KvmVgicGroup::RedistRegs, 7, 0x1_0200),
+            1 << 27
+        )));
+        assert!(regs.contains(&(KvmVgicAttr::new(KvmVgicGroup::DistRegs, 0, 0x304), 1 << 5)));
+        assert!(regs.contains(&(
+            KvmVgicAttr::new(KvmVgicGroup::DistRegs, 0, 0x424),
+            0x80 << 8
+        )));
+        assert!(regs.contains(&(
+            KvmVgicAttr::new(KvmVgicGroup::CpuSysregs, 7, ICC_CTLR_EL1),
+            2
+        )));
+        assert!(regs.contains(&(
+            KvmVgicAttr::new(KvmVgicGroup::CpuSysregs, 7, ICC_IGRPEN1_EL1),
+            0
+        )));
+
+        // Import in reverse order, and ignore another vCPU.
+        let mut reversed = [regs[0]; 16];
+        for (to, from) in reversed.iter_mut().zip(regs.iter().rev()) {
+            *to = *from;
+        }
+        let mut imported = VGicCpuInterface::<2>::new();
+        imported.import_kvm(7, &reversed[..len]).unwrap();
+        assert_eq!(imported.vpmr, 0xf0);
+        assert_eq!(imported.vctl, vgic.vctl);
+        let mut irqs = [None; 2];
+        for (to, irq) in irqs.iter_mut().zip(imported.interrupts()) {
+            *to = Some(*irq);
+        }
+        irqs.sort_by_key(|irq| irq.unwrap().intid);
+        assert_eq!(irqs, [vgic.irqs[0], vgic.irqs[1]]);
+
+        imported.import_kvm(8, &reversed[..len]).unwrap();
+        assert_eq!(imported.interrupts().count(), 1);
+    }
+}
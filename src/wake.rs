@@ -0,0 +1,134 @@
+//! Masking all interrupts but the wake sources across a system suspend.
+//!
+//! Before suspending, an OS leaves enabled only the interrupts that should
+//! wake the system up, and restores the others on resume. The kernel marks
+//! its wake sources in a [`WakeSources`] as drivers request it, then:
+//!
+//! ```ignore
+//! let saved = WAKE.suspend(&gic);
+//! enter_suspend();
+//! saved.resume(&gic);
+//! ```
+//!
+//! Only SGIs, PPIs and SPIs are covered, the private ones of the CPU doing
+//! this. Reserved interrupts are left alone.
+
+use crate::{GenericArmGic, GicError, IntId};
+
+/// Number of words of the bitmap of INTIDs below 1024.
+const WORDS: usize = 32;
+
+/// The interrupts allowed to wake the system from suspend.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct WakeSources {
+    bits: [u32; WORDS],
+}
+
+impl WakeSources {
+    /// Returns an empty set.
+    pub const fn new() -> Self {
+        Self { bits: [0; WORDS] }
+    }
+
+    /// Marks `intid` as a wake source, or not.
+    ///
+    /// Returns [`GicError::InvalidIntId`] for special INTIDs, extended
+    /// interrupts and LPIs.
+    pub fn set_wake_capable(&mut self, intid: IntId, wake: bool) -> Result<(), GicError> {
+        if intid.0 >= IntId::SPECIAL_START {
+            return Err(GicError::InvalidIntId);
+        }
+        let bit = 1 << (intid.0 % 32);
+        if wake {
+            self.bits[intid.0 / 32] |= bit;
+        } else {
+            self.bits[intid.0 / 32] &= !bit;
+        }
+        Ok(())
+    }
+
+    /// Returns whether `intid` is a wake source.
+    pub fn is_wake_capable(&self, intid: IntId) -> bool {
+        self.bits
+            .get(intid.0 / 32)
+            .is_some_and(|word| word & 1 << (intid.0 % 32) != 0)
+    }
+
+    /// Disables every enabled interrupt that isn't a wake source, returning
+    /// which ones to enable again on resume.
+    pub fn suspend(&self, gic: &impl GenericArmGic) -> SuspendState {
+        let enabled = gic.inspect::<0>();
+        let mut saved = SuspendState { masked: [0; WORDS] };
+        for intid in enabled.enabled_interrupts() {
+            if self.is_wake_capable(intid) || gic.is_reserved(intid) {
+                continue;
+            }
+            gic.disable_interrupt(intid);
+            saved.masked[intid.0 / 32] |= 1 << (intid.0 % 32);
+        }
+        saved
+    }
+}
+
+impl Default for WakeSources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The interrupts [`WakeSources::suspend`] disabled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[must_use = "the masked interrupts stay disabled until resumed"]
+pub struct SuspendState {
+    masked: [u32; WORDS],
+}
+
+impl SuspendState {
+    /// Returns the interrupts that were disabled, in ID order.
+    pub fn masked_interrupts(&self) -> impl Iterator<Item = IntId> + '_ {
+        (0..WORDS * 32)
+            .filter(|&id| self.masked[id / 32] & 1 << (id % 32) != 0)
+            .map(IntId)
+    }
+
+    /// Enables the interrupts that were disabled for the suspend again.
+    pub fn resume(self, gic: &impl GenericArmGic) {
+        for intid in self.masked_interrupts() {
+            gic.enable_interrupt(intid);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::{HandoffState, Simulation};
+
+    #[test]
+    fn test_suspend_resume() {
+        static SIM: Simulation<64, 1> = Simulation::new();
+        let gic = SIM.gic();
+        for intid in [IntId::ppi(14), IntId::spi(1), IntId::spi(2)] {
+            gic.enable_interrupt(intid);
+        }
+        let mut wake = WakeSources::new();
+        wake.set_wake_capable(IntId::spi(2), true).unwrap();
+        assert_eq!(
+            wake.set_wake_capable(IntId::from(1023), true),
+            Err(GicError::InvalidIntId)
+        );
+
+        let saved = wake.suspend(&gic);
+        assert!(saved
+            .masked_interrupts()
+            .eq([IntId::ppi(14), IntId::spi(1)]));
+        let state: HandoffState<0> = gic.inspect();
+        assert!(state.enabled_interrupts().eq([IntId::spi(2)]));
+
+        saved.resume(&gic);
+        let state: HandoffState<0> = gic.inspect();
+        assert!(state
+            .enabled_interrupts()
+            .eq([IntId::ppi(14), IntId::spi(1), IntId::spi(2)]));
+    }
+}